@@ -0,0 +1,70 @@
+//! Renders a low-latency playlist the way an origin does with every part,
+//! into a fresh String and into a reused buffer, and reports the time and
+//! heap allocations each takes. Run with `cargo bench --bench serialize`.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    fs::File,
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use llhls_rs::read_playlist;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const ITERATIONS: u32 = 20_000;
+
+fn measure(name: &str, mut render: impl FnMut()) {
+    // Warm up, e.g. so the reused buffer has grown to fit
+    render();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        render();
+    }
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    println!(
+        "{:<16} {:>8.2?}/render {:>6} allocations/render",
+        name,
+        elapsed / ITERATIONS,
+        allocations as f64 / f64::from(ITERATIONS)
+    );
+}
+
+fn main() {
+    let file = File::open("tests/resources/ll-hls-full.m3u8").expect("Opened test file");
+    let playlist = read_playlist(file).expect("Parsed test file");
+
+    measure("to_string", || {
+        black_box(playlist.to_string());
+    });
+    let mut buffer = Vec::new();
+    measure("serialize_into", || {
+        playlist.serialize_into(&mut buffer);
+        black_box(&buffer);
+    });
+}
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// Whatever parses renders to text that parses again, to the same text
+fuzz_target!(|data: &[u8]| {
+    if let Ok(playlist) = llhls_rs::parse_bytes(data) {
+        let rendered = playlist.to_string();
+        assert_eq!(playlist.estimated_serialized_len(), rendered.len());
+        let reparsed = llhls_rs::parse_bytes(rendered.as_bytes()).expect("rendered playlist parses");
+        assert_eq!(reparsed.to_string(), rendered);
+    }
+});
@@ -0,0 +1,428 @@
+use core::{fmt, str::FromStr};
+
+use crate::prelude::*;
+
+/// A hexadecimal-sequence attribute value, like the IV of EXT-X-KEY or the
+/// SCTE35-OUT of EXT-X-DATERANGE.
+///
+/// The text is kept as written, so a parsed value renders back with the same
+/// `0x`/`0X` prefix, digit case and number of digits, leading zeros included.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct HexBytes {
+    // Validated, prefix included
+    text: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseHexError {
+    /// The value doesn't start with `0x` or `0X`.
+    MissingPrefix,
+    /// There are no digits after the prefix.
+    Empty,
+    InvalidDigit,
+}
+
+impl HexBytes {
+    /// Renders `bytes` as `0x` followed by two lowercase digits per byte.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut text = String::with_capacity(2 + 2 * bytes.len());
+        text.push_str("0x");
+        for byte in bytes {
+            text.push_str(&format!("{:02x}", byte));
+        }
+        HexBytes { text }
+    }
+
+    /// The digits after the prefix, as written.
+    pub fn digits(&self) -> &str {
+        &self.text[2..]
+    }
+
+    /// The decoded bytes. An odd number of digits is read as if it had a
+    /// leading zero.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let digits = self.digits().as_bytes();
+        let mut bytes = Vec::with_capacity(digits.len().div_ceil(2));
+        let (head, rest) = digits.split_at(digits.len() % 2);
+        if let Some(&digit) = head.first() {
+            bytes.push(nibble(digit));
+        }
+        for pair in rest.chunks(2) {
+            bytes.push(nibble(pair[0]) << 4 | nibble(pair[1]));
+        }
+        bytes
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+}
+
+// Value of a digit already validated as hexadecimal
+fn nibble(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        b'a'..=b'f' => digit - b'a' + 10,
+        _ => digit - b'A' + 10,
+    }
+}
+
+impl FromStr for HexBytes {
+    type Err = ParseHexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s
+            .strip_prefix("0x")
+            .or_else(|| s.strip_prefix("0X"))
+            .ok_or(ParseHexError::MissingPrefix)?;
+        if digits.is_empty() {
+            return Err(ParseHexError::Empty);
+        }
+        if !digits.chars().all(|digit| digit.is_ascii_hexdigit()) {
+            return Err(ParseHexError::InvalidDigit);
+        }
+        Ok(HexBytes {
+            text: s.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for HexBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// An enumerated-string-list attribute value: a quoted string holding
+/// comma-separated enumerated strings, like RESTRICT="SKIP,JUMP" of an
+/// interstitial or the CHARACTERISTICS of EXT-X-MEDIA.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct EnumeratedStringList {
+    values: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseListError {
+    /// The value isn't enclosed in double quotes.
+    Unquoted,
+    /// An entry contains a character that can't be represented, e.g. a
+    /// double quote, a comma or a line break.
+    InvalidEntry { entry: String },
+}
+
+impl EnumeratedStringList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, value: impl Into<String>) -> Result<(), ParseListError> {
+        let value = value.into();
+        if value.is_empty() || value.contains(['"', ',', '\r', '\n']) {
+            return Err(ParseListError::InvalidEntry { entry: value });
+        }
+        self.values.push(value);
+        Ok(())
+    }
+
+    pub fn contains(&self, value: &str) -> bool {
+        self.values.iter().any(|entry| entry == value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.values.iter().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+impl FromStr for EnumeratedStringList {
+    type Err = ParseListError;
+
+    /// Parses the quoted form, e.g. `"SKIP,JUMP"`. `""` is the empty list.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let inner = s
+            .strip_prefix('"')
+            .and_then(|s| s.strip_suffix('"'))
+            .ok_or(ParseListError::Unquoted)?;
+        let mut list = EnumeratedStringList::new();
+        if !inner.is_empty() {
+            for value in inner.split(',') {
+                list.push(value)?;
+            }
+        }
+        Ok(list)
+    }
+}
+
+impl fmt::Display for EnumeratedStringList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\"", self.values.join(","))
+    }
+}
+
+/// A typed attribute value, as the attribute-list syntax tells it apart.
+///
+/// Parsing can only go by syntax, so a quoted enumerated-string-list comes
+/// out as a [`AttributeValue::QuotedString`]; [`AttributeValue::as_list`]
+/// reads it as a list where the attribute is known to be one.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AttributeValue {
+    /// decimal-integer
+    Integer(u64),
+    /// decimal-floating-point or signed-decimal-floating-point
+    Float(f64),
+    /// quoted-string, without its quotes
+    QuotedString(String),
+    /// enumerated-string, e.g. `YES` or `PART`
+    EnumeratedString(String),
+    /// hexadecimal-sequence
+    Hex(HexBytes),
+    /// decimal-resolution, width by height
+    Resolution(u32, u32),
+    /// enumerated-string-list
+    List(EnumeratedStringList),
+}
+
+impl AttributeValue {
+    /// Types `value` by its syntax. Anything unquoted that isn't a number,
+    /// hexadecimal-sequence or resolution is an enumerated-string.
+    pub fn parse(value: &str) -> AttributeValue {
+        if let Some(text) = value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+        {
+            return AttributeValue::QuotedString(text.to_string());
+        }
+        if let Ok(hex) = value.parse() {
+            return AttributeValue::Hex(hex);
+        }
+        let is_digits = |text: &str| !text.is_empty() && text.chars().all(|c| c.is_ascii_digit());
+        if is_digits(value) {
+            if let Ok(integer) = value.parse() {
+                return AttributeValue::Integer(integer);
+            }
+        }
+        if let Some((width, height)) = value.split_once('x') {
+            if is_digits(width) && is_digits(height) {
+                if let (Ok(width), Ok(height)) = (width.parse(), height.parse()) {
+                    return AttributeValue::Resolution(width, height);
+                }
+            }
+        }
+        let unsigned = value.strip_prefix('-').unwrap_or(value);
+        let is_decimal = unsigned
+            .split_once('.')
+            .map_or(is_digits(unsigned), |(whole, fraction)| {
+                is_digits(whole) && (fraction.is_empty() || is_digits(fraction))
+            });
+        if is_decimal {
+            if let Ok(float) = value.parse() {
+                return AttributeValue::Float(float);
+            }
+        }
+        AttributeValue::EnumeratedString(value.to_string())
+    }
+
+    /// Reads a quoted string as an enumerated-string-list.
+    pub fn as_list(&self) -> Option<EnumeratedStringList> {
+        match self {
+            AttributeValue::List(list) => Some(list.clone()),
+            AttributeValue::QuotedString(text) => format!("\"{}\"", text).parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// The value as a float, for integers too.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            AttributeValue::Integer(integer) => Some(*integer as f64),
+            AttributeValue::Float(float) => Some(*float),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            AttributeValue::QuotedString(text) | AttributeValue::EnumeratedString(text) => {
+                Some(text)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AttributeValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttributeValue::Integer(integer) => write!(f, "{}", integer),
+            AttributeValue::Float(float) => write!(f, "{}", float),
+            AttributeValue::QuotedString(text) => write!(f, "\"{}\"", text),
+            AttributeValue::EnumeratedString(text) => f.write_str(text),
+            AttributeValue::Hex(hex) => write!(f, "{}", hex),
+            AttributeValue::Resolution(width, height) => write!(f, "{}x{}", width, height),
+            AttributeValue::List(list) => write!(f, "{}", list),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AttributeListError {
+    /// A quoted string is never closed.
+    UnterminatedQuote,
+    /// An attribute has no `=VALUE`.
+    MissingValue { attribute: String },
+    /// A name is empty or has characters other than `A`-`Z`, `0`-`9`
+    /// and `-`.
+    InvalidName { name: String },
+    /// The list names an attribute twice, which the spec forbids.
+    DuplicateName { name: String },
+    /// A value has a double quote other than around the whole of it, e.g.
+    /// `URI="a"b` or `URI=a"b"`.
+    InvalidValue { attribute: String },
+}
+
+impl fmt::Display for AttributeListError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttributeListError::UnterminatedQuote => write!(f, "unterminated quoted string"),
+            AttributeListError::MissingValue { attribute } => {
+                write!(f, "attribute {} has no value", attribute)
+            }
+            AttributeListError::InvalidName { name } => {
+                write!(f, "invalid attribute name {}", name)
+            }
+            AttributeListError::DuplicateName { name } => write!(f, "attribute {} repeated", name),
+            AttributeListError::InvalidValue { attribute } => {
+                write!(f, "misplaced quote in attribute {}", attribute)
+            }
+        }
+    }
+}
+
+impl core::error::Error for AttributeListError {}
+
+/// Parses an attribute list, the `NAME=VALUE,...` after the colon of a tag,
+/// into names and typed values in their original order.
+///
+/// Commas inside quoted strings don't separate attributes, so values like
+/// `CODECS="avc1.64001f,mp4a.40.2"` stay whole. Values are typed by their
+/// syntax, see [`AttributeValue::parse`]. Useful for tags this crate doesn't
+/// know, e.g.
+///
+/// ```
+/// use llhls_rs::{parse_attribute_list, AttributeValue};
+///
+/// let attributes = parse_attribute_list("ID=\"ad-1\",DURATION=30").unwrap();
+/// assert_eq!(attributes[1], ("DURATION".to_string(), AttributeValue::Integer(30)));
+/// ```
+pub fn parse_attribute_list(
+    list: &str,
+) -> Result<Vec<(String, AttributeValue)>, AttributeListError> {
+    Ok(tokenize_attribute_list(list)?
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), AttributeValue::parse(value)))
+        .collect())
+}
+
+// Lexes an attribute list per RFC 8216 section 4.2 into names and raw
+// values, quotes included, in their original order. A quoted-string runs to
+// its closing quote whatever it contains; any other value runs to the next
+// comma and can't contain quotes. The empty list has no attributes.
+pub(crate) fn tokenize_attribute_list(list: &str) -> Result<Vec<(&str, &str)>, AttributeListError> {
+    let mut attributes: Vec<(&str, &str)> = Vec::new();
+    let mut rest = list;
+    while !rest.is_empty() {
+        let missing_value = |attribute: &str| AttributeListError::MissingValue {
+            attribute: attribute.to_string(),
+        };
+        let (name, after) = rest
+            .split_once('=')
+            .ok_or_else(|| missing_value(rest.split(',').next().unwrap_or_default()))?;
+        if let Some((name, _)) = name.split_once(',') {
+            return Err(missing_value(name));
+        }
+        let valid_name = !name.is_empty()
+            && name
+                .chars()
+                .all(|character| matches!(character, 'A'..='Z' | '0'..='9' | '-'));
+        if !valid_name {
+            return Err(AttributeListError::InvalidName {
+                name: name.to_string(),
+            });
+        }
+        let length = match after.strip_prefix('"') {
+            Some(quoted) => {
+                quoted
+                    .find('"')
+                    .ok_or(AttributeListError::UnterminatedQuote)?
+                    + 2
+            }
+            None => after.find(',').unwrap_or(after.len()),
+        };
+        let (value, remainder) = after.split_at(length);
+        let invalid_value = || AttributeListError::InvalidValue {
+            attribute: name.to_string(),
+        };
+        if value.is_empty() {
+            return Err(missing_value(name));
+        }
+        if !value.starts_with('"') && value.contains('"') {
+            return Err(invalid_value());
+        }
+        if attributes.iter().any(|(existing, _)| *existing == name) {
+            return Err(AttributeListError::DuplicateName {
+                name: name.to_string(),
+            });
+        }
+        attributes.push((name, value));
+        rest = match remainder.strip_prefix(',') {
+            // A trailing comma would leave an attribute without a name
+            Some("") => return Err(missing_value("")),
+            Some(next) => next,
+            None if remainder.is_empty() => remainder,
+            None => return Err(invalid_value()),
+        };
+    }
+    Ok(attributes)
+}
+
+// A quoted-string value without its quotes, or as written if a packager
+// left them out
+pub(crate) fn unquote(value: &str) -> &str {
+    unquoted(value).unwrap_or(value)
+}
+
+// A quoted-string value without its quotes, if it has them
+pub(crate) fn unquoted(value: &str) -> Option<&str> {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+}
+
+// Splits an attribute list on the commas between attributes, leaving those
+// inside quoted strings alone. Yields each attribute with its byte offset.
+pub(crate) fn split_attribute_list(list: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut quoted = false;
+    let mut start = 0;
+    let mut attributes = Vec::new();
+    for (index, character) in list.char_indices() {
+        match character {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                attributes.push((start, &list[start..index]));
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    attributes.push((start, &list[start..]));
+    attributes.into_iter()
+}
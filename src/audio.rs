@@ -0,0 +1,109 @@
+use core::time::Duration;
+
+use crate::prelude::*;
+use crate::{
+    float, EncoderTiming, MediaPlaylistBuilder, MediaType, MultivariantPlaylist, PartInf,
+    PartialSegment, Rendition, ServerControl,
+};
+
+// Samples in an AAC frame
+const AAC_FRAME_SAMPLES: u32 = 1024;
+
+/// An audio-only rendition played alongside the video variants of a
+/// presentation, carried in an EXT-X-MEDIA group.
+///
+/// Audio parts are cut on audio frame boundaries rather than on video
+/// frames, and since every audio frame can be decoded on its own, every part
+/// is INDEPENDENT. Audio has no keyframes to align EXT-X-MAP changes with,
+/// so its init segment stays the same for the whole stream.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AudioRendition {
+    pub rendition: Rendition,
+    pub sample_rate: u32,
+    /// Samples per audio frame, 1024 for AAC.
+    pub frame_samples: u32,
+}
+
+impl AudioRendition {
+    pub fn new(
+        group_id: impl Into<String>,
+        name: impl Into<String>,
+        uri: impl Into<String>,
+        sample_rate: u32,
+    ) -> Self {
+        let mut rendition = Rendition::new(MediaType::Audio, group_id, name);
+        rendition.uri = Some(uri.into());
+        rendition.autoselect = true;
+        AudioRendition {
+            rendition,
+            sample_rate,
+            frame_samples: AAC_FRAME_SAMPLES,
+        }
+    }
+
+    /// Duration of an audio frame, in seconds.
+    pub fn frame_duration(&self) -> f32 {
+        self.frame_samples as f32 / self.sample_rate as f32
+    }
+
+    /// The longest whole number of audio frames not exceeding
+    /// `part_target`, the video's part target, so audio parts are never
+    /// published later than the video parts they accompany.
+    pub fn part_target(&self, part_target: f32) -> f32 {
+        self.part_frames(part_target) as f32 * self.frame_duration()
+    }
+
+    fn part_frames(&self, part_target: f32) -> u32 {
+        (float::floor(f64::from(part_target / self.frame_duration())) as u32).max(1)
+    }
+
+    // Exact to the nanosecond, unlike `frame_duration`
+    fn duration_of(&self, frames: u32) -> Duration {
+        Duration::from_nanos(
+            u64::from(frames) * u64::from(self.frame_samples) * 1_000_000_000
+                / u64::from(self.sample_rate),
+        )
+    }
+
+    /// Sets the timing of the audio playlist from the video's: the same
+    /// target duration and CAN-SKIP-UNTIL, with the audio part target.
+    pub fn apply<'a>(
+        &self,
+        timing: &EncoderTiming,
+        builder: &'a mut MediaPlaylistBuilder,
+    ) -> &'a mut MediaPlaylistBuilder {
+        let part_target = self.duration_of(self.part_frames(timing.part_target));
+        builder
+            .target_duration(timing.target_duration)
+            .part_inf(PartInf::new(part_target))
+            .server_control(ServerControl::new(
+                true,
+                3 * part_target,
+                Duration::from_secs_f32(timing.can_skip_until),
+            ))
+    }
+
+    /// A part of `frames` audio frames, marked INDEPENDENT.
+    pub fn part(&self, frames: u32, uri: impl Into<String>) -> PartialSegment {
+        let mut part = PartialSegment::new(self.duration_of(frames), uri);
+        part.independent = Some(true);
+        part
+    }
+
+    /// Adds the EXT-X-MEDIA of this rendition to `playlist` and points every
+    /// variant without alternative audio at its group. The first rendition
+    /// of a group becomes its DEFAULT.
+    pub fn add_to(&self, playlist: &mut MultivariantPlaylist) {
+        let group_id = &self.rendition.group_id;
+        let mut rendition = self.rendition.clone();
+        if playlist.group(MediaType::Audio, group_id).next().is_none() {
+            rendition.default = true;
+        }
+        playlist.renditions.push(rendition);
+        for variant in &mut playlist.variants {
+            if variant.audio.is_none() {
+                variant.audio = Some(group_id.clone());
+            }
+        }
+    }
+}
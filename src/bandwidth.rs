@@ -0,0 +1,108 @@
+use alloc::collections::BTreeMap;
+
+use crate::prelude::*;
+use crate::{float, MediaType, MultivariantPlaylist};
+
+/// Measures the bit rates of media playlists from the segments actually
+/// produced, to correct BANDWIDTH and AVERAGE-BANDWIDTH of a multivariant
+/// playlist whose numbers came from encoder settings.
+#[derive(Clone, Debug, Default)]
+pub struct BandwidthMeter {
+    // Playlist URI to (duration in seconds, size in bytes) of its segments
+    segments: BTreeMap<String, Vec<(f32, u64)>>,
+}
+
+impl BandwidthMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a segment of the media playlist at `uri`, as referenced from
+    /// the multivariant playlist.
+    pub fn record_segment(&mut self, uri: &str, duration: f32, bytes: u64) {
+        self.segments
+            .entry(uri.to_string())
+            .or_default()
+            .push((duration, bytes));
+    }
+
+    /// The peak segment bit rate: the largest bit rate of any contiguous set
+    /// of segments lasting between 0.5 and 1.5 times the target duration.
+    pub fn peak_bitrate(&self, uri: &str, target_duration: u32) -> Option<u64> {
+        let segments = self.segments.get(uri)?;
+        let min = 0.5 * target_duration as f32;
+        let max = 1.5 * target_duration as f32;
+        let mut peak = None;
+        for start in 0..segments.len() {
+            let mut duration = 0.0;
+            let mut bytes = 0;
+            for &(segment_duration, segment_bytes) in &segments[start..] {
+                duration += segment_duration;
+                bytes += segment_bytes;
+                if duration > max {
+                    break;
+                }
+                if duration >= min {
+                    let bitrate = bitrate(bytes, duration);
+                    peak = Some(peak.map_or(bitrate, |peak: u64| peak.max(bitrate)));
+                }
+            }
+        }
+        // Too little media for a full set yet
+        peak.or_else(|| {
+            segments
+                .iter()
+                .map(|&(duration, bytes)| bitrate(bytes, duration))
+                .max()
+        })
+    }
+
+    /// The average segment bit rate over every recorded segment.
+    pub fn average_bitrate(&self, uri: &str) -> Option<u64> {
+        let segments = self.segments.get(uri)?;
+        let duration: f32 = segments.iter().map(|&(duration, _)| duration).sum();
+        let bytes: u64 = segments.iter().map(|&(_, bytes)| bytes).sum();
+        (duration > 0.0).then(|| bitrate(bytes, duration))
+    }
+
+    /// Rewrites BANDWIDTH and AVERAGE-BANDWIDTH of every measured variant.
+    /// A variant with alternative audio also counts the highest measured
+    /// rate of its audio group, since that audio plays alongside it.
+    pub fn apply(&self, playlist: &mut MultivariantPlaylist, target_duration: u32) {
+        let group_max = |group: &Option<String>, rate: &dyn Fn(&str) -> Option<u64>| {
+            group.as_deref().map_or(0, |group| {
+                playlist
+                    .group(MediaType::Audio, group)
+                    .filter_map(|rendition| rendition.uri.as_deref().and_then(rate))
+                    .max()
+                    .unwrap_or(0)
+            })
+        };
+        let peak = |uri: &str| self.peak_bitrate(uri, target_duration);
+        let average = |uri: &str| self.average_bitrate(uri);
+        let updates: Vec<_> = playlist
+            .variants
+            .iter()
+            .map(|variant| {
+                let bandwidth =
+                    peak(&variant.uri).map(|own| own + group_max(&variant.audio, &peak));
+                let average_bandwidth =
+                    average(&variant.uri).map(|own| own + group_max(&variant.audio, &average));
+                (bandwidth, average_bandwidth)
+            })
+            .collect();
+        for (variant, (bandwidth, average_bandwidth)) in playlist.variants.iter_mut().zip(updates) {
+            if let Some(bandwidth) = bandwidth {
+                variant.bandwidth = bandwidth;
+            }
+            if average_bandwidth.is_some() {
+                variant.average_bandwidth = average_bandwidth;
+            }
+        }
+    }
+}
+
+// Bits per second, rounded up
+fn bitrate(bytes: u64, duration: f32) -> u64 {
+    float::ceil(bytes as f64 * 8.0 / duration as f64) as u64
+}
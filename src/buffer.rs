@@ -0,0 +1,106 @@
+use core::time::Duration;
+
+use crate::prelude::*;
+
+/// A part as seen by [`BufferSimulation`]. Times are measured from an
+/// arbitrary origin shared by all parts, e.g. the start of the stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SimulatedPart {
+    pub duration: Duration,
+    /// When the origin published the part.
+    pub available_at: Duration,
+    /// How long fetching the part takes once requested.
+    pub download_time: Duration,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stall {
+    /// Index of the part playback ran out of media waiting for.
+    pub part: usize,
+    /// When playback stalled.
+    pub at: Duration,
+    pub duration: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BufferReport {
+    pub stalls: Vec<Stall>,
+    pub total_stall: Duration,
+    /// Lowest buffer occupancy once playback started, when a part finished
+    /// downloading.
+    pub min_buffer: Option<Duration>,
+    /// (time, buffered media) whenever a part finished downloading.
+    pub occupancy: Vec<(Duration, Duration)>,
+}
+
+/// Models the buffer of a client playing a live stream a fixed hold-back
+/// behind the live edge, to predict stalls for given part publishing and
+/// download times. Useful both for tuning client hold-back and for origins
+/// choosing PART-TARGET.
+///
+/// The client downloads parts one after another as soon as they are
+/// published, and starts playing once it is `hold_back` behind the end of the
+/// first part. After a stall, playback resumes as soon as the missing part
+/// arrives, so the client drifts further from the live edge.
+#[derive(Clone, Debug)]
+pub struct BufferSimulation {
+    hold_back: Duration,
+    parts: Vec<SimulatedPart>,
+}
+
+impl BufferSimulation {
+    pub fn new(hold_back: Duration) -> Self {
+        BufferSimulation {
+            hold_back,
+            parts: Vec::new(),
+        }
+    }
+
+    /// Adds the next part. Parts are expected in publication order.
+    pub fn push(&mut self, part: SimulatedPart) {
+        self.parts.push(part);
+    }
+
+    pub fn run(&self) -> BufferReport {
+        let mut report = BufferReport {
+            stalls: Vec::new(),
+            total_stall: Duration::ZERO,
+            min_buffer: None,
+            occupancy: Vec::with_capacity(self.parts.len()),
+        };
+        let Some(first) = self.parts.first() else {
+            return report;
+        };
+        // When media time zero plays, pushed back by every stall
+        let mut playback_start =
+            (first.available_at + self.hold_back).saturating_sub(first.duration);
+        let mut downloaded_until = Duration::ZERO;
+        let mut media_time = Duration::ZERO;
+        for (index, part) in self.parts.iter().enumerate() {
+            let finished = part.available_at.max(downloaded_until) + part.download_time;
+            downloaded_until = finished;
+
+            let due = playback_start + media_time;
+            if finished > due {
+                let stall = finished - due;
+                report.stalls.push(Stall {
+                    part: index,
+                    at: due,
+                    duration: stall,
+                });
+                report.total_stall += stall;
+                playback_start += stall;
+            }
+
+            media_time += part.duration;
+            let playhead = finished.saturating_sub(playback_start);
+            let buffered = media_time.saturating_sub(playhead);
+            report.occupancy.push((finished, buffered));
+            if finished >= playback_start {
+                report.min_buffer =
+                    Some(report.min_buffer.map_or(buffered, |min| min.min(buffered)));
+            }
+        }
+        report
+    }
+}
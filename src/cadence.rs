@@ -0,0 +1,117 @@
+use std::time::{Duration, SystemTime};
+
+use crate::{MediaPlaylist, PreloadHintType};
+
+/// Watches successive snapshots of a live playlist and measures how regularly
+/// the origin publishes parts, for origin health monitoring.
+///
+/// Publish times are taken to be the time of the first snapshot a part
+/// appears in, so snapshots should come from blocking reloads or frequent
+/// polling for the numbers to be meaningful.
+#[derive(Clone, Default)]
+pub struct CadenceAnalyzer {
+    first_observed: Option<SystemTime>,
+    last_observed: Option<SystemTime>,
+    // (MSN, part index) of the newest part seen so far
+    last_position: Option<(u64, u32)>,
+    // When the newest part was first seen, and its duration
+    last_publish: Option<(SystemTime, Duration)>,
+    pending_hint: Option<String>,
+    parts_observed: u64,
+    jitters: Vec<Duration>,
+    hints_checked: u64,
+    hints_matched: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CadenceReport {
+    pub parts_observed: u64,
+    pub parts_per_second: Option<f64>,
+    /// Mean absolute difference between when parts were expected (previous
+    /// publish time plus the previous part's duration) and first seen.
+    pub mean_jitter: Option<Duration>,
+    pub max_jitter: Option<Duration>,
+    /// Share of PART preload hints whose URI matched the next published part.
+    pub hint_accuracy: Option<f64>,
+}
+
+impl CadenceAnalyzer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn observe(&mut self, at: SystemTime, playlist: &MediaPlaylist) {
+        let baseline = self.last_position.is_none();
+        let mut first_new_part = true;
+        for (msn, index, part) in playlist.parts_with_position() {
+            if self.last_position.is_some_and(|last| (msn, index) <= last) {
+                continue;
+            }
+            self.last_position = Some((msn, index));
+            if baseline {
+                self.last_publish = Some((at, part.part_duration));
+                continue;
+            }
+            self.parts_observed += 1;
+            // Parts arriving together in one snapshot share a publish time,
+            // so only the first of them says anything about cadence
+            if first_new_part {
+                // A duration past what SystemTime holds can't be expected
+                let expected = self
+                    .last_publish
+                    .and_then(|(published, duration)| published.checked_add(duration));
+                if let Some(expected) = expected {
+                    let jitter = match at.duration_since(expected) {
+                        Ok(late) => late,
+                        Err(early) => early.duration(),
+                    };
+                    self.jitters.push(jitter);
+                }
+                if let Some(hint) = self.pending_hint.take() {
+                    self.hints_checked += 1;
+                    if hint == part.uri {
+                        self.hints_matched += 1;
+                    }
+                }
+                first_new_part = false;
+            }
+            self.last_publish = Some((at, part.part_duration));
+        }
+        // While nothing new is published keep checking against the hint
+        // that was outstanding
+        if baseline || !first_new_part || self.pending_hint.is_none() {
+            self.pending_hint = part_hint(playlist);
+        }
+        self.first_observed.get_or_insert(at);
+        self.last_observed = Some(at);
+    }
+
+    pub fn report(&self) -> CadenceReport {
+        let elapsed = match (self.first_observed, self.last_observed) {
+            (Some(first), Some(last)) => last.duration_since(first).ok(),
+            _ => None,
+        }
+        .filter(|elapsed| !elapsed.is_zero());
+        let mean_jitter = (!self.jitters.is_empty()).then(|| {
+            let total = self.jitters.iter().fold(Duration::ZERO, |total, jitter| {
+                total.saturating_add(*jitter)
+            });
+            total / u32::try_from(self.jitters.len()).unwrap_or(u32::MAX)
+        });
+        CadenceReport {
+            parts_observed: self.parts_observed,
+            parts_per_second: elapsed
+                .map(|elapsed| self.parts_observed as f64 / elapsed.as_secs_f64()),
+            mean_jitter,
+            max_jitter: self.jitters.iter().max().copied(),
+            hint_accuracy: (self.hints_checked > 0)
+                .then(|| self.hints_matched as f64 / self.hints_checked as f64),
+        }
+    }
+}
+
+fn part_hint(playlist: &MediaPlaylist) -> Option<String> {
+    playlist
+        .preload_hint_of(PreloadHintType::Part)
+        .map(|hint| hint.uri.clone())
+}
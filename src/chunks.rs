@@ -0,0 +1,81 @@
+use core::fmt;
+
+use crate::prelude::*;
+use crate::{CrLf, LineEnding, MediaPlaylist};
+
+/// Iterator over a rendered playlist in pieces, see
+/// [`MediaPlaylist::serialize_chunks`].
+pub struct Chunks<'a> {
+    playlist: &'a MediaPlaylist,
+    next: Position,
+}
+
+#[derive(Clone, Copy)]
+enum Position {
+    Header,
+    // Index into the listed segments
+    Segment(usize),
+    Done,
+}
+
+impl MediaPlaylist {
+    /// Renders the playlist a piece at a time: the header tags, then each
+    /// segment with its parts, then the parts of the segment still being
+    /// published with the preload hint and rendition reports. Concatenated,
+    /// the chunks equal the [`fmt::Display`] output.
+    ///
+    /// Only one chunk is formatted at a time, so a server can send the start
+    /// of a large playlist while the rest is still being rendered.
+    pub fn serialize_chunks(&self) -> Chunks<'_> {
+        Chunks {
+            playlist: self,
+            next: Position::Header,
+        }
+    }
+}
+
+impl Chunks<'_> {
+    fn render<F>(&self, render: F) -> String
+    where
+        F: Fn(&mut String) -> fmt::Result,
+    {
+        let mut chunk = String::new();
+        // Writing into a String can't fail
+        let _ = match self.playlist.line_ending() {
+            LineEnding::Lf => render(&mut chunk),
+            LineEnding::CrLf => {
+                let mut lf = String::new();
+                render(&mut lf).and_then(|_| fmt::Write::write_str(&mut CrLf(&mut chunk), &lf))
+            }
+        };
+        chunk
+    }
+}
+
+impl Iterator for Chunks<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        let playlist = self.playlist;
+        let segments = playlist.media_segments();
+        let chunk = match self.next {
+            Position::Header => {
+                self.next = Position::Segment(0);
+                self.render(|f| playlist.render_header(f))
+            }
+            Position::Segment(index) => match segments.get(index) {
+                Some(segment) => {
+                    self.next = Position::Segment(index + 1);
+                    self.render(|f| segment.render(f))
+                }
+                // The tail follows the last segment
+                None => {
+                    self.next = Position::Done;
+                    self.render(|f| playlist.render_tail(f))
+                }
+            },
+            Position::Done => return None,
+        };
+        Some(chunk)
+    }
+}
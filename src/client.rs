@@ -0,0 +1,196 @@
+use std::{error::Error, fmt, future::Future, time::Duration};
+
+use futures_util::Stream;
+
+use crate::{
+    read_media_playlist, reload::next_position, ClientSession, ErrorCode, MediaPlaylist, NoHooks,
+    ParseOptions, ParsePlaylistError, ReloadPolicy, ReloadRequest, SessionError,
+};
+
+/// Transport the [`LlHlsClient`] loads playlists over. Implemented for
+/// [`reqwest::Client`]; other HTTP stacks, or tests, can provide their own.
+pub trait Fetch {
+    type Error: Error + Send + Sync + 'static;
+
+    /// Loads the playlist at `url`, delivery directives included, as text.
+    /// For a blocking reload this only resolves once the server has
+    /// published the media asked for.
+    fn fetch(&self, url: &str) -> impl Future<Output = Result<String, Self::Error>> + Send;
+}
+
+/// Plain GET; a status other than 2xx is an error. The crate enables no TLS
+/// backend, so `https` URLs need one of reqwest's TLS features enabled by
+/// the application.
+impl Fetch for reqwest::Client {
+    type Error = reqwest::Error;
+
+    async fn fetch(&self, url: &str) -> Result<String, reqwest::Error> {
+        self.get(url).send().await?.error_for_status()?.text().await
+    }
+}
+
+#[derive(Debug)]
+pub enum ClientError {
+    /// The request failed or the server answered with an error status.
+    Fetch(Box<dyn Error + Send + Sync>),
+    /// The server didn't answer within the [`ReloadPolicy`] timeout.
+    Timeout,
+    Parse(ParsePlaylistError),
+    /// The response couldn't be applied to the playlist held so far. After
+    /// [`SessionError::SkippedSegmentsUnknown`] the next reload asks for the
+    /// full playlist.
+    Session(SessionError),
+}
+
+impl ClientError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ClientError::Fetch(_) => ErrorCode::FETCH_FAILED,
+            ClientError::Timeout => ErrorCode::BLOCKING_RELOAD_TIMEOUT,
+            ClientError::Parse(error) => error.code(),
+            ClientError::Session(error) => error.code(),
+        }
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Fetch(error) => write!(f, "couldn't load playlist: {}", error),
+            ClientError::Timeout => write!(f, "playlist reload timed out"),
+            ClientError::Parse(error) => write!(f, "invalid playlist: {}", error),
+            ClientError::Session(error) => write!(f, "couldn't apply playlist: {:?}", error),
+        }
+    }
+}
+
+impl Error for ClientError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ClientError::Fetch(error) => Some(error.as_ref()),
+            ClientError::Parse(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Follows a live media playlist with LL-HLS blocking reloads.
+///
+/// Once the playlist advertises `CAN-BLOCK-RELOAD=YES`, every reload asks
+/// with `_HLS_msn`/`_HLS_part` for the media after the newest one listed, so
+/// the server answers as soon as it is published. Servers that can't block
+/// are polled once per target duration instead, or after half of one when
+/// the previous reload found the playlist unchanged. Delta updates are asked for
+/// while the playlist advertises CAN-SKIP-UNTIL and merged through a
+/// [`ClientSession`], so each reload yields the full playlist.
+pub struct LlHlsClient<F> {
+    fetch: F,
+    uri: String,
+    options: ParseOptions,
+    policy: ReloadPolicy,
+    session: ClientSession,
+    // Set after a delta update couldn't be merged
+    full_reload: bool,
+    // Set when the last reload listed no new media
+    unchanged: bool,
+}
+
+impl<F: Fetch> LlHlsClient<F> {
+    pub fn new(fetch: F, uri: impl Into<String>) -> Self {
+        let uri = uri.into();
+        LlHlsClient {
+            fetch,
+            options: ParseOptions {
+                playlist_url: Some(uri.clone()),
+                ..ParseOptions::default()
+            },
+            uri,
+            policy: ReloadPolicy::default(),
+            session: ClientSession::new(),
+            full_reload: false,
+            unchanged: false,
+        }
+    }
+
+    pub fn policy(mut self, policy: ReloadPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Options the playlists are parsed with. Without a
+    /// [`ParseOptions::playlist_url`], QUERYPARAM variables take their values
+    /// from the URL the client was created with.
+    pub fn options(mut self, mut options: ParseOptions) -> Self {
+        options.playlist_url.get_or_insert_with(|| self.uri.clone());
+        self.options = options;
+        self
+    }
+
+    pub fn session(&self) -> &ClientSession {
+        &self.session
+    }
+
+    /// The request the next [`LlHlsClient::reload`] issues.
+    pub fn next_request(&self) -> ReloadRequest {
+        let blocking = self
+            .session
+            .playlist()
+            .filter(|playlist| playlist.server_control.can_block_reload)
+            .map(|playlist| next_position(playlist.last_position()));
+        ReloadRequest {
+            uri: self.uri.clone(),
+            msn: blocking.map(|(msn, _)| msn),
+            part: blocking.and_then(|(_, part)| part),
+            skip: !self.full_reload && self.session.wants_delta(),
+        }
+    }
+
+    /// Issues the next reload and returns the playlist as of its response.
+    /// Without blocking support it first waits a target duration, or half of
+    /// one if the previous reload found the playlist unchanged. On error the
+    /// previous playlist is kept and the next call tries again.
+    pub async fn reload(&mut self) -> Result<MediaPlaylist, ClientError> {
+        let request = self.next_request();
+        if let Some(playlist) = self.session.playlist() {
+            if request.msn.is_none() {
+                let mut wait = Duration::from_secs(playlist.target_duration.into());
+                if self.unchanged {
+                    wait /= 2;
+                }
+                tokio::time::sleep(wait).await;
+            }
+        }
+        let timeout = self.policy.timeout(self.session.playlist());
+        let text = tokio::time::timeout(timeout, self.fetch.fetch(&request.url()))
+            .await
+            .map_err(|_| ClientError::Timeout)?
+            .map_err(|error| ClientError::Fetch(Box::new(error)))?;
+        let response = read_media_playlist(text.as_bytes(), &self.options, &mut NoHooks)
+            .map_err(ClientError::Parse)?;
+        let previous_edge = self.session.playlist().map(MediaPlaylist::last_position);
+        if let Err(error) = self.session.receive(response) {
+            self.full_reload = matches!(error, SessionError::SkippedSegmentsUnknown { .. });
+            return Err(ClientError::Session(error));
+        }
+        self.full_reload = false;
+        // The session holds a playlist once it received one
+        let playlist = self
+            .session
+            .playlist()
+            .cloned()
+            .ok_or(ClientError::Session(SessionError::NoBasePlaylist))?;
+        self.unchanged = previous_edge == Some(playlist.last_position());
+        Ok(playlist)
+    }
+
+    /// Reloads until the playlist ends with EXT-X-ENDLIST, yielding the
+    /// playlist after every response, or the error a reload failed with.
+    pub fn updates(self) -> impl Stream<Item = Result<MediaPlaylist, ClientError>> {
+        futures_util::stream::unfold(Some(self), |client| async move {
+            let mut client = client?;
+            let result = client.reload().await;
+            let ended = result.as_ref().is_ok_and(|playlist| !playlist.is_live());
+            Some((result, (!ended).then_some(client)))
+        })
+    }
+}
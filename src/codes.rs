@@ -0,0 +1,437 @@
+use core::fmt;
+
+#[cfg(feature = "tokio")]
+use crate::WaitTimeout;
+use crate::{ParsePlaylistError, Rule, SessionError, ValidationIssue};
+
+/// Stable identifier of a class of failure, for logs, metrics and callers
+/// across an FFI boundary.
+///
+/// Numbers and names never change meaning between releases; new failures get
+/// new codes. Parse errors are numbered from 100, validation rules from 200
+/// and client errors from 300. [`ERROR_CODES`] lists them all.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ErrorCode {
+    pub code: u16,
+    /// Dotted lowercase name, e.g. `client.blocking_reload_timeout`.
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+const fn code(code: u16, name: &'static str, description: &'static str) -> ErrorCode {
+    ErrorCode {
+        code,
+        name,
+        description,
+    }
+}
+
+impl ErrorCode {
+    pub const EXTM3U_MISSING: ErrorCode = code(
+        100,
+        "parse.extm3u_missing",
+        "the file doesn't start with #EXTM3U",
+    );
+    pub const INVALID_TAG: ErrorCode = code(101, "parse.invalid_tag", "a tag value is malformed");
+    pub const IO_ERROR: ErrorCode = code(102, "parse.io_error", "the file couldn't be read");
+    /// No longer produced: unknown tags are reported to
+    /// [`crate::Hooks::on_unknown_tag`] instead of failing the parse.
+    pub const UNRECOGNIZED_TAG: ErrorCode =
+        code(103, "parse.unrecognized_tag", "a tag isn't supported");
+    pub const DUPLICATE_TAG: ErrorCode = code(
+        104,
+        "parse.duplicate_tag",
+        "a tag allowed once per playlist is repeated",
+    );
+    pub const INVALID_NUMBER: ErrorCode = code(
+        105,
+        "parse.invalid_number",
+        "a number is NaN, infinite, negative or too large",
+    );
+    pub const MISSING_FIELD: ErrorCode = code(
+        106,
+        "parse.missing_field",
+        "a tag lacks a required attribute",
+    );
+    pub const MISSING_TARGET_DURATION: ErrorCode = code(
+        107,
+        "parse.missing_target_duration",
+        "the playlist has no EXT-X-TARGETDURATION",
+    );
+    pub const MISSING_VERSION: ErrorCode = code(
+        108,
+        "parse.missing_version",
+        "the playlist has no EXT-X-VERSION",
+    );
+    /// No longer produced: a playlist without EXT-X-MEDIA-SEQUENCE starts
+    /// at 0.
+    pub const MISSING_MEDIA_SEQUENCE: ErrorCode = code(
+        109,
+        "parse.missing_media_sequence",
+        "the playlist has no EXT-X-MEDIA-SEQUENCE",
+    );
+    /// No longer produced: a playlist without EXT-X-SERVER-CONTROL has the
+    /// [`crate::ServerControl::default`] one.
+    pub const MISSING_SERVER_CONTROL: ErrorCode = code(
+        110,
+        "parse.missing_server_control",
+        "the playlist has no EXT-X-SERVER-CONTROL",
+    );
+    pub const MISSING_PART_INF: ErrorCode = code(
+        111,
+        "parse.missing_part_inf",
+        "EXT-X-PART-INF lacks PART-TARGET",
+    );
+    pub const INVALID_URI: ErrorCode = code(112, "parse.invalid_uri", "a URI line is malformed");
+    pub const UNDEFINED_VARIABLE: ErrorCode = code(
+        113,
+        "parse.undefined_variable",
+        "a variable is used or imported without a value",
+    );
+    pub const INVALID_UTF8: ErrorCode = code(114, "parse.invalid_utf8", "a line isn't valid UTF-8");
+
+    pub const SEGMENT_EXCEEDS_TARGET_DURATION: ErrorCode = code(
+        200,
+        "validation.segment_exceeds_target_duration",
+        "a segment is longer than EXT-X-TARGETDURATION",
+    );
+    pub const VERSION_TOO_LOW: ErrorCode = code(
+        201,
+        "validation.version_too_low",
+        "EXT-X-VERSION is lower than the playlist's features require",
+    );
+    pub const TARGET_DURATION_NOT_RECOMMENDED: ErrorCode = code(
+        202,
+        "validation.target_duration_not_recommended",
+        "EXT-X-TARGETDURATION isn't the recommended 6 seconds",
+    );
+    pub const PROGRAM_DATE_TIME_MISSING: ErrorCode = code(
+        203,
+        "validation.program_date_time_missing",
+        "no segment carries EXT-X-PROGRAM-DATE-TIME",
+    );
+    pub const PRELOAD_HINT_MISSING: ErrorCode = code(
+        204,
+        "validation.preload_hint_missing",
+        "a low-latency playlist has no EXT-X-PRELOAD-HINT",
+    );
+    pub const BLOCKING_RELOAD_UNSUPPORTED: ErrorCode = code(
+        205,
+        "validation.blocking_reload_unsupported",
+        "a low-latency playlist doesn't set CAN-BLOCK-RELOAD=YES",
+    );
+    pub const PART_HOLD_BACK_TOO_SHORT: ErrorCode = code(
+        206,
+        "validation.part_hold_back_too_short",
+        "PART-HOLD-BACK is less than three times PART-TARGET",
+    );
+    pub const RENDITION_REPORTS_MISSING: ErrorCode = code(
+        207,
+        "validation.rendition_reports_missing",
+        "a low-latency playlist has no EXT-X-RENDITION-REPORT",
+    );
+    pub const UNEVEN_PART_DURATIONS: ErrorCode = code(
+        208,
+        "validation.uneven_part_durations",
+        "parts of a segment last different durations",
+    );
+    pub const PARTS_DO_NOT_SUM_TO_SEGMENT: ErrorCode = code(
+        209,
+        "validation.parts_do_not_sum_to_segment",
+        "the parts of a segment don't add up to its EXTINF",
+    );
+    pub const PRELOAD_HINT_ALREADY_PUBLISHED: ErrorCode = code(
+        210,
+        "validation.preload_hint_already_published",
+        "the preload hint names a part that is already listed",
+    );
+    pub const PRELOAD_HINT_SKIPS_PARTS: ErrorCode = code(
+        211,
+        "validation.preload_hint_skips_parts",
+        "the preload hint isn't the part after the newest one",
+    );
+    pub const RENDITION_REPORT_STALE: ErrorCode = code(
+        212,
+        "validation.rendition_report_stale",
+        "a rendition report lags the playlist",
+    );
+    pub const RENDITION_REPORT_AHEAD: ErrorCode = code(
+        213,
+        "validation.rendition_report_ahead",
+        "a rendition report is ahead of the playlist",
+    );
+    pub const RENDITION_REPORT_URI_NOT_RELATIVE: ErrorCode = code(
+        214,
+        "validation.rendition_report_uri_not_relative",
+        "a rendition report URI isn't relative",
+    );
+    pub const SKIP_NOT_ALLOWED: ErrorCode = code(
+        215,
+        "validation.skip_not_allowed",
+        "EXT-X-SKIP without CAN-SKIP-UNTIL",
+    );
+    pub const SKIP_WINDOW_TOO_LARGE: ErrorCode = code(
+        216,
+        "validation.skip_window_too_large",
+        "EXT-X-SKIP skips more than CAN-SKIP-UNTIL allows",
+    );
+    pub const MIXED_CONTAINER_FORMATS: ErrorCode = code(
+        217,
+        "validation.mixed_container_formats",
+        "segments and parts use more than one container format",
+    );
+    pub const MAP_HINT_IN_MPEG_TS: ErrorCode = code(
+        218,
+        "validation.map_hint_in_mpeg_ts",
+        "a MAP preload hint in an MPEG-TS playlist",
+    );
+    pub const INDEPENDENT_PART_MISSING: ErrorCode = code(
+        219,
+        "validation.independent_part_missing",
+        "a segment has no part with INDEPENDENT=YES",
+    );
+    pub const MAP_CHANGE_WITHOUT_DISCONTINUITY: ErrorCode = code(
+        220,
+        "validation.map_change_without_discontinuity",
+        "EXT-X-MAP changes without EXT-X-DISCONTINUITY",
+    );
+    pub const MEDIA_DOES_NOT_MATCH_MAP: ErrorCode = code(
+        221,
+        "validation.media_does_not_match_map",
+        "media segments don't match the format of EXT-X-MAP",
+    );
+    pub const PART_INF_MISSING: ErrorCode = code(
+        222,
+        "validation.part_inf_missing",
+        "the playlist lists parts without EXT-X-PART-INF",
+    );
+    pub const PART_EXCEEDS_PART_TARGET: ErrorCode = code(
+        223,
+        "validation.part_exceeds_part_target",
+        "a part is longer than PART-TARGET",
+    );
+    pub const PART_HOLD_BACK_BELOW_MINIMUM: ErrorCode = code(
+        224,
+        "validation.part_hold_back_below_minimum",
+        "PART-HOLD-BACK is less than twice PART-TARGET",
+    );
+    pub const SKIP_WINDOW_BELOW_MINIMUM: ErrorCode = code(
+        225,
+        "validation.skip_window_below_minimum",
+        "CAN-SKIP-UNTIL is less than six times EXT-X-TARGETDURATION",
+    );
+    pub const MEDIA_SEQUENCE_DECREASED: ErrorCode = code(
+        226,
+        "validation.media_sequence_decreased",
+        "EXT-X-MEDIA-SEQUENCE went back between reloads",
+    );
+    pub const LIVE_EDGE_REGRESSED: ErrorCode = code(
+        227,
+        "validation.live_edge_regressed",
+        "the newest segment or part went back between reloads",
+    );
+    pub const PART_TARGET_MISMATCH: ErrorCode = code(
+        228,
+        "validation.part_target_mismatch",
+        "renditions advertise different PART-TARGETs",
+    );
+    pub const RENDITION_REPORT_UNKNOWN: ErrorCode = code(
+        229,
+        "validation.rendition_report_unknown",
+        "a rendition report names a playlist the multivariant playlist doesn't list",
+    );
+    pub const STREAM_INF_ATTRIBUTE_MISSING: ErrorCode = code(
+        230,
+        "validation.stream_inf_attribute_missing",
+        "EXT-X-STREAM-INF lacks CODECS, or RESOLUTION for video",
+    );
+    pub const STREAM_INF_ATTRIBUTE_INVALID: ErrorCode = code(
+        231,
+        "validation.stream_inf_attribute_invalid",
+        "EXT-X-STREAM-INF has a malformed BANDWIDTH, CODECS or RESOLUTION",
+    );
+
+    pub const NO_BASE_PLAYLIST: ErrorCode = code(
+        300,
+        "client.no_base_playlist",
+        "a delta update arrived before any full playlist",
+    );
+    pub const SKIPPED_SEGMENTS_UNKNOWN: ErrorCode = code(
+        301,
+        "client.skipped_segments_unknown",
+        "a delta update skipped segments the client never saw",
+    );
+    pub const UNKNOWN_RENDITION: ErrorCode = code(
+        302,
+        "client.unknown_rendition",
+        "a response arrived for a rendition that was never added",
+    );
+    pub const BLOCKING_RELOAD_TIMEOUT: ErrorCode = code(
+        303,
+        "client.blocking_reload_timeout",
+        "a blocking reload wasn't satisfied before its deadline",
+    );
+    pub const FETCH_FAILED: ErrorCode = code(
+        304,
+        "client.fetch_failed",
+        "a playlist request failed or got an error status",
+    );
+
+    /// The code with number `code`, if there is one.
+    pub fn from_code(code: u16) -> Option<ErrorCode> {
+        ERROR_CODES.iter().find(|entry| entry.code == code).copied()
+    }
+
+    /// The code named `name`, if there is one.
+    pub fn from_name(name: &str) -> Option<ErrorCode> {
+        ERROR_CODES.iter().find(|entry| entry.name == name).copied()
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.code, self.name)
+    }
+}
+
+/// Every [`ErrorCode`], by number.
+pub const ERROR_CODES: &[ErrorCode] = &[
+    ErrorCode::EXTM3U_MISSING,
+    ErrorCode::INVALID_TAG,
+    ErrorCode::IO_ERROR,
+    ErrorCode::UNRECOGNIZED_TAG,
+    ErrorCode::DUPLICATE_TAG,
+    ErrorCode::INVALID_NUMBER,
+    ErrorCode::MISSING_FIELD,
+    ErrorCode::MISSING_TARGET_DURATION,
+    ErrorCode::MISSING_VERSION,
+    ErrorCode::MISSING_MEDIA_SEQUENCE,
+    ErrorCode::MISSING_SERVER_CONTROL,
+    ErrorCode::MISSING_PART_INF,
+    ErrorCode::INVALID_URI,
+    ErrorCode::UNDEFINED_VARIABLE,
+    ErrorCode::INVALID_UTF8,
+    ErrorCode::SEGMENT_EXCEEDS_TARGET_DURATION,
+    ErrorCode::VERSION_TOO_LOW,
+    ErrorCode::TARGET_DURATION_NOT_RECOMMENDED,
+    ErrorCode::PROGRAM_DATE_TIME_MISSING,
+    ErrorCode::PRELOAD_HINT_MISSING,
+    ErrorCode::BLOCKING_RELOAD_UNSUPPORTED,
+    ErrorCode::PART_HOLD_BACK_TOO_SHORT,
+    ErrorCode::RENDITION_REPORTS_MISSING,
+    ErrorCode::UNEVEN_PART_DURATIONS,
+    ErrorCode::PARTS_DO_NOT_SUM_TO_SEGMENT,
+    ErrorCode::PRELOAD_HINT_ALREADY_PUBLISHED,
+    ErrorCode::PRELOAD_HINT_SKIPS_PARTS,
+    ErrorCode::RENDITION_REPORT_STALE,
+    ErrorCode::RENDITION_REPORT_AHEAD,
+    ErrorCode::RENDITION_REPORT_URI_NOT_RELATIVE,
+    ErrorCode::SKIP_NOT_ALLOWED,
+    ErrorCode::SKIP_WINDOW_TOO_LARGE,
+    ErrorCode::MIXED_CONTAINER_FORMATS,
+    ErrorCode::MAP_HINT_IN_MPEG_TS,
+    ErrorCode::INDEPENDENT_PART_MISSING,
+    ErrorCode::MAP_CHANGE_WITHOUT_DISCONTINUITY,
+    ErrorCode::MEDIA_DOES_NOT_MATCH_MAP,
+    ErrorCode::PART_INF_MISSING,
+    ErrorCode::PART_EXCEEDS_PART_TARGET,
+    ErrorCode::PART_HOLD_BACK_BELOW_MINIMUM,
+    ErrorCode::SKIP_WINDOW_BELOW_MINIMUM,
+    ErrorCode::MEDIA_SEQUENCE_DECREASED,
+    ErrorCode::LIVE_EDGE_REGRESSED,
+    ErrorCode::PART_TARGET_MISMATCH,
+    ErrorCode::RENDITION_REPORT_UNKNOWN,
+    ErrorCode::STREAM_INF_ATTRIBUTE_MISSING,
+    ErrorCode::STREAM_INF_ATTRIBUTE_INVALID,
+    ErrorCode::NO_BASE_PLAYLIST,
+    ErrorCode::SKIPPED_SEGMENTS_UNKNOWN,
+    ErrorCode::UNKNOWN_RENDITION,
+    ErrorCode::BLOCKING_RELOAD_TIMEOUT,
+    ErrorCode::FETCH_FAILED,
+];
+
+impl ParsePlaylistError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ParsePlaylistError::MissingHeader => ErrorCode::EXTM3U_MISSING,
+            ParsePlaylistError::InvalidTag { .. } => ErrorCode::INVALID_TAG,
+            #[cfg(feature = "std")]
+            ParsePlaylistError::Io(_) => ErrorCode::IO_ERROR,
+            ParsePlaylistError::InvalidUri { .. } => ErrorCode::INVALID_URI,
+            ParsePlaylistError::InvalidUtf8 { .. } => ErrorCode::INVALID_UTF8,
+            ParsePlaylistError::DuplicateTag { .. } => ErrorCode::DUPLICATE_TAG,
+            ParsePlaylistError::InvalidNumber { .. } => ErrorCode::INVALID_NUMBER,
+            ParsePlaylistError::UndefinedVariable { .. } => ErrorCode::UNDEFINED_VARIABLE,
+            // Missing header tags get their own codes, as they are what
+            // operators alert on
+            ParsePlaylistError::MissingField { tag, .. } => match tag.as_str() {
+                "EXT-X-TARGETDURATION" => ErrorCode::MISSING_TARGET_DURATION,
+                "EXT-X-VERSION" => ErrorCode::MISSING_VERSION,
+                "EXT-X-PART-INF" => ErrorCode::MISSING_PART_INF,
+                _ => ErrorCode::MISSING_FIELD,
+            },
+        }
+    }
+}
+
+impl Rule {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Rule::SegmentExceedsTargetDuration => ErrorCode::SEGMENT_EXCEEDS_TARGET_DURATION,
+            Rule::VersionTooLow => ErrorCode::VERSION_TOO_LOW,
+            Rule::TargetDurationNotRecommended => ErrorCode::TARGET_DURATION_NOT_RECOMMENDED,
+            Rule::ProgramDateTimeMissing => ErrorCode::PROGRAM_DATE_TIME_MISSING,
+            Rule::PreloadHintMissing => ErrorCode::PRELOAD_HINT_MISSING,
+            Rule::BlockingReloadUnsupported => ErrorCode::BLOCKING_RELOAD_UNSUPPORTED,
+            Rule::PartHoldBackTooShort => ErrorCode::PART_HOLD_BACK_TOO_SHORT,
+            Rule::RenditionReportsMissing => ErrorCode::RENDITION_REPORTS_MISSING,
+            Rule::UnevenPartDurations => ErrorCode::UNEVEN_PART_DURATIONS,
+            Rule::PartsDoNotSumToSegment => ErrorCode::PARTS_DO_NOT_SUM_TO_SEGMENT,
+            Rule::PreloadHintAlreadyPublished => ErrorCode::PRELOAD_HINT_ALREADY_PUBLISHED,
+            Rule::PreloadHintSkipsParts => ErrorCode::PRELOAD_HINT_SKIPS_PARTS,
+            Rule::RenditionReportStale => ErrorCode::RENDITION_REPORT_STALE,
+            Rule::RenditionReportAhead => ErrorCode::RENDITION_REPORT_AHEAD,
+            Rule::RenditionReportUriNotRelative => ErrorCode::RENDITION_REPORT_URI_NOT_RELATIVE,
+            Rule::SkipNotAllowed => ErrorCode::SKIP_NOT_ALLOWED,
+            Rule::SkipWindowTooLarge => ErrorCode::SKIP_WINDOW_TOO_LARGE,
+            Rule::MixedContainerFormats => ErrorCode::MIXED_CONTAINER_FORMATS,
+            Rule::MapHintInMpegTs => ErrorCode::MAP_HINT_IN_MPEG_TS,
+            Rule::IndependentPartMissing => ErrorCode::INDEPENDENT_PART_MISSING,
+            Rule::MapChangeWithoutDiscontinuity => ErrorCode::MAP_CHANGE_WITHOUT_DISCONTINUITY,
+            Rule::MediaDoesNotMatchMap => ErrorCode::MEDIA_DOES_NOT_MATCH_MAP,
+            Rule::PartInfMissing => ErrorCode::PART_INF_MISSING,
+            Rule::PartExceedsPartTarget => ErrorCode::PART_EXCEEDS_PART_TARGET,
+            Rule::PartHoldBackBelowMinimum => ErrorCode::PART_HOLD_BACK_BELOW_MINIMUM,
+            Rule::SkipWindowBelowMinimum => ErrorCode::SKIP_WINDOW_BELOW_MINIMUM,
+            Rule::MediaSequenceDecreased => ErrorCode::MEDIA_SEQUENCE_DECREASED,
+            Rule::LiveEdgeRegressed => ErrorCode::LIVE_EDGE_REGRESSED,
+            Rule::PartTargetMismatch => ErrorCode::PART_TARGET_MISMATCH,
+            Rule::RenditionReportUnknown => ErrorCode::RENDITION_REPORT_UNKNOWN,
+            Rule::StreamInfAttributeMissing => ErrorCode::STREAM_INF_ATTRIBUTE_MISSING,
+            Rule::StreamInfAttributeInvalid => ErrorCode::STREAM_INF_ATTRIBUTE_INVALID,
+        }
+    }
+}
+
+impl ValidationIssue {
+    pub fn code(&self) -> ErrorCode {
+        self.rule.code()
+    }
+}
+
+impl SessionError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            SessionError::NoBasePlaylist => ErrorCode::NO_BASE_PLAYLIST,
+            SessionError::SkippedSegmentsUnknown { .. } => ErrorCode::SKIPPED_SEGMENTS_UNKNOWN,
+            SessionError::UnknownRendition { .. } => ErrorCode::UNKNOWN_RENDITION,
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl WaitTimeout {
+    pub fn code(&self) -> ErrorCode {
+        ErrorCode::BLOCKING_RELOAD_TIMEOUT
+    }
+}
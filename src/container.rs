@@ -0,0 +1,50 @@
+use crate::{uri, MediaPlaylist};
+
+/// Container format of media segments and parts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContainerFormat {
+    /// Fragmented MP4 / CMAF, which needs an EXT-X-MAP init segment.
+    Fmp4,
+    /// MPEG-2 transport stream. Every segment and part carries its own
+    /// PAT/PMT, so there is no EXT-X-MAP.
+    MpegTs,
+    /// WebVTT subtitles. Cues are plain text, so like transport streams
+    /// there is no EXT-X-MAP.
+    WebVtt,
+}
+
+impl ContainerFormat {
+    /// Guesses the format from the extension of a segment or part URI.
+    pub fn from_uri(uri: &str) -> Option<ContainerFormat> {
+        let path = uri.split(['?', '#']).next().unwrap_or(uri);
+        let extension = path.rsplit_once('.')?.1.to_ascii_lowercase();
+        match extension.as_str() {
+            "ts" => Some(ContainerFormat::MpegTs),
+            "vtt" | "webvtt" => Some(ContainerFormat::WebVtt),
+            "mp4" | "m4s" | "m4v" | "m4a" | "cmfv" | "cmfa" | "cmft" => Some(ContainerFormat::Fmp4),
+            _ => None,
+        }
+    }
+}
+
+impl MediaPlaylist {
+    /// The container format of the listed segments and parts, as far as
+    /// their URIs tell. `None` when it can't be told or formats are mixed.
+    pub fn container_format(&self) -> Option<ContainerFormat> {
+        let mut formats = self.container_formats();
+        let first = formats.next()?;
+        formats.all(|format| format == first).then_some(first)
+    }
+
+    // Formats of every segment and part whose URI has a known extension
+    pub(crate) fn container_formats(&self) -> impl Iterator<Item = ContainerFormat> + '_ {
+        self.media_segments
+            .iter()
+            .map(|segment| uri::as_str(&segment.uri))
+            .chain(
+                self.parts_with_position()
+                    .map(|(_, _, part)| part.uri.as_str()),
+            )
+            .filter_map(ContainerFormat::from_uri)
+    }
+}
@@ -0,0 +1,204 @@
+use alloc::sync::Arc;
+use core::{any::Any, fmt};
+
+use crate::prelude::*;
+use crate::{ParseTagError, PartialSegment};
+
+/// A tag this crate doesn't know, defined by the application, e.g.
+/// `#EXT-X-CUE-OUT:DURATION=30`.
+///
+/// Custom tags belong to the segment they precede. They are parsed by the
+/// parsers of a [`TagRegistry`] and rendered with the segment, before its
+/// parts and EXTINF. They are left out when a playlist is serialized with
+/// the `serde` feature, as they can't be deserialized without the registry.
+pub trait CustomTag: fmt::Debug + Send + Sync + 'static {
+    /// Name of the tag without the leading `#`.
+    fn name(&self) -> &str;
+
+    /// What follows the colon, or `None` for a tag without a value.
+    fn value(&self) -> Option<String>;
+
+    /// `self` as [`Any`], so [`downcast_ref`](trait.CustomTag.html#method.downcast_ref)
+    /// can get the concrete type back.
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl dyn CustomTag {
+    /// The tag as its concrete type, if it is a `T`.
+    pub fn downcast_ref<T: CustomTag>(&self) -> Option<&T> {
+        self.as_any().downcast_ref()
+    }
+}
+
+impl fmt::Display for dyn CustomTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.name())?;
+        if let Some(value) = self.value() {
+            write!(f, ":{}", value)?;
+        }
+        Ok(())
+    }
+}
+
+type TagParser = dyn Fn(&str) -> Result<Arc<dyn CustomTag>, ParseTagError> + Send + Sync;
+
+/// Parsers for custom tags by tag name, see
+/// [`ParseOptions::custom_tags`](crate::ParseOptions::custom_tags).
+#[derive(Clone, Default)]
+pub struct TagRegistry {
+    parsers: Vec<(String, Arc<TagParser>)>,
+}
+
+impl TagRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses every `#<name>` tag with `parse`, which gets the value after
+    /// the colon (empty without one). Registering a name again replaces its
+    /// parser; built-in tags can't be overridden.
+    pub fn register<F>(&mut self, name: impl Into<String>, parse: F)
+    where
+        F: Fn(&str) -> Result<Arc<dyn CustomTag>, ParseTagError> + Send + Sync + 'static,
+    {
+        let name = name.into();
+        self.parsers.retain(|(existing, _)| *existing != name);
+        self.parsers.push((name, Arc::new(parse)));
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.names().any(|existing| existing == name)
+    }
+
+    // Only called for names the registry contains
+    pub(crate) fn parse(
+        &self,
+        name: &str,
+        value: &str,
+    ) -> Result<Arc<dyn CustomTag>, ParseTagError> {
+        let (_, parse) = self
+            .parsers
+            .iter()
+            .find(|(existing, _)| existing == name)
+            .ok_or_else(|| ParseTagError::Unknown {
+                tag: name.to_string(),
+            })?;
+        parse(value)
+    }
+
+    fn names(&self) -> impl Iterator<Item = &str> {
+        self.parsers.iter().map(|(name, _)| name.as_str())
+    }
+}
+
+impl fmt::Debug for TagRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+// Registries are compared by the tags they handle, as closures can't be
+// compared
+impl PartialEq for TagRegistry {
+    fn eq(&self, other: &Self) -> bool {
+        self.names().eq(other.names())
+    }
+}
+
+impl Eq for TagRegistry {}
+
+/// A tag the crate doesn't know and no [`TagRegistry`] parser was registered
+/// for, e.g. `#EXT-X-CUE-OUT:DURATION=30`, kept as read so it is written
+/// back when the playlist is serialized.
+///
+/// Unknown tags before the first segment belong to the playlist and render
+/// after its other header tags. The others belong to the segment they
+/// precede and render at their [`TagPosition`] among its parts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnknownTag {
+    /// Name of the tag without the leading `#`.
+    pub name: String,
+    /// What follows the colon, or `None` for a tag without a value.
+    pub value: Option<String>,
+    /// Where the tag was read among the tags of its segment. Ignored for
+    /// those of the playlist header.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub position: TagPosition,
+}
+
+impl UnknownTag {
+    /// A tag rendering before the parts of its segment.
+    pub fn new(name: impl Into<String>, value: Option<String>) -> Self {
+        UnknownTag {
+            name: name.into(),
+            value,
+            position: TagPosition::default(),
+        }
+    }
+
+    // A tag line, without its line terminator
+    pub(crate) fn parse(line: &str) -> Self {
+        let line = line.strip_prefix('#').unwrap_or(line);
+        match line.split_once(':') {
+            Some((name, value)) => UnknownTag::new(name, Some(value.to_string())),
+            None => UnknownTag::new(line, None),
+        }
+    }
+}
+
+/// Where an [`UnknownTag`] renders among the tags of its segment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TagPosition {
+    /// Before part `n` of the segment, or after its last part if it has no
+    /// more than `n`.
+    BeforePart(usize),
+    /// Last in the playlist, e.g. after EXT-X-ENDLIST. Only applies to the
+    /// tags of the segment still being published; those of a listed segment
+    /// render after its parts.
+    End,
+}
+
+impl Default for TagPosition {
+    fn default() -> Self {
+        TagPosition::BeforePart(0)
+    }
+}
+
+// Renders `parts` with the unknown tags of their segment before the part
+// each was read before. Those of `TagPosition::End` render after them
+// unless `end` takes them.
+pub(crate) fn render_parts<W: fmt::Write>(
+    f: &mut W,
+    parts: &[PartialSegment],
+    tags: &[UnknownTag],
+    end: bool,
+) -> fmt::Result {
+    let before = |index: usize| {
+        tags.iter().filter(move |tag| match tag.position {
+            TagPosition::BeforePart(n) => n == index || (n > index && index == parts.len()),
+            TagPosition::End => !end && index == parts.len(),
+        })
+    };
+    for (index, part) in parts.iter().enumerate() {
+        for tag in before(index) {
+            writeln!(f, "{}", tag)?;
+        }
+        writeln!(f, "{}", part)?;
+    }
+    for tag in before(parts.len()) {
+        writeln!(f, "{}", tag)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for UnknownTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.name)?;
+        if let Some(value) = &self.value {
+            write!(f, ":{}", value)?;
+        }
+        Ok(())
+    }
+}
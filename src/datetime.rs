@@ -0,0 +1,123 @@
+use core::fmt;
+#[cfg(feature = "std")]
+use std::time::SystemTime;
+
+#[cfg(not(feature = "chrono"))]
+use crate::prelude::*;
+use crate::InvalidValue;
+
+/// Wall-clock time used by PROGRAM-DATE-TIME and DATERANGE dates. Without
+/// the `chrono` feature, dates are kept as the raw ISO 8601 text. The `time`
+/// feature doesn't change this type, whichever else is enabled: it adds
+/// accessors such as `MediaSegment::program_date_time_offset` and
+/// `from_offset_date_time` to convert from and to
+/// `time::OffsetDateTime`, and checks dates kept as text.
+#[cfg(feature = "chrono")]
+pub type DateTime = chrono::DateTime<chrono::Utc>;
+#[cfg(not(feature = "chrono"))]
+pub type DateTime = String;
+
+#[cfg(feature = "chrono")]
+pub(crate) fn parse(s: &str) -> Result<DateTime, InvalidValue> {
+    s.parse().map_err(|_| InvalidValue)
+}
+
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+pub(crate) fn parse(s: &str) -> Result<DateTime, InvalidValue> {
+    parse_offset(s).ok_or(InvalidValue)?;
+    Ok(s.to_string())
+}
+
+#[cfg(not(any(feature = "chrono", feature = "time")))]
+pub(crate) fn parse(s: &str) -> Result<DateTime, InvalidValue> {
+    Ok(s.to_string())
+}
+
+// Written field by field, as chrono's formatter renders into a String first
+#[cfg(feature = "chrono")]
+pub(crate) fn display(value: &DateTime) -> impl fmt::Display + '_ {
+    use chrono::{Datelike, Timelike};
+
+    struct Rendered<'a>(&'a DateTime);
+
+    impl fmt::Display for Rendered<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let date = self.0;
+            // chrono counts a leap second as 1000ms and more into the
+            // second before it
+            let leap = date.nanosecond() / 1_000_000_000;
+            write!(
+                f,
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+                date.year(),
+                date.month(),
+                date.day(),
+                date.hour(),
+                date.minute(),
+                date.second() + leap,
+                date.nanosecond() % 1_000_000_000 / 1_000_000
+            )
+        }
+    }
+
+    Rendered(value)
+}
+
+#[cfg(not(feature = "chrono"))]
+pub(crate) fn display(value: &DateTime) -> impl fmt::Display + '_ {
+    value
+}
+
+// The instant a date stands for; dates kept as text can't tell unless the
+// `time` feature reads them
+#[cfg(feature = "chrono")]
+pub(crate) fn to_system_time(value: &DateTime) -> Option<SystemTime> {
+    Some((*value).into())
+}
+
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+pub(crate) fn to_system_time(value: &DateTime) -> Option<SystemTime> {
+    parse_offset(value).map(Into::into)
+}
+
+#[cfg(all(feature = "std", not(any(feature = "chrono", feature = "time"))))]
+pub(crate) fn to_system_time(_value: &DateTime) -> Option<SystemTime> {
+    None
+}
+
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+fn parse_offset(s: &str) -> Option<time::OffsetDateTime> {
+    time::OffsetDateTime::parse(s, &time::format_description::well_known::Rfc3339).ok()
+}
+
+// None for text that isn't an RFC 3339 date, which only a DateTime built by
+// hand can be
+#[cfg(feature = "time")]
+pub(crate) fn to_offset_date_time(value: &DateTime) -> Option<time::OffsetDateTime> {
+    to_system_time(value).map(Into::into)
+}
+
+/// The [`DateTime`] of a `time` date, for building playlists with the `time`
+/// feature. Without `chrono` it renders with millisecond precision, e.g.
+/// `2019-02-14T02:14:00.106Z`.
+#[cfg(feature = "time")]
+pub fn from_offset_date_time(value: time::OffsetDateTime) -> DateTime {
+    #[cfg(feature = "chrono")]
+    {
+        SystemTime::from(value).into()
+    }
+    #[cfg(not(feature = "chrono"))]
+    {
+        let utc = value.to_offset(time::UtcOffset::UTC);
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+            utc.year(),
+            u8::from(utc.month()),
+            utc.day(),
+            utc.hour(),
+            utc.minute(),
+            utc.second(),
+            utc.millisecond()
+        )
+    }
+}
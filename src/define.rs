@@ -0,0 +1,189 @@
+use alloc::{borrow::Cow, collections::BTreeMap};
+use core::{fmt, str::FromStr};
+
+use crate::prelude::*;
+use crate::{attribute, ParseAttributeError, ParseTagError};
+
+/// EXT-X-DEFINE: a variable that URI lines and quoted-string attribute values
+/// after it refer to as `{$NAME}`. Attributes this crate doesn't know are
+/// ignored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Define {
+    /// NAME and VALUE: a value given by the playlist itself.
+    Value { name: String, value: String },
+    /// IMPORT: the value the multivariant playlist defines, only allowed in
+    /// media playlists.
+    Import(String),
+    /// QUERYPARAM: the value of a query parameter of the playlist's URL.
+    QueryParam(String),
+}
+
+impl Define {
+    pub fn name(&self) -> &str {
+        match self {
+            Define::Value { name, .. } => name,
+            Define::Import(name) | Define::QueryParam(name) => name,
+        }
+    }
+}
+
+impl FromStr for Define {
+    type Err = ParseTagError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mut name, mut value, mut import, mut query_param) = (None, None, None, None);
+        for (attribute, raw) in attribute::tokenize_attribute_list(s)? {
+            let slot = match attribute {
+                "NAME" => &mut name,
+                "VALUE" => &mut value,
+                "IMPORT" => &mut import,
+                "QUERYPARAM" => &mut query_param,
+                // Attributes of later revisions of the spec can't change
+                // the variable, which is all the tag stands for
+                _ => continue,
+            };
+            let unquoted = raw
+                .strip_prefix('"')
+                .and_then(|raw| raw.strip_suffix('"'))
+                .ok_or_else(|| ParseAttributeError::InvalidValue {
+                    attribute: attribute.to_string(),
+                    value: raw.to_string(),
+                })?;
+            *slot = Some(unquoted.to_string());
+        }
+        let define = match (name, value, import, query_param) {
+            (Some(name), Some(value), None, None) => Define::Value { name, value },
+            (None, None, Some(name), None) => Define::Import(name),
+            (None, None, None, Some(name)) => Define::QueryParam(name),
+            (None, Some(_), None, None) => return Err(ParseTagError::MissingField("name")),
+            (Some(_), None, None, None) => return Err(ParseTagError::MissingField("value")),
+            _ => return Err(ParseTagError::invalid(s)),
+        };
+        if !is_variable_name(define.name()) {
+            return Err(ParseTagError::invalid(s));
+        }
+        Ok(define)
+    }
+}
+
+impl fmt::Display for Define {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Define::Value { name, value } => {
+                write!(f, "#EXT-X-DEFINE:NAME=\"{}\",VALUE=\"{}\"", name, value)
+            }
+            Define::Import(name) => write!(f, "#EXT-X-DEFINE:IMPORT=\"{}\"", name),
+            Define::QueryParam(name) => write!(f, "#EXT-X-DEFINE:QUERYPARAM=\"{}\"", name),
+        }
+    }
+}
+
+// Names are made of letters, digits, hyphens and underscores
+fn is_variable_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_')
+}
+
+// Why a variable has no value
+pub(crate) enum VariableError {
+    Redefined(String),
+    Undefined(String),
+}
+
+// Values of the variables defined so far while parsing a playlist
+#[derive(Default)]
+pub(crate) struct Variables(BTreeMap<String, String>);
+
+impl Variables {
+    // Gives the variable of `define` its value: its own, the one `imports`
+    // has for it, or the query parameter of `url`
+    pub(crate) fn define(
+        &mut self,
+        define: &Define,
+        imports: &BTreeMap<String, String>,
+        url: Option<&str>,
+    ) -> Result<(), VariableError> {
+        let name = define.name();
+        if self.0.contains_key(name) {
+            return Err(VariableError::Redefined(name.to_string()));
+        }
+        let value = match define {
+            Define::Value { value, .. } => Some(value.clone()),
+            Define::Import(name) => imports.get(name).cloned(),
+            Define::QueryParam(name) => url.and_then(|url| query_param(url, name)),
+        };
+        let value = value.ok_or_else(|| VariableError::Undefined(name.to_string()))?;
+        self.0.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    // Leaves references to `name` as written, for a variable whose value
+    // isn't known while parsing
+    pub(crate) fn keep(&mut self, name: &str) {
+        self.0.insert(name.to_string(), format!("{{${}}}", name));
+    }
+
+    // Replaces every `{$NAME}` of `text` with the variable's value
+    pub(crate) fn substitute<'a>(&self, text: &'a str) -> Result<Cow<'a, str>, VariableError> {
+        if !text.contains("{$") {
+            return Ok(Cow::Borrowed(text));
+        }
+        let mut output = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("{$") {
+            output.push_str(&rest[..start]);
+            let reference = &rest[start + 2..];
+            let Some(end) = reference.find('}') else {
+                // Not a reference without its closing brace
+                output.push_str(&rest[start..]);
+                return Ok(Cow::Owned(output));
+            };
+            let name = &reference[..end];
+            let value = self
+                .0
+                .get(name)
+                .ok_or_else(|| VariableError::Undefined(name.to_string()))?;
+            output.push_str(value);
+            rest = &reference[end + 1..];
+        }
+        output.push_str(rest);
+        Ok(Cow::Owned(output))
+    }
+
+    // Substitutes within the quoted strings of a tag value only; quoted
+    // strings can't contain quotes, so every other piece between quotes is
+    // one
+    pub(crate) fn substitute_quoted<'a>(
+        &self,
+        value: &'a str,
+    ) -> Result<Cow<'a, str>, VariableError> {
+        if !value.contains("{$") {
+            return Ok(Cow::Borrowed(value));
+        }
+        let mut output = String::with_capacity(value.len());
+        for (index, piece) in value.split('"').enumerate() {
+            if index > 0 {
+                output.push('"');
+            }
+            if index % 2 == 1 {
+                output.push_str(&self.substitute(piece)?);
+            } else {
+                output.push_str(piece);
+            }
+        }
+        Ok(Cow::Owned(output))
+    }
+}
+
+// The value of the query parameter `name` of `url`, as written
+fn query_param(url: &str, name: &str) -> Option<String> {
+    let (_, query) = url.split_once('?')?;
+    let query = query.split_once('#').map_or(query, |(query, _)| query);
+    query.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        (key == name).then(|| value.to_string())
+    })
+}
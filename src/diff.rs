@@ -0,0 +1,125 @@
+use crate::prelude::*;
+use crate::{attribute, MediaPlaylist, MediaSegment, PartialSegment, PreloadHint, RenditionReport};
+
+/// What changed between two reloads of a live media playlist, see
+/// [`MediaPlaylist::diff`]. Segments and parts come with their media
+/// sequence number; parts also with their index within their segment.
+pub struct PlaylistDelta<'a> {
+    /// Segments of the older playlist that fell out of the window.
+    pub removed_segments: Vec<(u64, &'a MediaSegment)>,
+    /// Segments the newer playlist completed since the older one.
+    pub added_segments: Vec<(u64, &'a MediaSegment)>,
+    /// Parts published since the older playlist, in publication order.
+    pub added_parts: Vec<(u64, u32, &'a PartialSegment)>,
+    /// The preload hints of the newer playlist, if they differ from the
+    /// older one's: an empty slice if the hints were dropped.
+    pub preload_hints: Option<&'a [PreloadHint]>,
+    /// Rendition reports of the newer playlist that are new or moved on.
+    pub updated_rendition_reports: Vec<&'a RenditionReport>,
+    /// Rendition reports of the older playlist the newer one no longer has.
+    pub removed_rendition_reports: Vec<&'a RenditionReport>,
+}
+
+impl PlaylistDelta<'_> {
+    /// Whether the playlists list the same media, hint and reports.
+    pub fn is_empty(&self) -> bool {
+        self.removed_segments.is_empty()
+            && self.added_segments.is_empty()
+            && self.added_parts.is_empty()
+            && self.preload_hints.is_none()
+            && self.updated_rendition_reports.is_empty()
+            && self.removed_rendition_reports.is_empty()
+    }
+}
+
+impl MediaPlaylist {
+    /// What changed from this playlist to `newer`, a later reload of the
+    /// same rendition. Segments a delta update skipped are neither removed
+    /// nor added.
+    pub fn diff<'a>(&'a self, newer: &'a MediaPlaylist) -> PlaylistDelta<'a> {
+        let removed_segments = self
+            .media_segments
+            .iter()
+            .zip(self.first_listed_msn()..)
+            .filter(|&(_, msn)| msn < newer.media_sequence_number)
+            .map(|(segment, msn)| (msn, segment))
+            .collect();
+
+        let listed_end = self.first_listed_msn() + self.media_segments.len() as u64;
+        let added_segments = newer
+            .media_segments
+            .iter()
+            .zip(newer.first_listed_msn()..)
+            .filter(|&(_, msn)| msn >= listed_end)
+            .map(|(segment, msn)| (msn, segment))
+            .collect();
+
+        let published = !self.media_segments.is_empty() || !self.trailing_parts.is_empty();
+        let (edge_msn, edge_part) = self.last_position();
+        let added_parts = newer
+            .parts_with_position()
+            .filter(|&(msn, index, _)| {
+                !published
+                    || msn > edge_msn
+                    || (msn == edge_msn && edge_part.is_none_or(|part| index > part))
+            })
+            .collect();
+
+        let preload_hints =
+            (self.preload_hints != newer.preload_hints).then_some(newer.preload_hints.as_slice());
+
+        let updated_rendition_reports = newer
+            .rendition_reports
+            .iter()
+            .filter(|report| !self.rendition_reports.contains(report))
+            .collect();
+        let removed_rendition_reports = self
+            .rendition_reports
+            .iter()
+            .filter(|report| {
+                !newer
+                    .rendition_reports
+                    .iter()
+                    .any(|newer| newer.uri == report.uri)
+            })
+            .collect();
+
+        PlaylistDelta {
+            removed_segments,
+            added_segments,
+            added_parts,
+            preload_hints,
+            updated_rendition_reports,
+            removed_rendition_reports,
+        }
+    }
+
+    /// Whether both playlists say the same, for round-trip and conformance
+    /// tests: attributes may come in any order, numbers formatted
+    /// differently, e.g. `4.000` for `4`, and lines end in either way. Tags
+    /// this crate doesn't parse have to match as written.
+    pub fn semantically_equal(&self, other: &MediaPlaylist) -> bool {
+        let (rendered, other_rendered) = (self.to_string(), other.to_string());
+        rendered
+            .lines()
+            .map(canonical_line)
+            .eq(other_rendered.lines().map(canonical_line))
+    }
+}
+
+// A rendered line with the attributes of its attribute list in order of
+// name; anything else, e.g. EXTINF or a URI, as it is
+fn canonical_line(line: &str) -> Vec<&str> {
+    let tokenized = line.split_once(':').and_then(|(tag, value)| {
+        let mut attributes = attribute::tokenize_attribute_list(value).ok()?;
+        attributes.sort_unstable();
+        let mut tokens = vec![tag];
+        tokens.extend(
+            attributes
+                .into_iter()
+                .flat_map(|(name, value)| [name, value]),
+        );
+        Some(tokens)
+    });
+    tokenized.unwrap_or_else(|| vec![line])
+}
@@ -0,0 +1,227 @@
+use core::fmt::Write;
+
+use crate::prelude::*;
+
+/// Value of the `_HLS_skip` delivery directive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SkipDirective {
+    /// `_HLS_skip=YES`: skip media segments.
+    Yes,
+    /// `_HLS_skip=v2`: also skip EXT-X-DATERANGE tags, for servers that
+    /// advertise CAN-SKIP-DATERANGES.
+    V2,
+}
+
+impl SkipDirective {
+    fn as_str(self) -> &'static str {
+        match self {
+            SkipDirective::Yes => "YES",
+            SkipDirective::V2 => "v2",
+        }
+    }
+
+    fn from_value(value: &str) -> Option<Self> {
+        match value {
+            "YES" => Some(SkipDirective::Yes),
+            "v2" => Some(SkipDirective::V2),
+            _ => None,
+        }
+    }
+}
+
+/// Why the query of a playlist request doesn't hold valid delivery
+/// directives. Origins answer such requests with 400.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DirectiveError {
+    /// `_HLS_part` was given without `_HLS_msn`.
+    PartWithoutMsn,
+    /// A directive that can only be given once was repeated.
+    Duplicate(&'static str),
+    /// A directive's value isn't a decimal integer, or `YES`/`v2` for
+    /// `_HLS_skip`.
+    InvalidValue { name: &'static str, value: String },
+}
+
+/// The LL-HLS delivery directives of a playlist request: `_HLS_msn`,
+/// `_HLS_part`, `_HLS_skip` and `_HLS_report`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DeliveryDirectives {
+    pub msn: Option<u64>,
+    /// Only meaningful along with `msn`.
+    pub part: Option<u32>,
+    pub skip: Option<SkipDirective>,
+    /// URIs of the renditions to report on, relative to the playlist.
+    pub report: Vec<String>,
+}
+
+impl DeliveryDirectives {
+    /// Reads the directives from the query of a request URL, without the
+    /// leading `?`. Other parameters are ignored, as are `_HLS_` ones that
+    /// aren't directives. Values are percent-decoded.
+    pub fn from_query_str(query: &str) -> Result<Self, DirectiveError> {
+        let mut directives = DeliveryDirectives::default();
+        for parameter in query.split('&').filter(|parameter| !parameter.is_empty()) {
+            let (name, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+            let value = decode_query(value);
+            let invalid = |name| DirectiveError::InvalidValue {
+                name,
+                value: value.clone(),
+            };
+            match name {
+                "_HLS_msn" => {
+                    let msn = parse_integer(&value).ok_or_else(|| invalid("_HLS_msn"))?;
+                    set_once(&mut directives.msn, msn, "_HLS_msn")?;
+                }
+                "_HLS_part" => {
+                    let part = parse_integer(&value).ok_or_else(|| invalid("_HLS_part"))?;
+                    set_once(&mut directives.part, part, "_HLS_part")?;
+                }
+                "_HLS_skip" => {
+                    let skip =
+                        SkipDirective::from_value(&value).ok_or_else(|| invalid("_HLS_skip"))?;
+                    set_once(&mut directives.skip, skip, "_HLS_skip")?;
+                }
+                "_HLS_report" => directives.report.push(value),
+                _ => {}
+            }
+        }
+        if directives.part.is_some() && directives.msn.is_none() {
+            return Err(DirectiveError::PartWithoutMsn);
+        }
+        Ok(directives)
+    }
+
+    /// The directives as query parameters in the order servers expect them,
+    /// e.g. `_HLS_msn=273&_HLS_part=2&_HLS_skip=YES`, with report URIs
+    /// percent-encoded. `_HLS_part` is left out without `_HLS_msn`.
+    pub fn to_query_string(&self) -> String {
+        let mut parameters = Vec::new();
+        if let Some(msn) = self.msn {
+            parameters.push(format!("_HLS_msn={}", msn));
+            if let Some(part) = self.part {
+                parameters.push(format!("_HLS_part={}", part));
+            }
+        }
+        if let Some(skip) = self.skip {
+            parameters.push(format!("_HLS_skip={}", skip.as_str()));
+        }
+        for report in &self.report {
+            parameters.push(format!("_HLS_report={}", encode_value(report)));
+        }
+        parameters.join("&")
+    }
+}
+
+fn set_once<T>(slot: &mut Option<T>, value: T, name: &'static str) -> Result<(), DirectiveError> {
+    if slot.replace(value).is_some() {
+        return Err(DirectiveError::Duplicate(name));
+    }
+    Ok(())
+}
+
+// Directive values are decimal-integers, without a sign or spaces
+fn parse_integer<T: core::str::FromStr>(value: &str) -> Option<T> {
+    if value.is_empty() || !value.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    value.parse().ok()
+}
+
+/// Adds LL-HLS delivery directives to a playlist URL.
+///
+/// Query parameters already in `url` keep their order and the directives
+/// follow them as `_HLS_msn`, `_HLS_part`, `_HLS_skip`, ahead of any
+/// fragment. Existing `_HLS_` parameters are dropped since the prefix is
+/// reserved for directives, and characters that can't appear in a query are
+/// percent-encoded. `_HLS_part` is only added along with `_HLS_msn`, as
+/// servers reject it on its own.
+pub fn apply_directives(
+    url: &str,
+    msn: Option<u64>,
+    part: Option<u32>,
+    skip: Option<SkipDirective>,
+) -> String {
+    let (url, fragment) = match url.split_once('#') {
+        Some((url, fragment)) => (url, Some(fragment)),
+        None => (url, None),
+    };
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+
+    let mut parameters: Vec<String> = query
+        .split('&')
+        .filter(|parameter| !parameter.is_empty())
+        .filter(|parameter| !parameter.starts_with("_HLS_"))
+        .map(encode_query)
+        .collect();
+    let directives = DeliveryDirectives {
+        msn,
+        part,
+        skip,
+        report: Vec::new(),
+    }
+    .to_query_string();
+    if !directives.is_empty() {
+        parameters.push(directives);
+    }
+
+    let mut result = path.to_string();
+    if !parameters.is_empty() {
+        result.push('?');
+        result.push_str(&parameters.join("&"));
+    }
+    if let Some(fragment) = fragment {
+        result.push('#');
+        result.push_str(fragment);
+    }
+    result
+}
+
+// Percent-encodes what RFC 3986 doesn't allow in a query, leaving existing
+// escapes alone
+fn encode_query(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c.is_ascii_alphanumeric() || "-._~!$&'()*+,;=:@/?%".contains(c) {
+            encoded.push(c);
+        } else {
+            let mut bytes = [0; 4];
+            for byte in c.encode_utf8(&mut bytes).bytes() {
+                let _ = write!(encoded, "%{:02X}", byte);
+            }
+        }
+    }
+    encoded
+}
+
+// Percent-encodes a parameter value, including the delimiters of the query
+fn encode_value(s: &str) -> String {
+    encode_query(s)
+        .replace('&', "%26")
+        .replace('=', "%3D")
+        .replace('+', "%2B")
+}
+
+// Decodes percent-escapes, leaving malformed ones as written
+fn decode_query(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escape = (bytes[index] == b'%')
+            .then(|| s.get(index + 1..index + 3))
+            .flatten()
+            .filter(|hex| hex.bytes().all(|byte| byte.is_ascii_hexdigit()))
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escape {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
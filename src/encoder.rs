@@ -0,0 +1,138 @@
+use core::time::Duration;
+
+use crate::prelude::*;
+use crate::{float, MediaPlaylistBuilder, PartInf, ServerControl};
+
+/// The cadence an encoder produces media at, from which consistent playlist
+/// timing can be derived with [`EncoderConfig::derive`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EncoderConfig {
+    /// Frames between keyframes.
+    pub gop_length: u32,
+    pub frame_rate: f32,
+    /// Requested duration of a CMAF chunk, which becomes a part, in seconds.
+    pub chunk_duration: f32,
+    /// GOPs per segment, one by default.
+    pub gops_per_segment: u32,
+}
+
+/// Playlist timing derived from an [`EncoderConfig`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct EncoderTiming {
+    /// Duration of every segment, in seconds.
+    pub segment_duration: f32,
+    pub target_duration: u32,
+    /// The chunk duration rounded to whole frames.
+    pub part_target: f32,
+    /// CAN-BLOCK-RELOAD=YES with the minimum PART-HOLD-BACK and CAN-SKIP-UNTIL
+    /// the spec recommends: three part targets and six target durations.
+    pub part_hold_back: f32,
+    pub can_skip_until: f32,
+    pub warnings: Vec<TimingWarning>,
+}
+
+/// A problem with an [`EncoderConfig`] that still leaves usable timing.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TimingWarning {
+    /// The chunk duration isn't a whole number of frames, so the part target
+    /// was rounded to `actual` seconds.
+    ChunkNotFrameAligned { requested: f32, actual: f32 },
+    /// The GOP isn't a whole number of chunks, so some keyframes fall inside
+    /// parts and those parts can't be marked INDEPENDENT.
+    KeyframesNotOnPartBoundaries { gop_length: u32, chunk_frames: u32 },
+    /// Parts last as long as whole segments, so they don't lower latency.
+    PartTargetNotBelowSegment {
+        part_target: f32,
+        segment_duration: f32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EncoderConfigError {
+    /// The frame rate isn't a positive number.
+    InvalidFrameRate,
+    /// The GOP length or GOPs per segment is zero.
+    EmptySegment,
+}
+
+// Slack for deciding whether a duration is a whole number of frames
+const FRAME_TOLERANCE: f32 = 0.001;
+
+impl EncoderConfig {
+    pub fn new(gop_length: u32, frame_rate: f32, chunk_duration: f32) -> Self {
+        EncoderConfig {
+            gop_length,
+            frame_rate,
+            chunk_duration,
+            gops_per_segment: 1,
+        }
+    }
+
+    pub fn derive(&self) -> Result<EncoderTiming, EncoderConfigError> {
+        if !(self.frame_rate > 0.0 && self.frame_rate.is_finite()) {
+            return Err(EncoderConfigError::InvalidFrameRate);
+        }
+        if self.gop_length == 0 || self.gops_per_segment == 0 {
+            return Err(EncoderConfigError::EmptySegment);
+        }
+        let mut warnings = Vec::new();
+
+        let frames = self.chunk_duration * self.frame_rate;
+        let chunk_frames = (float::round(f64::from(frames)) as u32).max(1);
+        let part_target = chunk_frames as f32 / self.frame_rate;
+        if (frames - chunk_frames as f32).abs() > FRAME_TOLERANCE {
+            warnings.push(TimingWarning::ChunkNotFrameAligned {
+                requested: self.chunk_duration,
+                actual: part_target,
+            });
+        }
+        if !self.gop_length.is_multiple_of(chunk_frames) {
+            warnings.push(TimingWarning::KeyframesNotOnPartBoundaries {
+                gop_length: self.gop_length,
+                chunk_frames,
+            });
+        }
+
+        let segment_frames = u64::from(self.gop_length) * u64::from(self.gops_per_segment);
+        let segment_duration = segment_frames as f32 / self.frame_rate;
+        if part_target >= segment_duration {
+            warnings.push(TimingWarning::PartTargetNotBelowSegment {
+                part_target,
+                segment_duration,
+            });
+        }
+        // EXTINF rounded to the nearest integer must not exceed it
+        let target_duration = (float::round(f64::from(segment_duration)) as u32).max(1);
+
+        Ok(EncoderTiming {
+            segment_duration,
+            target_duration,
+            part_target,
+            part_hold_back: 3.0 * part_target,
+            can_skip_until: 6.0 * target_duration as f32,
+            warnings,
+        })
+    }
+}
+
+impl EncoderTiming {
+    pub fn part_inf(&self) -> PartInf {
+        PartInf::new(Duration::from_secs_f32(self.part_target))
+    }
+
+    pub fn server_control(&self) -> ServerControl {
+        ServerControl::new(
+            true,
+            Duration::from_secs_f32(self.part_hold_back),
+            Duration::from_secs_f32(self.can_skip_until),
+        )
+    }
+
+    /// Sets EXT-X-TARGETDURATION, EXT-X-PART-INF and EXT-X-SERVER-CONTROL.
+    pub fn apply<'a>(&self, builder: &'a mut MediaPlaylistBuilder) -> &'a mut MediaPlaylistBuilder {
+        builder
+            .target_duration(self.target_duration)
+            .part_inf(self.part_inf())
+            .server_control(self.server_control())
+    }
+}
@@ -0,0 +1,198 @@
+use core::{error::Error, fmt};
+#[cfg(feature = "std")]
+use std::io;
+
+use crate::prelude::*;
+use crate::{AttributeListError, NumericError, UninitializedField};
+
+/// Why an attribute of a tag's attribute list was rejected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseAttributeError {
+    /// The tag doesn't define an attribute of this name.
+    Unknown { attribute: String },
+    /// The value doesn't have the attribute's type, or is out of range.
+    InvalidValue { attribute: String, value: String },
+}
+
+impl fmt::Display for ParseAttributeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseAttributeError::Unknown { attribute } => {
+                write!(f, "unknown attribute {}", attribute)
+            }
+            ParseAttributeError::InvalidValue { attribute, value } => {
+                write!(f, "invalid value {} for attribute {}", value, attribute)
+            }
+        }
+    }
+}
+
+impl Error for ParseAttributeError {}
+
+/// Why the value of a single tag was rejected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseTagError {
+    /// The value as a whole is malformed, e.g. a non-numeric EXT-X-VERSION.
+    Invalid { value: String },
+    /// The value isn't a well-formed attribute list.
+    AttributeList(AttributeListError),
+    /// An attribute of the value's attribute list is invalid.
+    Attribute(ParseAttributeError),
+    /// A required attribute is missing. Holds the name of the model field it
+    /// would fill, e.g. `part_duration` for the DURATION of EXT-X-PART.
+    MissingField(&'static str),
+    /// No parser handles the tag.
+    Unknown { tag: String },
+}
+
+impl ParseTagError {
+    /// Rejects the tag value `value` as a whole, e.g. from the parser of a
+    /// [`crate::CustomTag`].
+    pub fn invalid(value: &str) -> Self {
+        ParseTagError::Invalid {
+            value: value.to_string(),
+        }
+    }
+}
+
+impl From<ParseAttributeError> for ParseTagError {
+    fn from(error: ParseAttributeError) -> Self {
+        ParseTagError::Attribute(error)
+    }
+}
+
+impl From<AttributeListError> for ParseTagError {
+    fn from(error: AttributeListError) -> Self {
+        ParseTagError::AttributeList(error)
+    }
+}
+
+impl From<UninitializedField> for ParseTagError {
+    fn from(value: UninitializedField) -> Self {
+        ParseTagError::MissingField(value.0)
+    }
+}
+
+impl fmt::Display for ParseTagError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseTagError::Invalid { value } => write!(f, "invalid value {}", value),
+            ParseTagError::AttributeList(error) => write!(f, "{}", error),
+            ParseTagError::Attribute(error) => write!(f, "{}", error),
+            ParseTagError::MissingField(field) => write!(f, "missing {}", field),
+            ParseTagError::Unknown { tag } => write!(f, "unknown tag {}", tag),
+        }
+    }
+}
+
+impl Error for ParseTagError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseTagError::AttributeList(error) => Some(error),
+            ParseTagError::Attribute(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Why a playlist couldn't be read. Line numbers are 1-based.
+#[derive(Debug)]
+pub enum ParsePlaylistError {
+    /// The first line isn't `#EXTM3U`.
+    MissingHeader,
+    #[cfg(feature = "std")]
+    Io(io::Error),
+    /// The tag `tag` on `line` couldn't be parsed.
+    InvalidTag {
+        line: usize,
+        tag: String,
+        error: ParseTagError,
+    },
+    /// Line `line` isn't valid UTF-8.
+    InvalidUtf8 { line: usize },
+    /// The URI line `line` isn't a valid URI.
+    InvalidUri { line: usize, uri: String },
+    /// A tag allowed once per playlist appeared again.
+    DuplicateTag { line: usize, tag: String },
+    InvalidNumber {
+        line: usize,
+        tag: String,
+        error: NumericError,
+    },
+    /// The variable `name` is referenced or defined on `line`, but has no
+    /// value: it isn't defined, or the multivariant playlist or the
+    /// playlist's URL lacks the value it imports.
+    UndefinedVariable { line: usize, name: String },
+    /// `tag` lacks `field`. `line` is where the tag is, or `None` if the
+    /// playlist has no such tag at all.
+    MissingField {
+        line: Option<usize>,
+        tag: String,
+        field: &'static str,
+    },
+}
+
+impl ParsePlaylistError {
+    pub(crate) fn from_tag(line: usize, tag: &str, error: ParseTagError) -> Self {
+        match error {
+            ParseTagError::MissingField(field) => ParsePlaylistError::MissingField {
+                line: Some(line),
+                tag: tag.to_string(),
+                field,
+            },
+            error => ParsePlaylistError::InvalidTag {
+                line,
+                tag: tag.to_string(),
+                error,
+            },
+        }
+    }
+}
+
+impl fmt::Display for ParsePlaylistError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParsePlaylistError::MissingHeader => write!(f, "playlist doesn't start with #EXTM3U"),
+            #[cfg(feature = "std")]
+            ParsePlaylistError::Io(error) => write!(f, "couldn't read playlist: {}", error),
+            ParsePlaylistError::InvalidTag { line, tag, error } => {
+                write!(f, "line {}: #{}: {}", line, tag, error)
+            }
+            ParsePlaylistError::InvalidUtf8 { line } => {
+                write!(f, "line {} isn't valid UTF-8", line)
+            }
+            ParsePlaylistError::InvalidUri { line, uri } => {
+                write!(f, "line {}: invalid URI {}", line, uri)
+            }
+            ParsePlaylistError::DuplicateTag { line, tag } => {
+                write!(f, "line {}: #{} appears more than once", line, tag)
+            }
+            ParsePlaylistError::InvalidNumber { line, tag, error } => {
+                write!(f, "line {}: #{}: {}", line, tag, error)
+            }
+            ParsePlaylistError::UndefinedVariable { line, name } => {
+                write!(f, "line {}: variable {} has no value", line, name)
+            }
+            ParsePlaylistError::MissingField {
+                line: Some(line),
+                tag,
+                field,
+            } => write!(f, "line {}: #{} lacks {}", line, tag, field),
+            ParsePlaylistError::MissingField {
+                line: None, tag, ..
+            } => write!(f, "playlist has no #{}", tag),
+        }
+    }
+}
+
+impl Error for ParsePlaylistError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            ParsePlaylistError::Io(error) => Some(error),
+            ParsePlaylistError::InvalidTag { error, .. } => Some(error),
+            ParsePlaylistError::InvalidNumber { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
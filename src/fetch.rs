@@ -0,0 +1,429 @@
+use std::{collections::VecDeque, error::Error, future::Future};
+
+use futures_util::{future, Stream};
+
+use crate::{
+    uri, ByteRange, ClientError, Fetch, LlHlsClient, Map, MediaPlaylist, PreloadHint,
+    PreloadHintType,
+};
+
+/// Part of a resource to request: `length` bytes from `start`, or everything
+/// from `start` on without a length.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RangeRequest {
+    pub start: u64,
+    pub length: Option<u64>,
+}
+
+impl RangeRequest {
+    /// The value of the HTTP Range header, e.g. `bytes=0-719`.
+    pub fn header_value(&self) -> String {
+        match self.length {
+            Some(length) => format!(
+                "bytes={}-{}",
+                self.start,
+                (self.start + length).saturating_sub(1)
+            ),
+            None => format!("bytes={}-", self.start),
+        }
+    }
+
+    fn from_map(map: &Map) -> Option<Self> {
+        map.byterange.map(|byterange| RangeRequest {
+            start: byterange.offset.unwrap_or(0),
+            length: Some(byterange.length),
+        })
+    }
+
+    // A range without offset starts right after the previous one, `previous`
+    // being the resource and end of that
+    fn from_byterange<'a>(
+        uri: &'a str,
+        byterange: Option<ByteRange>,
+        previous: &mut Option<(&'a str, u64)>,
+    ) -> Option<Self> {
+        let Some(byterange) = byterange else {
+            *previous = None;
+            return None;
+        };
+        let start = byterange.offset.unwrap_or(match previous {
+            Some((previous_uri, end)) if *previous_uri == uri => *end,
+            _ => 0,
+        });
+        *previous = Some((uri, start.saturating_add(byterange.length)));
+        Some(RangeRequest {
+            start,
+            length: Some(byterange.length),
+        })
+    }
+
+    // BYTERANGE-START defaults to 0 once a length is given
+    fn from_hint(hint: &PreloadHint) -> Option<Self> {
+        if hint.byterange_start.is_none() && hint.byterange_length.is_none() {
+            return None;
+        }
+        Some(RangeRequest {
+            start: hint.byterange_start.unwrap_or(0),
+            length: hint.byterange_length,
+        })
+    }
+}
+
+/// Transport the [`MediaFetcher`] downloads media over. Implemented for
+/// [`reqwest::Client`]; other HTTP stacks, or tests, can provide their own.
+pub trait FetchBytes {
+    type Error: Error + Send + Sync + 'static;
+
+    /// Downloads the resource at `url`, or only `range` of it. A request for
+    /// a preload hint only resolves once the server has the media.
+    fn fetch_bytes(
+        &self,
+        url: &str,
+        range: Option<RangeRequest>,
+    ) -> impl Future<Output = Result<Vec<u8>, Self::Error>> + Send;
+}
+
+/// Plain GET with a Range header for sub-ranges; a status other than 2xx is
+/// an error.
+impl FetchBytes for reqwest::Client {
+    type Error = reqwest::Error;
+
+    async fn fetch_bytes(
+        &self,
+        url: &str,
+        range: Option<RangeRequest>,
+    ) -> Result<Vec<u8>, reqwest::Error> {
+        let mut request = self.get(url);
+        if let Some(range) = range {
+            request = request.header(reqwest::header::RANGE, range.header_value());
+        }
+        let response = request.send().await?.error_for_status()?;
+        Ok(response.bytes().await?.to_vec())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MediaKind {
+    /// The EXT-X-MAP init segment of the media after it.
+    Init,
+    Segment,
+    Part,
+}
+
+/// Media downloaded by a [`MediaFetcher`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct FetchedMedia {
+    pub kind: MediaKind,
+    /// Media sequence number of the segment the media belongs to, or of the
+    /// first segment an init segment applies to.
+    pub msn: u64,
+    /// Index of a part within its segment.
+    pub part: Option<u32>,
+    pub url: String,
+    pub data: Vec<u8>,
+}
+
+// A download decided on from a playlist
+#[derive(Clone)]
+struct Request {
+    kind: MediaKind,
+    msn: u64,
+    part: Option<u32>,
+    url: String,
+    range: Option<RangeRequest>,
+}
+
+// Response to a preload hint
+struct Prefetched {
+    r#type: PreloadHintType,
+    url: String,
+    range: Option<RangeRequest>,
+    data: Vec<u8>,
+}
+
+impl Prefetched {
+    // Whether the response holds the media of `request`: the same range, or
+    // all of it from an open-ended one hinted before its length was known
+    fn answers(&self, request: &Request) -> bool {
+        if self.url != request.url {
+            return false;
+        }
+        match (self.range, request.range) {
+            (
+                Some(RangeRequest {
+                    start,
+                    length: None,
+                }),
+                Some(RangeRequest {
+                    start: requested,
+                    length: Some(length),
+                }),
+            ) => start == requested && self.data.len() as u64 >= length,
+            (hinted, requested) => hinted == requested,
+        }
+    }
+}
+
+/// Downloads the media of a live playlist as it is published: the parts of
+/// segments listed with parts, whole segments otherwise, and init segments
+/// as EXT-X-MAP changes. Each piece of media is fetched once, in order.
+///
+/// The first playlist is joined at its
+/// [`MediaPlaylist::startup_position`]. The EXT-X-PRELOAD-HINT tags of a
+/// playlist can be requested ahead of time with
+/// [`MediaFetcher::prefetch_hint`], and the responses are used once the
+/// playlist lists the hinted media.
+pub struct MediaFetcher<F> {
+    fetch: F,
+    base_url: String,
+    // Newest (MSN, part index) fetched, no index for a whole segment
+    position: Option<(u64, Option<u32>)>,
+    // URL and range of the init segment fetched last
+    init: Option<(String, Option<RangeRequest>)>,
+    // Responses to preload hints whose media hasn't been listed yet, at most
+    // one per hint TYPE
+    prefetched: Vec<Prefetched>,
+}
+
+impl<F: FetchBytes> MediaFetcher<F> {
+    /// A fetcher for the media of the playlist at `base_url`, which relative
+    /// URIs are resolved against.
+    pub fn new(fetch: F, base_url: impl Into<String>) -> Self {
+        MediaFetcher {
+            fetch,
+            base_url: base_url.into(),
+            position: None,
+            init: None,
+            prefetched: Vec::new(),
+        }
+    }
+
+    /// Downloads the media `playlist` lists that wasn't fetched yet, in
+    /// order. After an error the media that did arrive is lost, and the rest
+    /// is fetched again from the next playlist.
+    pub async fn fetch_new(
+        &mut self,
+        playlist: &MediaPlaylist,
+    ) -> Result<Vec<FetchedMedia>, ClientError> {
+        let mut media = Vec::new();
+        for request in self.plan(playlist) {
+            media.push(self.fetch_request(request).await?);
+        }
+        Ok(media)
+    }
+
+    /// Requests the media the preload hints of `playlist` announce, in
+    /// order. The requests block until the server has published it, so this
+    /// is meant to run alongside the next reload.
+    pub async fn prefetch_hint(&mut self, playlist: &MediaPlaylist) -> Result<(), ClientError> {
+        for hint in playlist.preload_hints() {
+            let url = self.resolve(&hint.uri);
+            let range = RangeRequest::from_hint(hint);
+            let fetched = self
+                .prefetched
+                .iter()
+                .any(|prefetched| prefetched.url == url && prefetched.range == range);
+            if fetched || self.init.as_ref() == Some(&(url.clone(), range)) {
+                continue;
+            }
+            let data = self
+                .fetch
+                .fetch_bytes(&url, range)
+                .await
+                .map_err(|error| ClientError::Fetch(Box::new(error)))?;
+            self.prefetched
+                .retain(|prefetched| prefetched.r#type != hint.r#type);
+            self.prefetched.push(Prefetched {
+                r#type: hint.r#type,
+                url,
+                range,
+                data,
+            });
+        }
+        Ok(())
+    }
+
+    /// Follows the playlist `client` reloads and yields its media as it is
+    /// published. The preload hint of every playlist is requested alongside
+    /// the next reload. Errors are yielded and the stream carries on, until
+    /// the playlist ends with EXT-X-ENDLIST.
+    pub fn media<C: Fetch>(
+        self,
+        client: LlHlsClient<C>,
+    ) -> impl Stream<Item = Result<FetchedMedia, ClientError>> {
+        struct State<F, C> {
+            fetcher: MediaFetcher<F>,
+            client: LlHlsClient<C>,
+            requests: VecDeque<Request>,
+            // The last playlist, whose hint is fetched during the next reload
+            playlist: Option<MediaPlaylist>,
+        }
+
+        let state = State {
+            fetcher: self,
+            client,
+            requests: VecDeque::new(),
+            playlist: None,
+        };
+        futures_util::stream::unfold(state, |mut state| async move {
+            loop {
+                if let Some(request) = state.requests.pop_front() {
+                    let result = state.fetcher.fetch_request(request).await;
+                    if result.is_err() {
+                        state.requests.clear();
+                    }
+                    return Some((result, state));
+                }
+                if state
+                    .playlist
+                    .as_ref()
+                    .is_some_and(|playlist| !playlist.is_live())
+                {
+                    return None;
+                }
+                let prefetch = async {
+                    if let Some(playlist) = &state.playlist {
+                        // The hint may not materialize; the media is fetched
+                        // normally once listed
+                        let _ = state.fetcher.prefetch_hint(playlist).await;
+                    }
+                };
+                let (reload, ()) = future::join(state.client.reload(), prefetch).await;
+                match reload {
+                    Ok(playlist) => {
+                        state.requests = state.fetcher.plan(&playlist).into();
+                        state.playlist = Some(playlist);
+                    }
+                    Err(error) => return Some((Err(error), state)),
+                }
+            }
+        })
+    }
+
+    // The downloads `playlist` calls for, given what was fetched so far
+    fn plan(&self, playlist: &MediaPlaylist) -> Vec<Request> {
+        let start = match self.position {
+            Some(_) => None,
+            None => match playlist.startup_position() {
+                Some(start) => Some(start),
+                None => return Vec::new(),
+            },
+        };
+        let is_new = |msn: u64, part: Option<u32>| match (self.position, start) {
+            (Some((last_msn, last_part)), _) => {
+                msn > last_msn
+                    || (msn == last_msn
+                        && matches!((last_part, part), (Some(last), Some(part)) if part > last))
+            }
+            (None, Some((start_msn, start_part))) => {
+                msn > start_msn
+                    || (msn == start_msn
+                        && match (start_part, part) {
+                            (Some(start), Some(part)) => part >= start,
+                            (Some(_), None) => false,
+                            (None, _) => true,
+                        })
+            }
+            (None, None) => true,
+        };
+
+        // (MSN, part index, URI, range, init segment) of everything listed.
+        // Ranges without offset follow the previous segment's or part's.
+        let mut units = Vec::new();
+        let mut map = None;
+        let (mut segment_end, mut part_end) = (None, None);
+        let first_msn = playlist.first_listed_msn();
+        for (segment, msn) in playlist.media_segments.iter().zip(first_msn..) {
+            map = segment.map.as_ref().or(map);
+            let segment_uri = uri::as_str(&segment.uri);
+            let range =
+                RangeRequest::from_byterange(segment_uri, segment.byterange, &mut segment_end);
+            if segment.partial_segments.is_empty() {
+                units.push((msn, None, segment_uri, range, map));
+            }
+            for (part, index) in segment.partial_segments.iter().zip(0..) {
+                let range = RangeRequest::from_byterange(&part.uri, part.byterange, &mut part_end);
+                units.push((msn, Some(index), part.uri.as_str(), range, map));
+            }
+        }
+        let trailing_msn = first_msn + playlist.media_segments.len() as u64;
+        map = playlist.trailing_map.as_ref().or(map);
+        for (part, index) in playlist.trailing_parts.iter().zip(0..) {
+            let range = RangeRequest::from_byterange(&part.uri, part.byterange, &mut part_end);
+            units.push((trailing_msn, Some(index), part.uri.as_str(), range, map));
+        }
+
+        let mut init = self.init.clone();
+        let mut requests = Vec::new();
+        for (msn, part, uri, range, map) in units {
+            if !is_new(msn, part) {
+                continue;
+            }
+            if let Some(map) = map {
+                let map_init = (self.resolve(&map.uri), RangeRequest::from_map(map));
+                if init.as_ref() != Some(&map_init) {
+                    requests.push(Request {
+                        kind: MediaKind::Init,
+                        msn,
+                        part: None,
+                        url: map_init.0.clone(),
+                        range: map_init.1,
+                    });
+                    init = Some(map_init);
+                }
+            }
+            requests.push(Request {
+                kind: match part {
+                    Some(_) => MediaKind::Part,
+                    None => MediaKind::Segment,
+                },
+                msn,
+                part,
+                url: self.resolve(uri),
+                range,
+            });
+        }
+        requests
+    }
+
+    // Downloads `request`, or takes the prefetched response to it, and
+    // records it as fetched
+    async fn fetch_request(&mut self, request: Request) -> Result<FetchedMedia, ClientError> {
+        let prefetched = self
+            .prefetched
+            .iter()
+            .position(|prefetched| prefetched.answers(&request));
+        let data = match prefetched {
+            Some(index) => {
+                let mut data = self.prefetched.swap_remove(index).data;
+                if let Some(length) = request.range.and_then(|range| range.length) {
+                    data.truncate(usize::try_from(length).unwrap_or(usize::MAX));
+                }
+                data
+            }
+            None => self
+                .fetch
+                .fetch_bytes(&request.url, request.range)
+                .await
+                .map_err(|error| ClientError::Fetch(Box::new(error)))?,
+        };
+        match request.kind {
+            MediaKind::Init => self.init = Some((request.url.clone(), request.range)),
+            MediaKind::Segment | MediaKind::Part => {
+                self.position = Some((request.msn, request.part))
+            }
+        }
+        Ok(FetchedMedia {
+            kind: request.kind,
+            msn: request.msn,
+            part: request.part,
+            url: request.url,
+            data,
+        })
+    }
+
+    // URIs in playlists are relative to the playlist's own URL
+    fn resolve(&self, uri: &str) -> String {
+        uri::resolve(&self.base_url, uri)
+    }
+}
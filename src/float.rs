@@ -0,0 +1,32 @@
+// Rounding of floats, which core lacks, for builds without std
+
+// Floats from 2^52 up are whole numbers, their own floor
+const WHOLE: f64 = 4_503_599_627_370_496.0;
+
+pub(crate) fn floor(value: f64) -> f64 {
+    // Also passes NaN and infinities through
+    if value.is_nan() || value.abs() >= WHOLE {
+        return value;
+    }
+    let truncated = value as i64 as f64;
+    if truncated > value {
+        truncated - 1.0
+    } else {
+        truncated
+    }
+}
+
+pub(crate) fn ceil(value: f64) -> f64 {
+    -floor(-value)
+}
+
+// Halfway cases round away from zero, like f64::round
+pub(crate) fn round(value: f64) -> f64 {
+    let floor = floor(value);
+    let fraction = value - floor;
+    if fraction > 0.5 || (fraction == 0.5 && value > 0.0) {
+        floor + 1.0
+    } else {
+        floor
+    }
+}
@@ -0,0 +1,71 @@
+use std::{collections::VecDeque, time::SystemTime};
+
+use crate::MediaPlaylist;
+
+/// Keeps the last `capacity` playlist snapshots along with when they were
+/// taken, to answer questions about how a live playlist evolved. Snapshots
+/// share segment storage, so retaining many of them is cheap.
+#[derive(Clone)]
+pub struct PlaylistHistory {
+    capacity: usize,
+    snapshots: VecDeque<(SystemTime, MediaPlaylist)>,
+}
+
+impl PlaylistHistory {
+    pub fn new(capacity: usize) -> Self {
+        PlaylistHistory {
+            capacity,
+            snapshots: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Records a snapshot taken at `at`, evicting the oldest one when full.
+    /// Snapshots are expected in chronological order.
+    pub fn record(&mut self, at: SystemTime, playlist: MediaPlaylist) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back((at, playlist));
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (SystemTime, &MediaPlaylist)> {
+        self.snapshots.iter().map(|(at, playlist)| (*at, playlist))
+    }
+
+    pub fn latest(&self) -> Option<&MediaPlaylist> {
+        self.snapshots.back().map(|(_, playlist)| playlist)
+    }
+
+    /// The playlist as it was at `time`: the newest snapshot taken no later
+    /// than `time`.
+    pub fn at(&self, time: SystemTime) -> Option<&MediaPlaylist> {
+        let index = self.snapshots.partition_point(|(at, _)| *at <= time);
+        index.checked_sub(1).map(|index| &self.snapshots[index].1)
+    }
+
+    /// The (media sequence number, part index) of the newest media published
+    /// at `time`.
+    pub fn live_edge_at(&self, time: SystemTime) -> Option<(u64, Option<u32>)> {
+        self.at(time).map(MediaPlaylist::last_position)
+    }
+
+    /// When a segment with media sequence number `msn`, or its first part,
+    /// was first seen among the retained snapshots.
+    pub fn first_seen(&self, msn: u64) -> Option<SystemTime> {
+        self.snapshots
+            .iter()
+            .find(|(_, playlist)| playlist.contains_msn(msn))
+            .map(|(at, _)| *at)
+    }
+}
@@ -0,0 +1,63 @@
+use crate::prelude::*;
+use crate::{AttributeValue, MediaPlaylist, MediaSegment};
+
+/// Callbacks invoked while a playlist is parsed or written, e.g. to keep
+/// counters or capture raw input. Every method defaults to doing nothing.
+pub trait Hooks {
+    /// Called for every recognized tag with its name (without the leading
+    /// `#`) and raw value.
+    fn on_tag(&mut self, _tag: &str, _value: &str) {}
+
+    /// Called once a media segment has been fully parsed.
+    fn on_segment_complete(&mut self, _segment: &MediaSegment) {}
+
+    /// Called for every tag the parser doesn't know, with its raw value and
+    /// the typed NAME=VALUE pairs of its attribute list, if it has one.
+    fn on_unknown_tag(
+        &mut self,
+        _tag: &str,
+        _value: &str,
+        _attributes: &[(String, AttributeValue)],
+    ) {
+    }
+
+    /// Called for input that was tolerated but not understood.
+    fn on_warning(&mut self, _warning: &ParseWarning) {}
+
+    /// Called after a playlist has been written, with the number of bytes
+    /// written, by [`MediaPlaylist::write_to_with_hooks`] and
+    /// [`MediaPlaylist::serialize_into_with_hooks`].
+    fn on_serialize(&mut self, _playlist: &MediaPlaylist, _bytes: usize) {}
+}
+
+/// Hooks that ignore every event.
+#[derive(Clone, Copy, Default)]
+pub struct NoHooks;
+
+impl Hooks for NoHooks {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseWarning {
+    // `line` is 1-based
+    UnknownTag {
+        line: usize,
+        tag: String,
+    },
+    /// A tag allowed once per playlist was repeated, see
+    /// [`crate::DuplicateTagPolicy`].
+    DuplicateTag {
+        line: usize,
+        tag: String,
+    },
+    /// A pathological number was clamped, see [`crate::NumericPolicy`].
+    ClampedNumber {
+        line: usize,
+        tag: String,
+    },
+    /// A URI line that isn't a valid URI was percent-encoded or kept as
+    /// written, see [`crate::UriPolicy`].
+    RepairedUri {
+        line: usize,
+        uri: String,
+    },
+}
@@ -0,0 +1,195 @@
+use alloc::collections::BTreeMap;
+use core::{fmt, str::FromStr};
+
+use crate::prelude::*;
+use crate::{attribute, ExtraAttributes, HexBytes, ParseAttributeError, ParseTagError};
+
+/// EXT-X-KEY: how the segments after it are encrypted, until the next
+/// EXT-X-KEY of the same KEYFORMAT. Also the value of EXT-X-SESSION-KEY in
+/// multivariant playlists.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Key {
+    pub method: KeyMethod,
+    /// Where to get the key, required unless `method` is
+    /// [`KeyMethod::None`].
+    pub uri: Option<String>,
+    /// Initialization vector. Without one the media sequence number of the
+    /// segment is used.
+    pub iv: Option<HexBytes>,
+    /// How the key is represented at `uri`; `identity` when absent.
+    pub keyformat: Option<String>,
+    /// Slash-separated versions of `keyformat` the key complies with, e.g.
+    /// `1/2`.
+    pub keyformat_versions: Option<String>,
+    /// Attributes this crate doesn't know, e.g. of a later revision of the
+    /// spec, written back as read.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub extra_attributes: BTreeMap<String, String>,
+}
+
+impl Key {
+    /// A key of `method` fetched from `uri`.
+    pub fn new(method: KeyMethod, uri: impl Into<String>) -> Self {
+        Key {
+            method,
+            uri: Some(uri.into()),
+            iv: None,
+            keyformat: None,
+            keyformat_versions: None,
+            extra_attributes: BTreeMap::new(),
+        }
+    }
+
+    /// METHOD=NONE: the segments after it aren't encrypted.
+    pub fn none() -> Self {
+        Key {
+            method: KeyMethod::None,
+            uri: None,
+            iv: None,
+            keyformat: None,
+            keyformat_versions: None,
+            extra_attributes: BTreeMap::new(),
+        }
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.method != KeyMethod::None
+    }
+
+    // KEYFORMAT, whose absence means identity. A key applies until the next
+    // one of the same format.
+    pub(crate) fn format(&self) -> &str {
+        self.keyformat.as_deref().unwrap_or("identity")
+    }
+
+    // Adds to `keys`, declared on a segment, the ones of `earlier` whose
+    // format it doesn't redeclare. METHOD=NONE on either side ends the rest.
+    pub(crate) fn inherit(keys: &mut Vec<Key>, earlier: &[Key]) {
+        let ends = |keys: &[Key]| keys.iter().any(|key| !key.is_encrypted());
+        if ends(keys) || (!keys.is_empty() && ends(earlier)) {
+            return;
+        }
+        for key in earlier {
+            if !keys.iter().any(|kept| kept.format() == key.format()) {
+                keys.push(key.clone());
+            }
+        }
+    }
+
+    // The attribute list, shared by EXT-X-KEY and EXT-X-SESSION-KEY
+    pub(crate) fn write_attributes<W: fmt::Write>(&self, f: &mut W) -> fmt::Result {
+        write!(f, "METHOD={}", self.method.as_str())?;
+        if let Some(uri) = &self.uri {
+            write!(f, ",URI=\"{}\"", uri)?;
+        }
+        if let Some(iv) = &self.iv {
+            write!(f, ",IV={}", iv)?;
+        }
+        if let Some(keyformat) = &self.keyformat {
+            write!(f, ",KEYFORMAT=\"{}\"", keyformat)?;
+        }
+        if let Some(keyformat_versions) = &self.keyformat_versions {
+            write!(f, ",KEYFORMATVERSIONS=\"{}\"", keyformat_versions)?;
+        }
+        write!(f, "{}", ExtraAttributes(&self.extra_attributes))
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#EXT-X-KEY:")?;
+        self.write_attributes(f)
+    }
+}
+
+impl FromStr for Key {
+    type Err = ParseTagError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn invalid(name: &str, value: &str) -> ParseTagError {
+            ParseTagError::Attribute(ParseAttributeError::InvalidValue {
+                attribute: name.to_string(),
+                value: value.to_string(),
+            })
+        }
+        fn unquote(name: &str, value: &str) -> Result<String, ParseTagError> {
+            value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .map(str::to_string)
+                .ok_or_else(|| invalid(name, value))
+        }
+
+        let mut method = None;
+        let mut key = Key::none();
+        for (name, value) in attribute::tokenize_attribute_list(s)? {
+            match name {
+                "METHOD" => {
+                    method = Some(KeyMethod::from_str(value).map_err(|_| invalid(name, value))?)
+                }
+                "URI" => key.uri = Some(unquote(name, value)?),
+                "IV" => key.iv = Some(HexBytes::from_str(value).map_err(|_| invalid(name, value))?),
+                "KEYFORMAT" => key.keyformat = Some(unquote(name, value)?),
+                "KEYFORMATVERSIONS" => key.keyformat_versions = Some(unquote(name, value)?),
+                _ => {
+                    key.extra_attributes
+                        .insert(name.to_string(), value.to_string());
+                }
+            }
+        }
+        key.method = method.ok_or(ParseTagError::MissingField("method"))?;
+        if key.is_encrypted() && key.uri.is_none() {
+            return Err(ParseTagError::MissingField("uri"));
+        }
+        // Nothing else may accompany METHOD=NONE
+        if !key.is_encrypted() && key != Key::none() {
+            return Err(ParseTagError::invalid(s));
+        }
+        Ok(key)
+    }
+}
+
+/// The METHOD of EXT-X-KEY.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum KeyMethod {
+    None,
+    /// Whole segments encrypted with AES-128 in CBC mode.
+    Aes128,
+    /// Media samples encrypted, e.g. per the Apple Sample Encryption spec.
+    SampleAes,
+    /// Media samples encrypted per Common Encryption's `cenc` scheme.
+    SampleAesCtr,
+}
+
+impl KeyMethod {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            KeyMethod::None => "NONE",
+            KeyMethod::Aes128 => "AES-128",
+            KeyMethod::SampleAes => "SAMPLE-AES",
+            KeyMethod::SampleAesCtr => "SAMPLE-AES-CTR",
+        }
+    }
+}
+
+impl FromStr for KeyMethod {
+    type Err = ParseTagError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "NONE" => Ok(KeyMethod::None),
+            "AES-128" => Ok(KeyMethod::Aes128),
+            "SAMPLE-AES" => Ok(KeyMethod::SampleAes),
+            "SAMPLE-AES-CTR" => Ok(KeyMethod::SampleAesCtr),
+            _ => Err(ParseTagError::invalid(s)),
+        }
+    }
+}
+
+impl fmt::Display for KeyMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
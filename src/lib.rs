@@ -1,37 +1,1817 @@
-use chrono::{DateTime, Utc};
-use derive_builder::Builder;
-use fluent_uri::Uri;
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
+use core::{fmt, str::FromStr, time::Duration};
+use derive_builder::{Builder, UninitializedFieldError};
+#[cfg(feature = "std")]
 use std::{
-    collections::HashMap,
-    fmt,
     fs::File,
-    io::{BufRead, BufReader},
-    str::FromStr,
+    io::{self, Read},
 };
 
-#[derive(Builder)]
-pub struct MediaPlaylist {
-    target_duration: u32,
-    version: u32,
-    part_inf: PartInf,
-    media_sequence_number: u32,
-    media_segments: Vec<MediaSegment>,
-    skip: Option<Skip>,
-    preload_hint: Option<PreloadHint>,
-    rendition_reports: Vec<RenditionReport>,
-    server_control: ServerControl,
+use crate::prelude::*;
+
+mod attribute;
+mod audio;
+mod bandwidth;
+mod buffer;
+#[cfg(feature = "std")]
+mod cadence;
+mod chunks;
+#[cfg(feature = "reqwest")]
+mod client;
+mod codes;
+mod container;
+mod custom;
+mod datetime;
+mod define;
+mod diff;
+mod directives;
+mod encoder;
+mod error;
+#[cfg(feature = "reqwest")]
+mod fetch;
+mod float;
+#[cfg(feature = "std")]
+mod history;
+mod hooks;
+mod key;
+mod multivariant;
+mod numeric;
+mod options;
+mod parser;
+mod prelude;
+#[cfg(feature = "std")]
+mod reload;
+mod repair;
+mod reports;
+mod resolve;
+mod rewrite;
+mod seconds;
+mod segmenter;
+mod segments;
+mod selection;
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(feature = "tokio")]
+mod service;
+mod session;
+#[cfg(feature = "std")]
+mod sink;
+#[cfg(feature = "std")]
+mod startup;
+mod subtitles;
+#[cfg(feature = "std")]
+mod timeline;
+mod uri;
+mod validate;
+#[cfg(feature = "tokio")]
+mod waiters;
+#[cfg(all(feature = "wasm-bindgen", target_arch = "wasm32"))]
+mod wasm;
+#[cfg(feature = "reqwest")]
+mod watcher;
+mod window;
+#[cfg(feature = "std")]
+mod writer;
+
+pub use attribute::{
+    parse_attribute_list, AttributeListError, AttributeValue, EnumeratedStringList, HexBytes,
+    ParseHexError, ParseListError,
+};
+pub use audio::AudioRendition;
+pub use bandwidth::BandwidthMeter;
+pub use buffer::{BufferReport, BufferSimulation, SimulatedPart, Stall};
+#[cfg(feature = "std")]
+pub use cadence::{CadenceAnalyzer, CadenceReport};
+pub use chunks::Chunks;
+#[cfg(feature = "reqwest")]
+pub use client::{ClientError, Fetch, LlHlsClient};
+pub use codes::{ErrorCode, ERROR_CODES};
+pub use container::ContainerFormat;
+pub use custom::{CustomTag, TagPosition, TagRegistry, UnknownTag};
+#[cfg(feature = "time")]
+pub use datetime::from_offset_date_time;
+pub use datetime::DateTime;
+pub use define::Define;
+pub use diff::PlaylistDelta;
+pub use directives::{apply_directives, DeliveryDirectives, DirectiveError, SkipDirective};
+pub use encoder::{EncoderConfig, EncoderConfigError, EncoderTiming, TimingWarning};
+pub use error::{ParseAttributeError, ParsePlaylistError, ParseTagError};
+#[cfg(feature = "reqwest")]
+pub use fetch::{FetchBytes, FetchedMedia, MediaFetcher, MediaKind, RangeRequest};
+#[cfg(feature = "std")]
+pub use history::PlaylistHistory;
+pub use hooks::{Hooks, NoHooks, ParseWarning};
+pub use key::{Key, KeyMethod};
+#[cfg(feature = "std")]
+pub use multivariant::read_multivariant_playlist;
+pub use multivariant::{
+    IFrameStream, MediaType, MultivariantPlaylist, Rendition, SessionData, SessionDataError,
+    SessionDataFormat, SessionDataValue, VariantStream,
+};
+pub use numeric::{NumericError, NumericPolicy};
+pub use options::{DuplicateTagPolicy, ParseOptions, UriPolicy, VariablePolicy};
+pub use parser::PlaylistParser;
+#[cfg(feature = "std")]
+pub use reload::{ReloadCoordinator, ReloadPolicy, ReloadRequest};
+pub use repair::Repair;
+pub use rewrite::UriKind;
+pub use segmenter::{CmafChunk, CmafSegmenter, PartPublisher};
+pub use segments::MediaSegments;
+#[cfg(feature = "tokio")]
+pub use service::{BlockingPlaylistService, BlockingRequestError};
+pub use session::{ClientSession, SessionError, UpdateKind};
+#[cfg(feature = "std")]
+pub use sink::{write_atomically, FileSink, MemorySink, PlaylistSink};
+#[cfg(feature = "std")]
+pub use startup::LiveEdge;
+pub use subtitles::SubtitleRendition;
+pub use uri::Uri;
+pub use validate::{Rule, Severity, ValidationIssue, ValidationProfile};
+#[cfg(feature = "tokio")]
+pub use waiters::{BlockingWaiters, WaitTimeout};
+#[cfg(all(feature = "wasm-bindgen", target_arch = "wasm32"))]
+pub use wasm::parse_playlist;
+#[cfg(feature = "reqwest")]
+pub use watcher::PlaylistWatcher;
+pub use window::LivePlaylistWindow;
+#[cfg(feature = "tokio")]
+pub use writer::PublicationEvent;
+#[cfg(feature = "std")]
+pub use writer::{BudgetReport, LiveWriter};
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MediaPlaylist {
+    target_duration: u32,
+    version: u32,
+    // EXT-X-DEFINE tags, in order
+    definitions: Vec<Define>,
+    // Only required once the playlist carries partial segments
+    part_inf: Option<PartInf>,
+    media_sequence_number: u64,
+    // EXT-X-DISCONTINUITY-SEQUENCE; 0 when absent
+    discontinuity_sequence: u64,
+    media_segments: MediaSegments,
+    // Parts of the segment still being published, which has no EXTINF yet
+    trailing_parts: Vec<PartialSegment>,
+    // EXT-X-DISCONTINUITY, EXT-X-KEY and EXT-X-MAP opening the segment
+    // still being published
+    trailing_discontinuity: bool,
+    trailing_keys: Vec<Key>,
+    trailing_map: Option<Map>,
+    // EXT-X-PROGRAM-DATE-TIME, EXT-X-GAP and EXT-X-BITRATE read before the
+    // parts of the segment still being published
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, with = "serialize::option_date_time")
+    )]
+    trailing_program_date_time: Option<DateTime>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    trailing_gap: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    trailing_bitrate: Option<u32>,
+    // Custom tags for the segment still being published
+    #[cfg_attr(feature = "serde", serde(skip))]
+    trailing_tags: Vec<Arc<dyn CustomTag>>,
+    // Unknown tags for the segment still being published
+    #[cfg_attr(feature = "serde", serde(default))]
+    trailing_unknown_tags: Vec<UnknownTag>,
+    skip: Option<Skip>,
+    date_ranges: Vec<DateRange>,
+    // At most one per TYPE
+    preload_hints: Vec<PreloadHint>,
+    rendition_reports: Vec<RenditionReport>,
+    server_control: ServerControl,
+    // Whether EXT-X-SERVER-CONTROL and EXT-X-MEDIA-SEQUENCE are written out,
+    // as playlists without them are rendered without them
+    #[cfg_attr(feature = "serde", serde(default))]
+    declares_server_control: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    declares_media_sequence: bool,
+    // EXT-X-PLAYLIST-TYPE; live playlists have none
+    playlist_type: Option<PlaylistType>,
+    // EXT-X-I-FRAMES-ONLY: every segment is a single I-frame
+    i_frames_only: bool,
+    // EXT-X-INDEPENDENT-SEGMENTS
+    independent_segments: bool,
+    end_list: bool,
+    line_ending: LineEnding,
+    // Unknown tags before the first segment
+    #[cfg_attr(feature = "serde", serde(default))]
+    unknown_tags: Vec<UnknownTag>,
+    // Tags whose URI was read without its quotes, which rendering adds,
+    // until MediaPlaylist::repair reports them
+    #[cfg_attr(feature = "serde", serde(skip))]
+    unquoted_uris: Vec<(&'static str, String)>,
+}
+
+// Playlists are shared across async tasks on both clients and servers, so the
+// whole model must stay Send + Sync. This fails to compile if a field breaks it.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<MediaPlaylist>();
+    assert_send_sync::<MediaPlaylistBuilder>();
+    assert_send_sync::<MediaSegments>();
+    #[cfg(feature = "std")]
+    assert_send_sync::<PlaylistHistory>();
+    #[cfg(feature = "std")]
+    assert_send_sync::<CadenceAnalyzer>();
+    assert_send_sync::<BufferSimulation>();
+    assert_send_sync::<ClientSession>();
+    #[cfg(feature = "std")]
+    assert_send_sync::<ReloadCoordinator>();
+    assert_send_sync::<PartInf>();
+    assert_send_sync::<ServerControl>();
+    assert_send_sync::<MediaSegment>();
+    assert_send_sync::<PartialSegment>();
+    assert_send_sync::<Skip>();
+    assert_send_sync::<PreloadHint>();
+    assert_send_sync::<RenditionReport>();
+    assert_send_sync::<DateRange>();
+    assert_send_sync::<DateRangeSet>();
+    assert_send_sync::<RemovedDateRanges>();
+    assert_send_sync::<ParsePlaylistError>();
+    #[cfg(feature = "std")]
+    assert_send_sync::<LiveWriter>();
+    assert_send_sync::<MultivariantPlaylist>();
+    assert_send_sync::<BandwidthMeter>();
+    #[cfg(feature = "std")]
+    assert_send_sync::<FileSink>();
+    #[cfg(feature = "std")]
+    assert_send_sync::<MemorySink>();
+    #[cfg(feature = "tokio")]
+    assert_send_sync::<BlockingWaiters>();
+};
+
+/// Assembles a [`MediaPlaylist`] from its parts, e.g. on an origin that
+/// generates playlists rather than parsing them. Setters can be called in
+/// any order; see [`MediaPlaylistBuilder::build`] for the defaults of the
+/// fields left unset.
+#[derive(Clone, Default)]
+pub struct MediaPlaylistBuilder {
+    target_duration: Option<u32>,
+    version: Option<u32>,
+    definitions: Vec<Define>,
+    part_inf: Option<PartInf>,
+    media_sequence_number: Option<u64>,
+    discontinuity_sequence: u64,
+    media_segments: Vec<MediaSegment>,
+    trailing_parts: Vec<PartialSegment>,
+    skip: Option<Skip>,
+    date_ranges: Vec<DateRange>,
+    preload_hints: Vec<PreloadHint>,
+    rendition_reports: Vec<RenditionReport>,
+    server_control: Option<ServerControl>,
+    playlist_type: Option<PlaylistType>,
+    i_frames_only: bool,
+    independent_segments: bool,
+    end_list: bool,
+    line_ending: LineEnding,
+    unknown_tags: Vec<UnknownTag>,
+}
+
+impl MediaPlaylistBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn target_duration(&mut self, target_duration: u32) -> &mut Self {
+        self.target_duration = Some(target_duration);
+        self
+    }
+
+    pub fn version(&mut self, version: u32) -> &mut Self {
+        self.version = Some(version);
+        self
+    }
+
+    pub fn definitions(&mut self, definitions: Vec<Define>) -> &mut Self {
+        self.definitions = definitions;
+        self
+    }
+
+    pub fn part_inf(&mut self, part_inf: PartInf) -> &mut Self {
+        self.part_inf = Some(part_inf);
+        self
+    }
+
+    pub fn media_sequence_number(&mut self, media_sequence_number: u64) -> &mut Self {
+        self.media_sequence_number = Some(media_sequence_number);
+        self
+    }
+
+    pub fn discontinuity_sequence(&mut self, discontinuity_sequence: u64) -> &mut Self {
+        self.discontinuity_sequence = discontinuity_sequence;
+        self
+    }
+
+    pub fn media_segments(&mut self, media_segments: Vec<MediaSegment>) -> &mut Self {
+        self.media_segments = media_segments;
+        self
+    }
+
+    pub fn trailing_parts(&mut self, trailing_parts: Vec<PartialSegment>) -> &mut Self {
+        self.trailing_parts = trailing_parts;
+        self
+    }
+
+    pub fn skip(&mut self, skip: Option<Skip>) -> &mut Self {
+        self.skip = skip;
+        self
+    }
+
+    pub fn date_ranges(&mut self, date_ranges: Vec<DateRange>) -> &mut Self {
+        self.date_ranges = date_ranges;
+        self
+    }
+
+    /// Sets `preload_hint` as the only preload hint.
+    pub fn preload_hint(&mut self, preload_hint: Option<PreloadHint>) -> &mut Self {
+        self.preload_hints = preload_hint.into_iter().collect();
+        self
+    }
+
+    /// Sets the preload hints; of several of the same TYPE the last is kept.
+    pub fn preload_hints(&mut self, preload_hints: Vec<PreloadHint>) -> &mut Self {
+        self.preload_hints.clear();
+        for preload_hint in preload_hints {
+            replace_preload_hint(&mut self.preload_hints, preload_hint);
+        }
+        self
+    }
+
+    /// Adds a preload hint, replacing the one of the same TYPE if any.
+    pub fn add_preload_hint(&mut self, preload_hint: PreloadHint) -> &mut Self {
+        replace_preload_hint(&mut self.preload_hints, preload_hint);
+        self
+    }
+
+    pub fn rendition_reports(&mut self, rendition_reports: Vec<RenditionReport>) -> &mut Self {
+        self.rendition_reports = rendition_reports;
+        self
+    }
+
+    /// Adds a rendition report, replacing the one for the same URI if any.
+    pub fn add_rendition_report(&mut self, rendition_report: RenditionReport) -> &mut Self {
+        replace_rendition_report(&mut self.rendition_reports, rendition_report);
+        self
+    }
+
+    pub fn server_control(&mut self, server_control: ServerControl) -> &mut Self {
+        self.server_control = Some(server_control);
+        self
+    }
+
+    pub fn playlist_type(&mut self, playlist_type: PlaylistType) -> &mut Self {
+        self.playlist_type = Some(playlist_type);
+        self
+    }
+
+    pub fn i_frames_only(&mut self, i_frames_only: bool) -> &mut Self {
+        self.i_frames_only = i_frames_only;
+        self
+    }
+
+    pub fn independent_segments(&mut self, independent_segments: bool) -> &mut Self {
+        self.independent_segments = independent_segments;
+        self
+    }
+
+    pub fn end_list(&mut self, end_list: bool) -> &mut Self {
+        self.end_list = end_list;
+        self
+    }
+
+    pub fn line_ending(&mut self, line_ending: LineEnding) -> &mut Self {
+        self.line_ending = line_ending;
+        self
+    }
+
+    /// Tags this crate doesn't know, written after the other tags before the
+    /// first segment.
+    pub fn unknown_tags(&mut self, unknown_tags: Vec<UnknownTag>) -> &mut Self {
+        self.unknown_tags = unknown_tags;
+        self
+    }
+
+    /// Appends a segment. A segment without parts of its own takes the ones
+    /// added with [`MediaPlaylistBuilder::add_part`] since the previous
+    /// segment.
+    pub fn add_segment(&mut self, mut segment: MediaSegment) -> &mut Self {
+        if segment.partial_segments.is_empty() {
+            segment.partial_segments = core::mem::take(&mut self.trailing_parts);
+        }
+        self.media_segments.push(segment);
+        self
+    }
+
+    /// Appends a part to the segment being published. Parts still pending
+    /// when the playlist is built belong to the segment without EXTINF yet.
+    pub fn add_part(&mut self, part: PartialSegment) -> &mut Self {
+        self.trailing_parts.push(part);
+        self
+    }
+
+    /// Builds the playlist, filling in the fields left unset:
+    ///
+    /// - EXT-X-TARGETDURATION: the longest segment duration, rounded
+    /// - EXT-X-VERSION: the lowest the playlist's features require
+    /// - EXT-X-MEDIA-SEQUENCE: 0, not rendered
+    /// - EXT-X-PART-INF: the longest part duration, if there are parts
+    /// - EXT-X-SERVER-CONTROL: none, i.e. [`ServerControl::default`], not
+    ///   rendered
+    ///
+    /// Fails with the issues if the result breaks a MUST of RFC 8216, see
+    /// [`MediaPlaylist::validate_profile`].
+    pub fn build(&self) -> Result<MediaPlaylist, BuilderError> {
+        let mut builder = self.clone();
+        builder.target_duration.get_or_insert_with(|| {
+            self.media_segments
+                .iter()
+                .map(|segment| seconds::round(&segment.duration) as u32)
+                .max()
+                .unwrap_or(0)
+                .max(1)
+        });
+        builder.version.get_or_insert(1);
+        let mut playlist = builder
+            .build_unchecked()
+            .map_err(|UninitializedField(field)| BuilderError::MissingField { field })?;
+        if self.part_inf.is_none() {
+            playlist.part_inf = playlist
+                .parts()
+                .map(|part| part.part_duration)
+                .max()
+                .map(PartInf::new);
+        }
+        if self.version.is_none() {
+            playlist.version = playlist.required_version();
+        }
+        let issues: Vec<_> = playlist
+            .validate_profile(ValidationProfile::Rfc8216)
+            .into_iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .collect();
+        if !issues.is_empty() {
+            return Err(BuilderError::Invalid { issues });
+        }
+        Ok(playlist)
+    }
+
+    // Builds the playlist as set, without defaults or validation, as the
+    // parser does
+    fn build_unchecked(&self) -> Result<MediaPlaylist, UninitializedField> {
+        fn required<T: Clone>(
+            value: &Option<T>,
+            field: &'static str,
+        ) -> Result<T, UninitializedField> {
+            value.clone().ok_or(UninitializedField(field))
+        }
+        Ok(MediaPlaylist {
+            target_duration: required(&self.target_duration, "target_duration")?,
+            version: required(&self.version, "version")?,
+            definitions: self.definitions.clone(),
+            part_inf: self.part_inf.clone(),
+            media_sequence_number: self.media_sequence_number.unwrap_or(0),
+            discontinuity_sequence: self.discontinuity_sequence,
+            media_segments: self.media_segments.iter().cloned().collect(),
+            trailing_parts: self.trailing_parts.clone(),
+            trailing_discontinuity: false,
+            trailing_keys: Vec::new(),
+            trailing_map: None,
+            trailing_program_date_time: None,
+            trailing_gap: false,
+            trailing_bitrate: None,
+            trailing_tags: Vec::new(),
+            trailing_unknown_tags: Vec::new(),
+            skip: self.skip.clone(),
+            date_ranges: self.date_ranges.clone(),
+            preload_hints: self.preload_hints.clone(),
+            rendition_reports: self.rendition_reports.clone(),
+            server_control: self.server_control.clone().unwrap_or_default(),
+            declares_server_control: self.server_control.is_some(),
+            declares_media_sequence: self.media_sequence_number.is_some(),
+            playlist_type: self.playlist_type,
+            i_frames_only: self.i_frames_only,
+            independent_segments: self.independent_segments,
+            end_list: self.end_list,
+            line_ending: self.line_ending,
+            unknown_tags: self.unknown_tags.clone(),
+            unquoted_uris: Vec::new(),
+        })
+    }
+}
+
+impl MediaPlaylist {
+    /// Appends a part to the segment currently being published.
+    pub fn push_part(&mut self, part: PartialSegment) {
+        self.trailing_parts.push(part);
+    }
+
+    /// Closes the segment currently being published, turning the parts pushed
+    /// since the previous segment into its partial segments. Without
+    /// `program_date_time` it keeps the one read before those parts, if any.
+    pub fn complete_segment(
+        &mut self,
+        duration: Duration,
+        uri: Uri,
+        program_date_time: Option<DateTime>,
+    ) {
+        let partial_segments = core::mem::take(&mut self.trailing_parts);
+        let program_date_time = program_date_time.or(self.trailing_program_date_time.take());
+        let mut segment = MediaSegment::new(duration, uri, partial_segments, program_date_time);
+        segment.discontinuity = core::mem::take(&mut self.trailing_discontinuity);
+        segment.keys = core::mem::take(&mut self.trailing_keys);
+        segment.map = self.trailing_map.take();
+        segment.gap = core::mem::take(&mut self.trailing_gap);
+        if let Some(bitrate) = self.trailing_bitrate.take() {
+            segment.set_bitrate(Some(bitrate));
+        }
+        segment.custom_tags = core::mem::take(&mut self.trailing_tags);
+        segment.unknown_tags = core::mem::take(&mut self.trailing_unknown_tags);
+        self.media_segments.push(segment);
+    }
+
+    /// Adds an application-defined tag to the segment currently being
+    /// published. It renders before the parts pushed so far.
+    pub fn push_custom_tag(&mut self, tag: Arc<dyn CustomTag>) {
+        self.trailing_tags.push(tag);
+    }
+
+    /// Tags this crate doesn't know that precede the first segment, see
+    /// [`UnknownTag`]. Those of a segment are kept with it, see
+    /// [`MediaSegment::unknown_tags`].
+    pub fn unknown_tags(&self) -> &[UnknownTag] {
+        &self.unknown_tags
+    }
+
+    pub fn set_unknown_tags(&mut self, unknown_tags: Vec<UnknownTag>) {
+        self.unknown_tags = unknown_tags;
+    }
+
+    /// Adds a tag this crate doesn't know to the segment currently being
+    /// published, e.g. to pass one through from an upstream playlist. It
+    /// renders before the parts pushed so far.
+    pub fn push_unknown_tag(&mut self, tag: UnknownTag) {
+        self.trailing_unknown_tags.push(tag);
+    }
+
+    /// Starts the segment currently being published with an
+    /// EXT-X-DISCONTINUITY and a new EXT-X-MAP, e.g. after a codec or
+    /// resolution change. Meant to be called before its first part is
+    /// pushed, as the init segment applies to the whole segment.
+    pub fn rotate_map(&mut self, map: Map) {
+        self.trailing_discontinuity = true;
+        self.trailing_map = Some(map);
+    }
+
+    /// Switches the encryption of the segment currently being published and
+    /// those after it to `key`, e.g. on key rotation. Keys of different
+    /// KEYFORMATs can be pushed for the same segment.
+    pub fn push_key(&mut self, key: Key) {
+        self.trailing_keys.push(key);
+    }
+
+    /// Sets `preload_hint` as the only preload hint, or removes them all.
+    pub fn set_preload_hint(&mut self, preload_hint: Option<PreloadHint>) {
+        self.preload_hints = preload_hint.into_iter().collect();
+    }
+
+    /// Adds a preload hint, replacing the one of the same TYPE if any: a
+    /// playlist can hint at both its next part and its next init segment.
+    pub fn add_preload_hint(&mut self, preload_hint: PreloadHint) {
+        replace_preload_hint(&mut self.preload_hints, preload_hint);
+    }
+
+    /// Sets the preload hints; of several of the same TYPE the last is kept.
+    pub fn set_preload_hints(&mut self, preload_hints: Vec<PreloadHint>) {
+        self.preload_hints.clear();
+        for preload_hint in preload_hints {
+            replace_preload_hint(&mut self.preload_hints, preload_hint);
+        }
+    }
+
+    pub fn set_rendition_reports(&mut self, rendition_reports: Vec<RenditionReport>) {
+        self.rendition_reports = rendition_reports;
+    }
+
+    /// Adds a rendition report, replacing the one for the same URI if any,
+    /// e.g. as a sibling rendition advances.
+    pub fn add_rendition_report(&mut self, rendition_report: RenditionReport) {
+        replace_rendition_report(&mut self.rendition_reports, rendition_report);
+    }
+
+    /// EXT-X-DATERANGE tags of the playlist, one per ID.
+    pub fn date_ranges(&self) -> &[DateRange] {
+        &self.date_ranges
+    }
+
+    /// Replaces the dateranges, e.g. with those of a [`DateRangeSet`].
+    pub fn set_date_ranges(&mut self, date_ranges: Vec<DateRange>) {
+        self.date_ranges = date_ranges;
+    }
+
+    /// Appends a fully formed segment.
+    pub fn push_segment(&mut self, segment: MediaSegment) {
+        self.media_segments.push(segment);
+    }
+
+    /// Removes the oldest segment, advancing EXT-X-MEDIA-SEQUENCE, and
+    /// EXT-X-DISCONTINUITY-SEQUENCE if a discontinuity preceded it. Its
+    /// EXT-X-MAP moves to the next segment unless that has its own, and so
+    /// does each of its EXT-X-KEY tags unless the next segment declares one
+    /// of the same KEYFORMAT.
+    pub fn pop_segment(&mut self) -> Option<MediaSegment> {
+        let segment = self.media_segments.pop_front()?;
+        self.media_sequence_number += 1;
+        self.declares_media_sequence = true;
+        if segment.discontinuity {
+            self.discontinuity_sequence += 1;
+        }
+        if segment.bitrate.is_some() {
+            self.media_segments.update_first(
+                1,
+                |next| next.bitrate.is_some() && !next.declares_bitrate,
+                |next| next.declares_bitrate = true,
+            );
+        }
+        if !segment.keys.is_empty() {
+            if self.media_segments.is_empty() {
+                Key::inherit(&mut self.trailing_keys, &segment.keys);
+            } else {
+                self.media_segments.update_first(
+                    1,
+                    |_| true,
+                    |next| Key::inherit(&mut next.keys, &segment.keys),
+                );
+            }
+        }
+        if let Some(map) = &segment.map {
+            if self.media_segments.is_empty() {
+                if self.trailing_map.is_none() {
+                    self.trailing_map = Some(map.clone());
+                }
+            } else {
+                self.media_segments.update_first(
+                    1,
+                    |next| next.map.is_none(),
+                    |next| next.map = Some(map.clone()),
+                );
+            }
+        }
+        Some(segment)
+    }
+
+    pub fn target_duration(&self) -> u32 {
+        self.target_duration
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// EXT-X-DEFINE variables of the playlist, in order.
+    pub fn definitions(&self) -> &[Define] {
+        &self.definitions
+    }
+
+    pub fn part_inf(&self) -> Option<&PartInf> {
+        self.part_inf.as_ref()
+    }
+
+    /// EXT-X-MEDIA-SEQUENCE: the media sequence number of the first segment,
+    /// counting any a delta update skipped.
+    pub fn media_sequence_number(&self) -> u64 {
+        self.media_sequence_number
+    }
+
+    /// EXT-X-DISCONTINUITY-SEQUENCE: the discontinuity sequence number of
+    /// the first segment, counting any a delta update skipped.
+    pub fn discontinuity_sequence(&self) -> u64 {
+        self.discontinuity_sequence
+    }
+
+    /// The discontinuity sequence number of the segment at `index` of
+    /// [`MediaPlaylist::media_segments`]: EXT-X-DISCONTINUITY-SEQUENCE plus
+    /// the discontinuities up to and including it. An `index` past the last
+    /// segment asks for the segment still being published. In a delta
+    /// update, discontinuities among the skipped segments aren't counted.
+    pub fn discontinuity_sequence_for(&self, index: usize) -> u64 {
+        let listed = self
+            .media_segments
+            .iter()
+            .take(index.saturating_add(1))
+            .filter(|segment| segment.discontinuity)
+            .count() as u64;
+        let trailing = index >= self.media_segments.len() && self.trailing_discontinuity;
+        self.discontinuity_sequence + listed + trailing as u64
+    }
+
+    /// The discontinuity sequence number of the segment with media sequence
+    /// number `msn`, if it is listed or still being published.
+    pub fn discontinuity_sequence_of(&self, msn: u64) -> Option<u64> {
+        if !self.contains_msn(msn) {
+            return None;
+        }
+        Some(self.discontinuity_sequence_for((msn - self.first_listed_msn()) as usize))
+    }
+
+    pub fn server_control(&self) -> &ServerControl {
+        &self.server_control
+    }
+
+    pub fn set_server_control(&mut self, server_control: ServerControl) {
+        self.server_control = server_control;
+        self.declares_server_control = true;
+    }
+
+    /// How far from the end of the playlist clients start playing without
+    /// parts: HOLD-BACK, or three target durations when absent.
+    pub fn hold_back(&self) -> Duration {
+        match self.server_control.hold_back {
+            hold_back if hold_back.is_zero() => {
+                Duration::from_secs(3 * u64::from(self.target_duration))
+            }
+            hold_back => hold_back,
+        }
+    }
+
+    /// How far from the end of the playlist clients start playing with
+    /// parts: PART-HOLD-BACK, or three part targets when absent. Zero
+    /// without EXT-X-PART-INF.
+    pub fn part_hold_back(&self) -> Duration {
+        match (self.server_control.part_hold_back, &self.part_inf) {
+            (hold_back, Some(part_inf)) if hold_back.is_zero() => {
+                part_inf.part_target.saturating_mul(3)
+            }
+            (hold_back, _) => hold_back,
+        }
+    }
+
+    /// EXT-X-SKIP, if this is a delta update.
+    pub fn skip(&self) -> Option<&Skip> {
+        self.skip.as_ref()
+    }
+
+    /// The EXT-X-PRELOAD-HINT of the next part, see
+    /// [`MediaPlaylist::map_preload_hint`] for the one of the next init
+    /// segment.
+    pub fn preload_hint(&self) -> Option<&PreloadHint> {
+        self.preload_hint_of(PreloadHintType::Part)
+    }
+
+    /// The EXT-X-PRELOAD-HINT of the next init segment, if it changes with
+    /// the next part.
+    pub fn map_preload_hint(&self) -> Option<&PreloadHint> {
+        self.preload_hint_of(PreloadHintType::Map)
+    }
+
+    /// The EXT-X-PRELOAD-HINT tags, at most one per TYPE, the MAP hint
+    /// first.
+    pub fn preload_hints(&self) -> &[PreloadHint] {
+        &self.preload_hints
+    }
+
+    /// The preload hint of TYPE `r#type`, if any.
+    pub fn preload_hint_of(&self, r#type: PreloadHintType) -> Option<&PreloadHint> {
+        self.preload_hints.iter().find(|hint| hint.r#type == r#type)
+    }
+
+    pub fn rendition_reports(&self) -> &[RenditionReport] {
+        &self.rendition_reports
+    }
+
+    pub fn playlist_type(&self) -> Option<PlaylistType> {
+        self.playlist_type
+    }
+
+    pub fn set_playlist_type(&mut self, playlist_type: Option<PlaylistType>) {
+        self.playlist_type = playlist_type;
+    }
+
+    /// Whether EXT-X-I-FRAMES-ONLY marks the playlist as a trick-play one:
+    /// each segment is a single I-frame, usually a byte range of a media
+    /// segment, and its duration lasts until the next I-frame.
+    pub fn i_frames_only(&self) -> bool {
+        self.i_frames_only
+    }
+
+    pub fn set_i_frames_only(&mut self, i_frames_only: bool) {
+        self.i_frames_only = i_frames_only;
+    }
+
+    /// Whether EXT-X-INDEPENDENT-SEGMENTS states that every segment can be
+    /// decoded without the ones before it.
+    pub fn independent_segments(&self) -> bool {
+        self.independent_segments
+    }
+
+    pub fn set_independent_segments(&mut self, independent_segments: bool) {
+        self.independent_segments = independent_segments;
+    }
+
+    /// Whether EXT-X-ENDLIST ends the playlist: no segments will be added.
+    pub fn end_list(&self) -> bool {
+        self.end_list
+    }
+
+    /// Ends the playlist with EXT-X-ENDLIST, e.g. when the stream is over.
+    pub fn set_end_list(&mut self, end_list: bool) {
+        self.end_list = end_list;
+    }
+
+    /// Whether segments may still be added, so clients should keep
+    /// reloading. An EVENT playlist stays live until it gets EXT-X-ENDLIST.
+    pub fn is_live(&self) -> bool {
+        !self.end_list
+    }
+
+    pub fn media_segments(&self) -> &MediaSegments {
+        &self.media_segments
+    }
+
+    /// The listed segments, oldest first.
+    pub fn segments(&self) -> impl Iterator<Item = &MediaSegment> + '_ {
+        self.media_segments.iter()
+    }
+
+    /// Every listed part in publication order: those of the segments, then
+    /// those of the segment still being published.
+    pub fn parts(&self) -> impl Iterator<Item = &PartialSegment> + '_ {
+        self.parts_with_position().map(|(_, _, part)| part)
+    }
+
+    pub fn trailing_parts(&self) -> &[PartialSegment] {
+        &self.trailing_parts
+    }
+
+    /// The init segment that applies to the segment at `index` of
+    /// [`MediaPlaylist::media_segments`]: the newest EXT-X-MAP at or before
+    /// it. An `index` past the last segment asks for the segment still being
+    /// published.
+    pub fn map_for(&self, index: usize) -> Option<&Map> {
+        if index >= self.media_segments.len() {
+            if let Some(map) = &self.trailing_map {
+                return Some(map);
+            }
+        }
+        self.media_segments
+            .iter()
+            .take(index.saturating_add(1))
+            .rev()
+            .find_map(|segment| segment.map.as_ref())
+    }
+
+    /// Duration-weighted mean of the bit rates of the segments, in bits per
+    /// second, or `None` if no segment has one. Segments without a bit rate
+    /// are left out.
+    pub fn average_bitrate(&self) -> Option<u64> {
+        let (bits, duration) = self
+            .media_segments
+            .iter()
+            .filter_map(|segment| Some((segment.bitrate()?, segment.duration)))
+            .fold(
+                (0.0, 0.0),
+                |(bits, duration), (bitrate, segment_duration)| {
+                    (
+                        bits + bitrate as f64 * segment_duration.as_secs_f64(),
+                        duration + segment_duration.as_secs_f64(),
+                    )
+                },
+            );
+        (duration > 0.0).then(|| float::round(bits / duration) as u64)
+    }
+
+    /// The EXT-X-KEY tags that apply to the segment at `index` of
+    /// [`MediaPlaylist::media_segments`]: the newest one at or before it of
+    /// each KEYFORMAT, as a key applies until the next one of its KEYFORMAT,
+    /// those of the newest segment first. Empty if the segment isn't
+    /// encrypted because no key was declared; a key of [`KeyMethod::None`]
+    /// ends every earlier one and is returned as is. An `index` past the last
+    /// segment asks for the segment still being published.
+    pub fn keys_for(&self, index: usize) -> Vec<&Key> {
+        let trailing = (index >= self.media_segments.len()).then_some(&self.trailing_keys);
+        let declared = self
+            .media_segments
+            .iter()
+            .take(index.saturating_add(1))
+            .rev()
+            .map(|segment| &segment.keys);
+        let mut keys: Vec<&Key> = Vec::new();
+        for declared in trailing.into_iter().chain(declared) {
+            if declared.iter().any(|key| !key.is_encrypted()) {
+                if keys.is_empty() {
+                    keys.extend(declared);
+                }
+                break;
+            }
+            for key in declared {
+                if !keys.iter().any(|kept| kept.format() == key.format()) {
+                    keys.push(key);
+                }
+            }
+        }
+        keys
+    }
+
+    /// Line ending the playlist renders with. Parsed playlists keep the one
+    /// of their source, so they round-trip unchanged.
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.line_ending = line_ending;
+    }
+
+    /// The segment of media sequence number `msn`, if listed. Segments a
+    /// delta update skipped aren't.
+    pub fn get(&self, msn: u64) -> Option<&MediaSegment> {
+        let index = msn.checked_sub(self.first_listed_msn())?;
+        self.media_segments.get(usize::try_from(index).ok()?)
+    }
+
+    /// Part `part_index` of the segment of media sequence number `msn`,
+    /// which may be the segment still being published.
+    pub fn get_part(&self, msn: u64, part_index: u32) -> Option<&PartialSegment> {
+        let index = msn.checked_sub(self.first_listed_msn())?;
+        let parts = if index == self.media_segments.len() as u64 {
+            &self.trailing_parts
+        } else {
+            &self.get(msn)?.partial_segments
+        };
+        parts.get(usize::try_from(part_index).ok()?)
+    }
+
+    /// Media sequence number of the newest published media: the segment
+    /// still being published once it has parts, the newest complete one
+    /// otherwise. LAST-MSN of rendition reports for this playlist.
+    pub fn last_msn(&self) -> u64 {
+        self.last_position().0
+    }
+
+    /// Index of the newest part of the segment [`MediaPlaylist::last_msn`]
+    /// names, LAST-PART of rendition reports for this playlist. `None` if
+    /// that segment wasn't published as parts.
+    pub fn last_part(&self) -> Option<u32> {
+        self.last_position().1
+    }
+
+    // Media sequence number of the first segment listed, after any segments
+    // a delta update skipped
+    pub(crate) fn first_listed_msn(&self) -> u64 {
+        let skipped = self.skip.as_ref().map_or(0, |skip| skip.skipped_segments);
+        self.media_sequence_number + skipped
+    }
+
+    // Media sequence number and part index of the newest published media,
+    // as carried in LAST-MSN/LAST-PART of rendition reports
+    pub(crate) fn last_position(&self) -> (u64, Option<u32>) {
+        let segments = self.media_segments.len() as u64;
+        if !self.trailing_parts.is_empty() {
+            return (
+                self.first_listed_msn() + segments,
+                Some(self.trailing_parts.len() as u32 - 1),
+            );
+        }
+        let last_part = self
+            .media_segments
+            .last()
+            .and_then(|segment| segment.partial_segments.len().checked_sub(1))
+            .map(|part| part as u32);
+        (
+            (self.first_listed_msn() + segments).saturating_sub(1),
+            last_part,
+        )
+    }
+
+    pub(crate) fn contains_msn(&self, msn: u64) -> bool {
+        let (last_msn, _) = self.last_position();
+        let published = !self.media_segments.is_empty() || !self.trailing_parts.is_empty();
+        published && (self.first_listed_msn()..=last_msn).contains(&msn)
+    }
+
+    // Media the playlist lists, including the parts of the segment still
+    // being published, which a delta update must keep CAN-SKIP-UNTIL of
+    pub(crate) fn retained_duration(&self) -> Duration {
+        seconds::sum(
+            self.media_segments
+                .iter()
+                .map(|segment| segment.duration)
+                .chain(self.trailing_parts.iter().map(|part| part.part_duration)),
+        )
+    }
+
+    // Every listed part in publication order, with the media sequence number
+    // of its parent segment and its index within that segment
+    pub(crate) fn parts_with_position(
+        &self,
+    ) -> impl Iterator<Item = (u64, u32, &PartialSegment)> + '_ {
+        let first_msn = self.first_listed_msn();
+        let trailing_msn = first_msn + self.media_segments.len() as u64;
+        self.media_segments
+            .iter()
+            .zip(first_msn..)
+            .flat_map(|(segment, msn)| {
+                segment
+                    .partial_segments
+                    .iter()
+                    .zip(0..)
+                    .map(move |(part, index)| (msn, index, part))
+            })
+            .chain(
+                self.trailing_parts
+                    .iter()
+                    .zip(0..)
+                    .map(move |(part, index)| (trailing_msn, index, part)),
+            )
+    }
+
+    /// Estimates the number of bytes the playlist occupies once rendered,
+    /// without building the rendered text.
+    pub fn estimated_serialized_len(&self) -> usize {
+        let mut len = line_len("#EXTM3U".len(), self.line_ending);
+        len += line_len(
+            "#EXT-X-TARGETDURATION:".len() + display_len(&self.target_duration),
+            self.line_ending,
+        );
+        len += line_len(
+            "#EXT-X-VERSION:".len() + display_len(&self.version),
+            self.line_ending,
+        );
+        for define in &self.definitions {
+            len += line_len(display_len(define), self.line_ending);
+        }
+        if self.declares_server_control {
+            len += line_len(
+                self.server_control.estimated_serialized_len(),
+                self.line_ending,
+            );
+        }
+        if let Some(part_inf) = &self.part_inf {
+            len += line_len(
+                "#EXT-X-PART-INF:PART-TARGET=".len()
+                    + display_len(&seconds::display(&part_inf.part_target))
+                    + display_len(&ExtraAttributes(&part_inf.extra_attributes)),
+                self.line_ending,
+            );
+        }
+        if self.declares_media_sequence {
+            len += line_len(
+                "#EXT-X-MEDIA-SEQUENCE:".len() + display_len(&self.media_sequence_number),
+                self.line_ending,
+            );
+        }
+        if self.discontinuity_sequence != 0 {
+            len += line_len(
+                "#EXT-X-DISCONTINUITY-SEQUENCE:".len() + display_len(&self.discontinuity_sequence),
+                self.line_ending,
+            );
+        }
+        if let Some(playlist_type) = self.playlist_type {
+            len += line_len(
+                "#EXT-X-PLAYLIST-TYPE:".len() + playlist_type.as_str().len(),
+                self.line_ending,
+            );
+        }
+        if self.i_frames_only {
+            len += line_len("#EXT-X-I-FRAMES-ONLY".len(), self.line_ending);
+        }
+        if self.independent_segments {
+            len += line_len("#EXT-X-INDEPENDENT-SEGMENTS".len(), self.line_ending);
+        }
+        if let Some(skip) = &self.skip {
+            len += line_len(skip.estimated_serialized_len(), self.line_ending);
+        }
+        for date_range in &self.date_ranges {
+            len += line_len(display_len(date_range), self.line_ending);
+        }
+        for tag in &self.unknown_tags {
+            len += line_len(display_len(tag), self.line_ending);
+        }
+        for segment in &self.media_segments {
+            len += segment.estimated_serialized_len(self.line_ending);
+        }
+        if self.trailing_discontinuity {
+            len += line_len("#EXT-X-DISCONTINUITY".len(), self.line_ending);
+        }
+        for key in &self.trailing_keys {
+            len += line_len(display_len(key), self.line_ending);
+        }
+        if let Some(map) = &self.trailing_map {
+            len += line_len(display_len(map), self.line_ending);
+        }
+        if let Some(bitrate) = self.trailing_bitrate {
+            len += line_len(
+                "#EXT-X-BITRATE:".len() + display_len(&bitrate),
+                self.line_ending,
+            );
+        }
+        if let Some(program_date_time) = &self.trailing_program_date_time {
+            len += line_len(
+                "#EXT-X-PROGRAM-DATE-TIME:".len()
+                    + display_len(&datetime::display(program_date_time)),
+                self.line_ending,
+            );
+        }
+        if self.trailing_gap {
+            len += line_len("#EXT-X-GAP".len(), self.line_ending);
+        }
+        for tag in &self.trailing_tags {
+            len += line_len(display_len(&tag), self.line_ending);
+        }
+        for tag in &self.trailing_unknown_tags {
+            len += line_len(display_len(tag), self.line_ending);
+        }
+        for part in &self.trailing_parts {
+            len += line_len(display_len(part), self.line_ending);
+        }
+        for preload_hint in &self.preload_hints {
+            len += line_len(preload_hint.estimated_serialized_len(), self.line_ending);
+        }
+        for rendition_report in &self.rendition_reports {
+            len += line_len(
+                rendition_report.estimated_serialized_len(),
+                self.line_ending,
+            );
+        }
+        if self.end_list {
+            len += line_len("#EXT-X-ENDLIST".len(), self.line_ending);
+        }
+        len
+    }
+}
+
+/// EXT-X-PLAYLIST-TYPE. A playlist without one is live: segments may also
+/// be removed from it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PlaylistType {
+    /// Segments are only ever added, until EXT-X-ENDLIST.
+    Event,
+    /// The playlist never changes.
+    Vod,
+}
+
+impl PlaylistType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PlaylistType::Event => "EVENT",
+            PlaylistType::Vod => "VOD",
+        }
+    }
+}
+
+impl FromStr for PlaylistType {
+    type Err = ParseTagError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "EVENT" => Ok(PlaylistType::Event),
+            "VOD" => Ok(PlaylistType::Vod),
+            _ => Err(ParseTagError::invalid(s)),
+        }
+    }
+}
+
+impl fmt::Display for PlaylistType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Line terminator of a rendered playlist.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+// Turns the line feeds written through it into CRLF
+pub(crate) struct CrLf<'a, W: fmt::Write>(pub(crate) &'a mut W);
+
+impl<W: fmt::Write> fmt::Write for CrLf<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut lines = s.split('\n');
+        if let Some(first) = lines.next() {
+            self.0.write_str(first)?;
+        }
+        for line in lines {
+            self.0.write_str("\r\n")?;
+            self.0.write_str(line)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for MediaPlaylist {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line_ending {
+            LineEnding::Lf => self.render(f),
+            LineEnding::CrLf => self.render(&mut CrLf(f)),
+        }
+    }
+}
+
+impl MediaPlaylist {
+    /// Writes the rendered playlist to `writer`, e.g. a file or socket,
+    /// without building the whole text first.
+    #[cfg(feature = "std")]
+    pub fn write_to<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.write_to_with_hooks(writer, &mut NoHooks)
+    }
+
+    /// Like [`MediaPlaylist::write_to`], and tells
+    /// [`Hooks::on_serialize`] how many bytes were written.
+    #[cfg(feature = "std")]
+    pub fn write_to_with_hooks<W: io::Write, H: Hooks + ?Sized>(
+        &self,
+        writer: &mut W,
+        hooks: &mut H,
+    ) -> io::Result<()> {
+        let mut writer = CountingWriter {
+            inner: writer,
+            bytes: 0,
+        };
+        io::Write::write_fmt(&mut writer, format_args!("{}", self))?;
+        hooks.on_serialize(self, writer.bytes);
+        Ok(())
+    }
+
+    /// Renders the playlist into `buffer`, replacing what it held. An
+    /// origin regenerating the playlist with every part can keep reusing
+    /// one buffer: once it has grown to fit, rendering doesn't allocate.
+    pub fn serialize_into(&self, buffer: &mut Vec<u8>) {
+        self.serialize_into_with_hooks(buffer, &mut NoHooks);
+    }
+
+    /// Like [`MediaPlaylist::serialize_into`], and tells
+    /// [`Hooks::on_serialize`] how many bytes were rendered.
+    pub fn serialize_into_with_hooks<H: Hooks + ?Sized>(
+        &self,
+        buffer: &mut Vec<u8>,
+        hooks: &mut H,
+    ) {
+        buffer.clear();
+        let mut writer = BytesWriter(buffer);
+        // Writing to a Vec can't fail
+        let _ = match self.line_ending {
+            LineEnding::Lf => self.render(&mut writer),
+            LineEnding::CrLf => self.render(&mut CrLf(&mut writer)),
+        };
+        hooks.on_serialize(self, buffer.len());
+    }
+}
+
+// io::Write keeping count of the bytes written through it
+#[cfg(feature = "std")]
+struct CountingWriter<'a, W> {
+    inner: &'a mut W,
+    bytes: usize,
+}
+
+#[cfg(feature = "std")]
+impl<W: io::Write> io::Write for CountingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.bytes += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// fmt::Write into a byte buffer, for rendering without a String
+struct BytesWriter<'a>(&'a mut Vec<u8>);
+
+impl fmt::Write for BytesWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.extend_from_slice(s.as_bytes());
+        Ok(())
+    }
+}
+
+impl MediaPlaylist {
+    fn render<W: fmt::Write>(&self, f: &mut W) -> fmt::Result {
+        self.render_header(f)?;
+        for segment in &self.media_segments {
+            segment.render(f)?;
+        }
+        self.render_tail(f)
+    }
+
+    // Tags before the first segment
+    pub(crate) fn render_header<W: fmt::Write>(&self, f: &mut W) -> fmt::Result {
+        writeln!(f, "#EXTM3U")?;
+        writeln!(f, "#EXT-X-TARGETDURATION:{}", self.target_duration)?;
+        writeln!(f, "#EXT-X-VERSION:{}", self.version)?;
+        for define in &self.definitions {
+            writeln!(f, "{}", define)?;
+        }
+        if self.declares_server_control {
+            writeln!(f, "{}", self.server_control)?;
+        }
+        if let Some(part_inf) = &self.part_inf {
+            writeln!(f, "{}", part_inf)?;
+        }
+        if self.declares_media_sequence {
+            writeln!(f, "#EXT-X-MEDIA-SEQUENCE:{}", self.media_sequence_number)?;
+        }
+        if self.discontinuity_sequence != 0 {
+            writeln!(
+                f,
+                "#EXT-X-DISCONTINUITY-SEQUENCE:{}",
+                self.discontinuity_sequence
+            )?;
+        }
+        if let Some(playlist_type) = self.playlist_type {
+            writeln!(f, "#EXT-X-PLAYLIST-TYPE:{}", playlist_type)?;
+        }
+        if self.i_frames_only {
+            writeln!(f, "#EXT-X-I-FRAMES-ONLY")?;
+        }
+        if self.independent_segments {
+            writeln!(f, "#EXT-X-INDEPENDENT-SEGMENTS")?;
+        }
+        if let Some(skip) = &self.skip {
+            writeln!(f, "{}", skip)?;
+        }
+        for date_range in &self.date_ranges {
+            writeln!(f, "{}", date_range)?;
+        }
+        for tag in &self.unknown_tags {
+            writeln!(f, "{}", tag)?;
+        }
+        Ok(())
+    }
+
+    // The segment still being published and everything after it
+    pub(crate) fn render_tail<W: fmt::Write>(&self, f: &mut W) -> fmt::Result {
+        if self.trailing_discontinuity {
+            writeln!(f, "#EXT-X-DISCONTINUITY")?;
+        }
+        for key in &self.trailing_keys {
+            writeln!(f, "{}", key)?;
+        }
+        if let Some(map) = &self.trailing_map {
+            writeln!(f, "{}", map)?;
+        }
+        if let Some(bitrate) = self.trailing_bitrate {
+            writeln!(f, "#EXT-X-BITRATE:{}", bitrate)?;
+        }
+        if let Some(program_date_time) = &self.trailing_program_date_time {
+            writeln!(
+                f,
+                "#EXT-X-PROGRAM-DATE-TIME:{}",
+                datetime::display(program_date_time)
+            )?;
+        }
+        if self.trailing_gap {
+            writeln!(f, "#EXT-X-GAP")?;
+        }
+        for tag in &self.trailing_tags {
+            writeln!(f, "{}", tag)?;
+        }
+        custom::render_parts(f, &self.trailing_parts, &self.trailing_unknown_tags, true)?;
+        for preload_hint in &self.preload_hints {
+            writeln!(f, "{}", preload_hint)?;
+        }
+        for report in &self.rendition_reports {
+            writeln!(f, "{}", report)?;
+        }
+        if self.end_list {
+            writeln!(f, "#EXT-X-ENDLIST")?;
+        }
+        for tag in &self.trailing_unknown_tags {
+            if tag.position == TagPosition::End {
+                writeln!(f, "{}", tag)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Renders every line of the segment, each followed by a line break
+impl fmt::Display for MediaSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.render(f)
+    }
+}
+
+// Attributes left out are written as absent rather than zero
+impl fmt::Display for ServerControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD={}",
+            if self.can_block_reload { "YES" } else { "NO" }
+        )?;
+        if !self.hold_back.is_zero() {
+            write!(f, ",HOLD-BACK={}", seconds::display(&self.hold_back))?;
+        }
+        if !self.part_hold_back.is_zero() {
+            write!(
+                f,
+                ",PART-HOLD-BACK={}",
+                seconds::display(&self.part_hold_back)
+            )?;
+        }
+        if !self.can_skip_until.is_zero() {
+            write!(
+                f,
+                ",CAN-SKIP-UNTIL={}",
+                seconds::display(&self.can_skip_until)
+            )?;
+            if self.can_skip_dateranges {
+                write!(f, ",CAN-SKIP-DATERANGES=YES")?;
+            }
+        }
+        write!(f, "{}", ExtraAttributes(&self.extra_attributes))
+    }
+}
+
+impl fmt::Display for PartInf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#EXT-X-PART-INF:PART-TARGET={}{}",
+            seconds::display(&self.part_target),
+            ExtraAttributes(&self.extra_attributes)
+        )
+    }
+}
+
+impl fmt::Display for PreloadHint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#EXT-X-PRELOAD-HINT:TYPE={},URI=\"{}\"",
+            match self.r#type {
+                PreloadHintType::Part => "PART",
+                PreloadHintType::Map => "MAP",
+            },
+            self.uri
+        )?;
+        if let Some(byterange_start) = self.byterange_start {
+            write!(f, ",BYTERANGE-START={}", byterange_start)?;
+        }
+        if let Some(byterange_length) = self.byterange_length {
+            write!(f, ",BYTERANGE-LENGTH={}", byterange_length)?;
+        }
+        write!(f, "{}", ExtraAttributes(&self.extra_attributes))
+    }
+}
+
+impl fmt::Display for RenditionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#EXT-X-RENDITION-REPORT:URI=\"{}\",LAST-MSN={}",
+            self.uri, self.last_msn
+        )?;
+        if let Some(last_part) = self.last_part {
+            write!(f, ",LAST-PART={}", last_part)?;
+        }
+        write!(f, "{}", ExtraAttributes(&self.extra_attributes))
+    }
 }
 
-#[derive(Builder, Clone)]
-struct PartInf {
-    part_target: f32,
+impl MediaSegment {
+    pub(crate) fn render<W: fmt::Write>(&self, f: &mut W) -> fmt::Result {
+        if self.discontinuity {
+            writeln!(f, "#EXT-X-DISCONTINUITY")?;
+        }
+        for key in &self.keys {
+            writeln!(f, "{}", key)?;
+        }
+        if let Some(map) = &self.map {
+            writeln!(f, "{}", map)?;
+        }
+        if let Some(bitrate) = self.bitrate.filter(|_| self.declares_bitrate) {
+            writeln!(f, "#EXT-X-BITRATE:{}", bitrate)?;
+        }
+        if let Some(program_date_time) = &self.program_date_time {
+            writeln!(
+                f,
+                "#EXT-X-PROGRAM-DATE-TIME:{}",
+                datetime::display(program_date_time)
+            )?;
+        }
+        for tag in &self.custom_tags {
+            writeln!(f, "{}", tag)?;
+        }
+        custom::render_parts(f, &self.partial_segments, &self.unknown_tags, false)?;
+        if self.gap {
+            writeln!(f, "#EXT-X-GAP")?;
+        }
+        writeln!(
+            f,
+            "#EXTINF:{},{}",
+            seconds::display(&self.duration),
+            self.title.as_deref().unwrap_or_default()
+        )?;
+        if let Some(byterange) = &self.byterange {
+            writeln!(f, "#EXT-X-BYTERANGE:{}", byterange)?;
+        }
+        writeln!(f, "{}", self.written_uri())
+    }
+
+    /// The URI line as rendered: [`MediaSegment::raw_uri`] if the line was
+    /// kept as written, [`MediaSegment::uri`] otherwise.
+    pub fn written_uri(&self) -> &str {
+        self.raw_uri
+            .as_deref()
+            .unwrap_or_else(|| uri::as_str(&self.uri))
+    }
+}
+
+impl ServerControl {
+    fn estimated_serialized_len(&self) -> usize {
+        let seconds_len = |name: &str, value: &Duration| {
+            if value.is_zero() {
+                0
+            } else {
+                name.len() + display_len(&seconds::display(value))
+            }
+        };
+        let mut len = "#EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=".len()
+            + if self.can_block_reload { "YES" } else { "NO" }.len()
+            + seconds_len(",HOLD-BACK=", &self.hold_back)
+            + seconds_len(",PART-HOLD-BACK=", &self.part_hold_back)
+            + seconds_len(",CAN-SKIP-UNTIL=", &self.can_skip_until);
+        if self.can_skip_dateranges && !self.can_skip_until.is_zero() {
+            len += ",CAN-SKIP-DATERANGES=YES".len();
+        }
+        len + display_len(&ExtraAttributes(&self.extra_attributes))
+    }
+}
+
+impl Skip {
+    fn estimated_serialized_len(&self) -> usize {
+        let mut len = "#EXT-X-SKIP:SKIPPED-SEGMENTS=".len() + display_len(&self.skipped_segments);
+        if !self.recently_removed_dateranges.is_empty() {
+            // Quoted, tab-separated list of IDs
+            len += ",RECENTLY-REMOVED-DATERANGES=\"\"".len()
+                + self
+                    .recently_removed_dateranges
+                    .iter()
+                    .map(|id| id.len())
+                    .sum::<usize>()
+                + self.recently_removed_dateranges.len()
+                - 1;
+        }
+        len + display_len(&ExtraAttributes(&self.extra_attributes))
+    }
+}
+
+impl MediaSegment {
+    fn estimated_serialized_len(&self, line_ending: LineEnding) -> usize {
+        let mut len = 0;
+        if self.discontinuity {
+            len += line_len("#EXT-X-DISCONTINUITY".len(), line_ending);
+        }
+        for key in &self.keys {
+            len += line_len(display_len(key), line_ending);
+        }
+        if let Some(map) = &self.map {
+            len += line_len(display_len(map), line_ending);
+        }
+        if let Some(bitrate) = self.bitrate.filter(|_| self.declares_bitrate) {
+            len += line_len("#EXT-X-BITRATE:".len() + display_len(&bitrate), line_ending);
+        }
+        if let Some(program_date_time) = &self.program_date_time {
+            len += line_len(
+                "#EXT-X-PROGRAM-DATE-TIME:".len()
+                    + display_len(&datetime::display(program_date_time)),
+                line_ending,
+            );
+        }
+        for tag in &self.custom_tags {
+            len += line_len(display_len(&tag), line_ending);
+        }
+        for tag in &self.unknown_tags {
+            len += line_len(display_len(tag), line_ending);
+        }
+        for part in &self.partial_segments {
+            len += line_len(display_len(part), line_ending);
+        }
+        if self.gap {
+            len += line_len("#EXT-X-GAP".len(), line_ending);
+        }
+        len += line_len(
+            "#EXTINF:,".len()
+                + display_len(&seconds::display(&self.duration))
+                + self.title.as_ref().map_or(0, String::len),
+            line_ending,
+        );
+        if let Some(byterange) = &self.byterange {
+            len += line_len(
+                "#EXT-X-BYTERANGE:".len() + display_len(byterange),
+                line_ending,
+            );
+        }
+        len += line_len(self.written_uri().len(), line_ending);
+        len
+    }
+}
+
+impl PreloadHint {
+    fn estimated_serialized_len(&self) -> usize {
+        let mut len = "#EXT-X-PRELOAD-HINT:TYPE=".len()
+            + match self.r#type {
+                PreloadHintType::Part => "PART",
+                PreloadHintType::Map => "MAP",
+            }
+            .len()
+            + ",URI=\"\"".len()
+            + self.uri.len();
+        if let Some(byterange_start) = self.byterange_start {
+            len += ",BYTERANGE-START=".len() + display_len(&byterange_start);
+        }
+        if let Some(byterange_length) = self.byterange_length {
+            len += ",BYTERANGE-LENGTH=".len() + display_len(&byterange_length);
+        }
+        len + display_len(&ExtraAttributes(&self.extra_attributes))
+    }
+}
+
+impl RenditionReport {
+    fn estimated_serialized_len(&self) -> usize {
+        "#EXT-X-RENDITION-REPORT:URI=\"\"".len()
+            + self.uri.len()
+            + ",LAST-MSN=".len()
+            + display_len(&self.last_msn)
+            + self
+                .last_part
+                .map_or(0, |last_part| ",LAST-PART=".len() + display_len(&last_part))
+            + display_len(&ExtraAttributes(&self.extra_attributes))
+    }
+}
+
+fn line_len(len: usize, line_ending: LineEnding) -> usize {
+    len + line_ending.as_str().len()
+}
+
+// Attributes of later revisions of the spec, rendered after the known ones
+// in order of name, each with its leading comma
+pub(crate) struct ExtraAttributes<'a>(pub(crate) &'a BTreeMap<String, String>);
+
+impl fmt::Display for ExtraAttributes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in self.0 {
+            write!(f, ",{}={}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+// Counts the bytes a value would format to without allocating
+fn display_len<T: fmt::Display>(value: &T) -> usize {
+    struct Counter(usize);
+
+    impl fmt::Write for Counter {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.0 += s.len();
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    let _ = fmt::write(&mut counter, format_args!("{}", value));
+    counter.0
 }
 
 #[derive(Builder, Clone)]
-struct ServerControl {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[builder(vis = "pub(crate)", build_fn(error = "UninitializedField"))]
+pub struct PartInf {
+    #[cfg_attr(feature = "serde", serde(with = "serialize::seconds"))]
+    part_target: Duration,
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(default))]
+    extra_attributes: BTreeMap<String, String>,
+}
+
+impl PartInf {
+    pub fn new(part_target: Duration) -> Self {
+        PartInf {
+            part_target,
+            extra_attributes: BTreeMap::new(),
+        }
+    }
+
+    /// Attributes this crate doesn't know, e.g. of a later revision of the
+    /// spec, written back as read.
+    pub fn extra_attributes(&self) -> &BTreeMap<String, String> {
+        &self.extra_attributes
+    }
+
+    pub fn part_target(&self) -> Duration {
+        self.part_target
+    }
+}
+
+/// EXT-X-SERVER-CONTROL. The default is what a playlist without the tag
+/// offers: no blocking reloads, no delta updates and hold backs derived from
+/// the target durations.
+#[derive(Builder, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[builder(vis = "pub(crate)", build_fn(error = "UninitializedField"))]
+pub struct ServerControl {
+    #[builder(default)]
     can_block_reload: bool,
-    part_hold_back: f32,
-    can_skip_until: f32,
+    // Zero when absent, as are the other durations
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(default, with = "serialize::seconds"))]
+    hold_back: Duration,
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(with = "serialize::seconds"))]
+    part_hold_back: Duration,
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(with = "serialize::seconds"))]
+    can_skip_until: Duration,
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(default))]
+    can_skip_dateranges: bool,
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(default))]
+    extra_attributes: BTreeMap<String, String>,
+}
+
+impl ServerControl {
+    pub fn new(can_block_reload: bool, part_hold_back: Duration, can_skip_until: Duration) -> Self {
+        ServerControl {
+            can_block_reload,
+            hold_back: Duration::ZERO,
+            part_hold_back,
+            can_skip_until,
+            can_skip_dateranges: false,
+            extra_attributes: BTreeMap::new(),
+        }
+    }
+
+    /// Attributes this crate doesn't know, e.g. of a later revision of the
+    /// spec, written back as read.
+    pub fn extra_attributes(&self) -> &BTreeMap<String, String> {
+        &self.extra_attributes
+    }
+
+    pub fn can_block_reload(&self) -> bool {
+        self.can_block_reload
+    }
+
+    /// HOLD-BACK, zero when absent. See [`MediaPlaylist::hold_back`] for the
+    /// value that applies then.
+    pub fn hold_back(&self) -> Duration {
+        self.hold_back
+    }
+
+    pub fn set_hold_back(&mut self, hold_back: Duration) {
+        self.hold_back = hold_back;
+    }
+
+    /// PART-HOLD-BACK, zero when absent. See
+    /// [`MediaPlaylist::part_hold_back`] for the value that applies then.
+    pub fn part_hold_back(&self) -> Duration {
+        self.part_hold_back
+    }
+
+    /// CAN-SKIP-UNTIL, zero when the server offers no delta updates.
+    pub fn can_skip_until(&self) -> Duration {
+        self.can_skip_until
+    }
+
+    /// CAN-SKIP-DATERANGES: whether delta updates requested with
+    /// `_HLS_skip=v2` also skip dateranges. Only written along with
+    /// CAN-SKIP-UNTIL.
+    pub fn can_skip_dateranges(&self) -> bool {
+        self.can_skip_dateranges
+    }
+
+    pub fn set_can_skip_dateranges(&mut self, can_skip_dateranges: bool) {
+        self.can_skip_dateranges = can_skip_dateranges;
+    }
 }
 
 enum YesNo {
@@ -40,13 +1820,13 @@ enum YesNo {
 }
 
 impl FromStr for YesNo {
-    type Err = ParseAttributeError;
+    type Err = InvalidValue;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "YES" => Ok(YesNo::Yes),
             "NO" => Ok(YesNo::No),
-            _ => Err(ParseAttributeError),
+            _ => Err(InvalidValue),
         }
     }
 }
@@ -65,26 +1845,235 @@ impl FromStr for ServerControl {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut builder = ServerControlBuilder::default();
-        read_attributes::<ServerControlAttribute, ServerControlBuilder>(s, &mut builder)
-            .map_err(|_| ParseTagError)?;
-        builder.build().map_err(|_| ParseTagError)
+        let extra_attributes =
+            read_attributes::<ServerControlAttribute, ServerControlBuilder>(s, &mut builder)?;
+        builder.extra_attributes(extra_attributes);
+        Ok(builder.build()?)
     }
 }
 
 #[derive(Clone, Builder, Default)]
-struct MediaSegment {
-    duration: f32,
-    uri: Uri<String>,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[builder(vis = "pub(crate)", build_fn(error = "UninitializedField"))]
+pub struct MediaSegment {
+    #[cfg_attr(feature = "serde", serde(with = "serialize::seconds"))]
+    duration: Duration,
+    #[cfg_attr(feature = "serde", serde(with = "serialize::uri"))]
+    uri: Uri,
+    // EXTINF title, the text after the comma
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(default))]
+    title: Option<String>,
     partial_segments: Vec<PartialSegment>,
-    program_date_time: Option<chrono::DateTime<Utc>>,
+    #[cfg_attr(feature = "serde", serde(with = "serialize::option_date_time"))]
+    program_date_time: Option<DateTime>,
+    #[builder(default)]
+    discontinuity: bool,
+    // Only set where the encryption changes
+    #[builder(default)]
+    keys: Vec<Key>,
+    #[builder(default)]
+    gap: bool,
+    // EXT-X-BITRATE in kilobits per second, carried over from the segment
+    // that declared it
+    #[builder(default)]
+    bitrate: Option<u32>,
+    // Whether EXT-X-BITRATE precedes this segment
+    #[builder(default)]
+    declares_bitrate: bool,
+    // Only set where the init segment changes
+    #[builder(default)]
+    map: Option<Map>,
+    // EXT-X-BYTERANGE: only this range of the URI is the segment
+    #[builder(default)]
+    byterange: Option<ByteRange>,
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    custom_tags: Vec<Arc<dyn CustomTag>>,
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(default))]
+    unknown_tags: Vec<UnknownTag>,
+    // The URI line as written, when it isn't a valid URI and was kept with
+    // UriPolicy::Raw
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(default))]
+    raw_uri: Option<String>,
+}
+
+impl MediaSegment {
+    pub fn new(
+        duration: Duration,
+        uri: Uri,
+        partial_segments: Vec<PartialSegment>,
+        program_date_time: Option<DateTime>,
+    ) -> Self {
+        MediaSegment {
+            duration,
+            uri,
+            title: None,
+            partial_segments,
+            program_date_time,
+            discontinuity: false,
+            keys: Vec::new(),
+            gap: false,
+            bitrate: None,
+            declares_bitrate: false,
+            map: None,
+            byterange: None,
+            custom_tags: Vec::new(),
+            unknown_tags: Vec::new(),
+            raw_uri: None,
+        }
+    }
+
+    /// Duration from EXTINF.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// The segment URI. For a line kept as written by [`UriPolicy::Raw`]
+    /// it is percent-encoded, or empty if that isn't a valid URI either;
+    /// see [`MediaSegment::written_uri`] for the line itself.
+    pub fn uri(&self) -> &Uri {
+        &self.uri
+    }
+
+    /// The URI line as written, if it isn't a valid URI and was kept by
+    /// [`UriPolicy::Raw`]. It is rendered instead of
+    /// [`MediaSegment::uri`].
+    pub fn raw_uri(&self) -> Option<&str> {
+        self.raw_uri.as_deref()
+    }
+
+    /// Human-readable title from EXTINF, written after the duration.
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    pub fn set_title(&mut self, title: Option<String>) {
+        self.title = title;
+    }
+
+    /// The partial segments the segment was published as, in order.
+    pub fn parts(&self) -> &[PartialSegment] {
+        &self.partial_segments
+    }
+
+    pub fn program_date_time(&self) -> Option<&DateTime> {
+        self.program_date_time.as_ref()
+    }
+
+    /// EXT-X-PROGRAM-DATE-TIME as a `time` date.
+    #[cfg(feature = "time")]
+    pub fn program_date_time_offset(&self) -> Option<time::OffsetDateTime> {
+        self.program_date_time
+            .as_ref()
+            .and_then(datetime::to_offset_date_time)
+    }
+
+    /// Whether EXT-X-DISCONTINUITY precedes the segment.
+    pub fn discontinuity(&self) -> bool {
+        self.discontinuity
+    }
+
+    /// Whether EXT-X-GAP marks the segment as unavailable, so players skip
+    /// it instead of loading its URI.
+    pub fn is_gap(&self) -> bool {
+        self.gap
+    }
+
+    pub fn set_gap(&mut self, gap: bool) {
+        self.gap = gap;
+    }
+
+    /// Approximate bit rate of the segment in bits per second, from the
+    /// newest EXT-X-BITRATE at or before it.
+    pub fn bitrate(&self) -> Option<u64> {
+        self.bitrate.map(|kilobits| u64::from(kilobits) * 1000)
+    }
+
+    /// Declares the bit rate of the segment, in kilobits per second, with
+    /// EXT-X-BITRATE. Segments pushed after it don't inherit it.
+    pub fn set_bitrate(&mut self, kilobits_per_second: Option<u32>) {
+        self.bitrate = kilobits_per_second;
+        self.declares_bitrate = kilobits_per_second.is_some();
+    }
+
+    /// EXT-X-KEY tags preceding this segment, if the encryption changes
+    /// here. See [`MediaPlaylist::keys_for`] for the ones that apply to it.
+    pub fn keys(&self) -> &[Key] {
+        &self.keys
+    }
+
+    /// EXT-X-MAP of this segment, if the init segment changes here. See
+    /// [`MediaPlaylist::map_for`] for the one that applies to it.
+    pub fn map(&self) -> Option<&Map> {
+        self.map.as_ref()
+    }
+
+    /// EXT-X-BYTERANGE: the part of the resource at the URI that is the
+    /// segment, as I-frame playlists list the I-frames of media segments.
+    pub fn byterange(&self) -> Option<ByteRange> {
+        self.byterange
+    }
+
+    pub fn set_byterange(&mut self, byterange: Option<ByteRange>) {
+        self.byterange = byterange;
+    }
+
+    /// Application-defined tags parsed or pushed for this segment.
+    pub fn custom_tags(&self) -> &[Arc<dyn CustomTag>] {
+        &self.custom_tags
+    }
+
+    /// Tags this crate doesn't know that precede this segment, see
+    /// [`UnknownTag`].
+    pub fn unknown_tags(&self) -> &[UnknownTag] {
+        &self.unknown_tags
+    }
+
+    pub fn set_unknown_tags(&mut self, unknown_tags: Vec<UnknownTag>) {
+        self.unknown_tags = unknown_tags;
+    }
 }
 
 #[derive(Clone, Builder)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[builder(vis = "pub(crate)", build_fn(error = "UninitializedField"))]
 pub struct PartialSegment {
-    pub part_duration: f32,
+    #[cfg_attr(feature = "serde", serde(with = "serialize::seconds"))]
+    pub part_duration: Duration,
     pub uri: String,
     pub independent: Option<bool>,
-    // TODO: BYTERANGE and GAP
+    /// GAP=YES: the part is unavailable and must not be loaded.
+    #[builder(default)]
+    pub gap: bool,
+    /// Where the part lies within the resource at `uri`, if it doesn't span
+    /// all of it, e.g. a CMAF chunk of the segment file being written.
+    #[builder(default)]
+    pub byterange: Option<ByteRange>,
+    /// Attributes this crate doesn't know, e.g. of a later revision of the
+    /// spec, written back as read.
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub extra_attributes: BTreeMap<String, String>,
+}
+
+impl PartialSegment {
+    pub fn new(part_duration: Duration, uri: impl Into<String>) -> Self {
+        PartialSegment {
+            part_duration,
+            uri: uri.into(),
+            independent: None,
+            gap: false,
+            byterange: None,
+            extra_attributes: BTreeMap::new(),
+        }
+    }
+
+    pub fn is_gap(&self) -> bool {
+        self.gap
+    }
 }
 
 impl FromStr for PartialSegment {
@@ -92,30 +2081,221 @@ impl FromStr for PartialSegment {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut builder = PartialSegmentBuilder::default();
-        read_attributes::<PartialSegmentAttribute, PartialSegmentBuilder>(s, &mut builder)
-            .map_err(|_| ParseTagError)?;
+        let extra_attributes =
+            read_attributes::<PartialSegmentAttribute, PartialSegmentBuilder>(s, &mut builder)?;
+        builder.extra_attributes(extra_attributes);
         if builder.independent.is_none() {
             builder.independent(None);
         }
-        builder.build().map_err(|_| ParseTagError)
+        Ok(builder.build()?)
     }
 }
 
 #[derive(Clone, Builder)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[builder(vis = "pub(crate)", build_fn(error = "UninitializedField"))]
 pub struct Skip {
-    pub skipped_segments: u32,
+    pub skipped_segments: u64,
     pub recently_removed_dateranges: Vec<String>,
+    /// Attributes this crate doesn't know, e.g. of a later revision of the
+    /// spec, written back as read.
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub extra_attributes: BTreeMap<String, String>,
 }
 
-#[derive(Clone, Builder)]
+impl Skip {
+    pub fn new(skipped_segments: u64) -> Self {
+        Skip {
+            skipped_segments,
+            recently_removed_dateranges: Vec::new(),
+            extra_attributes: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Builder)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[builder(vis = "pub(crate)", build_fn(error = "UninitializedField"))]
 pub struct PreloadHint {
     pub r#type: PreloadHintType,
     pub uri: String,
-    pub byterange_start: Option<u32>,
-    pub byterange_length: Option<u32>,
+    pub byterange_start: Option<u64>,
+    pub byterange_length: Option<u64>,
+    /// Attributes this crate doesn't know, e.g. of a later revision of the
+    /// spec, written back as read.
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub extra_attributes: BTreeMap<String, String>,
 }
 
-#[derive(Clone)]
+// A later hint of the same TYPE takes the place of the earlier one. The
+// MAP hint goes first, as the init segment comes before the part it
+// initializes.
+fn replace_preload_hint(hints: &mut Vec<PreloadHint>, hint: PreloadHint) {
+    match hints
+        .iter_mut()
+        .find(|existing| existing.r#type == hint.r#type)
+    {
+        Some(existing) => *existing = hint,
+        None if hint.r#type == PreloadHintType::Map => hints.insert(0, hint),
+        None => hints.push(hint),
+    }
+}
+
+// A later report for the same rendition takes the place of the earlier one
+fn replace_rendition_report(reports: &mut Vec<RenditionReport>, report: RenditionReport) {
+    match reports
+        .iter_mut()
+        .find(|existing| existing.uri == report.uri)
+    {
+        Some(existing) => *existing = report,
+        None => reports.push(report),
+    }
+}
+
+impl PreloadHint {
+    pub fn new(r#type: PreloadHintType, uri: impl Into<String>) -> Self {
+        PreloadHint {
+            r#type,
+            uri: uri.into(),
+            byterange_start: None,
+            byterange_length: None,
+            extra_attributes: BTreeMap::new(),
+        }
+    }
+}
+
+/// EXT-X-MAP: the init segment needed to decode the segments after it.
+#[derive(Clone, Debug, PartialEq, Builder)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[builder(vis = "pub(crate)", build_fn(error = "UninitializedField"))]
+pub struct Map {
+    pub uri: String,
+    /// Where the init segment lies within the resource at `uri`, if it
+    /// doesn't span all of it.
+    #[builder(default)]
+    pub byterange: Option<ByteRange>,
+    /// Attributes this crate doesn't know, e.g. of a later revision of the
+    /// spec, written back as read.
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub extra_attributes: BTreeMap<String, String>,
+}
+
+impl Map {
+    pub fn new(uri: impl Into<String>) -> Self {
+        Map {
+            uri: uri.into(),
+            byterange: None,
+            extra_attributes: BTreeMap::new(),
+        }
+    }
+}
+
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#EXT-X-MAP:URI=\"{}\"", self.uri)?;
+        if let Some(byterange) = &self.byterange {
+            write!(f, ",BYTERANGE=\"{}\"", byterange)?;
+        }
+        write!(f, "{}", ExtraAttributes(&self.extra_attributes))
+    }
+}
+
+pub enum MapAttribute {
+    Uri,
+    Byterange,
+}
+
+impl FromStr for MapAttribute {
+    type Err = ParseAttributeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "URI" => Ok(MapAttribute::Uri),
+            "BYTERANGE" => Ok(MapAttribute::Byterange),
+            _ => Err(ParseAttributeError::Unknown {
+                attribute: s.to_string(),
+            }),
+        }
+    }
+}
+
+impl Attribute<MapBuilder> for MapAttribute {
+    fn read(&self, builder: &mut MapBuilder, attribute: &str) -> Result<(), InvalidValue> {
+        match self {
+            MapAttribute::Uri => {
+                builder.uri(attribute::unquote(attribute).to_string());
+            }
+            MapAttribute::Byterange => {
+                // A quoted-string, unlike the BYTERANGE tag of segments
+                let byterange = attribute::unquoted(attribute).ok_or(InvalidValue)?;
+                builder.byterange(Some(ByteRange::from_str(byterange)?));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Map {
+    type Err = ParseTagError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut builder = MapBuilder::default();
+        let extra_attributes = read_attributes::<MapAttribute, MapBuilder>(s, &mut builder)?;
+        builder.extra_attributes(extra_attributes);
+        Ok(builder.build()?)
+    }
+}
+
+/// A sub-range of a resource: `length` bytes starting at `offset`, or right
+/// after the previous range of the same resource if `offset` is `None`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ByteRange {
+    pub length: u64,
+    pub offset: Option<u64>,
+}
+
+impl ByteRange {
+    pub fn new(length: u64, offset: Option<u64>) -> Self {
+        ByteRange { length, offset }
+    }
+}
+
+impl FromStr for ByteRange {
+    type Err = ParseAttributeError;
+
+    /// Parses the `<length>[@<offset>]` form.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |_| ParseAttributeError::InvalidValue {
+            attribute: "BYTERANGE".to_string(),
+            value: s.to_string(),
+        };
+        let (length, offset) = match s.split_once('@') {
+            Some((length, offset)) => (length, Some(u64::from_str(offset).map_err(invalid)?)),
+            None => (s, None),
+        };
+        Ok(ByteRange {
+            length: u64::from_str(length).map_err(invalid)?,
+            offset,
+        })
+    }
+}
+
+impl fmt::Display for ByteRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.length)?;
+        if let Some(offset) = self.offset {
+            write!(f, "@{}", offset)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PreloadHintType {
     Part,
     Map,
@@ -126,10 +2306,28 @@ pub enum MediaPlaylistTag {
     Version,
     PartInf,
     MediaSequence,
+    DiscontinuitySequence,
     Skip,
     PreloadHint,
     RenditionReport,
     ServerControl,
+    DateRange,
+    PlaylistType,
+    IFramesOnly,
+    IndependentSegments,
+    EndList,
+}
+
+impl MediaPlaylistTag {
+    // Whether the tag may appear at most once per playlist
+    fn is_unique(&self) -> bool {
+        !matches!(
+            self,
+            MediaPlaylistTag::PreloadHint
+                | MediaPlaylistTag::RenditionReport
+                | MediaPlaylistTag::DateRange
+        )
+    }
 }
 
 impl FromStr for MediaPlaylistTag {
@@ -141,19 +2339,29 @@ impl FromStr for MediaPlaylistTag {
             "EXT-X-VERSION" => Ok(MediaPlaylistTag::Version),
             "EXT-X-PART-INF" => Ok(MediaPlaylistTag::PartInf),
             "EXT-X-MEDIA-SEQUENCE" => Ok(MediaPlaylistTag::MediaSequence),
+            "EXT-X-DISCONTINUITY-SEQUENCE" => Ok(MediaPlaylistTag::DiscontinuitySequence),
             "EXT-X-SKIP" => Ok(MediaPlaylistTag::Skip),
             "EXT-X-PRELOAD-HINT" => Ok(MediaPlaylistTag::PreloadHint),
             "EXT-X-RENDITION-REPORT" => Ok(MediaPlaylistTag::RenditionReport),
             "EXT-X-SERVER-CONTROL" => Ok(MediaPlaylistTag::ServerControl),
-            _ => Err(ParseTagError),
+            "EXT-X-DATERANGE" => Ok(MediaPlaylistTag::DateRange),
+            "EXT-X-PLAYLIST-TYPE" => Ok(MediaPlaylistTag::PlaylistType),
+            "EXT-X-I-FRAMES-ONLY" => Ok(MediaPlaylistTag::IFramesOnly),
+            "EXT-X-INDEPENDENT-SEGMENTS" => Ok(MediaPlaylistTag::IndependentSegments),
+            "EXT-X-ENDLIST" => Ok(MediaPlaylistTag::EndList),
+            _ => Err(ParseTagError::Unknown {
+                tag: value.to_string(),
+            }),
         }
     }
 }
 
 pub enum ServerControlAttribute {
     CanBlockReload,
+    HoldBack,
     PartHoldBack,
     CanSkipUntil,
+    CanSkipDateRanges,
 }
 
 impl FromStr for ServerControlAttribute {
@@ -162,15 +2370,19 @@ impl FromStr for ServerControlAttribute {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "CAN-BLOCK-RELOAD" => Ok(ServerControlAttribute::CanBlockReload),
+            "HOLD-BACK" => Ok(ServerControlAttribute::HoldBack),
             "PART-HOLD-BACK" => Ok(ServerControlAttribute::PartHoldBack),
             "CAN-SKIP-UNTIL" => Ok(Self::CanSkipUntil),
-            _ => Err(ParseAttributeError),
+            "CAN-SKIP-DATERANGES" => Ok(ServerControlAttribute::CanSkipDateRanges),
+            _ => Err(ParseAttributeError::Unknown {
+                attribute: s.to_string(),
+            }),
         }
     }
 }
 
 trait Attribute<B> {
-    fn read(&self, builder: &mut B, attribute: &str) -> Result<(), ParseAttributeError>;
+    fn read(&self, builder: &mut B, attribute: &str) -> Result<(), InvalidValue>;
 }
 
 impl Attribute<ServerControlBuilder> for ServerControlAttribute {
@@ -178,22 +2390,26 @@ impl Attribute<ServerControlBuilder> for ServerControlAttribute {
         &self,
         builder: &mut ServerControlBuilder,
         attribute: &str,
-    ) -> Result<(), ParseAttributeError> {
+    ) -> Result<(), InvalidValue> {
         match self {
             ServerControlAttribute::CanBlockReload => {
-                builder.can_block_reload(
-                    YesNo::from_str(attribute)
-                        .map_err(|_| ParseAttributeError)?
-                        .into(),
-                );
+                builder
+                    .can_block_reload(YesNo::from_str(attribute).map_err(|_| InvalidValue)?.into());
+            }
+            ServerControlAttribute::HoldBack => {
+                builder.hold_back(seconds::parse(attribute)?);
             }
             ServerControlAttribute::PartHoldBack => {
-                builder.part_hold_back(f32::from_str(attribute).map_err(|_| ParseAttributeError)?);
+                builder.part_hold_back(seconds::parse(attribute)?);
             }
             ServerControlAttribute::CanSkipUntil => {
-                builder.can_skip_until(f32::from_str(attribute).map_err(|_| ParseAttributeError)?);
+                builder.can_skip_until(seconds::parse(attribute)?);
+            }
+            ServerControlAttribute::CanSkipDateRanges => {
+                builder.can_skip_dateranges(
+                    YesNo::from_str(attribute).map_err(|_| InvalidValue)?.into(),
+                );
             }
-            _ => todo!(),
         }
         Ok(())
     }
@@ -203,6 +2419,8 @@ pub enum PartialSegmentAttribute {
     Duration,
     Uri,
     Independent,
+    Gap,
+    Byterange,
 }
 
 impl FromStr for PartialSegmentAttribute {
@@ -213,7 +2431,11 @@ impl FromStr for PartialSegmentAttribute {
             "DURATION" => Ok(PartialSegmentAttribute::Duration),
             "URI" => Ok(PartialSegmentAttribute::Uri),
             "INDEPENDENT" => Ok(PartialSegmentAttribute::Independent),
-            _ => Err(ParseAttributeError),
+            "GAP" => Ok(PartialSegmentAttribute::Gap),
+            "BYTERANGE" => Ok(PartialSegmentAttribute::Byterange),
+            _ => Err(ParseAttributeError::Unknown {
+                attribute: s.to_string(),
+            }),
         }
     }
 }
@@ -223,21 +2445,27 @@ impl Attribute<PartialSegmentBuilder> for PartialSegmentAttribute {
         &self,
         builder: &mut PartialSegmentBuilder,
         attribute: &str,
-    ) -> Result<(), ParseAttributeError> {
+    ) -> Result<(), InvalidValue> {
         match self {
             PartialSegmentAttribute::Duration => {
-                builder.part_duration(f32::from_str(attribute).map_err(|_| ParseAttributeError)?);
+                builder.part_duration(seconds::parse(attribute)?);
             }
             PartialSegmentAttribute::Uri => {
-                builder.uri(attribute.to_string());
+                builder.uri(attribute::unquote(attribute).to_string());
             }
             PartialSegmentAttribute::Independent => {
                 builder.independent(Some(
-                    YesNo::from_str(attribute)
-                        .map_err(|_| ParseAttributeError)?
-                        .into(),
+                    YesNo::from_str(attribute).map_err(|_| InvalidValue)?.into(),
                 ));
             }
+            PartialSegmentAttribute::Gap => {
+                builder.gap(YesNo::from_str(attribute).map_err(|_| InvalidValue)?.into());
+            }
+            PartialSegmentAttribute::Byterange => {
+                // A quoted-string, though some packagers leave it bare
+                let byterange = attribute::unquote(attribute);
+                builder.byterange(Some(ByteRange::from_str(byterange)?));
+            }
         }
         Ok(())
     }
@@ -249,6 +2477,12 @@ pub enum MediaSegmentTag {
     // Not strictly a tag, just makes things work nicer internally
     Uri,
     ProgramDateTime,
+    Map,
+    Discontinuity,
+    Key,
+    Gap,
+    Bitrate,
+    Byterange,
 }
 
 impl FromStr for MediaSegmentTag {
@@ -259,16 +2493,25 @@ impl FromStr for MediaSegmentTag {
             "EXTINF" => Ok(MediaSegmentTag::Inf),
             "EXT-X-PART" => Ok(MediaSegmentTag::Part),
             "EXT-X-PROGRAM-DATE-TIME" => Ok(MediaSegmentTag::ProgramDateTime),
+            "EXT-X-MAP" => Ok(MediaSegmentTag::Map),
+            "EXT-X-DISCONTINUITY" => Ok(MediaSegmentTag::Discontinuity),
+            "EXT-X-KEY" => Ok(MediaSegmentTag::Key),
+            "EXT-X-GAP" => Ok(MediaSegmentTag::Gap),
+            "EXT-X-BITRATE" => Ok(MediaSegmentTag::Bitrate),
+            "EXT-X-BYTERANGE" => Ok(MediaSegmentTag::Byterange),
             // lol
             _ => Ok(MediaSegmentTag::Uri),
         }
     }
 }
 
+// Only filled in through InfAttribute, the parser reads EXTINF itself
+#[allow(dead_code)]
 #[derive(Builder)]
+#[builder(vis = "pub(crate)", build_fn(error = "UninitializedField"))]
 pub struct Inf {
-    duration: f32,
-    uri: Uri<String>,
+    duration: Duration,
+    uri: Uri,
 }
 
 pub enum InfAttribute {
@@ -277,13 +2520,10 @@ pub enum InfAttribute {
 }
 
 impl Attribute<InfBuilder> for InfAttribute {
-    fn read(&self, builder: &mut InfBuilder, attribute: &str) -> Result<(), ParseAttributeError> {
+    fn read(&self, builder: &mut InfBuilder, attribute: &str) -> Result<(), InvalidValue> {
         match self {
-            InfAttribute::Duration => {
-                builder.duration(f32::from_str(attribute).map_err(|_| ParseAttributeError)?)
-            }
-            InfAttribute::Uri => builder
-                .uri(Uri::parse_from(attribute.to_string()).map_err(|_| ParseAttributeError)?),
+            InfAttribute::Duration => builder.duration(seconds::parse(attribute)?),
+            InfAttribute::Uri => builder.uri(uri::parse(attribute)?),
         };
         Ok(())
     }
@@ -292,6 +2532,11 @@ impl Attribute<InfBuilder> for InfAttribute {
 struct WrappedMediaSegmentBuilder {
     segment: MediaSegmentBuilder,
     parts: Vec<PartialSegment>,
+    keys: Vec<Key>,
+    // Applies to every segment until the next EXT-X-BITRATE
+    bitrate: Option<u32>,
+    custom_tags: Vec<Arc<dyn CustomTag>>,
+    unknown_tags: Vec<UnknownTag>,
 }
 
 impl Tag<WrappedMediaSegmentBuilder> for MediaSegmentTag {
@@ -302,31 +2547,60 @@ impl Tag<WrappedMediaSegmentBuilder> for MediaSegmentTag {
     ) -> Result<(), ParseTagError> {
         match self {
             MediaSegmentTag::Inf => {
+                let (duration, title) = attributes
+                    .split_once(',')
+                    .ok_or_else(|| ParseTagError::invalid(attributes))?;
                 builder
                     .segment
-                    // TODO: Clean up
                     .duration(
-                        f32::from_str(attributes.split_once(',').ok_or(ParseTagError)?.0)
-                            .map_err(|_| ParseTagError)?,
-                    );
+                        seconds::parse(duration).map_err(|_| ParseTagError::invalid(attributes))?,
+                    )
+                    .title((!title.is_empty()).then(|| title.to_string()));
+                Ok(())
+            }
+            MediaSegmentTag::Part => {
+                builder.parts.push(PartialSegment::from_str(attributes)?);
+                Ok(())
+            }
+            MediaSegmentTag::Uri => {
+                builder
+                    .segment
+                    .uri(uri::parse(attributes).map_err(|_| ParseTagError::invalid(attributes))?);
+                Ok(())
+            }
+            MediaSegmentTag::ProgramDateTime => {
+                builder.segment.program_date_time(Some(
+                    datetime::parse(attributes).map_err(|_| ParseTagError::invalid(attributes))?,
+                ));
+                Ok(())
+            }
+            MediaSegmentTag::Map => {
+                builder.segment.map(Some(Map::from_str(attributes)?));
+                Ok(())
+            }
+            MediaSegmentTag::Discontinuity => {
+                builder.segment.discontinuity(true);
                 Ok(())
             }
-            MediaSegmentTag::Part => {
-                builder
-                    .parts
-                    .push(PartialSegment::from_str(attributes).map_err(|_| ParseTagError)?);
+            MediaSegmentTag::Key => {
+                builder.keys.push(Key::from_str(attributes)?);
                 Ok(())
             }
-            MediaSegmentTag::Uri => {
-                builder
-                    .segment
-                    .uri(Uri::parse_from(attributes.to_string()).map_err(|_| ParseTagError)?);
+            MediaSegmentTag::Gap => {
+                builder.segment.gap(true);
                 Ok(())
             }
-            MediaSegmentTag::ProgramDateTime => {
-                builder.segment.program_date_time(Some(
-                    DateTime::from_str(attributes).map_err(|_| ParseTagError)?,
-                ));
+            MediaSegmentTag::Bitrate => {
+                builder.bitrate = Some(
+                    u32::from_str(attributes).map_err(|_| ParseTagError::invalid(attributes))?,
+                );
+                builder.segment.declares_bitrate(true);
+                Ok(())
+            }
+            MediaSegmentTag::Byterange => {
+                builder
+                    .segment
+                    .byterange(Some(ByteRange::from_str(attributes)?));
                 Ok(())
             }
         }
@@ -339,29 +2613,65 @@ pub enum MediaSegmentAttribute {
 }
 
 impl Attribute<MediaSegmentBuilder> for MediaSegmentAttribute {
-    fn read(
-        &self,
-        builder: &mut MediaSegmentBuilder,
-        attribute: &str,
-    ) -> Result<(), ParseAttributeError> {
+    fn read(&self, builder: &mut MediaSegmentBuilder, attribute: &str) -> Result<(), InvalidValue> {
         match self {
             MediaSegmentAttribute::Duration => {
-                builder.duration(f32::from_str(attribute).map_err(|_| ParseAttributeError)?);
+                builder.duration(seconds::parse(attribute)?);
             }
             MediaSegmentAttribute::Uri => {
-                builder
-                    .uri(Uri::parse_from(attribute.to_string()).map_err(|_| ParseAttributeError)?);
+                builder.uri(uri::parse(attribute)?);
             }
         }
         Ok(())
     }
 }
 
-#[derive(Builder, Clone)]
+#[derive(Builder, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[builder(vis = "pub(crate)", build_fn(error = "UninitializedField"))]
 pub struct RenditionReport {
     uri: String,
-    last_msn: u32,
-    last_part: u32,
+    last_msn: u64,
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(default))]
+    last_part: Option<u64>,
+    #[builder(default)]
+    #[cfg_attr(feature = "serde", serde(default))]
+    extra_attributes: BTreeMap<String, String>,
+}
+
+impl RenditionReport {
+    /// A report of the segment `last_msn` of the rendition at `uri`, and of
+    /// its part `last_part` if it was published as parts.
+    pub fn new(uri: impl Into<String>, last_msn: u64, last_part: Option<u64>) -> Self {
+        RenditionReport {
+            uri: uri.into(),
+            last_msn,
+            last_part,
+            extra_attributes: BTreeMap::new(),
+        }
+    }
+
+    /// Attributes this crate doesn't know, e.g. of a later revision of the
+    /// spec, written back as read.
+    pub fn extra_attributes(&self) -> &BTreeMap<String, String> {
+        &self.extra_attributes
+    }
+
+    /// URI of the rendition's playlist, relative to the playlist reporting
+    /// it.
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    pub fn last_msn(&self) -> u64 {
+        self.last_msn
+    }
+
+    /// LAST-PART, `None` if the segment wasn't published as parts.
+    pub fn last_part(&self) -> Option<u64> {
+        self.last_part
+    }
 }
 pub enum RenditionReportAttribute {
     Uri,
@@ -374,16 +2684,16 @@ impl Attribute<RenditionReportBuilder> for RenditionReportAttribute {
         &self,
         builder: &mut RenditionReportBuilder,
         attribute: &str,
-    ) -> Result<(), ParseAttributeError> {
+    ) -> Result<(), InvalidValue> {
         match self {
             RenditionReportAttribute::Uri => {
-                builder.uri(attribute.to_string());
+                builder.uri(attribute::unquote(attribute).to_string());
             }
             RenditionReportAttribute::LastMsn => {
-                builder.last_msn(u32::from_str(attribute).map_err(|_| ParseAttributeError)?);
+                builder.last_msn(u64::from_str(attribute).map_err(|_| InvalidValue)?);
             }
             RenditionReportAttribute::LastPart => {
-                builder.last_part(u32::from_str(attribute).map_err(|_| ParseAttributeError)?);
+                builder.last_part(Some(u64::from_str(attribute).map_err(|_| InvalidValue)?));
             }
         }
         Ok(())
@@ -393,6 +2703,8 @@ impl Attribute<RenditionReportBuilder> for RenditionReportAttribute {
 pub enum PreloadHintAttribute {
     Type,
     Uri,
+    ByterangeStart,
+    ByterangeLength,
 }
 
 impl FromStr for RenditionReport {
@@ -400,9 +2712,10 @@ impl FromStr for RenditionReport {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut builder: RenditionReportBuilder = RenditionReportBuilder::default();
-        read_attributes::<RenditionReportAttribute, RenditionReportBuilder>(s, &mut builder)
-            .map_err(|_| ParseTagError)?;
-        builder.build().map_err(|_| ParseTagError)
+        let extra_attributes =
+            read_attributes::<RenditionReportAttribute, RenditionReportBuilder>(s, &mut builder)?;
+        builder.extra_attributes(extra_attributes);
+        Ok(builder.build()?)
     }
 }
 
@@ -414,7 +2727,9 @@ impl FromStr for RenditionReportAttribute {
             "URI" => Ok(RenditionReportAttribute::Uri),
             "LAST-MSN" => Ok(RenditionReportAttribute::LastMsn),
             "LAST-PART" => Ok(RenditionReportAttribute::LastPart),
-            _ => Err(ParseAttributeError),
+            _ => Err(ParseAttributeError::Unknown {
+                attribute: s.to_string(),
+            }),
         }
     }
 }
@@ -426,23 +2741,28 @@ impl FromStr for PreloadHintType {
         match value {
             "PART" => Ok(PreloadHintType::Part),
             "MAP" => Ok(PreloadHintType::Map),
-            _ => Err(ParseAttributeError),
+            _ => Err(ParseAttributeError::InvalidValue {
+                attribute: "TYPE".to_string(),
+                value: value.to_string(),
+            }),
         }
     }
 }
 
 impl Attribute<PreloadHintBuilder> for PreloadHintAttribute {
-    fn read(
-        &self,
-        builder: &mut PreloadHintBuilder,
-        attribute: &str,
-    ) -> Result<(), ParseAttributeError> {
+    fn read(&self, builder: &mut PreloadHintBuilder, attribute: &str) -> Result<(), InvalidValue> {
         match self {
             PreloadHintAttribute::Type => {
                 builder.r#type(PreloadHintType::from_str(attribute)?);
             }
             PreloadHintAttribute::Uri => {
-                builder.uri(attribute.to_string());
+                builder.uri(attribute::unquote(attribute).to_string());
+            }
+            PreloadHintAttribute::ByterangeStart => {
+                builder.byterange_start(Some(u64::from_str(attribute).map_err(|_| InvalidValue)?));
+            }
+            PreloadHintAttribute::ByterangeLength => {
+                builder.byterange_length(Some(u64::from_str(attribute).map_err(|_| InvalidValue)?));
             }
         }
         Ok(())
@@ -453,10 +2773,10 @@ trait Tag<B> {
     fn read(&self, builder: &mut B, attributes: &str) -> Result<(), ParseTagError>;
 }
 
-#[derive(Builder)]
 struct WrappedMediaPlaylistBuilder {
     playlist: MediaPlaylistBuilder,
     rendition_reports: Vec<RenditionReport>,
+    date_ranges: Vec<DateRange>,
     media_segments: Vec<MediaSegment>,
 }
 
@@ -467,7 +2787,11 @@ impl FromStr for PreloadHintAttribute {
         match s {
             "TYPE" => Ok(PreloadHintAttribute::Type),
             "URI" => Ok(PreloadHintAttribute::Uri),
-            _ => Err(ParseAttributeError),
+            "BYTERANGE-START" => Ok(PreloadHintAttribute::ByterangeStart),
+            "BYTERANGE-LENGTH" => Ok(PreloadHintAttribute::ByterangeLength),
+            _ => Err(ParseAttributeError::Unknown {
+                attribute: s.to_string(),
+            }),
         }
     }
 }
@@ -477,15 +2801,16 @@ impl FromStr for PreloadHint {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut builder = PreloadHintBuilder::default();
-        read_attributes::<PreloadHintAttribute, PreloadHintBuilder>(s, &mut builder)
-            .map_err(|_| ParseTagError)?;
+        let extra_attributes =
+            read_attributes::<PreloadHintAttribute, PreloadHintBuilder>(s, &mut builder)?;
+        builder.extra_attributes(extra_attributes);
         if builder.byterange_start.is_none() {
             builder.byterange_start(None);
         }
         if builder.byterange_length.is_none() {
             builder.byterange_length(None);
         }
-        builder.build().map_err(|_| ParseTagError)
+        Ok(builder.build()?)
     }
 }
 
@@ -497,51 +2822,87 @@ impl Tag<WrappedMediaPlaylistBuilder> for MediaPlaylistTag {
     ) -> Result<(), ParseTagError> {
         match self {
             MediaPlaylistTag::TargetDuration => {
-                builder
-                    .playlist
-                    .target_duration(u32::from_str(attributes).map_err(|_| ParseTagError)?);
+                builder.playlist.target_duration(
+                    u32::from_str(attributes).map_err(|_| ParseTagError::invalid(attributes))?,
+                );
                 Ok(())
             }
             MediaPlaylistTag::Version => {
-                builder
-                    .playlist
-                    .version(u32::from_str(attributes).map_err(|_| ParseTagError)?);
+                builder.playlist.version(
+                    u32::from_str(attributes).map_err(|_| ParseTagError::invalid(attributes))?,
+                );
                 Ok(())
             }
             MediaPlaylistTag::PartInf => {
-                builder
-                    .playlist
-                    .part_inf(PartInf::from_str(attributes).map_err(|_| ParseTagError)?);
+                builder.playlist.part_inf(PartInf::from_str(attributes)?);
                 Ok(())
             }
             MediaPlaylistTag::MediaSequence => {
+                builder.playlist.media_sequence_number(
+                    u64::from_str(attributes).map_err(|_| ParseTagError::invalid(attributes))?,
+                );
+                Ok(())
+            }
+            MediaPlaylistTag::DiscontinuitySequence => {
+                builder.playlist.discontinuity_sequence(
+                    u64::from_str(attributes).map_err(|_| ParseTagError::invalid(attributes))?,
+                );
+                Ok(())
+            }
+            MediaPlaylistTag::PlaylistType => {
                 builder
                     .playlist
-                    .media_sequence_number(u32::from_str(attributes).map_err(|_| ParseTagError)?);
+                    .playlist_type(PlaylistType::from_str(attributes)?);
+                Ok(())
+            }
+            MediaPlaylistTag::IFramesOnly => {
+                builder.playlist.i_frames_only(true);
+                Ok(())
+            }
+            MediaPlaylistTag::IndependentSegments => {
+                builder.playlist.independent_segments(true);
+                Ok(())
+            }
+            MediaPlaylistTag::EndList => {
+                builder.playlist.end_list(true);
                 Ok(())
             }
             MediaPlaylistTag::Skip => {
-                builder
-                    .playlist
-                    .skip(Some(Skip::from_str(attributes).map_err(|_| ParseTagError)?));
+                builder.playlist.skip(Some(Skip::from_str(attributes)?));
                 Ok(())
             }
             MediaPlaylistTag::PreloadHint => {
-                builder.playlist.preload_hint(Some(
-                    PreloadHint::from_str(attributes).map_err(|_| ParseTagError)?,
-                ));
+                builder
+                    .playlist
+                    .add_preload_hint(PreloadHint::from_str(attributes)?);
                 Ok(())
             }
             MediaPlaylistTag::RenditionReport => {
-                builder
-                    .rendition_reports
-                    .push(RenditionReport::from_str(attributes).map_err(|_| ParseTagError)?);
+                replace_rendition_report(
+                    &mut builder.rendition_reports,
+                    RenditionReport::from_str(attributes)?,
+                );
                 Ok(())
             }
             MediaPlaylistTag::ServerControl => {
-                builder.playlist.server_control(
-                    ServerControl::from_str(attributes).map_err(|_| ParseTagError)?,
-                );
+                builder
+                    .playlist
+                    .server_control(ServerControl::from_str(attributes)?);
+                Ok(())
+            }
+            MediaPlaylistTag::DateRange => {
+                let date_range = DateRange::from_str(attributes)?;
+                // Tags repeating an ID add attributes to the same range
+                match builder
+                    .date_ranges
+                    .iter_mut()
+                    .find(|existing| existing.id == date_range.id)
+                {
+                    Some(existing) => existing
+                        .merge(date_range)
+                        .map_err(|_| ParseTagError::invalid(attributes))?,
+                    None => builder.date_ranges.push(date_range),
+                }
                 Ok(())
             }
         }
@@ -553,14 +2914,10 @@ pub enum PartInfAttribute {
 }
 
 impl Attribute<PartInfBuilder> for PartInfAttribute {
-    fn read(
-        &self,
-        builder: &mut PartInfBuilder,
-        attribute: &str,
-    ) -> Result<(), ParseAttributeError> {
+    fn read(&self, builder: &mut PartInfBuilder, attribute: &str) -> Result<(), InvalidValue> {
         match self {
             PartInfAttribute::PartTarget => {
-                builder.part_target(f32::from_str(attribute).map_err(|_| ParseAttributeError)?);
+                builder.part_target(seconds::parse(attribute)?);
                 Ok(())
             }
         }
@@ -573,7 +2930,9 @@ impl FromStr for PartInfAttribute {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "PART-TARGET" => Ok(PartInfAttribute::PartTarget),
-            _ => Err(ParseAttributeError),
+            _ => Err(ParseAttributeError::Unknown {
+                attribute: s.to_string(),
+            }),
         }
     }
 }
@@ -583,28 +2942,60 @@ impl FromStr for PartInf {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut builder: PartInfBuilder = PartInfBuilder::default();
-        read_attributes::<PartInfAttribute, PartInfBuilder>(s, &mut builder)
-            .map_err(|_| ParseTagError)?;
-        builder.build().map_err(|_| ParseTagError)
+        let extra_attributes =
+            read_attributes::<PartInfAttribute, PartInfBuilder>(s, &mut builder)?;
+        builder.extra_attributes(extra_attributes);
+        Ok(builder.build()?)
     }
 }
 
-fn read_attributes<T, B>(s: &str, builder: &mut B) -> Result<(), ParseAttributeError>
+// The URI of a tag whose URI attribute is a quoted-string, if it was written
+// without its quotes
+fn unquoted_uri(tag: &str, value: &str) -> Option<(&'static str, String)> {
+    let tag = [
+        "EXT-X-PART",
+        "EXT-X-MAP",
+        "EXT-X-PRELOAD-HINT",
+        "EXT-X-RENDITION-REPORT",
+    ]
+    .into_iter()
+    .find(|known| *known == tag)?;
+    let (_, uri) = attribute::tokenize_attribute_list(value)
+        .ok()?
+        .into_iter()
+        .find(|(name, _)| *name == "URI")?;
+    attribute::unquoted(uri)
+        .is_none()
+        .then(|| (tag, uri.to_string()))
+}
+
+// Reads the attributes `T` knows into `builder` and returns the others, so
+// that tags of later revisions of the spec still parse
+fn read_attributes<T, B>(
+    s: &str,
+    builder: &mut B,
+) -> Result<BTreeMap<String, String>, ParseTagError>
 where
-    T: FromStr + Attribute<B>,
+    T: FromStr<Err = ParseAttributeError> + Attribute<B>,
 {
-    let attributes: HashMap<String, String> = s
-        .split(",")
-        .filter_map(|x| {
-            x.split_once('=')
-                .map(|(k, v)| (k.to_string(), v.to_string()))
-        })
-        .collect();
-    for (k, v) in attributes {
-        let attribute = T::from_str(&k).map_err(|_| ParseAttributeError)?;
-        attribute.read(builder, &v)?;
+    let mut extra_attributes = BTreeMap::new();
+    for (name, value) in attribute::tokenize_attribute_list(s)? {
+        let attribute = match T::from_str(name) {
+            Ok(attribute) => attribute,
+            Err(ParseAttributeError::Unknown { .. }) => {
+                extra_attributes.insert(name.to_string(), value.to_string());
+                continue;
+            }
+            Err(error) => return Err(error.into()),
+        };
+        attribute.read(builder, value).map_err(|InvalidValue| {
+            ParseAttributeError::InvalidValue {
+                attribute: name.to_string(),
+                value: value.to_string(),
+            }
+        })?;
     }
-    Ok(())
+    Ok(extra_attributes)
 }
 
 pub enum SkipAttribute {
@@ -619,22 +3010,27 @@ impl FromStr for SkipAttribute {
         match s {
             "SKIPPED-SEGMENTS" => Ok(SkipAttribute::SkippedSegments),
             "RECENTLY-REMOVED-DATERANGES" => Ok(SkipAttribute::RecentlyRemovedDateRanges),
-            _ => Err(ParseAttributeError),
+            _ => Err(ParseAttributeError::Unknown {
+                attribute: s.to_string(),
+            }),
         }
     }
 }
 
 impl Attribute<SkipBuilder> for SkipAttribute {
-    fn read(&self, builder: &mut SkipBuilder, attribute: &str) -> Result<(), ParseAttributeError> {
+    fn read(&self, builder: &mut SkipBuilder, attribute: &str) -> Result<(), InvalidValue> {
         match self {
             SkipAttribute::SkippedSegments => {
-                builder
-                    .skipped_segments(u32::from_str(attribute).map_err(|_| ParseAttributeError)?);
+                builder.skipped_segments(u64::from_str(attribute).map_err(|_| InvalidValue)?);
                 Ok(())
             }
             SkipAttribute::RecentlyRemovedDateRanges => {
                 builder.recently_removed_dateranges(
-                    attribute.split('\t').map(|s| s.to_string()).collect(),
+                    attribute
+                        .trim_matches('"')
+                        .split('\t')
+                        .map(|s| s.to_string())
+                        .collect(),
                 );
                 Ok(())
             }
@@ -647,125 +3043,890 @@ impl FromStr for Skip {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut builder = SkipBuilder::default();
-        read_attributes::<SkipAttribute, SkipBuilder>(s, &mut builder)
-            .map_err(|_| ParseTagError)?;
+        let extra_attributes = read_attributes::<SkipAttribute, SkipBuilder>(s, &mut builder)?;
+        builder.extra_attributes(extra_attributes);
         if builder.recently_removed_dateranges.is_none() {
             builder.recently_removed_dateranges(Vec::new());
         }
-        builder.build().map_err(|_| ParseTagError)
+        Ok(builder.build()?)
+    }
+}
+
+impl fmt::Display for Skip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#EXT-X-SKIP:SKIPPED-SEGMENTS={}", self.skipped_segments)?;
+        if let Some((first, rest)) = self.recently_removed_dateranges.split_first() {
+            // Tab-separated IDs
+            write!(f, ",RECENTLY-REMOVED-DATERANGES=\"{}", first)?;
+            for id in rest {
+                write!(f, "\t{}", id)?;
+            }
+            write!(f, "\"")?;
+        }
+        write!(f, "{}", ExtraAttributes(&self.extra_attributes))
+    }
+}
+
+/// Server-side record of EXT-X-DATERANGE tags removed from a live playlist,
+/// used to fill RECENTLY-REMOVED-DATERANGES when producing delta updates for
+/// clients that requested CAN-SKIP-DATERANGES.
+#[derive(Clone, Default)]
+pub struct RemovedDateRanges {
+    // (ID, media sequence number of the newest segment when it was removed)
+    removed: Vec<(String, u64)>,
+}
+
+impl RemovedDateRanges {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that the daterange `id` was removed while `last_msn` was the
+    /// newest segment in the playlist.
+    pub fn record(&mut self, id: impl Into<String>, last_msn: u64) {
+        let id = id.into();
+        self.removed.retain(|(removed_id, _)| *removed_id != id);
+        self.removed.push((id, last_msn));
+    }
+
+    /// IDs a client holding every segment up to `last_skipped_msn` may still
+    /// have, i.e. those removed once that segment had been published.
+    pub fn since(&self, last_skipped_msn: u64) -> Vec<String> {
+        self.removed
+            .iter()
+            .filter(|(_, msn)| *msn >= last_skipped_msn)
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    /// Builds the EXT-X-SKIP tag for a delta update that skips
+    /// `skipped_segments` segments, the last of which is `last_skipped_msn`.
+    pub fn skip(&self, skipped_segments: u64, last_skipped_msn: u64) -> Skip {
+        Skip {
+            skipped_segments,
+            recently_removed_dateranges: self.since(last_skipped_msn),
+            extra_attributes: BTreeMap::new(),
+        }
+    }
+
+    /// Forgets removals that no delta update skipping past `last_skipped_msn`
+    /// can need anymore.
+    pub fn prune(&mut self, last_skipped_msn: u64) {
+        self.removed.retain(|(_, msn)| *msn >= last_skipped_msn);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DateRange {
+    pub id: String,
+    pub class: Option<String>,
+    #[cfg_attr(feature = "serde", serde(with = "serialize::date_time"))]
+    pub start_date: DateTime,
+    #[cfg_attr(feature = "serde", serde(with = "serialize::option_date_time"))]
+    pub end_date: Option<DateTime>,
+    pub duration: Option<f32>,
+    pub planned_duration: Option<f32>,
+    pub end_on_next: bool,
+    pub scte35_cmd: Option<HexBytes>,
+    pub scte35_out: Option<HexBytes>,
+    pub scte35_in: Option<HexBytes>,
+    // X-<client-attribute> names mapped to their raw attribute values
+    pub client_attributes: Vec<(String, String)>,
+    /// Attributes this crate doesn't know, e.g. of a later revision of the
+    /// spec, written back as read.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub extra_attributes: BTreeMap<String, String>,
+}
+
+impl DateRange {
+    pub fn new(id: impl Into<String>, start_date: DateTime) -> Self {
+        DateRange {
+            id: id.into(),
+            class: None,
+            start_date,
+            end_date: None,
+            duration: None,
+            planned_duration: None,
+            end_on_next: false,
+            scte35_cmd: None,
+            scte35_out: None,
+            scte35_in: None,
+            client_attributes: Vec::new(),
+            extra_attributes: BTreeMap::new(),
+        }
+    }
+
+    /// START-DATE as a `time` date.
+    #[cfg(feature = "time")]
+    pub fn start_date_offset(&self) -> Option<time::OffsetDateTime> {
+        datetime::to_offset_date_time(&self.start_date)
+    }
+
+    /// END-DATE as a `time` date.
+    #[cfg(feature = "time")]
+    pub fn end_date_offset(&self) -> Option<time::OffsetDateTime> {
+        self.end_date
+            .as_ref()
+            .and_then(datetime::to_offset_date_time)
+    }
+
+    // Combines a repeated tag with the same ID into this one. Attributes
+    // present in both must carry the same value.
+    fn merge(&mut self, other: DateRange) -> Result<(), DateRangeError> {
+        let conflict = |attribute: &str| DateRangeError::Conflict {
+            id: self.id.clone(),
+            attribute: attribute.to_string(),
+        };
+        if self.start_date != other.start_date {
+            return Err(conflict("START-DATE"));
+        }
+        merge_attribute(&mut self.class, other.class).map_err(|_| conflict("CLASS"))?;
+        merge_attribute(&mut self.end_date, other.end_date).map_err(|_| conflict("END-DATE"))?;
+        merge_attribute(&mut self.duration, other.duration).map_err(|_| conflict("DURATION"))?;
+        merge_attribute(&mut self.planned_duration, other.planned_duration)
+            .map_err(|_| conflict("PLANNED-DURATION"))?;
+        merge_attribute(&mut self.scte35_cmd, other.scte35_cmd)
+            .map_err(|_| conflict("SCTE35-CMD"))?;
+        merge_attribute(&mut self.scte35_out, other.scte35_out)
+            .map_err(|_| conflict("SCTE35-OUT"))?;
+        merge_attribute(&mut self.scte35_in, other.scte35_in).map_err(|_| conflict("SCTE35-IN"))?;
+        self.end_on_next |= other.end_on_next;
+        for (name, value) in other.client_attributes {
+            match self.client_attributes.iter().find(|(n, _)| *n == name) {
+                Some((_, existing)) if *existing != value => return Err(conflict(&name)),
+                Some(_) => {}
+                None => self.client_attributes.push((name, value)),
+            }
+        }
+        for (name, value) in other.extra_attributes {
+            match self.extra_attributes.get(&name) {
+                Some(existing) if *existing != value => return Err(conflict(&name)),
+                Some(_) => {}
+                None => {
+                    self.extra_attributes.insert(name, value);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DateRange {
+    type Err = ParseTagError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn invalid(name: &str, value: &str) -> ParseTagError {
+            ParseTagError::Attribute(ParseAttributeError::InvalidValue {
+                attribute: name.to_string(),
+                value: value.to_string(),
+            })
+        }
+        fn unquote<'a>(name: &str, value: &'a str) -> Result<&'a str, ParseTagError> {
+            value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .ok_or_else(|| invalid(name, value))
+        }
+        fn date(name: &str, value: &str) -> Result<DateTime, ParseTagError> {
+            datetime::parse(unquote(name, value)?).map_err(|_| invalid(name, value))
+        }
+        // Durations are decimal-floating-point; exponents too large for an
+        // f32 don't count
+        fn float(name: &str, value: &str) -> Result<f32, ParseTagError> {
+            f32::from_str(value)
+                .ok()
+                .filter(|value| value.is_finite() && *value >= 0.0)
+                .ok_or_else(|| invalid(name, value))
+        }
+        fn hex(name: &str, value: &str) -> Result<HexBytes, ParseTagError> {
+            HexBytes::from_str(value).map_err(|_| invalid(name, value))
+        }
+
+        let mut id = None;
+        let mut start_date = None;
+        let mut attributes = Vec::new();
+        for (name, value) in attribute::tokenize_attribute_list(s)? {
+            match name {
+                "ID" => id = Some(unquote(name, value)?.to_string()),
+                "START-DATE" => start_date = Some(date(name, value)?),
+                _ => attributes.push((name, value)),
+            }
+        }
+        let mut range = DateRange::new(
+            id.ok_or(ParseTagError::MissingField("id"))?,
+            start_date.ok_or(ParseTagError::MissingField("start_date"))?,
+        );
+        for (name, value) in attributes {
+            match name {
+                "CLASS" => range.class = Some(unquote(name, value)?.to_string()),
+                "END-DATE" => range.end_date = Some(date(name, value)?),
+                "DURATION" => range.duration = Some(float(name, value)?),
+                "PLANNED-DURATION" => range.planned_duration = Some(float(name, value)?),
+                "END-ON-NEXT" if value == "YES" => range.end_on_next = true,
+                "SCTE35-CMD" => range.scte35_cmd = Some(hex(name, value)?),
+                "SCTE35-OUT" => range.scte35_out = Some(hex(name, value)?),
+                "SCTE35-IN" => range.scte35_in = Some(hex(name, value)?),
+                // Kept verbatim, their type is up to the application
+                _ if name.starts_with("X-") => range
+                    .client_attributes
+                    .push((name.to_string(), value.to_string())),
+                // END-ON-NEXT=NO
+                "END-ON-NEXT" => {}
+                _ => {
+                    range
+                        .extra_attributes
+                        .insert(name.to_string(), value.to_string());
+                }
+            }
+        }
+        Ok(range)
+    }
+}
+
+fn merge_attribute<T: PartialEq>(current: &mut Option<T>, new: Option<T>) -> Result<(), ()> {
+    match (current.as_ref(), new) {
+        (Some(existing), Some(value)) if *existing != value => Err(()),
+        (None, Some(value)) => {
+            *current = Some(value);
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+impl fmt::Display for DateRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#EXT-X-DATERANGE:ID=\"{}\"", self.id)?;
+        if let Some(class) = &self.class {
+            write!(f, ",CLASS=\"{}\"", class)?;
+        }
+        write!(f, ",START-DATE=\"{}\"", datetime::display(&self.start_date))?;
+        if let Some(end_date) = &self.end_date {
+            write!(f, ",END-DATE=\"{}\"", datetime::display(end_date))?;
+        }
+        if let Some(duration) = self.duration {
+            write!(f, ",DURATION={}", duration)?;
+        }
+        if let Some(planned_duration) = self.planned_duration {
+            write!(f, ",PLANNED-DURATION={}", planned_duration)?;
+        }
+        for (name, value) in &self.client_attributes {
+            write!(f, ",{}={}", name, value)?;
+        }
+        if let Some(scte35_cmd) = &self.scte35_cmd {
+            write!(f, ",SCTE35-CMD={}", scte35_cmd)?;
+        }
+        if let Some(scte35_out) = &self.scte35_out {
+            write!(f, ",SCTE35-OUT={}", scte35_out)?;
+        }
+        if let Some(scte35_in) = &self.scte35_in {
+            write!(f, ",SCTE35-IN={}", scte35_in)?;
+        }
+        if self.end_on_next {
+            write!(f, ",END-ON-NEXT=YES")?;
+        }
+        write!(f, "{}", ExtraAttributes(&self.extra_attributes))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DateRangeError {
+    Duplicate { id: String },
+    Unknown { id: String },
+    Conflict { id: String, attribute: String },
+}
+
+/// Writer-side set of dateranges keyed by ID. Repeated updates to the same ID
+/// are merged so the playlist only ever carries one consistent tag per range.
+#[derive(Clone, Default)]
+pub struct DateRangeSet {
+    ranges: Vec<DateRange>,
+    removed: RemovedDateRanges,
+}
+
+impl DateRangeSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, range: DateRange) -> Result<(), DateRangeError> {
+        if self.get(&range.id).is_some() {
+            return Err(DateRangeError::Duplicate { id: range.id });
+        }
+        self.ranges.push(range);
+        Ok(())
+    }
+
+    /// Merges `range` into the existing range with the same ID, e.g. to add
+    /// DURATION once an ad break that was announced with PLANNED-DURATION
+    /// ends. Changing an attribute that was already published is rejected.
+    pub fn update(&mut self, range: DateRange) -> Result<(), DateRangeError> {
+        let existing = self
+            .ranges
+            .iter_mut()
+            .find(|r| r.id == range.id)
+            .ok_or_else(|| DateRangeError::Unknown {
+                id: range.id.clone(),
+            })?;
+        let mut merged = existing.clone();
+        merged.merge(range)?;
+        *existing = merged;
+        Ok(())
+    }
+
+    /// Removes the range, remembering its ID for RECENTLY-REMOVED-DATERANGES
+    /// in delta updates. `last_msn` is the newest segment at removal time.
+    pub fn remove(&mut self, id: &str, last_msn: u64) -> Option<DateRange> {
+        let index = self.ranges.iter().position(|r| r.id == id)?;
+        self.removed.record(id, last_msn);
+        Some(self.ranges.remove(index))
+    }
+
+    pub fn get(&self, id: &str) -> Option<&DateRange> {
+        self.ranges.iter().find(|r| r.id == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &DateRange> {
+        self.ranges.iter()
+    }
+
+    pub fn removed(&self) -> &RemovedDateRanges {
+        &self.removed
+    }
+
+    pub fn removed_mut(&mut self) -> &mut RemovedDateRanges {
+        &mut self.removed
     }
 }
 
 impl fmt::Display for PartialSegment {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut attrs = vec![
-            ("DURATION", self.part_duration.to_string()),
-            ("URI", self.uri.to_string()),
-        ];
+        write!(
+            f,
+            "#EXT-X-PART:DURATION={},URI=\"{}\"",
+            seconds::display(&self.part_duration),
+            self.uri
+        )?;
         if let Some(independent) = self.independent {
-            attrs.push((
-                "INDEPENDENT",
-                if independent {
-                    "YES".to_string()
-                } else {
-                    "FALSE".to_string()
-                },
-            ));
+            let independent = if independent { "YES" } else { "NO" };
+            write!(f, ",INDEPENDENT={}", independent)?;
         }
-        let attrs_str: Vec<String> = attrs
-            .into_iter()
-            .map(|(name, value)| format!("{}={}", name, value))
-            .collect();
-        write!(f, "#EXT-X-PART:{}", attrs_str.join(","))
+        if self.gap {
+            write!(f, ",GAP=YES")?;
+        }
+        if let Some(byterange) = &self.byterange {
+            write!(f, ",BYTERANGE=\"{}\"", byterange)?;
+        }
+        write!(f, "{}", ExtraAttributes(&self.extra_attributes))
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
-pub struct ParseTagError;
+/// Why [`MediaPlaylistBuilder::build`] failed.
+///
+/// Breaking change: this used to be a struct holding only the missing
+/// `field`, which is now [`BuilderError::MissingField`], as `build` also
+/// rejects playlists that break the specification.
+#[derive(Debug, PartialEq)]
+pub enum BuilderError {
+    /// A required field without default was never set.
+    MissingField { field: &'static str },
+    /// The playlist breaks the specification.
+    Invalid { issues: Vec<ValidationIssue> },
+}
 
-#[derive(Debug, PartialEq, Eq)]
-pub struct ParseAttributeError;
+impl fmt::Display for BuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuilderError::MissingField { field } => write!(f, "missing {}", field),
+            BuilderError::Invalid { issues } => {
+                write!(f, "invalid playlist")?;
+                for issue in issues {
+                    write!(f, "; {}", issue)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl core::error::Error for BuilderError {}
 
+// Build error of the internal derive_builder builders, kept out of the public
+// API so it isn't tied to derive_builder's types
 #[derive(Debug)]
-pub enum ParsePlaylistError {
-    EXT3U_TAG_MISSING,
-    BUILDER_ERROR,
-    IO_ERROR,
-    UNRECOGNIZED_TAG { tag: String },
+pub(crate) struct UninitializedField(pub(crate) &'static str);
+
+impl From<UninitializedFieldError> for UninitializedField {
+    fn from(value: UninitializedFieldError) -> Self {
+        UninitializedField(value.field_name())
+    }
+}
+
+// Rejected attribute value, named by read_attributes
+pub(crate) struct InvalidValue;
+
+impl From<ParseAttributeError> for InvalidValue {
+    fn from(_: ParseAttributeError) -> Self {
+        InvalidValue
+    }
 }
 
+impl ParsePlaylistError {
+    fn missing_playlist_field(UninitializedField(field): UninitializedField) -> Self {
+        let tag = match field {
+            "target_duration" => "EXT-X-TARGETDURATION",
+            "version" => "EXT-X-VERSION",
+            _ => "EXTM3U",
+        };
+        ParsePlaylistError::MissingField {
+            line: None,
+            tag: tag.to_string(),
+            field,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
 pub fn read_playlist(file: File) -> Result<MediaPlaylist, ParsePlaylistError> {
-    let mut parser = BufReader::new(file);
-    let mut line = String::new();
-    parser
-        .read_line(&mut line)
-        .map_err(|_| ParsePlaylistError::IO_ERROR)?;
-    if !line.trim().eq("#EXTM3U") {
-        return Err(ParsePlaylistError::EXT3U_TAG_MISSING);
-    }
-    let mut builder = WrappedMediaPlaylistBuilder {
-        playlist: MediaPlaylistBuilder::default(),
-        rendition_reports: Vec::new(),
-        media_segments: Vec::new(),
-    };
-    // Set some defaults so we don't forget later
-    builder.playlist.skip(None);
-    builder.playlist.preload_hint(None);
-    let mut media_segment_builder = WrappedMediaSegmentBuilder {
-        segment: MediaSegmentBuilder::default(),
-        parts: Vec::new(),
-    };
-    line.clear();
-    while let Ok(read_bytes) = parser.read_line(&mut line) {
+    read_playlist_with_hooks(file, &mut NoHooks)
+}
+
+#[cfg(feature = "std")]
+pub fn read_playlist_with_hooks<H: Hooks + ?Sized>(
+    file: File,
+    hooks: &mut H,
+) -> Result<MediaPlaylist, ParsePlaylistError> {
+    read_playlist_with_options(file, &ParseOptions::default(), hooks)
+}
+
+// Applies the numeric policy of `options` to the value of a tag
+fn sanitize_numbers<'a, H: Hooks + ?Sized>(
+    tag: &str,
+    value: &'a str,
+    options: &ParseOptions,
+    line: usize,
+    hooks: &mut H,
+) -> Result<Cow<'a, str>, ParsePlaylistError> {
+    let (value, clamped) = numeric::sanitize(value, options.numbers).map_err(|error| {
+        ParsePlaylistError::InvalidNumber {
+            line,
+            tag: tag.to_string(),
+            error,
+        }
+    })?;
+    if clamped {
+        hooks.on_warning(&ParseWarning::ClampedNumber {
+            line,
+            tag: tag.to_string(),
+        });
+    }
+    Ok(value)
+}
+
+#[cfg(feature = "std")]
+pub fn read_playlist_with_options<H: Hooks + ?Sized>(
+    mut file: File,
+    options: &ParseOptions,
+    hooks: &mut H,
+) -> Result<MediaPlaylist, ParsePlaylistError> {
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)
+        .map_err(ParsePlaylistError::Io)?;
+    read_media_playlist(&bytes, options, hooks)
+}
+
+/// Parses a media playlist from bytes of any origin, e.g. a response body
+/// or fuzzer input. Never panics: bytes that aren't UTF-8 and anything else
+/// that isn't a media playlist fail with a [`ParsePlaylistError`] naming
+/// the line.
+pub fn parse_bytes(bytes: &[u8]) -> Result<MediaPlaylist, ParsePlaylistError> {
+    read_media_playlist(bytes, &ParseOptions::default(), &mut NoHooks)
+}
+
+/// A playlist of either kind, see [`read_any_playlist`].
+// Playlists are read once per reload, so boxing wouldn't save anything
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Playlist {
+    Media(MediaPlaylist),
+    Multivariant(MultivariantPlaylist),
+}
+
+/// Reads a media or a multivariant playlist, see [`parse_any_playlist`].
+#[cfg(feature = "std")]
+pub fn read_any_playlist(mut file: File) -> Result<Playlist, ParsePlaylistError> {
+    let mut text = String::new();
+    file.read_to_string(&mut text)
+        .map_err(ParsePlaylistError::Io)?;
+    parse_any_playlist(&text)
+}
+
+/// Parses a media or a multivariant playlist, telling them apart by the tags
+/// only multivariant playlists carry, so a session can start from whichever
+/// URL it was given.
+pub fn parse_any_playlist(text: &str) -> Result<Playlist, ParsePlaylistError> {
+    let is_multivariant = text.lines().any(|line| {
+        [
+            "#EXT-X-STREAM-INF:",
+            "#EXT-X-I-FRAME-STREAM-INF:",
+            "#EXT-X-MEDIA:",
+            "#EXT-X-SESSION-DATA:",
+        ]
+        .iter()
+        .any(|tag| line.starts_with(tag))
+    });
+    if is_multivariant {
+        text.parse().map(Playlist::Multivariant)
+    } else {
+        parse_bytes(text.as_bytes()).map(Playlist::Media)
+    }
+}
+
+// Feeds the lines of `bytes` to the parser, each with its line terminator
+pub(crate) fn read_media_playlist<H: Hooks + ?Sized>(
+    bytes: &[u8],
+    options: &ParseOptions,
+    hooks: &mut H,
+) -> Result<MediaPlaylist, ParsePlaylistError> {
+    let mut state: Option<MediaPlaylistState> = None;
+    for (line, line_number) in bytes.split_inclusive(|&byte| byte == b'\n').zip(1..) {
+        let line = core::str::from_utf8(line)
+            .map_err(|_| ParsePlaylistError::InvalidUtf8 { line: line_number })?;
+        match &mut state {
+            Some(state) => state.read_line(line, options, hooks)?,
+            None => state = Some(MediaPlaylistState::new(line)?),
+        }
+    }
+    state.ok_or(ParsePlaylistError::MissingHeader)?.finish()
+}
+
+pub(crate) fn undefined_variable(line: usize, error: define::VariableError) -> ParsePlaylistError {
+    let (define::VariableError::Redefined(name) | define::VariableError::Undefined(name)) = error;
+    ParsePlaylistError::UndefinedVariable { line, name }
+}
+
+// What has been parsed of a media playlist so far, fed one line at a time
+pub(crate) struct MediaPlaylistState {
+    builder: WrappedMediaPlaylistBuilder,
+    segment: WrappedMediaSegmentBuilder,
+    line_number: usize,
+    seen_tags: BTreeSet<String>,
+    variables: define::Variables,
+    // Lines of EXT-X-MEDIA-SEQUENCE and EXT-X-DISCONTINUITY-SEQUENCE, for
+    // sequence numbers the listed segments would take past u64::MAX
+    sequence_lines: [usize; 2],
+    // Whether a tag or URI of a segment was read yet; unknown tags before
+    // that belong to the playlist
+    in_segments: bool,
+    // Whether a tag that follows the segments, such as EXT-X-ENDLIST, was
+    // read yet; unknown tags after that render last
+    at_end: bool,
+    unquoted_uris: Vec<(&'static str, String)>,
+}
+
+impl MediaPlaylistState {
+    // Starts from the first line, which must be the header
+    pub(crate) fn new(header: &str) -> Result<Self, ParsePlaylistError> {
+        if !header.trim().eq("#EXTM3U") {
+            return Err(ParsePlaylistError::MissingHeader);
+        }
+        let mut builder = WrappedMediaPlaylistBuilder {
+            playlist: MediaPlaylistBuilder::default(),
+            rendition_reports: Vec::new(),
+            date_ranges: Vec::new(),
+            media_segments: Vec::new(),
+        };
+        // Set some defaults so we don't forget later
+        builder.playlist.skip(None);
+        builder.playlist.preload_hint(None);
+        builder.playlist.line_ending(if header.ends_with("\r\n") {
+            LineEnding::CrLf
+        } else {
+            LineEnding::Lf
+        });
+        Ok(MediaPlaylistState {
+            builder,
+            segment: WrappedMediaSegmentBuilder {
+                segment: MediaSegmentBuilder::default(),
+                parts: Vec::new(),
+                keys: Vec::new(),
+                bitrate: None,
+                custom_tags: Vec::new(),
+                unknown_tags: Vec::new(),
+            },
+            line_number: 1,
+            seen_tags: BTreeSet::new(),
+            variables: define::Variables::default(),
+            sequence_lines: [0; 2],
+            in_segments: false,
+            at_end: false,
+            unquoted_uris: Vec::new(),
+        })
+    }
+
+    // Segments completed so far
+    pub(crate) fn segments(&self) -> &[MediaSegment] {
+        &self.builder.media_segments
+    }
+
+    // Reads the next line, with or without its line terminator
+    pub(crate) fn read_line<H: Hooks + ?Sized>(
+        &mut self,
+        line: &str,
+        options: &ParseOptions,
+        hooks: &mut H,
+    ) -> Result<(), ParsePlaylistError> {
+        self.line_number += 1;
+        let line_number = self.line_number;
+        if let Some(value) = line.trim_end().strip_prefix("#EXT-X-DEFINE:") {
+            return self.read_define(value, options, hooks);
+        }
         let is_uri = !line.starts_with('#') && !line.trim().is_empty();
+        let line = match options.variables {
+            VariablePolicy::Substitute if is_uri => self.variables.substitute(line),
+            VariablePolicy::Substitute => self.variables.substitute_quoted(line),
+            VariablePolicy::Keep => Ok(Cow::Borrowed(line)),
+        }
+        .map_err(|error| undefined_variable(line_number, error))?;
+        let line = line.as_ref();
+        let builder = &mut self.builder;
+        let media_segment_builder = &mut self.segment;
         if line.starts_with("#EXT-X") || line.starts_with("#EXT") {
             let tag = line
                 .trim_end()
                 .split_once(':')
-                .ok_or(ParseTagError)
-                .map_err(|_| ParsePlaylistError::IO_ERROR)?;
-            let tag_id = tag.0.split_once('#').ok_or(ParsePlaylistError::IO_ERROR)?.1;
+                // Tags like EXT-X-ENDLIST have no value
+                .unwrap_or((line.trim_end(), ""));
+            let tag_id = &tag.0[1..];
             if let Ok(media_playlist_tag) = MediaPlaylistTag::from_str(tag_id) {
+                if media_playlist_tag.is_unique() && !self.seen_tags.insert(tag_id.to_string()) {
+                    if options.duplicate_tags == DuplicateTagPolicy::Error {
+                        return Err(ParsePlaylistError::DuplicateTag {
+                            line: line_number,
+                            tag: tag_id.to_string(),
+                        });
+                    }
+                    hooks.on_warning(&ParseWarning::DuplicateTag {
+                        line: line_number,
+                        tag: tag_id.to_string(),
+                    });
+                    if options.duplicate_tags == DuplicateTagPolicy::FirstWins {
+                        return Ok(());
+                    }
+                }
+                let value = sanitize_numbers(tag_id, tag.1, options, line_number, hooks)?;
+                hooks.on_tag(tag_id, &value);
+                match media_playlist_tag {
+                    MediaPlaylistTag::MediaSequence => self.sequence_lines[0] = line_number,
+                    MediaPlaylistTag::DiscontinuitySequence => self.sequence_lines[1] = line_number,
+                    MediaPlaylistTag::PreloadHint
+                    | MediaPlaylistTag::RenditionReport
+                    | MediaPlaylistTag::EndList => self.at_end = true,
+                    _ => {}
+                }
                 media_playlist_tag
-                    .read(&mut builder, tag.1)
-                    .map_err(|_| ParsePlaylistError::BUILDER_ERROR)?;
-            } else if let Ok(media_segment_tag) = MediaSegmentTag::from_str(tag_id) {
-                media_segment_tag
-                    .read(&mut media_segment_builder, tag.1)
-                    .map_err(|_| ParsePlaylistError::BUILDER_ERROR)?;
+                    .read(builder, &value)
+                    .map_err(|e| ParsePlaylistError::from_tag(line_number, tag_id, e))?;
+                self.unquoted_uris.extend(unquoted_uri(tag_id, &value));
+            } else if options.custom_tags.contains(tag_id) {
+                let value = sanitize_numbers(tag_id, tag.1, options, line_number, hooks)?;
+                hooks.on_tag(tag_id, &value);
+                let custom_tag = options
+                    .custom_tags
+                    .parse(tag_id, &value)
+                    .map_err(|e| ParsePlaylistError::from_tag(line_number, tag_id, e))?;
+                media_segment_builder.custom_tags.push(custom_tag);
+                self.in_segments = true;
+            } else {
+                match MediaSegmentTag::from_str(tag_id) {
+                    Ok(MediaSegmentTag::Uri) | Err(_) => {
+                        hooks.on_warning(&ParseWarning::UnknownTag {
+                            line: line_number,
+                            tag: tag_id.to_string(),
+                        });
+                        hooks.on_unknown_tag(
+                            tag_id,
+                            tag.1,
+                            // Not every tag value is an attribute list
+                            &parse_attribute_list(tag.1).unwrap_or_default(),
+                        );
+                        let mut unknown_tag = UnknownTag::parse(line.trim_end());
+                        if self.at_end {
+                            unknown_tag.position = TagPosition::End;
+                            media_segment_builder.unknown_tags.push(unknown_tag);
+                        } else if self.in_segments {
+                            unknown_tag.position =
+                                TagPosition::BeforePart(media_segment_builder.parts.len());
+                            media_segment_builder.unknown_tags.push(unknown_tag);
+                        } else {
+                            builder.playlist.unknown_tags.push(unknown_tag);
+                        }
+                    }
+                    Ok(media_segment_tag) => {
+                        let value = sanitize_numbers(tag_id, tag.1, options, line_number, hooks)?;
+                        hooks.on_tag(tag_id, &value);
+                        media_segment_tag
+                            .read(media_segment_builder, &value)
+                            .map_err(|e| ParsePlaylistError::from_tag(line_number, tag_id, e))?;
+                        self.unquoted_uris.extend(unquoted_uri(tag_id, &value));
+                        self.in_segments = true;
+                    }
+                }
             }
         } else if is_uri {
-            if let Ok(media_segment_tag) = MediaSegmentTag::from_str(&line) {
-                media_segment_tag
-                    .read(&mut media_segment_builder, &line.trim_end())
-                    .map_err(|_| ParsePlaylistError::BUILDER_ERROR)?;
+            let written = line.trim_end();
+            if MediaSegmentTag::Uri
+                .read(media_segment_builder, written)
+                .is_err()
+            {
+                let invalid_uri = || ParsePlaylistError::InvalidUri {
+                    line: line_number,
+                    uri: written.to_string(),
+                };
+                let repaired = uri::parse(&uri::repair(written));
+                match options.uris {
+                    UriPolicy::Strict => return Err(invalid_uri()),
+                    UriPolicy::Repair => {
+                        let repaired = repaired.map_err(|_| invalid_uri())?;
+                        media_segment_builder.segment.uri(repaired);
+                    }
+                    UriPolicy::Raw => {
+                        media_segment_builder
+                            .segment
+                            .uri(repaired.unwrap_or_default())
+                            .raw_uri(Some(written.to_string()));
+                    }
+                }
+                hooks.on_warning(&ParseWarning::RepairedUri {
+                    line: line_number,
+                    uri: written.to_string(),
+                });
             }
         }
-        if is_uri || line.eq("EXT-X-ENDLIST") {
+        if is_uri {
+            self.in_segments = true;
             if media_segment_builder.segment.program_date_time.is_none() {
                 media_segment_builder.segment.program_date_time(None);
             }
-            builder.media_segments.push(
-                media_segment_builder
-                    .segment
-                    .partial_segments(media_segment_builder.parts)
-                    .build()
-                    .map_err(|_| ParsePlaylistError::BUILDER_ERROR)?,
-            );
-            media_segment_builder = WrappedMediaSegmentBuilder {
+            let next = WrappedMediaSegmentBuilder {
                 segment: MediaSegmentBuilder::default(),
                 parts: Vec::new(),
+                keys: Vec::new(),
+                bitrate: media_segment_builder.bitrate,
+                custom_tags: Vec::new(),
+                unknown_tags: Vec::new(),
             };
+            let mut media_segment_builder = core::mem::replace(media_segment_builder, next);
+            let segment = media_segment_builder
+                .segment
+                .partial_segments(media_segment_builder.parts)
+                .keys(media_segment_builder.keys)
+                .bitrate(media_segment_builder.bitrate)
+                .custom_tags(media_segment_builder.custom_tags)
+                .unknown_tags(media_segment_builder.unknown_tags)
+                .build()
+                .map_err(|e| ParsePlaylistError::from_tag(line_number, "EXTINF", e.into()))?;
+            hooks.on_segment_complete(&segment);
+            builder.media_segments.push(segment);
+        }
+        Ok(())
+    }
+
+    // Records EXT-X-DEFINE and, unless references are kept as written, the
+    // value of its variable
+    fn read_define<H: Hooks + ?Sized>(
+        &mut self,
+        value: &str,
+        options: &ParseOptions,
+        hooks: &mut H,
+    ) -> Result<(), ParsePlaylistError> {
+        const TAG: &str = "EXT-X-DEFINE";
+        hooks.on_tag(TAG, value);
+        let define = Define::from_str(value)
+            .map_err(|e| ParsePlaylistError::from_tag(self.line_number, TAG, e))?;
+        if options.variables == VariablePolicy::Substitute {
+            let url = options.playlist_url.as_deref();
+            self.variables
+                .define(&define, &options.imported_variables, url)
+                .map_err(|error| match error {
+                    define::VariableError::Redefined(_) => ParsePlaylistError::from_tag(
+                        self.line_number,
+                        TAG,
+                        ParseTagError::invalid(value),
+                    ),
+                    error => undefined_variable(self.line_number, error),
+                })?;
+        }
+        self.builder.playlist.definitions.push(define);
+        Ok(())
+    }
+
+    // The playlist, once every line has been read
+    pub(crate) fn finish(self) -> Result<MediaPlaylist, ParsePlaylistError> {
+        let MediaPlaylistState {
+            mut builder,
+            segment: media_segment_builder,
+            sequence_lines,
+            unquoted_uris,
+            ..
+        } = self;
+        let mut playlist = builder
+            .playlist
+            .media_segments(builder.media_segments)
+            .trailing_parts(media_segment_builder.parts)
+            .rendition_reports(builder.rendition_reports)
+            .date_ranges(builder.date_ranges)
+            .build_unchecked()
+            .map_err(ParsePlaylistError::missing_playlist_field)?;
+        playlist.trailing_discontinuity = media_segment_builder.segment.discontinuity == Some(true);
+        playlist.trailing_keys = media_segment_builder.keys;
+        playlist.trailing_map = media_segment_builder.segment.map.flatten();
+        playlist.trailing_program_date_time =
+            media_segment_builder.segment.program_date_time.flatten();
+        playlist.trailing_gap = media_segment_builder.segment.gap == Some(true);
+        if media_segment_builder.segment.declares_bitrate == Some(true) {
+            playlist.trailing_bitrate = media_segment_builder.bitrate;
         }
-        if read_bytes == 0 {
-            break;
+        playlist.trailing_tags = media_segment_builder.custom_tags;
+        playlist.trailing_unknown_tags = media_segment_builder.unknown_tags;
+        playlist.unquoted_uris = unquoted_uris;
+        // Every segment listed, skipped or being published takes a number
+        let numbered = playlist.media_segments.len() as u64 + 1;
+        let skipped = playlist
+            .skip
+            .as_ref()
+            .map_or(0, |skip| skip.skipped_segments);
+        let sequences = [
+            (
+                "EXT-X-MEDIA-SEQUENCE",
+                playlist.media_sequence_number,
+                skipped,
+            ),
+            (
+                "EXT-X-DISCONTINUITY-SEQUENCE",
+                playlist.discontinuity_sequence,
+                0,
+            ),
+        ];
+        for ((tag, first, skipped), line) in sequences.into_iter().zip(sequence_lines) {
+            if first
+                .checked_add(skipped)
+                .and_then(|first| first.checked_add(numbered))
+                .is_none()
+            {
+                return Err(ParsePlaylistError::InvalidNumber {
+                    line,
+                    tag: tag.to_string(),
+                    error: NumericError::Overflow,
+                });
+            }
         }
-        line.clear();
+        Ok(playlist)
     }
-    builder
-        .playlist
-        .media_segments(builder.media_segments)
-        .rendition_reports(builder.rendition_reports)
-        .build()
-        .map_err(|_| ParsePlaylistError::BUILDER_ERROR)
 }
@@ -0,0 +1,840 @@
+use alloc::{borrow::Cow, collections::BTreeMap};
+use core::{fmt, str::FromStr};
+#[cfg(feature = "std")]
+use std::{fs::File, io::Read};
+
+use crate::prelude::*;
+use crate::{
+    attribute::tokenize_attribute_list,
+    define::{self, Variables},
+    undefined_variable, AttributeValue, Define, EnumeratedStringList, ExtraAttributes, Key,
+    ParseAttributeError, ParsePlaylistError, ParseTagError, UnknownTag,
+};
+
+/// A multivariant (master) playlist listing the variant streams of a
+/// presentation and their alternative renditions.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MultivariantPlaylist {
+    pub version: Option<u32>,
+    pub independent_segments: bool,
+    /// EXT-X-DEFINE variables, in order. IMPORT isn't allowed here.
+    pub definitions: Vec<Define>,
+    pub session_data: Vec<SessionData>,
+    /// EXT-X-SESSION-KEY: keys of the media playlists, so clients can load
+    /// them before the first media playlist.
+    pub session_keys: Vec<Key>,
+    pub renditions: Vec<Rendition>,
+    pub variants: Vec<VariantStream>,
+    pub i_frame_streams: Vec<IFrameStream>,
+    /// Tags this crate doesn't know, written back after the session tags.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub unknown_tags: Vec<UnknownTag>,
+}
+
+impl MultivariantPlaylist {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The variables media playlists can import with EXT-X-DEFINE:IMPORT, for
+    /// [`crate::ParseOptions::imported_variables`]. Those taking the value of
+    /// a query parameter aren't known from the playlist alone.
+    pub fn variables(&self) -> BTreeMap<String, String> {
+        self.definitions
+            .iter()
+            .filter_map(|define| match define {
+                Define::Value { name, value } => Some((name.clone(), value.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn variant(&self, uri: &str) -> Option<&VariantStream> {
+        self.variants.iter().find(|variant| variant.uri == uri)
+    }
+
+    /// Adds EXT-X-SESSION-DATA carrying `value` inline, e.g. a channel name
+    /// under `com.example.channel`. Entries sharing a DATA-ID must differ in
+    /// LANGUAGE.
+    pub fn add_session_value(
+        &mut self,
+        data_id: impl Into<String>,
+        value: impl fmt::Display,
+        language: Option<&str>,
+    ) -> Result<(), SessionDataError> {
+        self.add_session_data(SessionData {
+            data_id: data_id.into(),
+            value: SessionDataValue::Value(value.to_string()),
+            language: language.map(str::to_string),
+            format: SessionDataFormat::Json,
+            extra_attributes: BTreeMap::new(),
+        })
+    }
+
+    /// Adds EXT-X-SESSION-DATA pointing at a JSON document at `uri`.
+    pub fn add_session_uri(
+        &mut self,
+        data_id: impl Into<String>,
+        uri: impl Into<String>,
+        language: Option<&str>,
+    ) -> Result<(), SessionDataError> {
+        self.add_session_data(SessionData {
+            data_id: data_id.into(),
+            value: SessionDataValue::Uri(uri.into()),
+            language: language.map(str::to_string),
+            format: SessionDataFormat::Json,
+            extra_attributes: BTreeMap::new(),
+        })
+    }
+
+    /// The EXT-X-SESSION-DATA of `data_id` to show a viewer who prefers
+    /// `language`: the entry in that language, else the one without
+    /// LANGUAGE, else the first one.
+    pub fn session_data_for(&self, data_id: &str, language: Option<&str>) -> Option<&SessionData> {
+        let mut entries = self
+            .session_data
+            .iter()
+            .filter(|data| data.data_id == data_id);
+        let first = entries.clone().next()?;
+        let in_language = language.and_then(|language| {
+            entries.clone().find(|data| {
+                data.language
+                    .as_deref()
+                    .is_some_and(|data_language| data_language.eq_ignore_ascii_case(language))
+            })
+        });
+        in_language
+            .or_else(|| entries.find(|data| data.language.is_none()))
+            .or(Some(first))
+    }
+
+    pub fn add_session_data(&mut self, data: SessionData) -> Result<(), SessionDataError> {
+        let text = match &data.value {
+            SessionDataValue::Value(value) | SessionDataValue::Uri(value) => value,
+        };
+        // Everything ends up in a quoted-string
+        for field in [&data.data_id, text]
+            .into_iter()
+            .chain(data.language.as_ref())
+        {
+            if field.contains(['"', '\r', '\n']) {
+                return Err(SessionDataError::Unquotable {
+                    data_id: data.data_id.clone(),
+                });
+            }
+        }
+        let duplicate = self
+            .session_data
+            .iter()
+            .any(|existing| existing.data_id == data.data_id && existing.language == data.language);
+        if duplicate {
+            return Err(SessionDataError::Duplicate {
+                data_id: data.data_id,
+                language: data.language,
+            });
+        }
+        self.session_data.push(data);
+        Ok(())
+    }
+
+    /// Renditions of `group_id` of the given type, e.g. the audio renditions
+    /// a variant's AUDIO attribute refers to.
+    pub fn group<'a>(
+        &'a self,
+        r#type: MediaType,
+        group_id: &'a str,
+    ) -> impl Iterator<Item = &'a Rendition> + 'a {
+        self.renditions
+            .iter()
+            .filter(move |rendition| rendition.r#type == r#type && rendition.group_id == group_id)
+    }
+}
+
+/// EXT-X-SESSION-DATA: arbitrary session metadata for players.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SessionData {
+    /// Reverse DNS style identifier, e.g. `com.example.title`.
+    pub data_id: String,
+    pub value: SessionDataValue,
+    pub language: Option<String>,
+    /// How the document at a URI is encoded; always JSON for inline values.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub format: SessionDataFormat,
+    /// Attributes this crate doesn't know, e.g. of a later revision of the
+    /// spec, written back as read.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub extra_attributes: BTreeMap<String, String>,
+}
+
+impl SessionData {
+    /// The inline VALUE, if the data isn't a URI.
+    pub fn value(&self) -> Option<&str> {
+        match &self.value {
+            SessionDataValue::Value(value) => Some(value),
+            SessionDataValue::Uri(_) => None,
+        }
+    }
+
+    /// The URI of the document holding the data, if it isn't inline.
+    pub fn uri(&self) -> Option<&str> {
+        match &self.value {
+            SessionDataValue::Value(_) => None,
+            SessionDataValue::Uri(uri) => Some(uri),
+        }
+    }
+}
+
+/// FORMAT of EXT-X-SESSION-DATA: how the document at its URI is encoded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SessionDataFormat {
+    /// A JSON document, the default.
+    #[default]
+    Json,
+    /// Raw bytes, whose meaning the DATA-ID defines.
+    Raw,
+}
+
+impl SessionDataFormat {
+    fn as_str(self) -> &'static str {
+        match self {
+            SessionDataFormat::Json => "JSON",
+            SessionDataFormat::Raw => "RAW",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        [SessionDataFormat::Json, SessionDataFormat::Raw]
+            .into_iter()
+            .find(|format| format.as_str() == name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SessionDataValue {
+    /// Carried inline in VALUE.
+    Value(String),
+    /// URI of a JSON document.
+    Uri(String),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SessionDataError {
+    /// An entry with the same DATA-ID and LANGUAGE already exists.
+    Duplicate {
+        data_id: String,
+        language: Option<String>,
+    },
+    /// A field contains a double quote or line break, which can't be
+    /// represented in a quoted-string.
+    Unquotable { data_id: String },
+}
+
+/// EXT-X-STREAM-INF and the URI of its media playlist.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VariantStream {
+    pub uri: String,
+    /// Peak segment bit rate in bits per second.
+    pub bandwidth: u64,
+    /// Average segment bit rate in bits per second.
+    pub average_bandwidth: Option<u64>,
+    pub codecs: Option<String>,
+    pub resolution: Option<(u32, u32)>,
+    pub frame_rate: Option<f32>,
+    /// SCORE: how much the variant is preferred over the others, higher
+    /// being better, whatever its bit rate.
+    pub score: Option<f32>,
+    /// GROUP-ID of the audio renditions to play with this variant.
+    pub audio: Option<String>,
+    pub subtitles: Option<String>,
+    /// Attributes this crate doesn't know, e.g. of a later revision of the
+    /// spec, written back as read.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub extra_attributes: BTreeMap<String, String>,
+}
+
+impl VariantStream {
+    pub fn new(uri: impl Into<String>, bandwidth: u64) -> Self {
+        VariantStream {
+            uri: uri.into(),
+            bandwidth,
+            average_bandwidth: None,
+            codecs: None,
+            resolution: None,
+            frame_rate: None,
+            score: None,
+            audio: None,
+            subtitles: None,
+            extra_attributes: BTreeMap::new(),
+        }
+    }
+}
+
+/// EXT-X-I-FRAME-STREAM-INF: a media playlist of I-frames only, used for
+/// trick play.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IFrameStream {
+    pub uri: String,
+    /// Peak segment bit rate in bits per second.
+    pub bandwidth: u64,
+    /// Average segment bit rate in bits per second.
+    pub average_bandwidth: Option<u64>,
+    pub codecs: Option<String>,
+    pub resolution: Option<(u32, u32)>,
+    /// Attributes this crate doesn't know, e.g. of a later revision of the
+    /// spec, written back as read.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub extra_attributes: BTreeMap<String, String>,
+}
+
+impl IFrameStream {
+    pub fn new(uri: impl Into<String>, bandwidth: u64) -> Self {
+        IFrameStream {
+            uri: uri.into(),
+            bandwidth,
+            average_bandwidth: None,
+            codecs: None,
+            resolution: None,
+            extra_attributes: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MediaType {
+    Audio,
+    Video,
+    Subtitles,
+    ClosedCaptions,
+}
+
+impl MediaType {
+    fn as_str(self) -> &'static str {
+        match self {
+            MediaType::Audio => "AUDIO",
+            MediaType::Video => "VIDEO",
+            MediaType::Subtitles => "SUBTITLES",
+            MediaType::ClosedCaptions => "CLOSED-CAPTIONS",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        [
+            MediaType::Audio,
+            MediaType::Video,
+            MediaType::Subtitles,
+            MediaType::ClosedCaptions,
+        ]
+        .into_iter()
+        .find(|r#type| r#type.as_str() == name)
+    }
+}
+
+/// EXT-X-MEDIA: an alternative rendition within a group.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rendition {
+    pub r#type: MediaType,
+    pub group_id: String,
+    pub name: String,
+    pub uri: Option<String>,
+    pub language: Option<String>,
+    pub default: bool,
+    pub autoselect: bool,
+    pub channels: Option<String>,
+    /// Uniform Type Identifiers of the rendition's characteristics, e.g.
+    /// `public.accessibility.describes-video`.
+    pub characteristics: Option<EnumeratedStringList>,
+    /// Attributes this crate doesn't know, e.g. of a later revision of the
+    /// spec, written back as read.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub extra_attributes: BTreeMap<String, String>,
+}
+
+impl Rendition {
+    pub fn new(r#type: MediaType, group_id: impl Into<String>, name: impl Into<String>) -> Self {
+        Rendition {
+            r#type,
+            group_id: group_id.into(),
+            name: name.into(),
+            uri: None,
+            language: None,
+            default: false,
+            autoselect: false,
+            channels: None,
+            characteristics: None,
+            extra_attributes: BTreeMap::new(),
+        }
+    }
+}
+
+impl fmt::Display for MultivariantPlaylist {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "#EXTM3U")?;
+        if let Some(version) = self.version {
+            writeln!(f, "#EXT-X-VERSION:{}", version)?;
+        }
+        if self.independent_segments {
+            writeln!(f, "#EXT-X-INDEPENDENT-SEGMENTS")?;
+        }
+        for define in &self.definitions {
+            writeln!(f, "{}", define)?;
+        }
+        for data in &self.session_data {
+            writeln!(f, "{}", data)?;
+        }
+        for key in &self.session_keys {
+            write!(f, "#EXT-X-SESSION-KEY:")?;
+            key.write_attributes(f)?;
+            writeln!(f)?;
+        }
+        for tag in &self.unknown_tags {
+            writeln!(f, "{}", tag)?;
+        }
+        for rendition in &self.renditions {
+            writeln!(f, "{}", rendition)?;
+        }
+        for variant in &self.variants {
+            writeln!(f, "{}", variant)?;
+        }
+        for stream in &self.i_frame_streams {
+            writeln!(f, "{}", stream)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SessionData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#EXT-X-SESSION-DATA:DATA-ID=\"{}\"", self.data_id)?;
+        match &self.value {
+            SessionDataValue::Value(value) => write!(f, ",VALUE=\"{}\"", value)?,
+            SessionDataValue::Uri(uri) => {
+                write!(f, ",URI=\"{}\"", uri)?;
+                if self.format != SessionDataFormat::Json {
+                    write!(f, ",FORMAT={}", self.format.as_str())?;
+                }
+            }
+        }
+        if let Some(language) = &self.language {
+            write!(f, ",LANGUAGE=\"{}\"", language)?;
+        }
+        write!(f, "{}", ExtraAttributes(&self.extra_attributes))
+    }
+}
+
+impl fmt::Display for Rendition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#EXT-X-MEDIA:TYPE={},GROUP-ID=\"{}\",NAME=\"{}\"",
+            self.r#type.as_str(),
+            self.group_id,
+            self.name
+        )?;
+        if let Some(language) = &self.language {
+            write!(f, ",LANGUAGE=\"{}\"", language)?;
+        }
+        if self.default {
+            write!(f, ",DEFAULT=YES")?;
+        }
+        if self.autoselect {
+            write!(f, ",AUTOSELECT=YES")?;
+        }
+        if let Some(channels) = &self.channels {
+            write!(f, ",CHANNELS=\"{}\"", channels)?;
+        }
+        if let Some(characteristics) = &self.characteristics {
+            write!(f, ",CHARACTERISTICS={}", characteristics)?;
+        }
+        if let Some(uri) = &self.uri {
+            write!(f, ",URI=\"{}\"", uri)?;
+        }
+        write!(f, "{}", ExtraAttributes(&self.extra_attributes))
+    }
+}
+
+// Renders the tag followed by the URI line
+impl fmt::Display for VariantStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#EXT-X-STREAM-INF:BANDWIDTH={}", self.bandwidth)?;
+        if let Some(average_bandwidth) = self.average_bandwidth {
+            write!(f, ",AVERAGE-BANDWIDTH={}", average_bandwidth)?;
+        }
+        if let Some(codecs) = &self.codecs {
+            write!(f, ",CODECS=\"{}\"", codecs)?;
+        }
+        if let Some((width, height)) = self.resolution {
+            write!(f, ",RESOLUTION={}x{}", width, height)?;
+        }
+        if let Some(frame_rate) = self.frame_rate {
+            write!(f, ",FRAME-RATE={:.3}", frame_rate)?;
+        }
+        if let Some(score) = self.score {
+            write!(f, ",SCORE={}", score)?;
+        }
+        if let Some(audio) = &self.audio {
+            write!(f, ",AUDIO=\"{}\"", audio)?;
+        }
+        if let Some(subtitles) = &self.subtitles {
+            write!(f, ",SUBTITLES=\"{}\"", subtitles)?;
+        }
+        write!(
+            f,
+            "{}\n{}",
+            ExtraAttributes(&self.extra_attributes),
+            self.uri
+        )
+    }
+}
+
+impl fmt::Display for IFrameStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#EXT-X-I-FRAME-STREAM-INF:BANDWIDTH={}", self.bandwidth)?;
+        if let Some(average_bandwidth) = self.average_bandwidth {
+            write!(f, ",AVERAGE-BANDWIDTH={}", average_bandwidth)?;
+        }
+        if let Some(codecs) = &self.codecs {
+            write!(f, ",CODECS=\"{}\"", codecs)?;
+        }
+        if let Some((width, height)) = self.resolution {
+            write!(f, ",RESOLUTION={}x{}", width, height)?;
+        }
+        write!(
+            f,
+            ",URI=\"{}\"{}",
+            self.uri,
+            ExtraAttributes(&self.extra_attributes)
+        )
+    }
+}
+
+/// Reads a multivariant playlist. Tags that only appear in media playlists,
+/// and any this crate doesn't know, are ignored.
+#[cfg(feature = "std")]
+pub fn read_multivariant_playlist(
+    mut file: File,
+) -> Result<MultivariantPlaylist, ParsePlaylistError> {
+    let mut text = String::new();
+    file.read_to_string(&mut text)
+        .map_err(ParsePlaylistError::Io)?;
+    text.parse()
+}
+
+impl FromStr for MultivariantPlaylist {
+    type Err = ParsePlaylistError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s
+            .lines()
+            .map(str::trim)
+            .enumerate()
+            .map(|(index, line)| (index + 1, line))
+            .filter(|(_, line)| !line.is_empty());
+        if lines.next().map(|(_, line)| line) != Some("#EXTM3U") {
+            return Err(ParsePlaylistError::MissingHeader);
+        }
+        let mut playlist = MultivariantPlaylist::new();
+        let lines = substitute_variables(lines, &mut playlist.definitions)?;
+        let mut lines = lines.iter().map(|(number, line)| (*number, line.as_ref()));
+        while let Some((number, line)) = lines.next() {
+            // A URI line not preceded by EXT-X-STREAM-INF has nothing to
+            // belong to
+            let Some(tag) = line.strip_prefix('#') else {
+                continue;
+            };
+            let (tag, value) = tag.split_once(':').unwrap_or((tag, ""));
+            match tag {
+                "EXT-X-VERSION" => {
+                    playlist.version = Some(value.parse().map_err(|_| {
+                        ParsePlaylistError::from_tag(number, tag, ParseTagError::invalid(value))
+                    })?);
+                }
+                "EXT-X-INDEPENDENT-SEGMENTS" => playlist.independent_segments = true,
+                "EXT-X-SESSION-DATA" => {
+                    let attributes = Attributes::parse(number, tag, value)?;
+                    let value = match (attributes.quoted("VALUE")?, attributes.quoted("URI")?) {
+                        (Some(value), None) => SessionDataValue::Value(value),
+                        (None, Some(uri)) => SessionDataValue::Uri(uri),
+                        (None, None) => return Err(attributes.missing("value")),
+                        (Some(_), Some(_)) => return Err(attributes.invalid("URI")),
+                    };
+                    // FORMAT only describes a document at a URI
+                    let format = match attributes.enumerated("FORMAT")? {
+                        Some(format) => match (&value, SessionDataFormat::from_name(&format)) {
+                            (SessionDataValue::Uri(_), Some(format)) => format,
+                            _ => return Err(attributes.invalid("FORMAT")),
+                        },
+                        None => SessionDataFormat::Json,
+                    };
+                    playlist.session_data.push(SessionData {
+                        data_id: attributes.required(attributes.quoted("DATA-ID")?, "data_id")?,
+                        value,
+                        language: attributes.quoted("LANGUAGE")?,
+                        format,
+                        extra_attributes: attributes
+                            .extra(&["DATA-ID", "VALUE", "URI", "FORMAT", "LANGUAGE"]),
+                    });
+                }
+                "EXT-X-SESSION-KEY" => {
+                    let key = Key::from_str(value)
+                        .map_err(|error| ParsePlaylistError::from_tag(number, tag, error))?;
+                    // Only keys that actually encrypt something are shared
+                    if !key.is_encrypted() {
+                        return Err(ParsePlaylistError::from_tag(
+                            number,
+                            tag,
+                            ParseTagError::invalid(value),
+                        ));
+                    }
+                    playlist.session_keys.push(key);
+                }
+                "EXT-X-MEDIA" => {
+                    let attributes = Attributes::parse(number, tag, value)?;
+                    let r#type = attributes.required(attributes.enumerated("TYPE")?, "type")?;
+                    let r#type =
+                        MediaType::from_name(&r#type).ok_or_else(|| attributes.invalid("TYPE"))?;
+                    let characteristics =
+                        attributes.typed("CHARACTERISTICS", AttributeValue::as_list)?;
+                    playlist.renditions.push(Rendition {
+                        r#type,
+                        group_id: attributes
+                            .required(attributes.quoted("GROUP-ID")?, "group_id")?,
+                        name: attributes.required(attributes.quoted("NAME")?, "name")?,
+                        uri: attributes.quoted("URI")?,
+                        language: attributes.quoted("LANGUAGE")?,
+                        default: attributes.yes("DEFAULT")?,
+                        autoselect: attributes.yes("AUTOSELECT")?,
+                        channels: attributes.quoted("CHANNELS")?,
+                        characteristics,
+                        extra_attributes: attributes.extra(&[
+                            "TYPE",
+                            "GROUP-ID",
+                            "NAME",
+                            "URI",
+                            "LANGUAGE",
+                            "DEFAULT",
+                            "AUTOSELECT",
+                            "CHANNELS",
+                            "CHARACTERISTICS",
+                        ]),
+                    });
+                }
+                "EXT-X-STREAM-INF" => {
+                    let attributes = Attributes::parse(number, tag, value)?;
+                    let uri = lines
+                        .by_ref()
+                        .find(|(_, line)| !line.starts_with('#'))
+                        .map(|(_, line)| line)
+                        .ok_or_else(|| attributes.missing("uri"))?;
+                    playlist.variants.push(VariantStream {
+                        uri: uri.to_string(),
+                        bandwidth: attributes
+                            .required(attributes.integer("BANDWIDTH")?, "bandwidth")?,
+                        average_bandwidth: attributes.integer("AVERAGE-BANDWIDTH")?,
+                        codecs: attributes.quoted("CODECS")?,
+                        resolution: attributes.resolution("RESOLUTION")?,
+                        frame_rate: attributes.float("FRAME-RATE")?.map(|rate| rate as f32),
+                        score: attributes.float("SCORE")?.map(|score| score as f32),
+                        audio: attributes.quoted("AUDIO")?,
+                        subtitles: attributes.quoted("SUBTITLES")?,
+                        extra_attributes: attributes.extra(&[
+                            "BANDWIDTH",
+                            "AVERAGE-BANDWIDTH",
+                            "CODECS",
+                            "RESOLUTION",
+                            "FRAME-RATE",
+                            "SCORE",
+                            "AUDIO",
+                            "SUBTITLES",
+                        ]),
+                    });
+                }
+                "EXT-X-I-FRAME-STREAM-INF" => {
+                    let attributes = Attributes::parse(number, tag, value)?;
+                    playlist.i_frame_streams.push(IFrameStream {
+                        uri: attributes.required(attributes.quoted("URI")?, "uri")?,
+                        bandwidth: attributes
+                            .required(attributes.integer("BANDWIDTH")?, "bandwidth")?,
+                        average_bandwidth: attributes.integer("AVERAGE-BANDWIDTH")?,
+                        codecs: attributes.quoted("CODECS")?,
+                        resolution: attributes.resolution("RESOLUTION")?,
+                        extra_attributes: attributes.extra(&[
+                            "URI",
+                            "BANDWIDTH",
+                            "AVERAGE-BANDWIDTH",
+                            "CODECS",
+                            "RESOLUTION",
+                        ]),
+                    });
+                }
+                _ if tag.starts_with("EXT") => playlist.unknown_tags.push(UnknownTag::parse(line)),
+                _ => {}
+            }
+        }
+        Ok(playlist)
+    }
+}
+
+// Records the EXT-X-DEFINE tags into `definitions` and substitutes their
+// variables in the lines after them. The playlist's URL isn't known here, so
+// references to QUERYPARAM variables stay as written.
+fn substitute_variables<'a>(
+    lines: impl Iterator<Item = (usize, &'a str)>,
+    definitions: &mut Vec<Define>,
+) -> Result<Vec<(usize, Cow<'a, str>)>, ParsePlaylistError> {
+    const TAG: &str = "EXT-X-DEFINE";
+    let mut variables = Variables::default();
+    let mut substituted = Vec::new();
+    for (number, line) in lines {
+        let Some(value) = line.strip_prefix("#EXT-X-DEFINE:") else {
+            let line = if line.starts_with('#') {
+                variables.substitute_quoted(line)
+            } else {
+                variables.substitute(line)
+            }
+            .map_err(|error| undefined_variable(number, error))?;
+            substituted.push((number, line));
+            continue;
+        };
+        let invalid = |error| ParsePlaylistError::from_tag(number, TAG, error);
+        let define = Define::from_str(value).map_err(invalid)?;
+        match &define {
+            Define::Import(_) => return Err(invalid(ParseTagError::invalid(value))),
+            Define::QueryParam(name) => variables.keep(name),
+            Define::Value { .. } => {
+                variables
+                    .define(&define, &BTreeMap::new(), None)
+                    .map_err(|error| match error {
+                        define::VariableError::Redefined(_) => {
+                            invalid(ParseTagError::invalid(value))
+                        }
+                        error => undefined_variable(number, error),
+                    })?
+            }
+        }
+        definitions.push(define);
+    }
+    Ok(substituted)
+}
+
+// Attribute list of a multivariant playlist tag. Getters return None for
+// absent attributes and fail for ones of the wrong type.
+struct Attributes<'a> {
+    line: usize,
+    tag: &'a str,
+    list: Vec<(String, AttributeValue)>,
+    raw: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Attributes<'a> {
+    fn parse(line: usize, tag: &'a str, value: &'a str) -> Result<Self, ParsePlaylistError> {
+        let raw = tokenize_attribute_list(value)
+            .map_err(|error| ParsePlaylistError::from_tag(line, tag, error.into()))?;
+        let list = raw
+            .iter()
+            .map(|(name, value)| (name.to_string(), AttributeValue::parse(value)))
+            .collect();
+        Ok(Attributes {
+            line,
+            tag,
+            list,
+            raw,
+        })
+    }
+
+    // The attributes not named in `known`, as written
+    fn extra(&self, known: &[&str]) -> BTreeMap<String, String> {
+        self.raw
+            .iter()
+            .filter(|(name, _)| !known.contains(name))
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn get(&self, name: &str) -> Option<&AttributeValue> {
+        self.list
+            .iter()
+            .find(|(existing, _)| existing == name)
+            .map(|(_, value)| value)
+    }
+
+    fn missing(&self, field: &'static str) -> ParsePlaylistError {
+        ParsePlaylistError::MissingField {
+            line: Some(self.line),
+            tag: self.tag.to_string(),
+            field,
+        }
+    }
+
+    // The attribute `name` is present but has the wrong type or value
+    fn invalid(&self, name: &str) -> ParsePlaylistError {
+        let value = self.get(name).map(ToString::to_string).unwrap_or_default();
+        let error = ParseAttributeError::InvalidValue {
+            attribute: name.to_string(),
+            value,
+        };
+        ParsePlaylistError::from_tag(self.line, self.tag, error.into())
+    }
+
+    fn required<T>(&self, value: Option<T>, field: &'static str) -> Result<T, ParsePlaylistError> {
+        value.ok_or_else(|| self.missing(field))
+    }
+
+    fn typed<T>(
+        &self,
+        name: &str,
+        convert: impl Fn(&AttributeValue) -> Option<T>,
+    ) -> Result<Option<T>, ParsePlaylistError> {
+        self.get(name)
+            .map(|value| convert(value).ok_or_else(|| self.invalid(name)))
+            .transpose()
+    }
+
+    fn quoted(&self, name: &str) -> Result<Option<String>, ParsePlaylistError> {
+        self.typed(name, |value| match value {
+            AttributeValue::QuotedString(text) => Some(text.clone()),
+            _ => None,
+        })
+    }
+
+    fn enumerated(&self, name: &str) -> Result<Option<String>, ParsePlaylistError> {
+        self.typed(name, |value| match value {
+            AttributeValue::EnumeratedString(text) => Some(text.clone()),
+            _ => None,
+        })
+    }
+
+    fn integer(&self, name: &str) -> Result<Option<u64>, ParsePlaylistError> {
+        self.typed(name, |value| match value {
+            AttributeValue::Integer(integer) => Some(*integer),
+            _ => None,
+        })
+    }
+
+    fn float(&self, name: &str) -> Result<Option<f64>, ParsePlaylistError> {
+        self.typed(name, AttributeValue::as_f64)
+    }
+
+    fn resolution(&self, name: &str) -> Result<Option<(u32, u32)>, ParsePlaylistError> {
+        self.typed(name, |value| match value {
+            AttributeValue::Resolution(width, height) => Some((*width, *height)),
+            _ => None,
+        })
+    }
+
+    fn yes(&self, name: &str) -> Result<bool, ParsePlaylistError> {
+        let value = self.typed(name, |value| match value.as_str() {
+            Some("YES") => Some(true),
+            Some("NO") => Some(false),
+            _ => None,
+        })?;
+        Ok(value.unwrap_or(false))
+    }
+}
@@ -0,0 +1,120 @@
+use alloc::borrow::Cow;
+use core::{error::Error, fmt};
+
+use crate::attribute::split_attribute_list;
+use crate::prelude::*;
+
+/// What to do with numeric values no real playlist carries: NaN, infinity,
+/// negative numbers where the spec only allows unsigned ones, and integers
+/// too large for the model.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NumericPolicy {
+    /// Fail with [`crate::ParsePlaylistError::InvalidNumber`].
+    #[default]
+    Reject,
+    /// Replace NaN and negative numbers with 0, and infinite or overflowing
+    /// ones with the largest supported integer.
+    Clamp,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumericError {
+    NotANumber,
+    Infinite,
+    Negative,
+    Overflow,
+}
+
+impl fmt::Display for NumericError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            NumericError::NotANumber => "number is NaN",
+            NumericError::Infinite => "number is infinite",
+            NumericError::Negative => "number is negative",
+            NumericError::Overflow => "number is too large",
+        })
+    }
+}
+
+impl Error for NumericError {}
+
+// Largest decimal-integer the spec allows, 2^64-1
+const MAX_INTEGER: u64 = u64::MAX;
+
+/// Checks the numeric values of a tag: its bare value, e.g. the duration of
+/// EXTINF, and the values of its attribute list. Quoted strings and other
+/// non-numeric values are left alone. With [`NumericPolicy::Clamp`]
+/// pathological values are replaced in the returned text, and the flag
+/// tells whether any were.
+pub(crate) fn sanitize(
+    value: &str,
+    policy: NumericPolicy,
+) -> Result<(Cow<'_, str>, bool), NumericError> {
+    let mut clamped: Option<String> = None;
+    let mut copied = 0;
+    for (index, (start, token)) in split_attribute_list(value).enumerate() {
+        // Only the first token of a bare value is a number; the rest of
+        // EXTINF is a free-form title
+        let (offset, number) = match token.split_once('=') {
+            Some((name, number)) => (name.len() + 1, number),
+            None if index == 0 => (0, token),
+            None => continue,
+        };
+        let Err(error) = check(number) else {
+            continue;
+        };
+        if policy == NumericPolicy::Reject {
+            return Err(error);
+        }
+        let replacement = match error {
+            NumericError::NotANumber | NumericError::Negative => "0".to_string(),
+            NumericError::Infinite | NumericError::Overflow => MAX_INTEGER.to_string(),
+        };
+        let output = clamped.get_or_insert_with(String::new);
+        output.push_str(&value[copied..start + offset]);
+        output.push_str(&replacement);
+        copied = start + offset + number.len();
+    }
+    Ok(match clamped {
+        Some(mut output) => {
+            output.push_str(&value[copied..]);
+            (Cow::Owned(output), true)
+        }
+        None => (Cow::Borrowed(value), false),
+    })
+}
+
+fn check(value: &str) -> Result<(), NumericError> {
+    let unsigned = value.strip_prefix(['-', '+']).unwrap_or(value);
+    let lowercase = unsigned.to_ascii_lowercase();
+    if lowercase == "nan" {
+        return Err(NumericError::NotANumber);
+    }
+    if lowercase == "inf" || lowercase == "infinity" {
+        return Err(if value.starts_with('-') {
+            NumericError::Negative
+        } else {
+            NumericError::Infinite
+        });
+    }
+    let is_number = !unsigned.is_empty()
+        && unsigned
+            .chars()
+            .all(|character| character.is_ascii_digit() || character == '.');
+    if !is_number {
+        return Ok(());
+    }
+    if value.starts_with('-')
+        && unsigned
+            .chars()
+            .any(|character| matches!(character, '1'..='9'))
+    {
+        return Err(NumericError::Negative);
+    }
+    let integer = unsigned.split('.').next().unwrap_or_default();
+    // Only digits are left, so parsing fails on overflow alone
+    if !integer.trim_start_matches('0').is_empty() && integer.parse::<u64>().is_err() {
+        return Err(NumericError::Overflow);
+    }
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use alloc::collections::BTreeMap;
+
+use crate::prelude::*;
+use crate::{NumericPolicy, TagRegistry};
+
+/// Settings for [`crate::read_playlist_with_options`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    pub duplicate_tags: DuplicateTagPolicy,
+    pub numbers: NumericPolicy,
+    /// Parsers for application-defined tags. Tags without one are reported
+    /// to [`crate::Hooks::on_unknown_tag`] and kept as [`crate::UnknownTag`]s.
+    pub custom_tags: TagRegistry,
+    pub variables: VariablePolicy,
+    /// Values for EXT-X-DEFINE:IMPORT, i.e. the variables of the
+    /// multivariant playlist, see [`crate::MultivariantPlaylist::variables`].
+    pub imported_variables: BTreeMap<String, String>,
+    /// URL the playlist was loaded from, whose query parameters give
+    /// EXT-X-DEFINE:QUERYPARAM variables their values.
+    pub playlist_url: Option<String>,
+    pub uris: UriPolicy,
+}
+
+impl ParseOptions {
+    /// Options rejecting anything the spec forbids.
+    pub fn strict() -> Self {
+        ParseOptions {
+            duplicate_tags: DuplicateTagPolicy::Error,
+            numbers: NumericPolicy::Reject,
+            custom_tags: TagRegistry::default(),
+            variables: VariablePolicy::default(),
+            imported_variables: BTreeMap::new(),
+            playlist_url: None,
+            uris: UriPolicy::Strict,
+        }
+    }
+}
+
+/// What to do with `{$NAME}` references to EXT-X-DEFINE variables.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VariablePolicy {
+    /// Replace them with the variables' values in URI lines and quoted-string
+    /// attribute values. A reference to a variable without a value fails
+    /// with [`crate::ParsePlaylistError::UndefinedVariable`].
+    #[default]
+    Substitute,
+    /// Leave them as written, e.g. to pass the playlist on unchanged. With
+    /// the `fluent-uri` feature a URI line with a reference isn't a valid
+    /// URI, and fails with [`crate::ParsePlaylistError::InvalidUri`] unless
+    /// [`UriPolicy::Raw`] keeps it.
+    Keep,
+}
+
+/// What to do with a segment URI line that isn't a valid URI, as some
+/// packagers write them with spaces, non-ASCII characters or stray `%`.
+/// URIs are only validated with the `fluent-uri` feature; without it every
+/// policy keeps them as written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UriPolicy {
+    /// Fail with [`crate::ParsePlaylistError::InvalidUri`].
+    #[default]
+    Strict,
+    /// Percent-encode the characters a URI can't hold and render the
+    /// result. Fails if it still isn't a valid URI.
+    Repair,
+    /// Keep the line as written, see [`crate::MediaSegment::raw_uri`], so
+    /// the playlist renders unchanged, e.g. on a proxy passing it on.
+    Raw,
+}
+
+/// What to do when a tag that may appear only once in a playlist, such as
+/// EXT-X-TARGETDURATION or EXT-X-SERVER-CONTROL, is repeated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicateTagPolicy {
+    /// Fail with [`crate::ParsePlaylistError::DuplicateTag`].
+    Error,
+    /// Keep the first occurrence and ignore the rest.
+    FirstWins,
+    /// Let every occurrence replace the previous one.
+    #[default]
+    LastWins,
+}
@@ -0,0 +1,101 @@
+use core::str;
+
+use crate::prelude::*;
+use crate::{
+    Hooks, MediaPlaylist, MediaPlaylistState, MediaSegment, ParseOptions, ParsePlaylistError,
+};
+
+/// Parses a media playlist fed in chunks of bytes as they arrive, e.g. the
+/// body of a blocking reload read off the network, instead of waiting for
+/// the whole response.
+///
+/// Every line is parsed as soon as its line terminator arrives: tags and
+/// completed segments are reported to the [`Hooks`] passed to
+/// [`PlaylistParser::feed`], and [`PlaylistParser::segments`] lists the
+/// segments parsed so far. Once a chunk failed to parse the parser shouldn't
+/// be fed any further.
+#[derive(Default)]
+pub struct PlaylistParser {
+    options: ParseOptions,
+    // Bytes of the line whose terminator hasn't arrived yet
+    pending: Vec<u8>,
+    // Lines parsed so far
+    lines: usize,
+    // None until the header line has arrived
+    state: Option<MediaPlaylistState>,
+}
+
+impl PlaylistParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_options(options: ParseOptions) -> Self {
+        PlaylistParser {
+            options,
+            ..Self::default()
+        }
+    }
+
+    /// Parses the lines `chunk` completes. A line may be split across any
+    /// number of chunks, even within a UTF-8 sequence.
+    pub fn feed<H: Hooks + ?Sized>(
+        &mut self,
+        chunk: &[u8],
+        hooks: &mut H,
+    ) -> Result<(), ParsePlaylistError> {
+        self.pending.extend_from_slice(chunk);
+        let mut start = 0;
+        while let Some(length) = self.pending[start..].iter().position(|&byte| byte == b'\n') {
+            let end = start + length + 1;
+            self.lines += 1;
+            let line =
+                str::from_utf8(&self.pending[start..end]).map_err(|_| self.invalid_utf8())?;
+            read_line(&mut self.state, line, &self.options, hooks)?;
+            start = end;
+        }
+        self.pending.drain(..start);
+        Ok(())
+    }
+
+    /// Segments parsed so far, oldest first.
+    pub fn segments(&self) -> &[MediaSegment] {
+        self.state.as_ref().map_or(&[], |state| state.segments())
+    }
+
+    /// Parses the last line, which may lack a line terminator, and returns
+    /// the playlist.
+    pub fn finish<H: Hooks + ?Sized>(
+        mut self,
+        hooks: &mut H,
+    ) -> Result<MediaPlaylist, ParsePlaylistError> {
+        if !self.pending.is_empty() {
+            self.lines += 1;
+            let line = str::from_utf8(&self.pending).map_err(|_| self.invalid_utf8())?;
+            read_line(&mut self.state, line, &self.options, hooks)?;
+        }
+        self.state
+            .ok_or(ParsePlaylistError::MissingHeader)?
+            .finish()
+    }
+
+    fn invalid_utf8(&self) -> ParsePlaylistError {
+        ParsePlaylistError::InvalidUtf8 { line: self.lines }
+    }
+}
+
+// The first line starts the playlist, the others continue it
+fn read_line<H: Hooks + ?Sized>(
+    state: &mut Option<MediaPlaylistState>,
+    line: &str,
+    options: &ParseOptions,
+    hooks: &mut H,
+) -> Result<(), ParsePlaylistError> {
+    match state {
+        Some(state) => state.read_line(line, options, hooks),
+        None => {
+            *state = Some(MediaPlaylistState::new(line)?);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,8 @@
+// What the std prelude brings into scope, for builds without std
+pub(crate) use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
@@ -0,0 +1,263 @@
+use std::time::{Duration, SystemTime};
+
+use crate::{
+    apply_directives, ClientSession, MediaPlaylist, SessionError, SkipDirective, UpdateKind,
+};
+
+/// A playlist reload the client should issue. Without `msn` it is a plain,
+/// non-blocking request for the whole playlist.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReloadRequest {
+    pub uri: String,
+    pub msn: Option<u64>,
+    pub part: Option<u32>,
+    /// Ask for a delta update with `_HLS_skip=YES`.
+    pub skip: bool,
+}
+
+impl ReloadRequest {
+    /// The request URL with its delivery directives applied.
+    pub fn url(&self) -> String {
+        apply_directives(
+            &self.uri,
+            self.msn,
+            self.part,
+            self.skip.then_some(SkipDirective::Yes),
+        )
+    }
+}
+
+/// When the [`ReloadCoordinator`] gives up on a request.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReloadPolicy {
+    /// A blocking request is abandoned and re-issued after this many target
+    /// durations. Servers answer a request they can't satisfy within three
+    /// target durations with 503, so waiting longer than that is pointless.
+    pub timeout_target_durations: f32,
+    /// Timeout for requests made before the target duration is known.
+    pub initial_timeout: Duration,
+    /// Consecutive failures after which the next request asks for the full
+    /// playlist, without blocking or skipping.
+    pub max_failures: u32,
+}
+
+impl ReloadPolicy {
+    // How long a request for the playlist following `playlist` may take
+    pub(crate) fn timeout(&self, playlist: Option<&MediaPlaylist>) -> Duration {
+        match playlist {
+            Some(playlist) => {
+                // Negative and NaN multipliers time out at once, and those
+                // too large to represent never do
+                let seconds =
+                    playlist.target_duration as f32 * self.timeout_target_durations.max(0.0);
+                Duration::try_from_secs_f32(seconds).unwrap_or(Duration::MAX)
+            }
+            None => self.initial_timeout,
+        }
+    }
+}
+
+impl Default for ReloadPolicy {
+    fn default() -> Self {
+        ReloadPolicy {
+            timeout_target_durations: 3.0,
+            initial_timeout: Duration::from_secs(10),
+            max_failures: 3,
+        }
+    }
+}
+
+/// Schedules the blocking reloads of every rendition a client plays at once,
+/// e.g. video, audio and subtitles. Each rendition has at most one request in
+/// flight, and all of them wait for the same (MSN, part) so the playlists
+/// advance in lockstep. Rendition reports in one rendition's playlist move
+/// the others' targets forward without an extra round trip.
+///
+/// Renditions are identified by their URI as it appears in the
+/// EXT-X-RENDITION-REPORT tags of the other renditions.
+///
+/// Requests that outlive the [`ReloadPolicy`] timeout are abandoned and
+/// issued again by [`ReloadCoordinator::requests`], and repeated failures
+/// fall back to a full reload.
+#[derive(Clone, Default)]
+pub struct ReloadCoordinator {
+    policy: ReloadPolicy,
+    renditions: Vec<Rendition>,
+}
+
+#[derive(Clone)]
+struct Rendition {
+    uri: String,
+    session: ClientSession,
+    // When the outstanding request was issued
+    sent_at: Option<SystemTime>,
+    failures: u32,
+    // Newest (MSN, part index) another rendition reported for this one
+    reported: Option<(u64, Option<u32>)>,
+}
+
+impl ReloadCoordinator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_policy(policy: ReloadPolicy) -> Self {
+        ReloadCoordinator {
+            policy,
+            renditions: Vec::new(),
+        }
+    }
+
+    pub fn add_rendition(&mut self, uri: impl Into<String>) {
+        let uri = uri.into();
+        if self.rendition(&uri).is_none() {
+            self.renditions.push(Rendition {
+                uri,
+                session: ClientSession::new(),
+                sent_at: None,
+                failures: 0,
+                reported: None,
+            });
+        }
+    }
+
+    pub fn remove_rendition(&mut self, uri: &str) {
+        self.renditions.retain(|rendition| rendition.uri != uri);
+    }
+
+    pub fn session(&self, uri: &str) -> Option<&ClientSession> {
+        self.rendition(uri).map(|rendition| &rendition.session)
+    }
+
+    /// The newest LAST-MSN and LAST-PART another rendition reported for
+    /// rendition `uri`, if any did.
+    pub fn reported(&self, uri: &str) -> Option<(u64, Option<u32>)> {
+        self.rendition(uri)?.reported
+    }
+
+    /// The reloads to issue at `now`, one for each rendition without a
+    /// request in flight. Requests that timed out count as failed and are
+    /// issued again. Those renditions are marked in flight until their
+    /// response or failure is reported.
+    pub fn requests(&mut self, now: SystemTime) -> Vec<ReloadRequest> {
+        for rendition in &mut self.renditions {
+            let timeout = rendition.timeout(&self.policy);
+            let timed_out = rendition.sent_at.is_some_and(|sent_at| {
+                now.duration_since(sent_at)
+                    .is_ok_and(|waited| waited >= timeout)
+            });
+            if timed_out {
+                rendition.sent_at = None;
+                rendition.failures += 1;
+            }
+        }
+
+        let target = self.target();
+        let max_failures = self.policy.max_failures;
+        self.renditions
+            .iter_mut()
+            .filter(|rendition| rendition.sent_at.is_none())
+            .map(|rendition| {
+                rendition.sent_at = Some(now);
+                let escalate = rendition.failures >= max_failures;
+                let (msn, part) = match (rendition.session.playlist(), target) {
+                    (Some(_), Some((msn, part))) if !escalate => (Some(msn), part),
+                    _ => (None, None),
+                };
+                ReloadRequest {
+                    uri: rendition.uri.clone(),
+                    msn,
+                    part,
+                    skip: !escalate && rendition.session.wants_delta(),
+                }
+            })
+            .collect()
+    }
+
+    /// Applies the response to the outstanding request of rendition `uri`
+    /// and takes note of the rendition reports it carries.
+    pub fn receive(
+        &mut self,
+        uri: &str,
+        response: MediaPlaylist,
+    ) -> Result<UpdateKind, SessionError> {
+        for report in &response.rendition_reports {
+            if let Some(rendition) = self.rendition_mut(&report.uri) {
+                // A part index past u32::MAX can't be asked for anyway
+                let part = report
+                    .last_part
+                    .map(|part| u32::try_from(part).unwrap_or(u32::MAX));
+                let edge = (report.last_msn, part);
+                if rendition.reported.is_none_or(|reported| reported < edge) {
+                    rendition.reported = Some(edge);
+                }
+            }
+        }
+        let rendition = self
+            .rendition_mut(uri)
+            .ok_or_else(|| SessionError::UnknownRendition {
+                uri: uri.to_string(),
+            })?;
+        rendition.sent_at = None;
+        let result = rendition.session.receive(response);
+        if result.is_ok() {
+            rendition.failures = 0;
+        } else {
+            rendition.failures += 1;
+        }
+        result
+    }
+
+    /// Records that the outstanding request of rendition `uri` failed, so it
+    /// is scheduled again.
+    pub fn failed(&mut self, uri: &str) {
+        if let Some(rendition) = self.rendition_mut(uri) {
+            rendition.sent_at = None;
+            rendition.failures += 1;
+        }
+    }
+
+    // The (MSN, part) every rendition should block on: the one after the
+    // newest media known to be published in any rendition
+    fn target(&self) -> Option<(u64, Option<u32>)> {
+        self.renditions
+            .iter()
+            .flat_map(|rendition| {
+                let own = rendition
+                    .session
+                    .playlist()
+                    .map(MediaPlaylist::last_position);
+                own.into_iter().chain(rendition.reported)
+            })
+            .max()
+            .map(next_position)
+    }
+
+    fn rendition(&self, uri: &str) -> Option<&Rendition> {
+        self.renditions
+            .iter()
+            .find(|rendition| rendition.uri == uri)
+    }
+
+    fn rendition_mut(&mut self, uri: &str) -> Option<&mut Rendition> {
+        self.renditions
+            .iter_mut()
+            .find(|rendition| rendition.uri == uri)
+    }
+}
+
+impl Rendition {
+    fn timeout(&self, policy: &ReloadPolicy) -> Duration {
+        policy.timeout(self.session.playlist())
+    }
+}
+
+// The (MSN, part) to block on for the media after `(msn, part)`
+pub(crate) fn next_position((msn, part): (u64, Option<u32>)) -> (u64, Option<u32>) {
+    match part {
+        // A part index past the end of the segment stands for the first
+        // part of the next one
+        Some(part) => (msn, Some(part + 1)),
+        None => (msn + 1, None),
+    }
+}
@@ -0,0 +1,106 @@
+use core::time::Duration;
+
+use crate::prelude::*;
+use crate::{ContainerFormat, MediaPlaylist, PartInf, PreloadHintType};
+
+/// A change made by [`MediaPlaylist::repair`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Repair {
+    /// A quoted-string attribute was read without its quotes, which
+    /// rendering adds. `value` is the attribute's value.
+    QuotedAttribute {
+        tag: &'static str,
+        attribute: &'static str,
+        value: String,
+    },
+    /// EXT-X-PART-INF was missing although the playlist lists parts.
+    DerivedPartInf { part_target: Duration },
+    /// The preload hint pointed at a part that was already published.
+    RemovedStalePreloadHint { uri: String },
+    /// EXT-X-VERSION was lower than the features in use require.
+    RaisedVersion { from: u32, to: u32 },
+}
+
+impl MediaPlaylist {
+    /// Fixes mechanically fixable mistakes, as found in third-party feeds
+    /// passing through a proxy, and reports every change made.
+    pub fn repair(&mut self) -> Vec<Repair> {
+        let mut repairs = Vec::new();
+
+        for (tag, uri) in core::mem::take(&mut self.unquoted_uris) {
+            repairs.push(Repair::QuotedAttribute {
+                tag,
+                attribute: "URI",
+                value: uri,
+            });
+        }
+
+        if self.part_inf.is_none() {
+            let part_target = self
+                .parts_with_position()
+                .map(|(_, _, part)| part.part_duration)
+                .max();
+            if let Some(part_target) = part_target {
+                self.part_inf = Some(PartInf::new(part_target));
+                repairs.push(Repair::DerivedPartInf { part_target });
+            }
+        }
+
+        let stale = self.preload_hints.iter().position(|hint| {
+            hint.r#type == PreloadHintType::Part
+                && self
+                    .parts_with_position()
+                    .any(|(_, _, part)| part.uri == hint.uri)
+        });
+        if let Some(index) = stale {
+            let hint = self.preload_hints.remove(index);
+            repairs.push(Repair::RemovedStalePreloadHint { uri: hint.uri });
+        }
+
+        let required = self.required_version();
+        if self.version < required {
+            repairs.push(Repair::RaisedVersion {
+                from: self.version,
+                to: required,
+            });
+            self.version = required;
+        }
+
+        repairs
+    }
+
+    // Lowest EXT-X-VERSION compatible with the features the playlist uses
+    pub(crate) fn required_version(&self) -> u32 {
+        let mut version = 1;
+        if self
+            .media_segments
+            .iter()
+            .any(|segment| segment.duration.subsec_nanos() != 0)
+        {
+            version = version.max(3);
+        }
+        let has_byterange = self
+            .media_segments
+            .iter()
+            .any(|segment| segment.byterange.is_some());
+        if self.i_frames_only || has_byterange {
+            version = version.max(4);
+        }
+        // fMP4 segments come with EXT-X-MAP, which transport streams don't need
+        let has_map = self.trailing_map.is_some()
+            || self
+                .media_segments
+                .iter()
+                .any(|segment| segment.map.is_some());
+        if has_map || self.container_format() == Some(ContainerFormat::Fmp4) {
+            version = version.max(6);
+        }
+        if let Some(skip) = &self.skip {
+            version = version.max(9);
+            if !skip.recently_removed_dateranges.is_empty() {
+                version = version.max(10);
+            }
+        }
+        version
+    }
+}
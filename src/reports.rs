@@ -0,0 +1,51 @@
+use crate::prelude::*;
+use crate::{uri, MediaPlaylist, RenditionReport, Uri};
+
+impl RenditionReport {
+    /// The EXT-X-RENDITION-REPORT tags an origin serves in `current` for its
+    /// sibling renditions `others`, each given with the URI clients reach it
+    /// at relative to `current`. Renditions that list no media yet are left
+    /// out.
+    ///
+    /// A report never runs ahead of `current` into a newer segment: a
+    /// sibling that already started one is reported at the last part it has
+    /// of the newest segment of `current`, so the playlists served together
+    /// describe the same moment. Siblings without parts are reported with
+    /// LAST-PART 0.
+    pub fn from_playlists(
+        current: &MediaPlaylist,
+        others: &[(Uri, &MediaPlaylist)],
+    ) -> Vec<RenditionReport> {
+        let published = |playlist: &MediaPlaylist| {
+            !playlist.media_segments.is_empty() || !playlist.trailing_parts.is_empty()
+        };
+        let limit = published(current).then(|| current.last_position().0);
+        others
+            .iter()
+            .filter(|(_, playlist)| published(playlist))
+            .map(|(uri, playlist)| {
+                let (last_msn, last_part) = match limit {
+                    Some(limit) if playlist.last_position().0 > limit => {
+                        let part = playlist
+                            .parts_with_position()
+                            .filter(|&(msn, _, _)| msn == limit)
+                            .map(|(_, index, _)| index)
+                            .last();
+                        (limit, part)
+                    }
+                    _ => playlist.last_position(),
+                };
+                RenditionReport::new(uri::as_str(uri), last_msn, last_part.map(u64::from))
+            })
+            .collect()
+    }
+}
+
+impl MediaPlaylist {
+    /// Replaces the rendition reports with fresh ones for `others`, see
+    /// [`RenditionReport::from_playlists`]. Meant to be called right before
+    /// the playlist is served.
+    pub fn refresh_rendition_reports(&mut self, others: &[(Uri, &MediaPlaylist)]) {
+        self.rendition_reports = RenditionReport::from_playlists(self, others);
+    }
+}
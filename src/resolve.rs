@@ -0,0 +1,72 @@
+use crate::prelude::*;
+use crate::{
+    uri::{self, Uri},
+    Key, Map, MediaPlaylist, MediaSegment, PartialSegment, PreloadHint, RenditionReport,
+    SessionData,
+};
+
+fn absolute(base: &Uri, value: &str) -> String {
+    uri::resolve(uri::as_str(base), value)
+}
+
+impl MediaSegment {
+    /// The URI of the segment resolved against `base`, the URL of the
+    /// playlist.
+    pub fn absolute_uri(&self, base: &Uri) -> String {
+        absolute(base, uri::as_str(&self.uri))
+    }
+}
+
+impl PartialSegment {
+    /// The URI of the part resolved against `base`.
+    pub fn absolute_uri(&self, base: &Uri) -> String {
+        absolute(base, &self.uri)
+    }
+}
+
+impl PreloadHint {
+    /// The URI of the hinted resource resolved against `base`.
+    pub fn absolute_uri(&self, base: &Uri) -> String {
+        absolute(base, &self.uri)
+    }
+}
+
+impl Map {
+    /// The URI of the init segment resolved against `base`.
+    pub fn absolute_uri(&self, base: &Uri) -> String {
+        absolute(base, &self.uri)
+    }
+}
+
+impl Key {
+    /// The URI of the key resolved against `base`, if the key has one.
+    pub fn absolute_uri(&self, base: &Uri) -> Option<String> {
+        self.uri.as_deref().map(|key_uri| absolute(base, key_uri))
+    }
+}
+
+impl SessionData {
+    /// The URI of the session data document resolved against `base`, the
+    /// URL of the multivariant playlist, if the data isn't inline.
+    pub fn absolute_uri(&self, base: &Uri) -> Option<String> {
+        self.uri().map(|data_uri| absolute(base, data_uri))
+    }
+}
+
+impl RenditionReport {
+    /// The URI of the rendition's playlist resolved against `base`.
+    pub fn absolute_uri(&self, base: &Uri) -> String {
+        absolute(base, &self.uri)
+    }
+}
+
+impl MediaPlaylist {
+    /// Rewrites every URI of the playlist as an absolute one, resolved
+    /// against `base`, the URL the playlist was loaded from: segments,
+    /// parts, init segments, keys, the preload hint and rendition reports.
+    /// The playlist can then be served from anywhere, e.g. by a proxy.
+    pub fn resolve_uris(&mut self, base: &Uri) {
+        let base = uri::as_str(base);
+        self.map_uris(|_, value| uri::resolve(base, value));
+    }
+}
@@ -0,0 +1,85 @@
+use crate::prelude::*;
+use crate::{uri, Key, MediaPlaylist, MediaSegment};
+
+/// What a URI handed to [`MediaPlaylist::map_uris`] addresses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UriKind {
+    /// A media segment, from the line after EXTINF.
+    Segment,
+    /// EXT-X-PART.
+    Part,
+    /// EXT-X-PRELOAD-HINT.
+    PreloadHint,
+    /// EXT-X-KEY.
+    Key,
+    /// EXT-X-MAP.
+    Map,
+    /// EXT-X-RENDITION-REPORT.
+    RenditionReport,
+}
+
+impl MediaPlaylist {
+    /// Replaces every URI of the playlist with what `f` returns for it, in
+    /// one pass over the playlist: segments, parts, keys, init segments,
+    /// preload hints and rendition reports, including those of the segment
+    /// still being published. `f` gets the kind of the URI and the URI as
+    /// written, e.g. for a CDN to add a signed token to the
+    /// query or swap the hostname before the playlist is served.
+    ///
+    /// A segment URI `f` turns into an invalid one is left unchanged, unless
+    /// the line was kept as written by [`crate::UriPolicy::Raw`]: then the
+    /// new line is kept the same way, see [`MediaSegment::raw_uri`].
+    pub fn map_uris<F>(&mut self, mut f: F)
+    where
+        F: FnMut(UriKind, &str) -> String,
+    {
+        self.media_segments
+            .update_where(|_| true, |segment| map_segment(segment, &mut f));
+        for part in &mut self.trailing_parts {
+            part.uri = f(UriKind::Part, &part.uri);
+        }
+        for key in &mut self.trailing_keys {
+            map_key(key, &mut f);
+        }
+        if let Some(map) = &mut self.trailing_map {
+            map.uri = f(UriKind::Map, &map.uri);
+        }
+        for hint in &mut self.preload_hints {
+            hint.uri = f(UriKind::PreloadHint, &hint.uri);
+        }
+        for report in &mut self.rendition_reports {
+            report.uri = f(UriKind::RenditionReport, &report.uri);
+        }
+    }
+}
+
+fn map_segment<F: FnMut(UriKind, &str) -> String>(segment: &mut MediaSegment, f: &mut F) {
+    let mapped = f(UriKind::Segment, segment.written_uri());
+    match uri::parse(&mapped) {
+        Ok(uri) => {
+            segment.uri = uri;
+            segment.raw_uri = None;
+        }
+        // Kept as written, as UriPolicy::Raw parsed the line
+        Err(_) if segment.raw_uri.is_some() => {
+            segment.uri = uri::parse(&uri::repair(&mapped)).unwrap_or_default();
+            segment.raw_uri = Some(mapped);
+        }
+        Err(_) => {}
+    }
+    for part in &mut segment.partial_segments {
+        part.uri = f(UriKind::Part, &part.uri);
+    }
+    for key in &mut segment.keys {
+        map_key(key, f);
+    }
+    if let Some(map) = &mut segment.map {
+        map.uri = f(UriKind::Map, &map.uri);
+    }
+}
+
+fn map_key<F: FnMut(UriKind, &str) -> String>(key: &mut Key, f: &mut F) {
+    if let Some(key_uri) = &mut key.uri {
+        *key_uri = f(UriKind::Key, key_uri);
+    }
+}
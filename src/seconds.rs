@@ -0,0 +1,69 @@
+use core::{fmt, str::FromStr, time::Duration};
+
+use crate::InvalidValue;
+
+// Durations are decimal-floating-point numbers of seconds. Parsing keeps
+// every digit down to the nanosecond instead of rounding through a float,
+// so a duration is written back exactly as it was read.
+pub(crate) fn parse(s: &str) -> Result<Duration, InvalidValue> {
+    let (integer, fraction) = s.split_once('.').unwrap_or((s, ""));
+    let is_digits = |text: &str| text.bytes().all(|byte| byte.is_ascii_digit());
+    if (integer.is_empty() && fraction.is_empty()) || !is_digits(integer) || !is_digits(fraction) {
+        // Not in the spec's format, e.g. an exponent; take what a float can
+        return f64::from_str(s)
+            .ok()
+            .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+            .ok_or(InvalidValue);
+    }
+    let seconds = match integer {
+        "" => 0,
+        integer => integer.parse().map_err(|_| InvalidValue)?,
+    };
+    // Digits past the nanosecond are dropped
+    let nanos = fraction
+        .bytes()
+        .chain(core::iter::repeat(b'0'))
+        .take(9)
+        .fold(0, |nanos, digit| nanos * 10 + u32::from(digit - b'0'));
+    Ok(Duration::new(seconds, nanos))
+}
+
+// Renders the shortest decimal that parses back to the same duration: whole
+// seconds without a fraction, e.g. 4, and otherwise no trailing zeros, e.g.
+// 0.33334
+pub(crate) fn display(value: &Duration) -> impl fmt::Display + '_ {
+    struct Rendered(Duration);
+
+    impl fmt::Display for Rendered {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.0.as_secs())?;
+            let nanos = self.0.subsec_nanos();
+            if nanos == 0 {
+                return Ok(());
+            }
+            // Nine digits without the trailing zeros
+            let mut fraction = nanos;
+            let mut digits = 9;
+            while fraction.is_multiple_of(10) {
+                fraction /= 10;
+                digits -= 1;
+            }
+            write!(f, ".{:0digits$}", fraction)
+        }
+    }
+
+    Rendered(*value)
+}
+
+// Rounded to the nearest second, as EXT-X-TARGETDURATION bounds it
+pub(crate) fn round(value: &Duration) -> u64 {
+    value.as_secs() + u64::from(value.subsec_nanos() >= 500_000_000)
+}
+
+// Total of durations read from a playlist, which can be arbitrarily large,
+// saturating instead of overflowing
+pub(crate) fn sum(durations: impl IntoIterator<Item = Duration>) -> Duration {
+    durations
+        .into_iter()
+        .fold(Duration::ZERO, Duration::saturating_add)
+}
@@ -0,0 +1,203 @@
+use core::time::Duration;
+
+use crate::prelude::*;
+use crate::{
+    uri::{self, Uri},
+    ByteRange, DateTime, LivePlaylistWindow, MediaPlaylist, PartialSegment, PreloadHint,
+    PreloadHintType,
+};
+
+/// A CMAF chunk the packager finished writing to the segment file, published
+/// as a part of the segment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CmafChunk {
+    pub duration: Duration,
+    /// Where the chunk lies in the segment file. Without an offset the chunk
+    /// starts right after the previous one.
+    pub byterange: ByteRange,
+    /// Whether the chunk starts with a keyframe, which players joining
+    /// mid-segment must start decoding at.
+    pub independent: bool,
+}
+
+impl CmafChunk {
+    pub fn new(duration: Duration, byterange: ByteRange, independent: bool) -> Self {
+        CmafChunk {
+            duration,
+            byterange,
+            independent,
+        }
+    }
+}
+
+/// Where a [`CmafSegmenter`] publishes parts, segments and preload hints,
+/// e.g. a [`LivePlaylistWindow`] or a [`LiveWriter`](crate::LiveWriter).
+pub trait PartPublisher {
+    /// The playlist as published so far.
+    fn playlist(&self) -> &MediaPlaylist;
+
+    /// Appends a part to the segment being written.
+    fn publish_part(&mut self, part: PartialSegment);
+
+    /// Completes the segment being written from the parts published since
+    /// the previous one.
+    fn publish_segment(
+        &mut self,
+        duration: Duration,
+        uri: Uri,
+        program_date_time: Option<DateTime>,
+    );
+
+    /// Replaces the preload hint of the same TYPE.
+    fn publish_preload_hint(&mut self, preload_hint: PreloadHint);
+}
+
+impl PartPublisher for LivePlaylistWindow {
+    fn playlist(&self) -> &MediaPlaylist {
+        LivePlaylistWindow::playlist(self)
+    }
+
+    fn publish_part(&mut self, part: PartialSegment) {
+        self.push_part(part);
+    }
+
+    fn publish_segment(
+        &mut self,
+        duration: Duration,
+        uri: Uri,
+        program_date_time: Option<DateTime>,
+    ) {
+        self.complete_segment(duration, uri, program_date_time);
+    }
+
+    fn publish_preload_hint(&mut self, preload_hint: PreloadHint) {
+        self.add_preload_hint(preload_hint);
+    }
+}
+
+#[cfg(feature = "std")]
+impl PartPublisher for crate::LiveWriter {
+    fn playlist(&self) -> &MediaPlaylist {
+        crate::LiveWriter::playlist(self)
+    }
+
+    fn publish_part(&mut self, part: PartialSegment) {
+        self.push_part(part);
+    }
+
+    fn publish_segment(
+        &mut self,
+        duration: Duration,
+        uri: Uri,
+        program_date_time: Option<DateTime>,
+    ) {
+        self.complete_segment(duration, uri, program_date_time);
+    }
+
+    fn publish_preload_hint(&mut self, preload_hint: PreloadHint) {
+        self.add_preload_hint(preload_hint);
+    }
+}
+
+type SegmentNamer = dyn Fn(u64) -> Uri + Send + Sync;
+
+/// Turns the chunk boundaries of a CMAF packager into playlist updates.
+///
+/// Every chunk becomes an EXT-X-PART addressing its byte range of the
+/// segment file being written, and the preload hint moves on to the bytes
+/// after it. Completing a segment rolls its parts up into an EXTINF of
+/// their total duration and hints at the first part of the next segment
+/// file. Segment files are named by their MSN.
+pub struct CmafSegmenter<P> {
+    publisher: P,
+    segment_uri: Box<SegmentNamer>,
+    // Where the next chunk starts in the segment file, unless it says
+    next_offset: u64,
+    // Duration of the chunks published since the previous segment
+    segment_duration: Duration,
+    chunks: usize,
+}
+
+impl<P: PartPublisher> CmafSegmenter<P> {
+    /// A segmenter publishing to `publisher` the segment file
+    /// `segment_uri(msn)` of every segment.
+    pub fn new<F>(publisher: P, segment_uri: F) -> Self
+    where
+        F: Fn(u64) -> Uri + Send + Sync + 'static,
+    {
+        CmafSegmenter {
+            publisher,
+            segment_uri: Box::new(segment_uri),
+            next_offset: 0,
+            segment_duration: Duration::ZERO,
+            chunks: 0,
+        }
+    }
+
+    pub fn publisher(&self) -> &P {
+        &self.publisher
+    }
+
+    pub fn publisher_mut(&mut self) -> &mut P {
+        &mut self.publisher
+    }
+
+    pub fn into_publisher(self) -> P {
+        self.publisher
+    }
+
+    pub fn playlist(&self) -> &MediaPlaylist {
+        self.publisher.playlist()
+    }
+
+    /// MSN of the segment being written, which chunks belong to.
+    pub fn next_msn(&self) -> u64 {
+        let playlist = self.publisher.playlist();
+        playlist.first_listed_msn() + playlist.media_segments.len() as u64
+    }
+
+    /// Publishes `chunk` as the next part of the segment being written and
+    /// returns its MSN and part index.
+    pub fn push_chunk(&mut self, chunk: CmafChunk) -> (u64, u32) {
+        let msn = self.next_msn();
+        let index = self.publisher.playlist().trailing_parts.len() as u32;
+        let segment_uri = uri::as_str(&(self.segment_uri)(msn)).to_string();
+        let offset = chunk.byterange.offset.unwrap_or(self.next_offset);
+        let mut part = PartialSegment::new(chunk.duration, segment_uri.clone());
+        part.independent = chunk.independent.then_some(true);
+        part.byterange = Some(ByteRange::new(chunk.byterange.length, Some(offset)));
+        self.publisher.publish_part(part);
+        self.next_offset = offset.saturating_add(chunk.byterange.length);
+        self.segment_duration += chunk.duration;
+        self.chunks += 1;
+
+        // The next part is the rest of the segment file being written
+        let mut hint = PreloadHint::new(PreloadHintType::Part, segment_uri);
+        hint.byterange_start = Some(self.next_offset);
+        self.publisher.publish_preload_hint(hint);
+        (msn, index)
+    }
+
+    /// Completes the segment being written from the chunks pushed since the
+    /// previous one and returns its MSN, or `None` if no chunk was pushed.
+    pub fn complete_segment(&mut self, program_date_time: Option<DateTime>) -> Option<u64> {
+        if self.chunks == 0 {
+            return None;
+        }
+        let msn = self.next_msn();
+        self.publisher.publish_segment(
+            self.segment_duration,
+            (self.segment_uri)(msn),
+            program_date_time,
+        );
+        self.next_offset = 0;
+        self.segment_duration = Duration::ZERO;
+        self.chunks = 0;
+
+        // The next part starts the next segment file
+        let next_uri = uri::as_str(&(self.segment_uri)(self.next_msn())).to_string();
+        self.publisher
+            .publish_preload_hint(PreloadHint::new(PreloadHintType::Part, next_uri));
+        Some(msn)
+    }
+}
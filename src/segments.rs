@@ -0,0 +1,153 @@
+use alloc::sync::Arc;
+
+use crate::prelude::*;
+use crate::MediaSegment;
+
+// A persistent vector where the im crate is available, a plain one without
+// std
+#[cfg(feature = "std")]
+type Storage = im::Vector<Arc<MediaSegment>>;
+#[cfg(feature = "std")]
+type StorageIter<'a> = im::vector::Iter<'a, Arc<MediaSegment>>;
+#[cfg(not(feature = "std"))]
+type Storage = Vec<Arc<MediaSegment>>;
+#[cfg(not(feature = "std"))]
+type StorageIter<'a> = core::slice::Iter<'a, Arc<MediaSegment>>;
+
+/// Segment storage of a [`crate::MediaPlaylist`]. With the `std` feature,
+/// cloning shares structure with the original, so taking a snapshot of a
+/// live playlist after appending a part or segment doesn't copy the rest of
+/// the window.
+#[derive(Clone, Default)]
+pub struct MediaSegments(Storage);
+
+impl MediaSegments {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&MediaSegment> {
+        self.0.get(index).map(|segment| segment.as_ref())
+    }
+
+    pub fn first(&self) -> Option<&MediaSegment> {
+        self.iter().next()
+    }
+
+    pub fn last(&self) -> Option<&MediaSegment> {
+        self.iter().next_back()
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter(self.0.iter())
+    }
+
+    pub fn push(&mut self, segment: MediaSegment) {
+        #[cfg(feature = "std")]
+        self.0.push_back(Arc::new(segment));
+        #[cfg(not(feature = "std"))]
+        self.0.push(Arc::new(segment));
+    }
+
+    // Applies `f` to the segments matching `filter`, leaving the others
+    // shared with any snapshots
+    pub(crate) fn update_where(
+        &mut self,
+        filter: impl Fn(&MediaSegment) -> bool,
+        mut f: impl FnMut(&mut MediaSegment),
+    ) {
+        for index in 0..self.0.len() {
+            if filter(&self.0[index]) {
+                f(Arc::make_mut(&mut self.0[index]));
+            }
+        }
+    }
+
+    // Like `update_where`, but only looking at the first `count` segments
+    pub(crate) fn update_first(
+        &mut self,
+        count: usize,
+        filter: impl Fn(&MediaSegment) -> bool,
+        mut f: impl FnMut(&mut MediaSegment),
+    ) {
+        for index in 0..count.min(self.0.len()) {
+            if filter(&self.0[index]) {
+                f(Arc::make_mut(&mut self.0[index]));
+            }
+        }
+    }
+
+    // Segments `start..end`, sharing storage with `self`
+    pub(crate) fn slice(&self, start: usize, end: usize) -> MediaSegments {
+        #[cfg(feature = "std")]
+        return MediaSegments(self.0.clone().slice(start..end));
+        #[cfg(not(feature = "std"))]
+        MediaSegments(self.0[start..end].to_vec())
+    }
+
+    pub(crate) fn append(&mut self, other: MediaSegments) {
+        #[cfg(feature = "std")]
+        self.0.append(other.0);
+        #[cfg(not(feature = "std"))]
+        self.0.extend(other.0);
+    }
+
+    pub fn pop_front(&mut self) -> Option<MediaSegment> {
+        #[cfg(feature = "std")]
+        let front = self.0.pop_front();
+        #[cfg(not(feature = "std"))]
+        let front = (!self.0.is_empty()).then(|| self.0.remove(0));
+        front.map(|segment| Arc::try_unwrap(segment).unwrap_or_else(|shared| (*shared).clone()))
+    }
+}
+
+impl From<Vec<MediaSegment>> for MediaSegments {
+    fn from(segments: Vec<MediaSegment>) -> Self {
+        segments.into_iter().collect()
+    }
+}
+
+impl FromIterator<MediaSegment> for MediaSegments {
+    fn from_iter<I: IntoIterator<Item = MediaSegment>>(iter: I) -> Self {
+        MediaSegments(iter.into_iter().map(Arc::new).collect())
+    }
+}
+
+impl<'a> IntoIterator for &'a MediaSegments {
+    type Item = &'a MediaSegment;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+pub struct Iter<'a>(StorageIter<'a>);
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a MediaSegment;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|segment| segment.as_ref())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for Iter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|segment| segment.as_ref())
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
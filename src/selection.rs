@@ -0,0 +1,98 @@
+use crate::prelude::*;
+use crate::{MediaType, MultivariantPlaylist, Rendition, VariantStream};
+
+impl VariantStream {
+    /// The bit rate the variant needs sustained: AVERAGE-BANDWIDTH, or the
+    /// peak BANDWIDTH when it isn't given.
+    pub fn sustained_bandwidth(&self) -> u64 {
+        self.average_bandwidth.unwrap_or(self.bandwidth)
+    }
+}
+
+impl MultivariantPlaylist {
+    /// Picks the variant to play over a connection of `bandwidth_bps` bits
+    /// per second on a display of at most `resolution`, among those whose
+    /// every CODECS entry `codecs_supported` accepts. Variants without
+    /// CODECS or RESOLUTION aren't filtered out on them.
+    ///
+    /// Of the variants whose sustained bit rate fits, see
+    /// [`VariantStream::sustained_bandwidth`], the one with the highest
+    /// SCORE wins, then the one with the highest BANDWIDTH. When none fits,
+    /// the variant with the lowest BANDWIDTH is picked so playback can
+    /// start. `None` if no variant is playable.
+    pub fn select(
+        &self,
+        bandwidth_bps: u64,
+        resolution: Option<(u32, u32)>,
+        codecs_supported: impl Fn(&str) -> bool,
+    ) -> Option<&VariantStream> {
+        let playable = self.variants.iter().filter(|variant| {
+            let codecs = variant
+                .codecs
+                .as_deref()
+                .is_none_or(|codecs| codecs.split(',').map(str::trim).all(&codecs_supported));
+            let fits_display = match (variant.resolution, resolution) {
+                (Some((width, height)), Some((max_width, max_height))) => {
+                    width <= max_width && height <= max_height
+                }
+                _ => true,
+            };
+            codecs && fits_display
+        });
+        let best = playable
+            .clone()
+            .filter(|variant| variant.sustained_bandwidth() <= bandwidth_bps)
+            .max_by(|a, b| {
+                let score = |variant: &VariantStream| variant.score.unwrap_or(0.0);
+                score(a)
+                    .total_cmp(&score(b))
+                    .then(a.bandwidth.cmp(&b.bandwidth))
+            });
+        best.or_else(|| playable.min_by_key(|variant| variant.bandwidth))
+    }
+
+    /// The GROUP-IDs of renditions of the given type, in the order they are
+    /// first listed.
+    pub fn group_ids(&self, r#type: MediaType) -> impl Iterator<Item = &str> + '_ {
+        let mut seen: Vec<&str> = Vec::new();
+        self.renditions
+            .iter()
+            .filter(move |rendition| rendition.r#type == r#type)
+            .map(|rendition| rendition.group_id.as_str())
+            .filter(move |group_id| {
+                let first = !seen.contains(group_id);
+                if first {
+                    seen.push(group_id);
+                }
+                first
+            })
+    }
+
+    /// The audio renditions to offer alongside `variant`, from the group its
+    /// AUDIO attribute names.
+    pub fn audio_renditions<'a>(
+        &'a self,
+        variant: &'a VariantStream,
+    ) -> impl Iterator<Item = &'a Rendition> + 'a {
+        self.renditions_of(MediaType::Audio, variant.audio.as_deref())
+    }
+
+    /// The subtitle renditions to offer alongside `variant`, from the group
+    /// its SUBTITLES attribute names.
+    pub fn subtitle_renditions<'a>(
+        &'a self,
+        variant: &'a VariantStream,
+    ) -> impl Iterator<Item = &'a Rendition> + 'a {
+        self.renditions_of(MediaType::Subtitles, variant.subtitles.as_deref())
+    }
+
+    fn renditions_of<'a>(
+        &'a self,
+        r#type: MediaType,
+        group_id: Option<&'a str>,
+    ) -> impl Iterator<Item = &'a Rendition> + 'a {
+        group_id
+            .into_iter()
+            .flat_map(move |group_id| self.group(r#type, group_id))
+    }
+}
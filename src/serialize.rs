@@ -0,0 +1,131 @@
+// Serde support for the types of the playlist model that aren't plain data.
+// URIs and dates are written as they appear in a playlist, whichever of the
+// `fluent-uri`, `chrono` and `time` features is enabled, so serialized
+// playlists can be read back by a build with different features.
+
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{datetime, HexBytes, MediaSegment, MediaSegments};
+
+pub(crate) mod uri {
+    use super::*;
+    use crate::uri::{self as playlist_uri, Uri};
+
+    pub(crate) fn serialize<S: Serializer>(uri: &Uri, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(playlist_uri::as_str(uri))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Uri, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        playlist_uri::parse(&text)
+            .map_err(|_| de::Error::invalid_value(de::Unexpected::Str(&text), &"a URI"))
+    }
+}
+
+pub(crate) mod date_time {
+    use super::*;
+    use crate::DateTime;
+
+    pub(crate) fn serialize<S: Serializer>(
+        date_time: &DateTime,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&datetime::display(date_time))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        datetime::parse(&text)
+            .map_err(|_| de::Error::invalid_value(de::Unexpected::Str(&text), &"an ISO 8601 date"))
+    }
+}
+
+pub(crate) mod option_date_time {
+    use super::*;
+    use crate::DateTime;
+
+    pub(crate) fn serialize<S: Serializer>(
+        date_time: &Option<DateTime>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match date_time {
+            Some(date_time) => serializer.collect_str(&datetime::display(date_time)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<DateTime>, D::Error> {
+        let Some(text) = Option::<String>::deserialize(deserializer)? else {
+            return Ok(None);
+        };
+        datetime::parse(&text)
+            .map(Some)
+            .map_err(|_| de::Error::invalid_value(de::Unexpected::Str(&text), &"an ISO 8601 date"))
+    }
+}
+
+// Deserializes a value from its text, validating it like the parser does
+fn from_text<'de, D, T>(deserializer: D, expected: &'static str) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+{
+    let text = String::deserialize(deserializer)?;
+    text.parse()
+        .map_err(|_| de::Error::invalid_value(de::Unexpected::Str(&text), &expected))
+}
+
+impl Serialize for HexBytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for HexBytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        from_text(deserializer, "a hexadecimal-sequence")
+    }
+}
+
+// A plain list of segments; the storage sharing is rebuilt on the way in
+impl Serialize for MediaSegments {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl<'de> Deserialize<'de> for MediaSegments {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Vec::<MediaSegment>::deserialize(deserializer)?
+            .into_iter()
+            .collect())
+    }
+}
+
+pub(crate) mod seconds {
+    use super::*;
+    use crate::seconds as playlist_seconds;
+    use std::time::Duration;
+
+    pub(crate) fn serialize<S: Serializer>(
+        duration: &Duration,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&playlist_seconds::display(duration))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Duration, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        playlist_seconds::parse(&text).map_err(|_| {
+            de::Error::invalid_value(de::Unexpected::Str(&text), &"a number of seconds")
+        })
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+use crate::{BlockingWaiters, DeliveryDirectives, MediaPlaylist, RemovedDateRanges, SkipDirective};
+
+/// Why a [`BlockingPlaylistService`] couldn't answer a playlist request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockingRequestError {
+    /// `_HLS_part` was given without `_HLS_msn`.
+    PartWithoutMsn,
+    /// `_HLS_msn` is more than two segments past the newest one, which the
+    /// server can't expect to publish in time.
+    TooFarAhead { msn: u64, last_msn: u64 },
+    /// The requested media wasn't published before the hold timeout.
+    Timeout,
+}
+
+impl BlockingRequestError {
+    /// The HTTP status to answer with: 400 for a malformed request, 503
+    /// once the hold timed out.
+    pub fn status_code(&self) -> u16 {
+        match self {
+            BlockingRequestError::PartWithoutMsn | BlockingRequestError::TooFarAhead { .. } => 400,
+            BlockingRequestError::Timeout => 503,
+        }
+    }
+}
+
+// The playlist as last published, along with the dateranges removed from it
+struct Published {
+    playlist: Arc<MediaPlaylist>,
+    removed: RemovedDateRanges,
+}
+
+/// Answers blocking playlist reloads on an origin, whatever the HTTP
+/// framework: a request handler passes the delivery directives of the
+/// request to [`BlockingPlaylistService::respond`], which holds it until
+/// the media asked for is published and returns the playlist to serve.
+///
+/// The live writer hands every new playlist to
+/// [`BlockingPlaylistService::publish`], which releases the requests it
+/// satisfies. Clones share the same state, so each handler can own one.
+#[derive(Clone)]
+pub struct BlockingPlaylistService {
+    published: Arc<Mutex<Published>>,
+    waiters: BlockingWaiters,
+    hold_timeout: Option<Duration>,
+}
+
+impl BlockingPlaylistService {
+    pub fn new(playlist: impl Into<Arc<MediaPlaylist>>) -> Self {
+        let playlist = playlist.into();
+        let service = BlockingPlaylistService {
+            published: Arc::new(Mutex::new(Published {
+                playlist: playlist.clone(),
+                removed: RemovedDateRanges::new(),
+            })),
+            waiters: BlockingWaiters::new(),
+            hold_timeout: None,
+        };
+        service.publish(playlist);
+        service
+    }
+
+    /// How long a request is held before it fails with
+    /// [`BlockingRequestError::Timeout`]. Defaults to three target
+    /// durations of the current playlist, after which clients give up.
+    pub fn set_hold_timeout(&mut self, hold_timeout: Option<Duration>) {
+        self.hold_timeout = hold_timeout;
+    }
+
+    /// Makes `playlist` the one served and releases the requests waiting for
+    /// media it lists.
+    pub fn publish(&self, playlist: impl Into<Arc<MediaPlaylist>>) {
+        let playlist = playlist.into();
+        let (msn, part) = playlist.last_position();
+        let published = !playlist.media_segments.is_empty() || !playlist.trailing_parts.is_empty();
+        // The newest segment is complete unless parts follow it
+        let part = part.filter(|_| !playlist.trailing_parts.is_empty());
+        let ended = !playlist.is_live();
+        self.lock().playlist = playlist;
+        if ended {
+            // Nothing more will be published, so every request is answered
+            self.waiters.notify_published(u64::MAX, None);
+        } else if published {
+            self.waiters.notify_published(msn, part);
+        }
+    }
+
+    /// The dateranges `_HLS_skip=v2` responses list in
+    /// RECENTLY-REMOVED-DATERANGES.
+    pub fn set_removed_date_ranges(&self, removed: RemovedDateRanges) {
+        self.lock().removed = removed;
+    }
+
+    /// The playlist currently served.
+    pub fn playlist(&self) -> Arc<MediaPlaylist> {
+        self.lock().playlist.clone()
+    }
+
+    /// Answers a request with the delivery directives it carried, see
+    /// [`DeliveryDirectives::from_query_str`], returning the playlist to
+    /// serve.
+    ///
+    /// With `_HLS_msn` the request is held until segment `msn` is complete,
+    /// or with `_HLS_part` until that part of it is published. A part index
+    /// past the last part of the segment is satisfied by the first part of
+    /// the next one. Requests for media already published, or made of a
+    /// playlist that ended, are answered at once.
+    ///
+    /// With `_HLS_skip` the delta update is served if the playlist
+    /// advertises CAN-SKIP-UNTIL, the full playlist otherwise. Dateranges
+    /// are only skipped for `_HLS_skip=v2` with CAN-SKIP-DATERANGES=YES.
+    pub async fn respond(
+        &self,
+        directives: &DeliveryDirectives,
+    ) -> Result<Vec<u8>, BlockingRequestError> {
+        let DeliveryDirectives {
+            msn, part, skip, ..
+        } = *directives;
+        if let Some(msn) = msn {
+            let playlist = self.playlist();
+            let (last_msn, _) = playlist.last_position();
+            if msn > last_msn.saturating_add(2) {
+                return Err(BlockingRequestError::TooFarAhead { msn, last_msn });
+            }
+            if playlist.is_live() {
+                let hold = self.hold_timeout.unwrap_or_else(|| {
+                    Duration::from_secs(3 * u64::from(playlist.target_duration))
+                });
+                self.waiters
+                    .wait_for(msn, part, Instant::now() + hold)
+                    .await
+                    .map_err(|_| BlockingRequestError::Timeout)?;
+            }
+        } else if part.is_some() {
+            return Err(BlockingRequestError::PartWithoutMsn);
+        }
+
+        let published = self.lock();
+        let playlist = &published.playlist;
+        let can_skip_until = playlist.server_control.can_skip_until;
+        let rendered = match skip {
+            Some(_) if can_skip_until.is_zero() => playlist.to_string(),
+            Some(SkipDirective::V2) if playlist.server_control.can_skip_dateranges => playlist
+                .to_delta_v2(can_skip_until, &published.removed)
+                .to_string(),
+            Some(_) => playlist.to_delta(can_skip_until).to_string(),
+            None => playlist.to_string(),
+        };
+        Ok(rendered.into_bytes())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Published> {
+        // Fields are only ever replaced whole, so a poisoned lock still
+        // holds a consistent state
+        self.published
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
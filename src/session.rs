@@ -0,0 +1,227 @@
+use crate::prelude::*;
+use crate::{Key, MediaPlaylist};
+
+/// Whether a playlist response was a delta update or a full playlist.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UpdateKind {
+    Full,
+    Delta,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SessionError {
+    /// A delta update arrived with no earlier playlist to apply it to.
+    NoBasePlaylist,
+    /// The delta skipped segments the session never saw, so it has to be
+    /// re-requested without `_HLS_skip`.
+    SkippedSegmentsUnknown { first_msn: u64, last_msn: u64 },
+    /// A response arrived for a rendition that was never added to the
+    /// [`crate::ReloadCoordinator`].
+    UnknownRendition { uri: String },
+}
+
+/// Client-side playlist state for a single rendition. Responses to reload
+/// requests are fed through [`ClientSession::receive`] whether or not they
+/// turned out to be delta updates, and the session keeps the full playlist.
+#[derive(Clone, Default)]
+pub struct ClientSession {
+    playlist: Option<MediaPlaylist>,
+    reports: Vec<ReportAge>,
+}
+
+#[derive(Clone)]
+struct ReportAge {
+    uri: String,
+    last_msn: u64,
+    last_part: Option<u64>,
+    // Reloads in which this rendition advanced while the report didn't
+    stale_reloads: u32,
+}
+
+impl ClientSession {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The full playlist as of the last update.
+    pub fn playlist(&self) -> Option<&MediaPlaylist> {
+        self.playlist.as_ref()
+    }
+
+    /// Whether the next reload should ask for a delta update with
+    /// `_HLS_skip=YES`. Only true while the last playlist advertised
+    /// CAN-SKIP-UNTIL, so a server that stops offering skips gets full
+    /// requests again.
+    pub fn wants_delta(&self) -> bool {
+        self.playlist
+            .as_ref()
+            .is_some_and(|playlist| !playlist.server_control.can_skip_until.is_zero())
+    }
+
+    /// Drops the playlist so the next reload asks for and expects a full
+    /// playlist.
+    pub fn reset(&mut self) {
+        self.playlist = None;
+        self.reports.clear();
+    }
+
+    /// How many reloads the rendition report for `uri` has fallen behind:
+    /// the number of reloads in which this rendition advanced while the
+    /// report stayed the same. `None` if the last playlist carried no such
+    /// report.
+    pub fn report_age(&self, uri: &str) -> Option<u32> {
+        self.reports
+            .iter()
+            .find(|report| report.uri == uri)
+            .map(|report| report.stale_reloads)
+    }
+
+    /// Whether the rendition report for `uri` can be trusted to target a
+    /// blocking request right after switching to that rendition. When it
+    /// can't, the first request after the switch has to be an unblocked one.
+    pub fn is_report_usable(&self, uri: &str) -> bool {
+        self.report_age(uri) == Some(0)
+    }
+
+    /// Applies a reload response, which may be a full playlist even when a
+    /// delta was asked for. On error the session keeps its previous playlist.
+    pub fn receive(&mut self, response: MediaPlaylist) -> Result<UpdateKind, SessionError> {
+        let (playlist, kind) = self.apply(response)?;
+        let advanced = self
+            .playlist
+            .as_ref()
+            .is_none_or(|previous| previous.last_position() != playlist.last_position());
+        self.reports = playlist
+            .rendition_reports
+            .iter()
+            .map(|report| {
+                let previous = self.reports.iter().find(|r| r.uri == report.uri);
+                let stale_reloads = match previous {
+                    Some(previous)
+                        if (previous.last_msn, previous.last_part)
+                            == (report.last_msn, report.last_part) =>
+                    {
+                        previous.stale_reloads + u32::from(advanced)
+                    }
+                    _ => 0,
+                };
+                ReportAge {
+                    uri: report.uri.clone(),
+                    last_msn: report.last_msn,
+                    last_part: report.last_part,
+                    stale_reloads,
+                }
+            })
+            .collect();
+        self.playlist = Some(playlist);
+        Ok(kind)
+    }
+
+    fn apply(&self, response: MediaPlaylist) -> Result<(MediaPlaylist, UpdateKind), SessionError> {
+        if response.skip.is_none() {
+            return Ok((response, UpdateKind::Full));
+        }
+        let base = self.playlist.as_ref().ok_or(SessionError::NoBasePlaylist)?;
+        Ok((base.merge_delta(response)?, UpdateKind::Delta))
+    }
+}
+
+impl MediaPlaylist {
+    /// Applies a playlist reloaded with `_HLS_skip` to `self`, the full
+    /// playlist as of an earlier reload. The segments the delta skipped are
+    /// taken from `self`, along with the EXT-X-MAP that applies to the first
+    /// of them, and its keys unless the first segment listed declares its
+    /// own. If the server advertises CAN-SKIP-DATERANGES, dateranges are kept
+    /// unless the delta lists them again or names them in
+    /// RECENTLY-REMOVED-DATERANGES; otherwise the delta's dateranges replace
+    /// them. A `delta` without
+    /// EXT-X-SKIP is a full playlist and replaces `self`.
+    ///
+    /// On error `self` is left unchanged; the delta has to be requested again
+    /// without `_HLS_skip`.
+    pub fn apply_delta(&mut self, delta: MediaPlaylist) -> Result<UpdateKind, SessionError> {
+        if delta.skip.is_none() {
+            *self = delta;
+            return Ok(UpdateKind::Full);
+        }
+        *self = self.merge_delta(delta)?;
+        Ok(UpdateKind::Delta)
+    }
+
+    fn merge_delta(&self, delta: MediaPlaylist) -> Result<MediaPlaylist, SessionError> {
+        let first_skipped = delta.media_sequence_number;
+        let first_listed = delta.first_listed_msn();
+        let base_end = self.first_listed_msn() + self.media_segments.len() as u64;
+        if first_skipped < self.first_listed_msn() || first_listed > base_end {
+            return Err(SessionError::SkippedSegmentsUnknown {
+                first_msn: first_skipped,
+                last_msn: first_listed.saturating_sub(1),
+            });
+        }
+
+        let start = (first_skipped - self.first_listed_msn()) as usize;
+        let end = (first_listed - self.first_listed_msn()) as usize;
+        let mut media_segments = self.media_segments.slice(start, end);
+        let mut playlist = delta;
+        // The listed segments inherit EXT-X-BITRATE of the skipped ones until
+        // they declare their own
+        let inherited = end
+            .checked_sub(1)
+            .and_then(|last| self.media_segments.get(last))
+            .and_then(|last| last.bitrate);
+        let undeclared = playlist
+            .media_segments
+            .iter()
+            .take_while(|segment| !segment.declares_bitrate)
+            .count();
+        playlist.media_segments.update_first(
+            undeclared,
+            |_| true,
+            |segment| segment.bitrate = inherited,
+        );
+        media_segments.append(playlist.media_segments);
+        media_segments.update_first(
+            1,
+            |first| first.bitrate.is_some(),
+            |first| first.declares_bitrate = true,
+        );
+        // The init segment and keys of the first segment kept may have been
+        // declared on one that fell out of the window
+        if let Some(map) = self.map_for(start) {
+            media_segments.update_first(
+                1,
+                |first| first.map.is_none(),
+                |first| first.map = Some(map.clone()),
+            );
+        }
+        let keys: Vec<Key> = self.keys_for(start).into_iter().cloned().collect();
+        if !keys.is_empty() {
+            media_segments.update_first(
+                1,
+                |first| first.keys.is_empty(),
+                |first| first.keys = keys.clone(),
+            );
+        }
+        playlist.media_segments = media_segments;
+        // A delta for _HLS_skip=v2 leaves out dateranges the client already
+        // has, listing the ones removed since in RECENTLY-REMOVED-DATERANGES.
+        // Other deltas list every daterange still current.
+        if playlist.server_control.can_skip_dateranges {
+            let removed = playlist
+                .skip
+                .as_ref()
+                .map_or(&[][..], |skip| &skip.recently_removed_dateranges);
+            let mut date_ranges: Vec<_> = self
+                .date_ranges
+                .iter()
+                .filter(|range| !removed.contains(&range.id))
+                .filter(|range| !playlist.date_ranges.iter().any(|new| new.id == range.id))
+                .cloned()
+                .collect();
+            date_ranges.append(&mut playlist.date_ranges);
+            playlist.date_ranges = date_ranges;
+        }
+        playlist.skip = None;
+        Ok(playlist)
+    }
+}
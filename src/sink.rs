@@ -0,0 +1,147 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Destination for rendered playlists, so a [`crate::LiveWriter`] can publish
+/// to disk, an object store or an in-process HTTP server alike.
+///
+/// `variant` names the rendition, e.g. `"2M"`; [`PlaylistSink::name`] turns
+/// it into the name the playlist is stored under.
+pub trait PlaylistSink {
+    /// Stores the full playlist of `variant`.
+    fn write_full(&mut self, variant: &str, playlist: &str) -> io::Result<()>;
+
+    /// Stores the delta update (with EXT-X-SKIP) of `variant`, served to
+    /// `_HLS_skip` requests.
+    fn write_delta(&mut self, variant: &str, playlist: &str) -> io::Result<()>;
+
+    /// Name of the full or delta playlist of `variant`. Defaults to
+    /// `<variant>.m3u8` and `<variant>_delta.m3u8`.
+    fn name(&self, variant: &str, delta: bool) -> String {
+        if delta {
+            format!("{}_delta.m3u8", variant)
+        } else {
+            format!("{}.m3u8", variant)
+        }
+    }
+}
+
+/// Writes playlists as files in a directory. Files are replaced atomically
+/// with [`write_atomically`], so readers never see a truncated playlist.
+#[derive(Clone, Debug)]
+pub struct FileSink {
+    directory: PathBuf,
+    fsync: bool,
+}
+
+impl FileSink {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        FileSink {
+            directory: directory.into(),
+            fsync: false,
+        }
+    }
+
+    /// Also flushes every playlist to disk before it replaces the previous
+    /// one, so it survives a crash. Costs a disk round trip per publication.
+    pub fn fsync(mut self, fsync: bool) -> Self {
+        self.fsync = fsync;
+        self
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    fn write(&self, name: String, playlist: &str) -> io::Result<()> {
+        write_atomically(&self.directory.join(name), playlist.as_bytes(), self.fsync)
+    }
+}
+
+// Distinguishes temporary files of concurrent writes from one process
+static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Replaces the file at `path` with `contents` by writing a temporary file
+/// next to it and renaming it into place. Readers see either the old or the
+/// new file, never a partly written one. With `fsync` the data, and on Unix
+/// the directory entry, are flushed to disk before returning.
+pub fn write_atomically(path: &Path, contents: &[u8], fsync: bool) -> io::Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let temp_path = path.with_file_name(temp_name);
+
+    let result = (|| {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(contents)?;
+        if fsync {
+            file.sync_all()?;
+        }
+        fs::rename(&temp_path, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result?;
+
+    #[cfg(unix)]
+    if fsync {
+        if let Some(directory) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            File::open(directory)?.sync_all()?;
+        }
+    }
+    Ok(())
+}
+
+impl PlaylistSink for FileSink {
+    fn write_full(&mut self, variant: &str, playlist: &str) -> io::Result<()> {
+        self.write(self.name(variant, false), playlist)
+    }
+
+    fn write_delta(&mut self, variant: &str, playlist: &str) -> io::Result<()> {
+        self.write(self.name(variant, true), playlist)
+    }
+}
+
+/// Keeps the latest playlists in memory by name, e.g. for an in-process
+/// HTTP server.
+#[derive(Clone, Debug, Default)]
+pub struct MemorySink {
+    playlists: HashMap<String, String>,
+}
+
+impl MemorySink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The playlist last stored under `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.playlists.get(name).map(String::as_str)
+    }
+}
+
+impl PlaylistSink for MemorySink {
+    fn write_full(&mut self, variant: &str, playlist: &str) -> io::Result<()> {
+        self.playlists
+            .insert(self.name(variant, false), playlist.to_string());
+        Ok(())
+    }
+
+    fn write_delta(&mut self, variant: &str, playlist: &str) -> io::Result<()> {
+        self.playlists
+            .insert(self.name(variant, true), playlist.to_string());
+        Ok(())
+    }
+}
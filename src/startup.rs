@@ -0,0 +1,93 @@
+use std::time::{Duration, SystemTime};
+
+use crate::MediaPlaylist;
+
+/// Where to start playing a live stream, see [`MediaPlaylist::live_edge`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LiveEdge {
+    pub msn: u64,
+    /// The part to start at, `None` for a whole segment.
+    pub part_index: Option<u32>,
+    /// How far behind the wall clock playback starting there is, going by
+    /// EXT-X-PROGRAM-DATE-TIME. `None` without a program date time, or
+    /// without the `chrono` or `time` feature to read it.
+    pub latency: Option<Duration>,
+}
+
+impl MediaPlaylist {
+    /// The recommended position to start playing at as of `now`, see
+    /// [`MediaPlaylist::startup_position`], along with the end-to-end
+    /// latency playback from there has. Returns `None` for an empty
+    /// playlist.
+    pub fn live_edge(&self, now: SystemTime) -> Option<LiveEdge> {
+        let (msn, part_index) = self.startup_position()?;
+        let latency = self
+            .datetime_of(msn, part_index)
+            .map(|start| now.duration_since(start).unwrap_or(Duration::ZERO));
+        Some(LiveEdge {
+            msn,
+            part_index,
+            latency,
+        })
+    }
+
+    /// Where a client joining the live stream should start: the (media
+    /// sequence number, part index) of the first part or segment to fetch.
+    ///
+    /// The position is at least PART-HOLD-BACK from the end of the playlist,
+    /// or HOLD-BACK when no parts are listed, and is the newest
+    /// such INDEPENDENT part if there is one. Segments listed without parts
+    /// are taken to start independently. Returns `None` for an empty
+    /// playlist, and the oldest position when none is far enough back.
+    pub fn startup_position(&self) -> Option<(u64, Option<u32>)> {
+        // (MSN, part index, duration, starts independently) of everything
+        // listed, in publication order
+        let mut positions = Vec::new();
+        let mut has_parts = false;
+        let first_msn = self.first_listed_msn();
+        for (segment, msn) in self.media_segments.iter().zip(first_msn..) {
+            if segment.partial_segments.is_empty() {
+                positions.push((msn, None, segment.duration, true));
+            }
+            for (part, index) in segment.partial_segments.iter().zip(0..) {
+                has_parts = true;
+                positions.push((
+                    msn,
+                    Some(index),
+                    part.part_duration,
+                    part.independent == Some(true),
+                ));
+            }
+        }
+        let trailing_msn = first_msn + self.media_segments.len() as u64;
+        for (part, index) in self.trailing_parts.iter().zip(0..) {
+            has_parts = true;
+            positions.push((
+                trailing_msn,
+                Some(index),
+                part.part_duration,
+                part.independent == Some(true),
+            ));
+        }
+
+        let hold_back = if has_parts {
+            self.part_hold_back()
+        } else {
+            self.hold_back()
+        };
+
+        let mut from_end = Duration::ZERO;
+        let mut fallback = None;
+        for &(msn, part, duration, independent) in positions.iter().rev() {
+            from_end = from_end.saturating_add(duration);
+            if from_end < hold_back {
+                continue;
+            }
+            if independent {
+                return Some((msn, part));
+            }
+            fallback.get_or_insert((msn, part));
+        }
+        fallback.or_else(|| positions.first().map(|&(msn, part, _, _)| (msn, part)))
+    }
+}
@@ -0,0 +1,71 @@
+use core::time::Duration;
+
+use crate::prelude::*;
+use crate::{
+    EncoderTiming, MediaPlaylistBuilder, MediaType, MultivariantPlaylist, PartialSegment, Rendition,
+};
+
+/// A WebVTT subtitle rendition, carried in an EXT-X-MEDIA group of type
+/// SUBTITLES.
+///
+/// Subtitle playlists list `.vtt` segments spanning the same times as the
+/// video segments. Parts are optional: captions are small and players often
+/// fetch them at segment granularity, but live captioning can publish them
+/// as parts to keep up with low-latency video. Every cue stands on its own,
+/// so every part is INDEPENDENT.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubtitleRendition {
+    pub rendition: Rendition,
+    /// Whether the playlist lists parts, true by default.
+    pub parts: bool,
+}
+
+impl SubtitleRendition {
+    pub fn new(
+        group_id: impl Into<String>,
+        name: impl Into<String>,
+        uri: impl Into<String>,
+    ) -> Self {
+        let mut rendition = Rendition::new(MediaType::Subtitles, group_id, name);
+        rendition.uri = Some(uri.into());
+        rendition.autoselect = true;
+        SubtitleRendition {
+            rendition,
+            parts: true,
+        }
+    }
+
+    /// Sets the timing of the subtitle playlist from the video's. Without
+    /// parts there is no EXT-X-PART-INF.
+    pub fn apply<'a>(
+        &self,
+        timing: &EncoderTiming,
+        builder: &'a mut MediaPlaylistBuilder,
+    ) -> &'a mut MediaPlaylistBuilder {
+        builder
+            .target_duration(timing.target_duration)
+            .server_control(timing.server_control());
+        if self.parts {
+            builder.part_inf(timing.part_inf());
+        }
+        builder
+    }
+
+    /// A WebVTT part, marked INDEPENDENT.
+    pub fn part(&self, duration: Duration, uri: impl Into<String>) -> PartialSegment {
+        let mut part = PartialSegment::new(duration, uri);
+        part.independent = Some(true);
+        part
+    }
+
+    /// Adds the EXT-X-MEDIA of this rendition to `playlist` and points every
+    /// variant without subtitles at its group.
+    pub fn add_to(&self, playlist: &mut MultivariantPlaylist) {
+        playlist.renditions.push(self.rendition.clone());
+        for variant in &mut playlist.variants {
+            if variant.subtitles.is_none() {
+                variant.subtitles = Some(self.rendition.group_id.clone());
+            }
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use std::time::{Duration, SystemTime};
+
+use crate::{datetime, MediaPlaylist, PartialSegment};
+
+// A segment or part placed on the wall clock
+struct TimedMedia {
+    msn: u64,
+    part: Option<u32>,
+    start: SystemTime,
+    duration: Duration,
+}
+
+impl TimedMedia {
+    fn contains(&self, time: SystemTime) -> bool {
+        time >= self.start
+            && self
+                .start
+                .checked_add(self.duration)
+                .is_none_or(|end| time < end)
+    }
+}
+
+impl MediaPlaylist {
+    /// When the media at `msn`, or at part `part` of it, was captured, from
+    /// the newest EXT-X-PROGRAM-DATE-TIME at or before it plus the durations
+    /// listed since, or else the first one after it minus the durations in
+    /// between. `None` if the playlist doesn't list that media or has no
+    /// program date time, or without the `chrono` or `time` feature to read
+    /// one.
+    pub fn datetime_of(&self, msn: u64, part: Option<u32>) -> Option<SystemTime> {
+        self.timeline()
+            .into_iter()
+            .find(|media| media.msn == msn && media.part == part)
+            .map(|media| media.start)
+    }
+
+    /// The media captured at `time`, e.g. to seek by time within a DVR
+    /// window or to line a splice up with a SCTE-35 cue: the (MSN, part
+    /// index) of the part spanning it, or of the segment if it lists no
+    /// parts. Times are mapped as for [`MediaPlaylist::datetime_of`];
+    /// `None` outside of the listed media.
+    pub fn segment_at(&self, time: SystemTime) -> Option<(u64, Option<u32>)> {
+        let timeline = self.timeline();
+        let spanning = |parts: bool| {
+            timeline
+                .iter()
+                .find(|media| media.part.is_some() == parts && media.contains(time))
+        };
+        spanning(true)
+            .or_else(|| spanning(false))
+            .map(|media| (media.msn, media.part))
+    }
+
+    // Every listed segment and part in publication order, segments before
+    // their parts; empty without a program date time to place them by
+    fn timeline(&self) -> Vec<TimedMedia> {
+        // Offset of each segment from the start of the playlist, and its
+        // program date time
+        let mut offset = Duration::ZERO;
+        let mut offsets = Vec::with_capacity(self.media_segments.len());
+        for segment in &self.media_segments {
+            let date = segment
+                .program_date_time
+                .as_ref()
+                .and_then(datetime::to_system_time);
+            offsets.push((offset, date));
+            offset = offset.saturating_add(segment.duration);
+        }
+        let Some(mut anchor) = offsets
+            .iter()
+            .find_map(|&(offset, date)| Some((date?, offset)))
+        else {
+            return Vec::new();
+        };
+        let at = |(date, anchor_offset): (SystemTime, Duration), offset: Duration| {
+            if offset >= anchor_offset {
+                date.checked_add(offset - anchor_offset)
+            } else {
+                date.checked_sub(anchor_offset - offset)
+            }
+        };
+        let mut timeline = Vec::new();
+        let push_parts = |timeline: &mut Vec<TimedMedia>,
+                          anchor,
+                          msn,
+                          mut offset: Duration,
+                          parts: &[PartialSegment]| {
+            for (part, index) in parts.iter().zip(0..) {
+                if let Some(start) = at(anchor, offset) {
+                    timeline.push(TimedMedia {
+                        msn,
+                        part: Some(index),
+                        start,
+                        duration: part.part_duration,
+                    });
+                }
+                offset = offset.saturating_add(part.part_duration);
+            }
+        };
+        let first_msn = self.first_listed_msn();
+        for ((segment, &(offset, date)), msn) in
+            self.media_segments.iter().zip(&offsets).zip(first_msn..)
+        {
+            if let Some(date) = date {
+                anchor = (date, offset);
+            }
+            if let Some(start) = at(anchor, offset) {
+                timeline.push(TimedMedia {
+                    msn,
+                    part: None,
+                    start,
+                    duration: segment.duration,
+                });
+            }
+            push_parts(
+                &mut timeline,
+                anchor,
+                msn,
+                offset,
+                &segment.partial_segments,
+            );
+        }
+        let trailing_msn = first_msn + self.media_segments.len() as u64;
+        push_parts(
+            &mut timeline,
+            anchor,
+            trailing_msn,
+            offset,
+            &self.trailing_parts,
+        );
+        timeline
+    }
+}
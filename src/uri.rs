@@ -0,0 +1,240 @@
+use core::{fmt, str::FromStr};
+
+use crate::prelude::*;
+use crate::{InvalidValue, ParseTagError};
+
+/// URI of a media segment, as written. The `fluent-uri` feature checks that
+/// it is a valid URI reference when parsed; without it any text is kept.
+/// Either way this is the type the model holds.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Uri(String);
+
+impl Uri {
+    pub fn parse_from(s: String) -> Result<Self, ParseTagError> {
+        if !is_valid(&s) {
+            return Err(ParseTagError::Invalid { value: s });
+        }
+        Ok(Uri(s))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl FromStr for Uri {
+    type Err = ParseTagError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Uri::parse_from(s.to_string())
+    }
+}
+
+impl AsRef<str> for Uri {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Uri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(feature = "fluent-uri")]
+fn is_valid(s: &str) -> bool {
+    fluent_uri::Uri::parse(s).is_ok()
+}
+
+#[cfg(not(feature = "fluent-uri"))]
+fn is_valid(_s: &str) -> bool {
+    true
+}
+
+pub(crate) fn parse(s: &str) -> Result<Uri, InvalidValue> {
+    s.parse().map_err(|_| InvalidValue)
+}
+
+pub(crate) fn as_str(uri: &Uri) -> &str {
+    uri.as_str()
+}
+
+// Resolves `reference` against `base` per RFC 3986 section 5.2
+pub(crate) fn resolve(base: &str, reference: &str) -> String {
+    let base = Components::split(base);
+    let reference = Components::split(reference);
+    let (scheme, authority, path, query) = if reference.scheme.is_some() {
+        (
+            reference.scheme,
+            reference.authority,
+            remove_dot_segments(reference.path),
+            reference.query,
+        )
+    } else if reference.authority.is_some() {
+        (
+            base.scheme,
+            reference.authority,
+            remove_dot_segments(reference.path),
+            reference.query,
+        )
+    } else if reference.path.is_empty() {
+        (
+            base.scheme,
+            base.authority,
+            base.path.to_string(),
+            reference.query.or(base.query),
+        )
+    } else if reference.path.starts_with('/') {
+        (
+            base.scheme,
+            base.authority,
+            remove_dot_segments(reference.path),
+            reference.query,
+        )
+    } else {
+        let merged = if base.authority.is_some() && base.path.is_empty() {
+            format!("/{}", reference.path)
+        } else {
+            let directory = base.path.rfind('/').map_or(0, |index| index + 1);
+            format!("{}{}", &base.path[..directory], reference.path)
+        };
+        (
+            base.scheme,
+            base.authority,
+            remove_dot_segments(&merged),
+            reference.query,
+        )
+    };
+
+    let mut target = String::new();
+    if let Some(scheme) = scheme {
+        target.push_str(scheme);
+        target.push(':');
+    }
+    if let Some(authority) = authority {
+        target.push_str("//");
+        target.push_str(authority);
+    }
+    target.push_str(&path);
+    if let Some(query) = query {
+        target.push('?');
+        target.push_str(query);
+    }
+    if let Some(fragment) = reference.fragment {
+        target.push('#');
+        target.push_str(fragment);
+    }
+    target
+}
+
+// The five components of a URI reference, split as in RFC 3986 appendix B
+struct Components<'a> {
+    scheme: Option<&'a str>,
+    authority: Option<&'a str>,
+    path: &'a str,
+    query: Option<&'a str>,
+    fragment: Option<&'a str>,
+}
+
+impl<'a> Components<'a> {
+    fn split(uri: &'a str) -> Self {
+        let (rest, fragment) = match uri.split_once('#') {
+            Some((rest, fragment)) => (rest, Some(fragment)),
+            None => (uri, None),
+        };
+        let (rest, query) = match rest.split_once('?') {
+            Some((rest, query)) => (rest, Some(query)),
+            None => (rest, None),
+        };
+        let (scheme, rest) = match rest.split_once(':') {
+            Some((scheme, rest)) if !scheme.is_empty() && !scheme.contains('/') => {
+                (Some(scheme), rest)
+            }
+            _ => (None, rest),
+        };
+        let (authority, path) = match rest.strip_prefix("//") {
+            Some(rest) => {
+                let end = rest.find('/').unwrap_or(rest.len());
+                (Some(&rest[..end]), &rest[end..])
+            }
+            None => (None, rest),
+        };
+        Components {
+            scheme,
+            authority,
+            path,
+            query,
+            fragment,
+        }
+    }
+}
+
+// RFC 3986 section 5.2.4
+fn remove_dot_segments(path: &str) -> String {
+    let mut input = path;
+    let mut output = String::with_capacity(path.len());
+    let pop = |output: &mut String| output.truncate(output.rfind('/').unwrap_or(0));
+    while !input.is_empty() {
+        if let Some(rest) = input.strip_prefix("../") {
+            input = rest;
+        } else if let Some(rest) = input.strip_prefix("./") {
+            input = rest;
+        } else if input.starts_with("/./") {
+            input = &input[2..];
+        } else if input == "/." {
+            input = "/";
+        } else if input.starts_with("/../") {
+            input = &input[3..];
+            pop(&mut output);
+        } else if input == "/.." {
+            input = "/";
+            pop(&mut output);
+        } else if input == "." || input == ".." {
+            input = "";
+        } else {
+            let start = usize::from(input.starts_with('/'));
+            let end = input[start..]
+                .find('/')
+                .map_or(input.len(), |index| index + start);
+            output.push_str(&input[..end]);
+            input = &input[end..];
+        }
+    }
+    output
+}
+
+// Percent-encodes what a URI can't hold as written: spaces, non-ASCII and
+// other characters outside RFC 3986, `%` not starting an escape, and `#`
+// after the one starting the fragment
+pub(crate) fn repair(s: &str) -> String {
+    use core::fmt::Write;
+
+    let mut repaired = String::with_capacity(s.len());
+    let mut in_fragment = false;
+    for (index, c) in s.char_indices() {
+        let escape = s.as_bytes()[index + 1..]
+            .get(..2)
+            .is_some_and(|hex| hex.iter().all(u8::is_ascii_hexdigit));
+        let kept = match c {
+            '%' => escape,
+            '#' => !core::mem::replace(&mut in_fragment, true),
+            'A'..='Z' | 'a'..='z' | '0'..='9' => true,
+            _ => "-._~:/?[]@!$&'()*+,;=".contains(c),
+        };
+        if kept {
+            repaired.push(c);
+        } else {
+            let mut bytes = [0; 4];
+            for byte in c.encode_utf8(&mut bytes).bytes() {
+                // Writing to a String can't fail
+                let _ = write!(repaired, "%{:02X}", byte);
+            }
+        }
+    }
+    repaired
+}
@@ -0,0 +1,847 @@
+use alloc::collections::BTreeMap;
+use core::{fmt, time::Duration};
+
+use crate::prelude::*;
+use crate::{
+    seconds, uri, ContainerFormat, Map, MediaPlaylist, MultivariantPlaylist, PartialSegment,
+    PreloadHintType, VariantStream,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// Violates a MUST of the profile.
+    Error,
+    /// Violates a SHOULD of the profile.
+    Warning,
+}
+
+/// The check that raised a [`ValidationIssue`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Rule {
+    SegmentExceedsTargetDuration,
+    VersionTooLow,
+    TargetDurationNotRecommended,
+    ProgramDateTimeMissing,
+    PreloadHintMissing,
+    BlockingReloadUnsupported,
+    PartHoldBackTooShort,
+    RenditionReportsMissing,
+    UnevenPartDurations,
+    PartsDoNotSumToSegment,
+    PreloadHintAlreadyPublished,
+    PreloadHintSkipsParts,
+    RenditionReportStale,
+    RenditionReportAhead,
+    RenditionReportUriNotRelative,
+    SkipNotAllowed,
+    SkipWindowTooLarge,
+    MixedContainerFormats,
+    MapHintInMpegTs,
+    IndependentPartMissing,
+    MapChangeWithoutDiscontinuity,
+    MediaDoesNotMatchMap,
+    PartInfMissing,
+    PartExceedsPartTarget,
+    PartHoldBackBelowMinimum,
+    SkipWindowBelowMinimum,
+    MediaSequenceDecreased,
+    LiveEdgeRegressed,
+    PartTargetMismatch,
+    RenditionReportUnknown,
+    StreamInfAttributeMissing,
+    StreamInfAttributeInvalid,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationIssue {
+    pub rule: Rule,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}", severity, self.message)
+    }
+}
+
+/// Set of rules to validate a playlist against.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValidationProfile {
+    /// The requirements of the HLS specification itself.
+    Rfc8216,
+    /// The specification plus the requirements its second edition adds for
+    /// Low-Latency HLS, such as the minimum PART-HOLD-BACK.
+    Rfc8216bis,
+    /// The specification plus Apple's HLS Authoring Specification checks that
+    /// can be made on a media playlist alone. Requirements on the
+    /// multivariant playlist, such as providing I-frame playlists, are not
+    /// covered.
+    AppleAuthoring,
+}
+
+// Slack allowed when comparing part durations
+const PART_DURATION_TOLERANCE: Duration = Duration::from_millis(1);
+
+// Apple's recommended EXT-X-TARGETDURATION, in seconds
+const APPLE_TARGET_DURATION: u32 = 6;
+
+impl MediaPlaylist {
+    /// Checks the playlist against [`ValidationProfile::Rfc8216bis`], the
+    /// current specification including Low-Latency HLS.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        self.validate_profile(ValidationProfile::Rfc8216bis)
+    }
+
+    pub fn validate_profile(&self, profile: ValidationProfile) -> Vec<ValidationIssue> {
+        let mut issues = Issues::default();
+        self.validate_rfc8216(&mut issues);
+        match profile {
+            ValidationProfile::Rfc8216 => {}
+            ValidationProfile::Rfc8216bis => self.validate_rfc8216bis(&mut issues),
+            ValidationProfile::AppleAuthoring => self.validate_apple_authoring(&mut issues),
+        }
+        issues.0
+    }
+
+    /// Checks that this playlist can follow `previous`, an earlier reload of
+    /// the same rendition: the media sequence number and the live edge never
+    /// move back. A stale playlist served by a CDN cache typically breaks
+    /// this.
+    pub fn validate_update(&self, previous: &MediaPlaylist) -> Vec<ValidationIssue> {
+        let mut issues = Issues::default();
+        if self.media_sequence_number < previous.media_sequence_number {
+            issues.error(
+                Rule::MediaSequenceDecreased,
+                format!(
+                    "EXT-X-MEDIA-SEQUENCE went from {} back to {}",
+                    previous.media_sequence_number, self.media_sequence_number
+                ),
+            );
+        }
+        // A segment listed without parts has no part index, so parts are
+        // only compared when both edges have one
+        let (msn, part) = self.last_position();
+        let (previous_msn, previous_part) = previous.last_position();
+        let regressed = msn < previous_msn
+            || (msn == previous_msn
+                && previous_part.is_some_and(|previous| part.is_some_and(|part| part < previous)));
+        if regressed {
+            issues.error(
+                Rule::LiveEdgeRegressed,
+                format!(
+                    "live edge went from {} back to {}",
+                    position(previous_msn, previous_part),
+                    position(msn, part)
+                ),
+            );
+        }
+        issues.0
+    }
+
+    fn validate_rfc8216(&self, issues: &mut Issues) {
+        for (segment, msn) in self.media_segments.iter().zip(self.first_listed_msn()..) {
+            if seconds::round(&segment.duration) > u64::from(self.target_duration) {
+                issues.error(
+                    Rule::SegmentExceedsTargetDuration,
+                    format!(
+                        "segment {} lasts {}s, more than EXT-X-TARGETDURATION {}s",
+                        msn,
+                        seconds::display(&segment.duration),
+                        self.target_duration
+                    ),
+                );
+            }
+        }
+        for (segment, msn) in self.media_segments.iter().zip(self.first_listed_msn()..) {
+            let parts = &segment.partial_segments;
+            if parts.is_empty() {
+                continue;
+            }
+            check_uneven_parts(msn, parts, issues);
+            // Without an independent part, clients can only join this
+            // segment at its start
+            if !parts.iter().any(|part| part.independent == Some(true)) {
+                issues.warning(
+                    Rule::IndependentPartMissing,
+                    format!("segment {} has no part with INDEPENDENT=YES", msn),
+                );
+            }
+            let sum = seconds::sum(parts.iter().map(|part| part.part_duration));
+            if sum.abs_diff(segment.duration) > PART_DURATION_TOLERANCE {
+                issues.warning(
+                    Rule::PartsDoNotSumToSegment,
+                    format!(
+                        "parts of segment {} add up to {}s but its EXTINF is {}s",
+                        msn,
+                        seconds::display(&sum),
+                        seconds::display(&segment.duration)
+                    ),
+                );
+            }
+        }
+        let (trailing_msn, _) = self.last_position();
+        if !self.trailing_parts.is_empty() {
+            check_uneven_parts(trailing_msn, &self.trailing_parts, issues);
+        }
+        if self.part_inf.is_none() && self.parts_with_position().next().is_some() {
+            issues.error(
+                Rule::PartInfMissing,
+                "playlist lists EXT-X-PART tags without EXT-X-PART-INF".to_string(),
+            );
+        }
+
+        self.validate_preload_hint(issues);
+        self.validate_rendition_reports(issues);
+        self.validate_skip(issues);
+        self.validate_container_format(issues);
+        self.validate_maps(issues);
+
+        let required_version = self.required_version();
+        if self.version < required_version {
+            issues.error(
+                Rule::VersionTooLow,
+                format!(
+                    "EXT-X-VERSION {} is lower than the {} the playlist's features require",
+                    self.version, required_version
+                ),
+            );
+        }
+    }
+
+    // A PART hint must name the part right after the newest published one.
+    // URIs are opaque, so beyond an exact match this compares the numbers
+    // embedded in otherwise identically shaped URIs, like filePart273.4.mp4.
+    fn validate_preload_hint(&self, issues: &mut Issues) {
+        let Some(hint) = self.preload_hint_of(PreloadHintType::Part) else {
+            return;
+        };
+        let ranged = hint.byterange_start.is_some() || hint.byterange_length.is_some();
+        if let (true, Some((uri, end))) = (ranged, self.last_part_range_end()) {
+            if uri == hint.uri {
+                self.validate_preload_hint_range(
+                    hint.uri.as_str(),
+                    hint.byterange_start,
+                    end,
+                    issues,
+                );
+                return;
+            }
+        }
+        if self
+            .parts_with_position()
+            .any(|(_, _, part)| part.uri == hint.uri && (!ranged || part.byterange.is_none()))
+        {
+            issues.error(
+                Rule::PreloadHintAlreadyPublished,
+                format!(
+                    "preload hint {} names a part that is already listed",
+                    hint.uri
+                ),
+            );
+            return;
+        }
+        let Some((_, _, last)) = self.parts_with_position().last() else {
+            return;
+        };
+        let (last_shape, last_numbers) = numeric_shape(&last.uri);
+        let (hint_shape, hint_numbers) = numeric_shape(&hint.uri);
+        if last_shape != hint_shape || last_numbers.len() != hint_numbers.len() {
+            return;
+        }
+        // The first embedded number that differs, e.g. the part index within
+        // the same segment or the segment number, must advance by one
+        let Some((hint_number, last_number)) = hint_numbers
+            .iter()
+            .zip(&last_numbers)
+            .find(|(hint, last)| hint != last)
+        else {
+            return;
+        };
+        if hint_number < last_number {
+            issues.error(
+                Rule::PreloadHintAlreadyPublished,
+                format!(
+                    "preload hint {} does not come after the last part {}",
+                    hint.uri, last.uri
+                ),
+            );
+        } else if *hint_number > last_number.saturating_add(1) {
+            issues.warning(
+                Rule::PreloadHintSkipsParts,
+                format!(
+                    "preload hint {} leaves a gap after the last part {}",
+                    hint.uri, last.uri
+                ),
+            );
+        }
+    }
+
+    // Parts of one resource, e.g. CMAF chunks of the segment file being
+    // written, differ only by byte range, so the hinted range must start
+    // where the last part's ends
+    fn validate_preload_hint_range(
+        &self,
+        uri: &str,
+        start: Option<u64>,
+        end: u64,
+        issues: &mut Issues,
+    ) {
+        // BYTERANGE-START defaults to 0 once a length is given
+        let start = start.unwrap_or(0);
+        if start < end {
+            issues.error(
+                Rule::PreloadHintAlreadyPublished,
+                format!(
+                    "preload hint {} starts at byte {}, within the last part ending at {}",
+                    uri, start, end
+                ),
+            );
+        } else if start > end {
+            issues.warning(
+                Rule::PreloadHintSkipsParts,
+                format!(
+                    "preload hint {} starts at byte {}, leaving a gap after the last part ending at {}",
+                    uri, start, end
+                ),
+            );
+        }
+    }
+
+    // The resource of the last part and the end of its byte range, a range
+    // without offset continuing the previous part's of the same resource
+    fn last_part_range_end(&self) -> Option<(&str, u64)> {
+        let mut end = None;
+        for (_, _, part) in self.parts_with_position() {
+            end = part.byterange.map(|byterange| {
+                let start = byterange.offset.unwrap_or(match end {
+                    Some((uri, end)) if uri == part.uri => end,
+                    _ => 0,
+                });
+                (part.uri.as_str(), start.saturating_add(byterange.length))
+            });
+        }
+        end
+    }
+
+    // Sibling renditions are published in lockstep, so their reports should
+    // sit within one part of this playlist's own live edge
+    fn validate_rendition_reports(&self, issues: &mut Issues) {
+        let (edge_msn, edge_part) = self.last_position();
+        for report in &self.rendition_reports {
+            if report.uri.starts_with("//") || has_scheme(&report.uri) {
+                issues.warning(
+                    Rule::RenditionReportUriNotRelative,
+                    format!(
+                        "rendition report URI {} is not relative to this playlist",
+                        report.uri
+                    ),
+                );
+            }
+            let lag = match (report.last_part, edge_part) {
+                (Some(report_part), Some(edge_part)) => compare_edges(
+                    (report.last_msn, report_part),
+                    (edge_msn, u64::from(edge_part)),
+                ),
+                _ => i128::from(report.last_msn) - i128::from(edge_msn),
+            };
+            let position = match report.last_part {
+                Some(part) => format!("{}.{}", report.last_msn, part),
+                None => report.last_msn.to_string(),
+            };
+            if lag < -1 {
+                issues.warning(
+                    Rule::RenditionReportStale,
+                    format!(
+                        "rendition report for {} is at {}, behind the live edge {}",
+                        report.uri, position, edge_msn
+                    ),
+                );
+            } else if lag > 1 {
+                issues.warning(
+                    Rule::RenditionReportAhead,
+                    format!(
+                        "rendition report for {} is at {}, ahead of the live edge {}",
+                        report.uri, position, edge_msn
+                    ),
+                );
+            }
+        }
+    }
+
+    // A delta update may only skip segments older than CAN-SKIP-UNTIL seconds
+    // from the end of the playlist, so at least that much must stay listed
+    fn validate_skip(&self, issues: &mut Issues) {
+        let Some(skip) = &self.skip else {
+            return;
+        };
+        let can_skip_until = self.server_control.can_skip_until;
+        if can_skip_until.is_zero() {
+            issues.error(
+                Rule::SkipNotAllowed,
+                "EXT-X-SKIP is present but the server does not advertise CAN-SKIP-UNTIL"
+                    .to_string(),
+            );
+            return;
+        }
+        let retained = self.retained_duration();
+        if retained < can_skip_until {
+            issues.error(
+                Rule::SkipWindowTooLarge,
+                format!(
+                    "skipping {} segments leaves {}s listed, less than CAN-SKIP-UNTIL {}s",
+                    skip.skipped_segments,
+                    seconds::display(&retained),
+                    seconds::display(&can_skip_until)
+                ),
+            );
+        }
+    }
+
+    // Transport stream and fMP4 media can't share a playlist, and transport
+    // streams have no init segment to hint at
+    fn validate_container_format(&self, issues: &mut Issues) {
+        let mut formats = self.container_formats();
+        let Some(first) = formats.next() else {
+            return;
+        };
+        if formats.any(|format| format != first) {
+            issues.error(
+                Rule::MixedContainerFormats,
+                "playlist mixes MPEG-TS and fMP4 segments or parts".to_string(),
+            );
+            return;
+        }
+        let map_hint = self.preload_hint_of(PreloadHintType::Map).is_some();
+        if first == ContainerFormat::MpegTs && map_hint {
+            issues.error(
+                Rule::MapHintInMpegTs,
+                "MPEG-TS playlist hints at an EXT-X-MAP init segment".to_string(),
+            );
+        }
+    }
+
+    // A new init segment usually means new codec parameters, which players
+    // only expect after a discontinuity. Media following a map must also be
+    // of the map's format, or players decode it with the wrong init segment.
+    fn validate_maps(&self, issues: &mut Issues) {
+        let (trailing_msn, _) = self.last_position();
+        let segments = self
+            .media_segments
+            .iter()
+            .zip(self.first_listed_msn()..)
+            .map(|(segment, msn)| {
+                let uris: Vec<_> = segment
+                    .partial_segments
+                    .iter()
+                    .map(|part| part.uri.as_str())
+                    .chain([uri::as_str(&segment.uri)])
+                    .collect();
+                (msn, segment.discontinuity, segment.map.as_ref(), uris)
+            })
+            .chain([(
+                trailing_msn,
+                self.trailing_discontinuity,
+                self.trailing_map.as_ref(),
+                self.trailing_parts
+                    .iter()
+                    .map(|part| part.uri.as_str())
+                    .collect(),
+            )]);
+        let mut current: Option<&Map> = None;
+        for (msn, discontinuity, map, uris) in segments {
+            if let Some(map) = map {
+                if current.is_some_and(|previous| previous != map) && !discontinuity {
+                    issues.warning(
+                        Rule::MapChangeWithoutDiscontinuity,
+                        format!(
+                            "segment {} switches to EXT-X-MAP {} without EXT-X-DISCONTINUITY",
+                            msn, map.uri
+                        ),
+                    );
+                }
+                current = Some(map);
+            }
+            let Some(map) = current else {
+                continue;
+            };
+            let Some(format) = ContainerFormat::from_uri(&map.uri) else {
+                continue;
+            };
+            for uri in uris {
+                if ContainerFormat::from_uri(uri).is_some_and(|other| other != format) {
+                    issues.error(
+                        Rule::MediaDoesNotMatchMap,
+                        format!(
+                            "{} of segment {} doesn't match EXT-X-MAP {}",
+                            uri, msn, map.uri
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    fn validate_rfc8216bis(&self, issues: &mut Issues) {
+        if let Some(part_inf) = &self.part_inf {
+            for (msn, index, part) in self.parts_with_position() {
+                if part.part_duration > part_inf.part_target {
+                    issues.error(
+                        Rule::PartExceedsPartTarget,
+                        format!(
+                            "part {}.{} lasts {}s, more than PART-TARGET {}s",
+                            msn,
+                            index,
+                            seconds::display(&part.part_duration),
+                            seconds::display(&part_inf.part_target)
+                        ),
+                    );
+                }
+            }
+            let minimum = part_inf.part_target.saturating_mul(2);
+            if self.server_control.part_hold_back < minimum {
+                issues.error(
+                    Rule::PartHoldBackBelowMinimum,
+                    format!(
+                        "PART-HOLD-BACK {}s is less than twice PART-TARGET ({}s)",
+                        seconds::display(&self.server_control.part_hold_back),
+                        seconds::display(&minimum)
+                    ),
+                );
+            }
+        }
+        // Zero means the server doesn't offer delta updates
+        let can_skip_until = self.server_control.can_skip_until;
+        let minimum = Duration::from_secs(6 * u64::from(self.target_duration));
+        if !can_skip_until.is_zero() && can_skip_until < minimum {
+            issues.error(
+                Rule::SkipWindowBelowMinimum,
+                format!(
+                    "CAN-SKIP-UNTIL {}s is less than six target durations ({}s)",
+                    seconds::display(&can_skip_until),
+                    seconds::display(&minimum)
+                ),
+            );
+        }
+    }
+
+    fn validate_apple_authoring(&self, issues: &mut Issues) {
+        if self.target_duration != APPLE_TARGET_DURATION {
+            issues.warning(
+                Rule::TargetDurationNotRecommended,
+                format!(
+                    "EXT-X-TARGETDURATION is {}s rather than the recommended {}s",
+                    self.target_duration, APPLE_TARGET_DURATION
+                ),
+            );
+        }
+        if !self.media_segments.is_empty()
+            && self
+                .media_segments
+                .iter()
+                .all(|segment| segment.program_date_time.is_none())
+        {
+            issues.warning(
+                Rule::ProgramDateTimeMissing,
+                "no segment carries EXT-X-PROGRAM-DATE-TIME".to_string(),
+            );
+        }
+
+        let low_latency = self.part_inf.is_some() || self.parts_with_position().next().is_some();
+        if !low_latency {
+            return;
+        }
+        if self.preload_hints.is_empty() {
+            issues.error(
+                Rule::PreloadHintMissing,
+                "low-latency playlist has no EXT-X-PRELOAD-HINT".to_string(),
+            );
+        }
+        if !self.server_control.can_block_reload {
+            issues.error(
+                Rule::BlockingReloadUnsupported,
+                "low-latency playlist does not set CAN-BLOCK-RELOAD=YES".to_string(),
+            );
+        }
+        if let Some(part_inf) = &self.part_inf {
+            let minimum = part_inf.part_target.saturating_mul(3);
+            if self.server_control.part_hold_back < minimum {
+                issues.warning(
+                    Rule::PartHoldBackTooShort,
+                    format!(
+                        "PART-HOLD-BACK {}s is less than three times PART-TARGET ({}s)",
+                        seconds::display(&self.server_control.part_hold_back),
+                        seconds::display(&minimum)
+                    ),
+                );
+            }
+        }
+        if self.rendition_reports.is_empty() {
+            issues.warning(
+                Rule::RenditionReportsMissing,
+                "low-latency playlist has no EXT-X-RENDITION-REPORT tags".to_string(),
+            );
+        }
+    }
+}
+
+// All parts but the last of a segment should last the same
+fn check_uneven_parts(msn: u64, parts: &[PartialSegment], issues: &mut Issues) {
+    let Some((_, leading)) = parts.split_last() else {
+        return;
+    };
+    let Some(first) = leading.first() else {
+        return;
+    };
+    if let Some((index, part)) = leading.iter().enumerate().find(|(_, part)| {
+        part.part_duration.abs_diff(first.part_duration) > PART_DURATION_TOLERANCE
+    }) {
+        issues.warning(
+            Rule::UnevenPartDurations,
+            format!(
+                "part {}.{} lasts {}s while earlier parts of the segment last {}s",
+                msn,
+                index,
+                seconds::display(&part.part_duration),
+                seconds::display(&first.part_duration)
+            ),
+        );
+    }
+}
+
+// e.g. 273 for a segment, 273.4 for a part
+fn position(msn: u64, part: Option<u32>) -> String {
+    match part {
+        Some(part) => format!("{}.{}", msn, part),
+        None => msn.to_string(),
+    }
+}
+
+// How many parts `report` is ahead of (positive) or behind (negative) `edge`,
+// as far as can be told without knowing how many parts sibling segments have.
+// Crossing a segment boundary counts as one part only from or to the first
+// part of a segment; anything further is reported as two.
+fn compare_edges(report: (u64, u64), edge: (u64, u64)) -> i128 {
+    let ((report_msn, report_part), (edge_msn, edge_part)) = (report, edge);
+    if report_msn == edge_msn {
+        return i128::from(report_part) - i128::from(edge_part);
+    }
+    if report_msn.checked_add(1) == Some(edge_msn) {
+        return if edge_part == 0 { -1 } else { -2 };
+    }
+    if edge_msn.checked_add(1) == Some(report_msn) {
+        return if report_part == 0 { 1 } else { 2 };
+    }
+    if report_msn < edge_msn {
+        -2
+    } else {
+        2
+    }
+}
+
+impl MultivariantPlaylist {
+    /// Checks that the renditions of a Low-Latency HLS presentation fit
+    /// together. `media_playlists` maps the URIs listed in this playlist, as
+    /// written, to their media playlists; renditions missing from it aren't
+    /// checked.
+    ///
+    /// Every rendition must advertise the same PART-TARGET so players can
+    /// switch between them part by part, rendition reports must name
+    /// renditions of this playlist, and EXT-X-STREAM-INF must carry a
+    /// well-formed BANDWIDTH, CODECS and, for video, RESOLUTION.
+    pub fn validate_llhls(
+        &self,
+        media_playlists: &BTreeMap<String, MediaPlaylist>,
+    ) -> Vec<ValidationIssue> {
+        let mut issues = Issues::default();
+        for variant in &self.variants {
+            validate_stream_inf(variant, &mut issues);
+        }
+
+        let mut listed: Vec<&str> = Vec::new();
+        let uris = self
+            .variants
+            .iter()
+            .map(|variant| variant.uri.as_str())
+            .chain(
+                self.renditions
+                    .iter()
+                    .filter_map(|rendition| rendition.uri.as_deref()),
+            );
+        for listed_uri in uris {
+            if !listed.contains(&listed_uri) {
+                listed.push(listed_uri);
+            }
+        }
+        let playlists: Vec<_> = listed
+            .iter()
+            .filter_map(|listed_uri| {
+                media_playlists
+                    .get(*listed_uri)
+                    .map(|playlist| (*listed_uri, playlist))
+            })
+            .collect();
+
+        if let Some((first_uri, first)) = playlists.first() {
+            let part_target = |playlist: &MediaPlaylist| {
+                playlist
+                    .part_inf
+                    .as_ref()
+                    .map(|part_inf| part_inf.part_target())
+            };
+            let expected = part_target(first);
+            for (playlist_uri, playlist) in &playlists[1..] {
+                let actual = part_target(playlist);
+                if actual != expected {
+                    issues.error(
+                        Rule::PartTargetMismatch,
+                        format!(
+                            "{} has {} but {} has {}",
+                            playlist_uri,
+                            describe_part_target(actual),
+                            first_uri,
+                            describe_part_target(expected)
+                        ),
+                    );
+                }
+            }
+        }
+
+        // Report URIs are relative to the playlist carrying them. Relative
+        // URIs are rooted first, so dot segments resolve the same everywhere.
+        let known: Vec<String> = listed
+            .iter()
+            .map(|listed_uri| uri::resolve("/", listed_uri))
+            .collect();
+        for (playlist_uri, playlist) in &playlists {
+            let base = uri::resolve("/", playlist_uri);
+            for report in &playlist.rendition_reports {
+                let resolved = uri::resolve(&base, &report.uri);
+                if !known.contains(&resolved) {
+                    issues.error(
+                        Rule::RenditionReportUnknown,
+                        format!(
+                            "{} reports on {}, which the multivariant playlist doesn't list",
+                            playlist_uri, report.uri
+                        ),
+                    );
+                }
+            }
+        }
+        issues.0
+    }
+}
+
+fn describe_part_target(part_target: Option<Duration>) -> String {
+    match part_target {
+        Some(part_target) => format!("PART-TARGET {}s", seconds::display(&part_target)),
+        None => "no EXT-X-PART-INF".to_string(),
+    }
+}
+
+// Sample entries of the video codecs HLS carries
+const VIDEO_CODECS: [&str; 8] = [
+    "avc1", "avc3", "hvc1", "hev1", "dvh1", "dvhe", "av01", "vp09",
+];
+
+fn validate_stream_inf(variant: &VariantStream, issues: &mut Issues) {
+    let uri = &variant.uri;
+    if variant.bandwidth == 0 {
+        issues.error(
+            Rule::StreamInfAttributeInvalid,
+            format!("variant {} has a BANDWIDTH of 0", uri),
+        );
+    }
+    let Some(codecs) = &variant.codecs else {
+        issues.warning(
+            Rule::StreamInfAttributeMissing,
+            format!("variant {} has no CODECS", uri),
+        );
+        return;
+    };
+    let well_formed = |codec: &str| {
+        codec.starts_with(|c: char| c.is_ascii_alphanumeric())
+            && codec
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+    };
+    if !codecs.split(',').map(str::trim).all(well_formed) {
+        issues.error(
+            Rule::StreamInfAttributeInvalid,
+            format!("variant {} has malformed CODECS \"{}\"", uri, codecs),
+        );
+    }
+    let video = codecs.split(',').any(|codec| {
+        let entry = codec.trim().split('.').next().unwrap_or_default();
+        VIDEO_CODECS.contains(&entry)
+    });
+    match variant.resolution {
+        Some((width, height)) if width == 0 || height == 0 => issues.error(
+            Rule::StreamInfAttributeInvalid,
+            format!("variant {} has a RESOLUTION of {}x{}", uri, width, height),
+        ),
+        None if video => issues.warning(
+            Rule::StreamInfAttributeMissing,
+            format!("video variant {} has no RESOLUTION", uri),
+        ),
+        _ => {}
+    }
+}
+
+fn has_scheme(uri: &str) -> bool {
+    match uri.split_once(':') {
+        Some((scheme, _)) => {
+            !scheme.is_empty()
+                && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        }
+        None => false,
+    }
+}
+
+// Splits a URI into its non-digit skeleton and the numbers embedded in it
+fn numeric_shape(uri: &str) -> (String, Vec<u64>) {
+    let mut shape = String::new();
+    let mut numbers = Vec::new();
+    let mut digits = String::new();
+    for c in uri.chars().chain(core::iter::once('\0')) {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        if !digits.is_empty() {
+            numbers.push(digits.parse().unwrap_or(u64::MAX));
+            shape.push('#');
+            digits.clear();
+        }
+        shape.push(c);
+    }
+    (shape, numbers)
+}
+
+#[derive(Default)]
+struct Issues(Vec<ValidationIssue>);
+
+impl Issues {
+    fn error(&mut self, rule: Rule, message: String) {
+        self.0.push(ValidationIssue {
+            rule,
+            severity: Severity::Error,
+            message,
+        });
+    }
+
+    fn warning(&mut self, rule: Rule, message: String) {
+        self.0.push(ValidationIssue {
+            rule,
+            severity: Severity::Warning,
+            message,
+        });
+    }
+}
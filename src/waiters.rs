@@ -0,0 +1,96 @@
+use std::sync::Arc;
+
+use tokio::{sync::watch, time::Instant};
+
+/// Returned by [`BlockingWaiters::wait_for`] when the deadline passed first.
+/// Origins answer such requests with 503.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WaitTimeout;
+
+/// Wait queue for blocking playlist reloads on an origin. Request handlers
+/// wait for the `_HLS_msn`/`_HLS_part` they were asked for while the live
+/// writer reports what it published. Clones share the same queue.
+#[derive(Clone)]
+pub struct BlockingWaiters {
+    // Newest published position, see `position`
+    published: Arc<watch::Sender<Option<(u64, u32)>>>,
+}
+
+impl Default for BlockingWaiters {
+    fn default() -> Self {
+        BlockingWaiters {
+            published: Arc::new(watch::Sender::new(None)),
+        }
+    }
+}
+
+impl BlockingWaiters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that part `part` of segment `msn` was published, or with
+    /// `part` of `None` that the whole segment was. Wakes every waiter the
+    /// publication satisfies. Positions older than one already reported are
+    /// ignored.
+    pub fn notify_published(&self, msn: u64, part: Option<u32>) {
+        let published = position(msn, part);
+        self.published.send_if_modified(|current| {
+            if current.is_some_and(|current| current >= published) {
+                return false;
+            }
+            *current = Some(published);
+            true
+        });
+    }
+
+    /// Waits until part `part` of segment `msn` has been published, or with
+    /// `part` of `None` the whole segment. Returns at once if it already has.
+    ///
+    /// A part index past the last part of the segment is satisfied by the
+    /// first part of the next one, as the spec requires of servers.
+    pub async fn wait_for(
+        &self,
+        msn: u64,
+        part: Option<u32>,
+        deadline: Instant,
+    ) -> Result<(), WaitTimeout> {
+        let mut published = self.published.subscribe();
+        let wait = async {
+            // The sender lives as long as `self`, so this can't fail
+            let _ = published
+                .wait_for(|published| {
+                    published.is_some_and(|published| satisfies(published, msn, part))
+                })
+                .await;
+        };
+        tokio::time::timeout_at(deadline, wait)
+            .await
+            .map_err(|_| WaitTimeout)
+    }
+
+    /// The newest published (media sequence number, part index).
+    pub fn published(&self) -> Option<(u64, Option<u32>)> {
+        self.published.borrow().map(|(msn, part)| {
+            let part = (part != u32::MAX).then_some(part);
+            (msn, part)
+        })
+    }
+}
+
+// A completed segment orders after all of its parts
+fn position(msn: u64, part: Option<u32>) -> (u64, u32) {
+    (msn, part.unwrap_or(u32::MAX))
+}
+
+fn satisfies((published_msn, published_part): (u64, u32), msn: u64, part: Option<u32>) -> bool {
+    match part {
+        None => (published_msn, published_part) >= position(msn, None),
+        // Completing the segment doesn't publish a part index past its last
+        // one, only the next segment's first part does
+        Some(part) => {
+            published_msn > msn
+                || (published_msn == msn && published_part != u32::MAX && published_part >= part)
+        }
+    }
+}
@@ -0,0 +1,22 @@
+// Bindings for players and debugging tools running in the browser
+
+use wasm_bindgen::prelude::*;
+
+use crate::{parse_any_playlist, Playlist};
+
+/// Parses a media or a multivariant playlist, see [`parse_any_playlist`],
+/// into the object its serde serialization describes, e.g.
+/// `{ Media: { target_duration: 4, ... } }`. Throws an `Error` with the
+/// message of the parse error.
+#[wasm_bindgen(js_name = parsePlaylist)]
+pub fn parse_playlist(text: &str) -> Result<JsValue, JsValue> {
+    let playlist = parse_any_playlist(text).map_err(JsError::from)?;
+    to_js(&playlist)
+}
+
+// Goes through JSON, which JavaScript parses natively, rather than building
+// the object one property at a time across the boundary
+fn to_js(playlist: &Playlist) -> Result<JsValue, JsValue> {
+    let json = serde_json::to_string(playlist).map_err(JsError::from)?;
+    js_sys::JSON::parse(&json)
+}
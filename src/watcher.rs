@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+use futures_util::Stream;
+
+use crate::{ClientError, Fetch, LlHlsClient, MediaPlaylist, ParseOptions, ReloadPolicy};
+
+/// Watches live media playlists, one [`LlHlsClient`] per subscription,
+/// following the reload timing rules of the spec: blocking reloads while
+/// the playlist advertises `CAN-BLOCK-RELOAD=YES`, otherwise a reload every
+/// target duration, or half of one after an unchanged playlist.
+///
+/// After a failed reload the next one waits, starting from the initial
+/// backoff and doubling with every consecutive failure up to the maximum,
+/// so a struggling origin isn't hammered with requests.
+#[derive(Clone)]
+pub struct PlaylistWatcher<F> {
+    fetch: F,
+    policy: ReloadPolicy,
+    options: ParseOptions,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl<F: Fetch + Clone> PlaylistWatcher<F> {
+    pub fn new(fetch: F) -> Self {
+        PlaylistWatcher {
+            fetch,
+            policy: ReloadPolicy::default(),
+            options: ParseOptions::default(),
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(8),
+        }
+    }
+
+    pub fn policy(mut self, policy: ReloadPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Options the playlists are parsed with, see [`LlHlsClient::options`].
+    pub fn options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// How long to wait after the first of consecutive failed reloads, and
+    /// at most after many. Defaults to 500ms and 8s.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Follows the playlist at `url`, yielding it after every reload, or the
+    /// error a reload failed with, until it ends with EXT-X-ENDLIST.
+    pub fn subscribe(
+        &self,
+        url: impl Into<String>,
+    ) -> impl Stream<Item = Result<MediaPlaylist, ClientError>> {
+        struct State<F> {
+            client: LlHlsClient<F>,
+            // Wait before the next reload, after a failed one
+            backoff: Option<Duration>,
+            initial_backoff: Duration,
+            max_backoff: Duration,
+        }
+
+        let client = LlHlsClient::new(self.fetch.clone(), url)
+            .policy(self.policy.clone())
+            .options(self.options.clone());
+        let state = State {
+            client,
+            backoff: None,
+            initial_backoff: self.initial_backoff,
+            max_backoff: self.max_backoff,
+        };
+        futures_util::stream::unfold(Some(state), |state| async move {
+            let mut state = state?;
+            if let Some(backoff) = state.backoff {
+                tokio::time::sleep(backoff).await;
+            }
+            let result = state.client.reload().await;
+            state.backoff = match (&result, state.backoff) {
+                (Ok(_), _) => None,
+                (Err(_), None) => Some(state.initial_backoff),
+                (Err(_), Some(backoff)) => Some(backoff.saturating_mul(2).min(state.max_backoff)),
+            };
+            let ended = result.as_ref().is_ok_and(|playlist| !playlist.is_live());
+            Some((result, (!ended).then_some(state)))
+        })
+    }
+}
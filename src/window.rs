@@ -0,0 +1,150 @@
+use core::time::Duration;
+
+use crate::{DateTime, MediaPlaylist, PartialSegment, PreloadHint, Uri};
+
+/// The rolling window of segments an origin serves for a live rendition.
+///
+/// Parts and segments are appended at the live edge. As segments complete,
+/// the oldest are removed, advancing EXT-X-MEDIA-SEQUENCE, as long as the
+/// window is still listed afterwards. The window is never shorter than
+/// three target durations, the least the spec allows a live playlist to
+/// list. Parts more than three target durations from the end are dropped
+/// from their segments, as the spec recommends.
+pub struct LivePlaylistWindow {
+    playlist: MediaPlaylist,
+    window: Duration,
+}
+
+impl LivePlaylistWindow {
+    /// A window over `playlist` that keeps three target durations of media.
+    pub fn new(playlist: MediaPlaylist) -> Self {
+        let mut window = LivePlaylistWindow {
+            playlist,
+            window: Duration::ZERO,
+        };
+        window.trim();
+        window
+    }
+
+    /// Keeps at least `window` of media listed, e.g. for DVR. Shorter
+    /// windows are raised to three target durations.
+    pub fn set_window(&mut self, window: Duration) {
+        self.window = window;
+        self.trim();
+    }
+
+    /// The media kept listed once old segments are removed.
+    pub fn window(&self) -> Duration {
+        self.window.max(self.minimum())
+    }
+
+    pub fn playlist(&self) -> &MediaPlaylist {
+        &self.playlist
+    }
+
+    pub fn into_playlist(self) -> MediaPlaylist {
+        self.playlist
+    }
+
+    /// The EXT-X-MEDIA-SEQUENCE: MSN of the oldest segment listed.
+    pub fn media_sequence_number(&self) -> u64 {
+        self.playlist.media_sequence_number
+    }
+
+    /// MSN of the segment being written, which pushed parts belong to.
+    pub fn next_msn(&self) -> u64 {
+        self.playlist.first_listed_msn() + self.playlist.media_segments.len() as u64
+    }
+
+    /// Appends a part to the segment being written and returns its MSN and
+    /// part index.
+    pub fn push_part(&mut self, part: PartialSegment) -> (u64, u32) {
+        let position = (self.next_msn(), self.playlist.trailing_parts.len() as u32);
+        self.playlist.push_part(part);
+        self.playlist.drop_expired_parts();
+        position
+    }
+
+    /// Completes the segment being written from the parts pushed since the
+    /// previous one, removes the segments that fell out of the window and
+    /// returns the MSN of the completed segment.
+    pub fn complete_segment(
+        &mut self,
+        duration: Duration,
+        uri: Uri,
+        program_date_time: Option<DateTime>,
+    ) -> u64 {
+        let msn = self.next_msn();
+        self.playlist
+            .complete_segment(duration, uri, program_date_time);
+        self.trim();
+        msn
+    }
+
+    /// Hints at the next part or init segment, replacing the hint of the
+    /// same TYPE.
+    pub fn add_preload_hint(&mut self, preload_hint: PreloadHint) {
+        self.playlist.add_preload_hint(preload_hint);
+    }
+
+    fn minimum(&self) -> Duration {
+        Duration::from_secs(3 * u64::from(self.playlist.target_duration))
+    }
+
+    // A segment is removed only if the window is still listed without it
+    fn trim(&mut self) {
+        let window = self.window();
+        let mut listed: Duration = self
+            .playlist
+            .media_segments
+            .iter()
+            .map(|segment| segment.duration)
+            .sum();
+        while self.playlist.media_segments.len() > 1 {
+            let oldest = self.playlist.oldest_duration();
+            if listed - oldest < window {
+                break;
+            }
+            listed -= oldest;
+            self.playlist.pop_segment();
+        }
+        self.playlist.drop_expired_parts();
+    }
+}
+
+impl MediaPlaylist {
+    // Drops the parts of segments that end more than three target durations
+    // from the end of the playlist, and returns how many
+    pub(crate) fn drop_expired_parts(&mut self) -> usize {
+        let retention = Duration::from_secs(3 * u64::from(self.target_duration));
+        let mut from_end: Duration = self
+            .trailing_parts
+            .iter()
+            .map(|part| part.part_duration)
+            .sum();
+        let mut keep_parts_from = self.media_segments.len();
+        for (index, segment) in self.media_segments.iter().enumerate().rev() {
+            if from_end >= retention {
+                break;
+            }
+            from_end += segment.duration;
+            keep_parts_from = index;
+        }
+        let mut dropped = 0;
+        self.media_segments.update_first(
+            keep_parts_from,
+            |segment| !segment.partial_segments.is_empty(),
+            |segment| {
+                dropped += segment.partial_segments.len();
+                segment.partial_segments.clear();
+            },
+        );
+        dropped
+    }
+
+    pub(crate) fn oldest_duration(&self) -> Duration {
+        self.media_segments
+            .first()
+            .map_or(Duration::ZERO, |segment| segment.duration)
+    }
+}
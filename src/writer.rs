@@ -0,0 +1,368 @@
+#[cfg(feature = "tokio")]
+use std::sync::Arc;
+use std::{io, time::Duration};
+
+#[cfg(feature = "tokio")]
+use tokio::sync::{broadcast, watch};
+
+use crate::{
+    DateTime, Map, MediaPlaylist, PartialSegment, PlaylistSink, PreloadHint, RemovedDateRanges,
+    Skip, Uri,
+};
+
+/// Something the [`LiveWriter`] published.
+#[cfg(feature = "tokio")]
+#[derive(Clone)]
+pub enum PublicationEvent {
+    /// Part `index` of segment `msn` was published.
+    Part {
+        msn: u64,
+        index: u32,
+        part: PartialSegment,
+    },
+    /// Segment `msn` was completed.
+    Segment { msn: u64 },
+    /// The playlist after a publication, shared with every subscriber.
+    Snapshot(Arc<MediaPlaylist>),
+}
+
+// Events a lagging subscriber can fall behind by before it misses some
+#[cfg(feature = "tokio")]
+const EVENT_CAPACITY: usize = 256;
+
+/// What the size budget of a [`LiveWriter`] did to the playlist, see
+/// [`LiveWriter::set_size_budget`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct BudgetReport {
+    /// Size of the playlist clients reload, once trimmed.
+    pub serialized_len: usize,
+    /// Parts dropped from segments they were no longer needed for.
+    pub parts_trimmed: usize,
+    /// Segments removed on top of those the window would have removed.
+    pub segments_trimmed: u32,
+    /// Media left listed, shorter than the configured window when segments
+    /// were trimmed.
+    pub window: Duration,
+    pub within_budget: bool,
+}
+
+/// Owns the playlist of a live rendition on an origin and publishes parts
+/// and segments into it.
+///
+/// Segments older than the configured window are removed as new ones are
+/// completed. A size budget can trim the playlist further.
+///
+/// With the `tokio` feature, every publication is also broadcast to
+/// subscribers, so edge push, monitoring or prefetching can react to new
+/// media without polling the playlist.
+pub struct LiveWriter {
+    playlist: MediaPlaylist,
+    // DVR window
+    window: Option<Duration>,
+    size_budget: Option<usize>,
+    budget_report: Option<BudgetReport>,
+    // Init segment to switch to once the segment being written completes
+    pending_map: Option<Map>,
+    #[cfg(feature = "tokio")]
+    events: broadcast::Sender<PublicationEvent>,
+    #[cfg(feature = "tokio")]
+    snapshots: watch::Sender<Arc<MediaPlaylist>>,
+}
+
+impl LiveWriter {
+    pub fn new(playlist: MediaPlaylist) -> Self {
+        LiveWriter {
+            #[cfg(feature = "tokio")]
+            events: broadcast::Sender::new(EVENT_CAPACITY),
+            #[cfg(feature = "tokio")]
+            snapshots: watch::Sender::new(Arc::new(playlist.clone())),
+            playlist,
+            window: None,
+            size_budget: None,
+            budget_report: None,
+            pending_map: None,
+        }
+    }
+
+    pub fn playlist(&self) -> &MediaPlaylist {
+        &self.playlist
+    }
+
+    /// Keeps about `window` of media listed, the DVR window. Without a
+    /// window segments are never removed.
+    pub fn set_window(&mut self, window: Option<Duration>) {
+        self.window = window;
+        self.trim_window();
+        self.enforce_budget();
+    }
+
+    /// Keeps the playlist clients reload under `max_bytes` once rendered.
+    /// When the server offers delta updates that is the delta update, as
+    /// clients only fetch the full playlist once.
+    ///
+    /// Over budget, parts more than three target durations from the end are
+    /// dropped first, since clients no longer need them. Then the oldest
+    /// segments are removed, but never below three target durations of
+    /// media (or CAN-SKIP-UNTIL with delta updates), as the spec requires.
+    pub fn set_size_budget(&mut self, max_bytes: Option<usize>) {
+        self.size_budget = max_bytes;
+        self.enforce_budget();
+    }
+
+    /// What the size budget last did to the playlist, or `None` when it
+    /// fits without trimming.
+    pub fn budget_report(&self) -> Option<&BudgetReport> {
+        self.budget_report.as_ref()
+    }
+
+    /// Publishes a part of the segment currently being written.
+    pub fn push_part(&mut self, part: PartialSegment) {
+        #[cfg(feature = "tokio")]
+        let event = PublicationEvent::Part {
+            msn: self.playlist.first_listed_msn() + self.playlist.media_segments.len() as u64,
+            index: self.playlist.trailing_parts.len() as u32,
+            part: part.clone(),
+        };
+        self.playlist.push_part(part);
+        self.enforce_budget();
+        #[cfg(feature = "tokio")]
+        self.publish(event);
+    }
+
+    /// Publishes a CMAF chunk of the segment currently being written as a
+    /// part. Only chunks starting with a keyframe are marked
+    /// INDEPENDENT=YES, since players joining mid-segment must start
+    /// decoding at one.
+    pub fn push_chunk(&mut self, duration: Duration, uri: impl Into<String>, keyframe: bool) {
+        let mut part = PartialSegment::new(duration, uri);
+        part.independent = keyframe.then_some(true);
+        self.push_part(part);
+    }
+
+    /// Completes the segment currently being written from the parts pushed
+    /// since the previous one.
+    pub fn complete_segment(
+        &mut self,
+        duration: Duration,
+        uri: Uri,
+        program_date_time: Option<DateTime>,
+    ) {
+        self.playlist
+            .complete_segment(duration, uri, program_date_time);
+        if let Some(map) = self.pending_map.take() {
+            self.playlist.rotate_map(map);
+        }
+        self.trim_window();
+        self.enforce_budget();
+        #[cfg(feature = "tokio")]
+        {
+            let (msn, _) = self.playlist.last_position();
+            self.publish(PublicationEvent::Segment { msn });
+        }
+    }
+
+    /// Hints at the next part or init segment, replacing the hint of the
+    /// same TYPE.
+    pub fn add_preload_hint(&mut self, preload_hint: PreloadHint) {
+        self.playlist.add_preload_hint(preload_hint);
+        self.enforce_budget();
+    }
+
+    /// Switches to a new init segment, e.g. after the encoder changed codec
+    /// or resolution. The next segment starts with EXT-X-DISCONTINUITY and
+    /// the new EXT-X-MAP: the segment currently being written if no part of
+    /// it was pushed yet, otherwise the one after it.
+    pub fn rotate_map(&mut self, map: Map) {
+        if self.playlist.trailing_parts.is_empty() {
+            self.playlist.rotate_map(map);
+            self.enforce_budget();
+        } else {
+            self.pending_map = Some(map);
+        }
+    }
+
+    /// Renders the playlist into `sink` as the full playlist of `variant`.
+    pub fn publish_to<S: PlaylistSink + ?Sized>(
+        &self,
+        sink: &mut S,
+        variant: &str,
+    ) -> io::Result<()> {
+        sink.write_full(variant, &self.playlist.to_string())
+    }
+
+    fn trim_window(&mut self) {
+        let Some(window) = self.window else {
+            return;
+        };
+        while self.listed_duration() - self.oldest_duration() >= window
+            && self.playlist.media_segments.len() > 1
+        {
+            self.playlist.pop_segment();
+        }
+    }
+
+    fn enforce_budget(&mut self) {
+        let Some(max_bytes) = self.size_budget else {
+            self.budget_report = None;
+            return;
+        };
+        if self.reloaded_len() <= max_bytes {
+            self.budget_report = None;
+            return;
+        }
+
+        let parts_trimmed = self.playlist.drop_expired_parts();
+
+        let mut segments_trimmed = 0;
+        let minimum = Duration::from_secs(3 * u64::from(self.playlist.target_duration))
+            .max(self.playlist.server_control.can_skip_until);
+        while self.reloaded_len() > max_bytes
+            && self.playlist.media_segments.len() > 1
+            && self.listed_duration() - self.oldest_duration() >= minimum
+        {
+            self.playlist.pop_segment();
+            segments_trimmed += 1;
+        }
+
+        let serialized_len = self.reloaded_len();
+        self.budget_report = Some(BudgetReport {
+            serialized_len,
+            parts_trimmed,
+            segments_trimmed,
+            window: self.listed_duration(),
+            within_budget: serialized_len <= max_bytes,
+        });
+    }
+
+    // Size of what clients reload: the delta update when there is one
+    fn reloaded_len(&self) -> usize {
+        let full = self.playlist.estimated_serialized_len();
+        let skipped = self
+            .playlist
+            .skippable_segments(self.playlist.server_control.can_skip_until);
+        if skipped == 0 {
+            return full;
+        }
+        let skipped_len: usize = self
+            .playlist
+            .media_segments
+            .iter()
+            .take(skipped)
+            .map(|segment| segment.estimated_serialized_len(self.playlist.line_ending))
+            .sum();
+        full - skipped_len
+            + crate::line_len(
+                Skip::new(skipped as u64).estimated_serialized_len(),
+                self.playlist.line_ending,
+            )
+    }
+
+    fn listed_duration(&self) -> Duration {
+        self.playlist
+            .media_segments
+            .iter()
+            .map(|segment| segment.duration)
+            .sum()
+    }
+
+    fn oldest_duration(&self) -> Duration {
+        self.playlist.oldest_duration()
+    }
+
+    /// Subscribes to publication events from now on.
+    #[cfg(feature = "tokio")]
+    pub fn subscribe(&self) -> broadcast::Receiver<PublicationEvent> {
+        self.events.subscribe()
+    }
+
+    /// Watches the newest snapshot of the playlist, for consumers that only
+    /// care about the latest state.
+    #[cfg(feature = "tokio")]
+    pub fn snapshots(&self) -> watch::Receiver<Arc<MediaPlaylist>> {
+        self.snapshots.subscribe()
+    }
+
+    #[cfg(feature = "tokio")]
+    fn publish(&self, event: PublicationEvent) {
+        let snapshot = Arc::new(self.playlist.clone());
+        // Sending only fails without subscribers, which is fine
+        let _ = self.events.send(event);
+        let _ = self
+            .events
+            .send(PublicationEvent::Snapshot(snapshot.clone()));
+        self.snapshots.send_replace(snapshot);
+    }
+}
+
+impl MediaPlaylist {
+    /// The delta update served to `_HLS_skip=YES` requests: the playlist with
+    /// the oldest segments replaced by EXT-X-SKIP, keeping at least
+    /// `can_skip_until` of media listed, and EXT-X-VERSION raised to the 9
+    /// EXT-X-SKIP needs. Dateranges are all kept. Without segments to skip
+    /// the full playlist is returned.
+    pub fn to_delta(&self, can_skip_until: Duration) -> MediaPlaylist {
+        let skipped = self.skippable_segments(can_skip_until);
+        let mut delta = self.clone();
+        if skipped > 0 {
+            delta.media_segments = self
+                .media_segments
+                .slice(skipped, self.media_segments.len());
+            delta.skip = Some(Skip::new(skipped as u64));
+            delta.version = delta.version.max(delta.required_version());
+        }
+        delta
+    }
+
+    /// The delta update served to `_HLS_skip=v2` requests. On top of
+    /// [`MediaPlaylist::to_delta`], dateranges that start before the skipped
+    /// segments end are left out, as the client got them with an earlier
+    /// reload, and the ones in `removed` a client may still hold are listed
+    /// in RECENTLY-REMOVED-DATERANGES, which raises EXT-X-VERSION to 10.
+    ///
+    /// The skip boundary is taken from the newest EXT-X-PROGRAM-DATE-TIME at
+    /// or before it; without one every daterange is kept. Without the
+    /// `chrono` feature dates are compared as text.
+    pub fn to_delta_v2(
+        &self,
+        can_skip_until: Duration,
+        removed: &RemovedDateRanges,
+    ) -> MediaPlaylist {
+        let mut delta = self.to_delta(can_skip_until);
+        let Some(skip) = &mut delta.skip else {
+            return delta;
+        };
+        let last_skipped_msn = self.first_listed_msn() + skip.skipped_segments - 1;
+        skip.recently_removed_dateranges = removed.since(last_skipped_msn);
+        let boundary = self
+            .media_segments
+            .iter()
+            .take(skip.skipped_segments as usize + 1)
+            .filter_map(|segment| segment.program_date_time.as_ref())
+            .last();
+        if let Some(boundary) = boundary {
+            delta
+                .date_ranges
+                .retain(|range| range.start_date >= *boundary);
+        }
+        delta.version = delta.version.max(delta.required_version());
+        delta
+    }
+
+    // Number of oldest segments a delta update can skip while still listing
+    // `can_skip_until` of media
+    pub(crate) fn skippable_segments(&self, can_skip_until: Duration) -> usize {
+        if can_skip_until.is_zero() {
+            return 0;
+        }
+        let mut retained = self.retained_duration();
+        let mut skipped = 0;
+        for segment in &self.media_segments {
+            if retained - segment.duration < can_skip_until {
+                break;
+            }
+            retained -= segment.duration;
+            skipped += 1;
+        }
+        skipped
+    }
+}
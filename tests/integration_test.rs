@@ -1,8 +1,4838 @@
-use llhls_rs::read_playlist;
-use std::fs;
+// Reads playlists from files and drives the live publishing APIs
+#![cfg(feature = "std")]
+
+use llhls_rs::{
+    apply_directives, parse_attribute_list, parse_bytes, read_any_playlist,
+    read_multivariant_playlist, read_playlist, read_playlist_with_hooks,
+    read_playlist_with_options, write_atomically, AttributeListError, AttributeValue,
+    AudioRendition, BandwidthMeter, BudgetReport, BufferSimulation, BuilderError, ByteRange,
+    CadenceAnalyzer, ClientSession, CmafChunk, CmafSegmenter, ContainerFormat, CustomTag,
+    DateRange, DateRangeError, DateRangeSet, DateTime, Define, DeliveryDirectives, DirectiveError,
+    DuplicateTagPolicy, EncoderConfig, EncoderConfigError, EnumeratedStringList, ErrorCode,
+    FileSink, HexBytes, Hooks, IFrameStream, Key, KeyMethod, LineEnding, LivePlaylistWindow,
+    LiveWriter, Map, MediaPlaylist, MediaPlaylistBuilder, MediaSegment, MediaType, MemorySink,
+    MultivariantPlaylist, NoHooks, NumericError, NumericPolicy, ParseAttributeError, ParseHexError,
+    ParseListError, ParseOptions, ParsePlaylistError, ParseTagError, ParseWarning, PartInf,
+    PartialSegment, Playlist, PlaylistHistory, PlaylistParser, PlaylistSink, PlaylistType,
+    PreloadHint, PreloadHintType, ReloadCoordinator, ReloadPolicy, ReloadRequest,
+    RemovedDateRanges, Rendition, RenditionReport, Repair, Rule, ServerControl, SessionData,
+    SessionDataError, SessionDataFormat, SessionError, Severity, SimulatedPart, Skip,
+    SkipDirective, Stall, SubtitleRendition, TagPosition, TimingWarning, UnknownTag, UpdateKind,
+    Uri, UriKind, UriPolicy, ValidationIssue, ValidationProfile, VariablePolicy, VariantStream,
+    ERROR_CODES,
+};
+use std::{
+    collections::BTreeMap,
+    fs,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, UNIX_EPOCH},
+};
+
+// Parses a playlist put together by the test, as read_playlist_with_options
+// would once written to a file
+fn parse_with_options<H: Hooks + ?Sized>(
+    source: &str,
+    options: ParseOptions,
+    hooks: &mut H,
+) -> Result<MediaPlaylist, ParsePlaylistError> {
+    let mut parser = PlaylistParser::with_options(options);
+    parser.feed(source.as_bytes(), hooks)?;
+    parser.finish(hooks)
+}
 
 #[test]
 fn parse_ll_hls_basic() {
     let file = fs::File::open("tests/resources/ll-hls.m3u8").expect("Opened test file");
     assert!(read_playlist(file).is_ok())
 }
+
+#[test]
+fn estimated_serialized_len_matches_canonical_playlist() {
+    let source =
+        fs::read_to_string("tests/resources/ll-hls-canonical.m3u8").expect("Read test file");
+    let file = fs::File::open("tests/resources/ll-hls-canonical.m3u8").expect("Opened test file");
+    let playlist = read_playlist(file).expect("Parsed test file");
+    assert_eq!(playlist.estimated_serialized_len(), source.len());
+}
+
+#[test]
+fn delta_skip_reports_recently_removed_dateranges() {
+    let mut removed = RemovedDateRanges::new();
+    removed.record("ad-1", 268);
+    removed.record("ad-2", 271);
+    removed.record("ad-3", 272);
+
+    let skip = removed.skip(3, 271);
+    assert_eq!(skip.recently_removed_dateranges, vec!["ad-2", "ad-3"]);
+    assert_eq!(
+        skip.to_string(),
+        "#EXT-X-SKIP:SKIPPED-SEGMENTS=3,RECENTLY-REMOVED-DATERANGES=\"ad-2\tad-3\""
+    );
+
+    removed.prune(272);
+    assert_eq!(
+        removed.skip(4, 272).recently_removed_dateranges,
+        vec!["ad-3"]
+    );
+    assert_eq!(
+        removed.skip(5, 273).to_string(),
+        "#EXT-X-SKIP:SKIPPED-SEGMENTS=5"
+    );
+}
+
+#[cfg(feature = "chrono")]
+fn date(date: &str) -> DateTime {
+    date.parse().expect("Parsed date")
+}
+
+#[cfg(not(feature = "chrono"))]
+fn date(date: &str) -> DateTime {
+    date.to_string()
+}
+
+// The time feature converts dates without changing DateTime, so it can be
+// enabled along with chrono
+#[cfg(feature = "time")]
+#[test]
+fn time_feature_reads_and_builds_dates() {
+    let source =
+        fs::read_to_string("tests/resources/ll-hls-canonical.m3u8").expect("Read test file");
+    let playlist = parse_bytes(source.as_bytes()).expect("Parsed playlist");
+    let segment = playlist.media_segments().get(1).expect("Segment listed");
+    let offset = segment
+        .program_date_time_offset()
+        .expect("Read program date time");
+    assert_eq!(offset.unix_timestamp(), 1_550_110_440);
+    assert_eq!(offset.millisecond(), 106);
+
+    let range = DateRange::new("ad-1", llhls_rs::from_offset_date_time(offset));
+    assert_eq!(range.start_date, date("2019-02-14T02:14:00.106Z"));
+    assert_eq!(range.start_date_offset(), Some(offset));
+    assert_eq!(range.end_date_offset(), None);
+}
+
+#[test]
+fn daterange_updates_merge_by_id() {
+    let start = || date("2019-02-14T02:14:00.106Z");
+    let mut ranges = DateRangeSet::new();
+    let mut ad = DateRange::new("ad-1", start());
+    ad.class = Some("com.example.ad".to_string());
+    ad.planned_duration = Some(30.0);
+    ranges.add(ad.clone()).expect("Added daterange");
+    assert!(matches!(
+        ranges.add(ad),
+        Err(DateRangeError::Duplicate { .. })
+    ));
+
+    let mut ended = DateRange::new("ad-1", start());
+    ended.duration = Some(29.5);
+    ranges.update(ended).expect("Merged daterange");
+    assert_eq!(
+        ranges.get("ad-1").expect("Kept daterange").to_string(),
+        "#EXT-X-DATERANGE:ID=\"ad-1\",CLASS=\"com.example.ad\",START-DATE=\"2019-02-14T02:14:00.106Z\",DURATION=29.5,PLANNED-DURATION=30"
+    );
+
+    let mut conflicting = DateRange::new("ad-1", start());
+    conflicting.planned_duration = Some(15.0);
+    assert_eq!(
+        ranges.update(conflicting),
+        Err(DateRangeError::Conflict {
+            id: "ad-1".to_string(),
+            attribute: "PLANNED-DURATION".to_string()
+        })
+    );
+
+    assert!(ranges.remove("ad-1", 272).is_some());
+    assert_eq!(ranges.iter().count(), 0);
+    assert_eq!(ranges.removed().since(272), vec!["ad-1"]);
+}
+
+#[test]
+fn missing_playlist_tag_is_reported() {
+    let file =
+        fs::File::open("tests/resources/missing-target-duration.m3u8").expect("Opened test file");
+    assert!(matches!(
+        read_playlist(file),
+        Err(ParsePlaylistError::MissingField { line: None, tag, field: "target_duration" })
+            if tag == "EXT-X-TARGETDURATION"
+    ));
+}
+
+#[test]
+fn missing_tag_attribute_is_reported() {
+    let file =
+        fs::File::open("tests/resources/missing-part-duration.m3u8").expect("Opened test file");
+    assert!(matches!(
+        read_playlist(file),
+        Err(ParsePlaylistError::MissingField { line: Some(_), tag, field: "part_duration" })
+            if tag == "EXT-X-PART"
+    ));
+}
+
+fn segment_uri(uri: &str) -> Uri {
+    Uri::parse_from(uri.to_string()).expect("Parsed URI")
+}
+
+// Uri is the same type whatever the features, which only decide whether
+// it's validated
+#[test]
+fn uris_are_validated_with_fluent_uri() {
+    let uri: Uri = "fileSequence270.mp4?token=a%20b"
+        .parse()
+        .expect("Parsed URI");
+    assert_eq!(uri.as_str(), "fileSequence270.mp4?token=a%20b");
+    assert_eq!(uri.to_string(), uri.clone().into_string());
+    assert_eq!(
+        Uri::parse_from("file Sequence270.mp4".to_string()).is_err(),
+        cfg!(feature = "fluent-uri")
+    );
+}
+
+#[test]
+fn programmatic_playlist_construction() {
+    let mut part = PartialSegment::new(Duration::from_micros(333340), "filePart270.0.mp4");
+    part.independent = Some(true);
+    let segment = MediaSegment::new(
+        Duration::from_micros(4000080),
+        segment_uri("fileSequence270.mp4"),
+        vec![part],
+        None,
+    );
+    let mut builder = MediaPlaylistBuilder::new();
+    builder
+        .target_duration(4)
+        .version(9)
+        .part_inf(PartInf::new(Duration::from_micros(333340)))
+        .media_sequence_number(270)
+        .media_segments(vec![segment])
+        .preload_hint(Some(PreloadHint::new(
+            PreloadHintType::Part,
+            "filePart271.0.mp4",
+        )))
+        .rendition_reports(vec![RenditionReport::new(
+            "../1M/waitForMSN.php",
+            270,
+            Some(1),
+        )]);
+    // Without EXT-X-SERVER-CONTROL, as a playlist lacking the tag
+    let playlist = builder.build().expect("Built playlist");
+    assert!(!playlist.server_control().can_block_reload());
+    assert!(!playlist.to_string().contains("#EXT-X-SERVER-CONTROL"));
+
+    builder.server_control(ServerControl::new(
+        true,
+        Duration::from_secs(1),
+        Duration::from_secs(24),
+    ));
+    let playlist = builder.build().expect("Built playlist");
+    assert!(playlist.estimated_serialized_len() > 0);
+    assert_eq!(Skip::new(3).to_string(), "#EXT-X-SKIP:SKIPPED-SEGMENTS=3");
+}
+
+#[test]
+fn parsed_playlist_can_be_shared_across_threads() {
+    let file = fs::File::open("tests/resources/ll-hls.m3u8").expect("Opened test file");
+    let playlist = std::sync::Arc::new(read_playlist(file).expect("Parsed test file"));
+    let shared = std::sync::Arc::clone(&playlist);
+    let len = std::thread::spawn(move || shared.estimated_serialized_len())
+        .join()
+        .expect("Joined thread");
+    assert_eq!(len, playlist.estimated_serialized_len());
+}
+
+#[test]
+fn snapshots_are_independent_of_later_updates() {
+    let file = fs::File::open("tests/resources/ll-hls.m3u8").expect("Opened test file");
+    let mut playlist = read_playlist(file).expect("Parsed test file");
+    assert_eq!(playlist.media_segments().len(), 4);
+    assert_eq!(playlist.trailing_parts().len(), 4);
+
+    let snapshot = playlist.clone();
+    playlist.push_part(PartialSegment::new(
+        Duration::from_micros(333340),
+        "filePart273.4.mp4",
+    ));
+    playlist.complete_segment(
+        Duration::from_micros(1666700),
+        segment_uri("fileSequence273.mp4"),
+        None,
+    );
+    playlist.pop_segment();
+
+    assert_eq!(playlist.media_segments().len(), 4);
+    assert!(playlist.trailing_parts().is_empty());
+    assert_eq!(snapshot.media_segments().len(), 4);
+    assert_eq!(snapshot.trailing_parts().len(), 4);
+    assert!(playlist.estimated_serialized_len() != snapshot.estimated_serialized_len());
+}
+
+#[test]
+fn history_answers_time_travel_queries() {
+    let file = fs::File::open("tests/resources/ll-hls.m3u8").expect("Opened test file");
+    let mut playlist = read_playlist(file).expect("Parsed test file");
+    let start = UNIX_EPOCH + Duration::from_secs(1_000);
+    let mut history = PlaylistHistory::new(2);
+    assert!(history.at(start).is_none());
+
+    // Parts 273.0-273.3 are live at `start`
+    history.record(start, playlist.clone());
+    playlist.push_part(PartialSegment::new(
+        Duration::from_micros(333340),
+        "filePart273.4.mp4",
+    ));
+    history.record(start + Duration::from_secs(1), playlist.clone());
+    playlist.complete_segment(
+        Duration::from_micros(1666700),
+        segment_uri("fileSequence273.mp4"),
+        None,
+    );
+    playlist.push_part(PartialSegment::new(
+        Duration::from_micros(333340),
+        "filePart274.0.mp4",
+    ));
+    history.record(start + Duration::from_secs(2), playlist.clone());
+
+    assert_eq!(history.len(), 2);
+    assert!(history.at(start).is_none());
+    assert_eq!(
+        history.live_edge_at(start + Duration::from_millis(1_500)),
+        Some((273, Some(4)))
+    );
+    assert_eq!(
+        history.live_edge_at(start + Duration::from_secs(60)),
+        Some((274, Some(0)))
+    );
+    assert_eq!(
+        history.first_seen(273),
+        Some(start + Duration::from_secs(1))
+    );
+    assert_eq!(
+        history.first_seen(274),
+        Some(start + Duration::from_secs(2))
+    );
+    assert_eq!(history.first_seen(275), None);
+}
+
+#[derive(Default)]
+struct CountingHooks {
+    tags: usize,
+    segments: usize,
+    warnings: Vec<ParseWarning>,
+    serialized: Vec<usize>,
+}
+
+impl Hooks for CountingHooks {
+    fn on_tag(&mut self, _tag: &str, _value: &str) {
+        self.tags += 1;
+    }
+
+    fn on_segment_complete(&mut self, _segment: &MediaSegment) {
+        self.segments += 1;
+    }
+
+    fn on_warning(&mut self, warning: &ParseWarning) {
+        self.warnings.push(warning.clone());
+    }
+
+    fn on_serialize(&mut self, _playlist: &MediaPlaylist, bytes: usize) {
+        self.serialized.push(bytes);
+    }
+}
+
+#[test]
+fn parse_hooks_observe_tags_segments_and_warnings() {
+    let file = fs::File::open("tests/resources/unknown-tag.m3u8").expect("Opened test file");
+    let mut hooks = CountingHooks::default();
+    let playlist = read_playlist_with_hooks(file, &mut hooks).expect("Parsed test file");
+    assert_eq!(playlist.media_segments().len(), 2);
+    assert_eq!(hooks.tags, 8);
+    assert_eq!(hooks.segments, 2);
+    assert_eq!(
+        hooks.warnings,
+        vec![ParseWarning::UnknownTag {
+            line: 7,
+            tag: "EXT-X-CUE-OUT".to_string()
+        }]
+    );
+
+    let rendered = playlist.to_string();
+    let mut buffer = Vec::new();
+    playlist.serialize_into_with_hooks(&mut buffer, &mut hooks);
+    let mut written = Vec::new();
+    playlist
+        .write_to_with_hooks(&mut written, &mut hooks)
+        .expect("Wrote playlist");
+    assert_eq!(hooks.serialized, [rendered.len(), rendered.len()]);
+    assert_eq!(buffer, written);
+}
+
+#[test]
+fn cadence_analyzer_measures_parts_jitter_and_hints() {
+    let file = fs::File::open("tests/resources/ll-hls.m3u8").expect("Opened test file");
+    let mut playlist = read_playlist(file).expect("Parsed test file");
+    let start = UNIX_EPOCH + Duration::from_secs(1_000);
+    let mut analyzer = CadenceAnalyzer::new();
+    analyzer.observe(start, &playlist);
+    assert_eq!(analyzer.report().parts_observed, 0);
+
+    // Published exactly one part duration later, as hinted
+    playlist.push_part(PartialSegment::new(
+        Duration::from_millis(500),
+        "filePart273.4.mp4",
+    ));
+    playlist.set_preload_hint(Some(PreloadHint::new(
+        PreloadHintType::Part,
+        "filePart273.5.mp4",
+    )));
+    analyzer.observe(start + Duration::from_millis(333), &playlist);
+    analyzer.observe(start + Duration::from_millis(600), &playlist);
+
+    // 100ms late and not the hinted URI
+    playlist.push_part(PartialSegment::new(
+        Duration::from_millis(500),
+        "filePart273.5b.mp4",
+    ));
+    analyzer.observe(start + Duration::from_millis(933), &playlist);
+
+    let report = analyzer.report();
+    assert_eq!(report.parts_observed, 2);
+    assert_eq!(report.hint_accuracy, Some(0.5));
+    let max_jitter = report.max_jitter.expect("Measured jitter");
+    assert!(max_jitter > Duration::from_millis(99) && max_jitter < Duration::from_millis(101));
+    let parts_per_second = report.parts_per_second.expect("Measured rate");
+    assert!((parts_per_second - 2.0 / 0.933).abs() < 1e-6);
+
+    // A part too long to expect the next one after isn't measured, only
+    // how late it came itself
+    playlist.push_part(PartialSegment::new(Duration::MAX, "filePart273.6.mp4"));
+    analyzer.observe(start + Duration::from_secs(2), &playlist);
+    playlist.push_part(PartialSegment::new(
+        Duration::from_millis(500),
+        "filePart273.7.mp4",
+    ));
+    analyzer.observe(start + Duration::from_secs(3), &playlist);
+    let report = analyzer.report();
+    assert_eq!(report.parts_observed, 4);
+    assert_eq!(report.max_jitter, Some(Duration::from_millis(567)));
+}
+
+#[test]
+fn repair_fixes_and_reports_common_mistakes() {
+    let file = fs::File::open("tests/resources/needs-repair.m3u8").expect("Opened test file");
+    let mut playlist = read_playlist(file).expect("Parsed test file");
+    assert_eq!(
+        playlist.repair(),
+        vec![
+            Repair::QuotedAttribute {
+                tag: "EXT-X-PART",
+                attribute: "URI",
+                value: "filePart268.0.mp4".to_string()
+            },
+            Repair::DerivedPartInf {
+                part_target: Duration::from_millis(500)
+            },
+            Repair::RemovedStalePreloadHint {
+                uri: "filePart268.1.mp4".to_string()
+            },
+            Repair::RaisedVersion { from: 3, to: 9 },
+        ]
+    );
+    assert!(playlist.repair().is_empty());
+    assert!(playlist
+        .to_string()
+        .contains("#EXT-X-PART:DURATION=0.33334,URI=\"filePart268.0.mp4\",INDEPENDENT=YES\n"));
+}
+
+#[test]
+fn apple_authoring_profile_flags_low_latency_gaps() {
+    let file = fs::File::open("tests/resources/ll-hls.m3u8").expect("Opened test file");
+    let playlist = read_playlist(file).expect("Parsed test file");
+    assert!(playlist
+        .validate_profile(ValidationProfile::Rfc8216)
+        .is_empty());
+    let rules: Vec<Rule> = playlist
+        .validate_profile(ValidationProfile::AppleAuthoring)
+        .into_iter()
+        .map(|issue| issue.rule)
+        .collect();
+    assert_eq!(
+        rules,
+        vec![
+            Rule::TargetDurationNotRecommended,
+            Rule::PartHoldBackTooShort
+        ]
+    );
+
+    let file = fs::File::open("tests/resources/needs-repair.m3u8").expect("Opened test file");
+    let playlist = read_playlist(file).expect("Parsed test file");
+    let issues = playlist.validate_profile(ValidationProfile::AppleAuthoring);
+    assert!(issues
+        .iter()
+        .any(|issue| issue.rule == Rule::VersionTooLow && issue.severity == Severity::Error));
+    assert!(issues
+        .iter()
+        .any(|issue| issue.rule == Rule::ProgramDateTimeMissing));
+    assert!(issues
+        .iter()
+        .any(|issue| issue.rule == Rule::RenditionReportsMissing));
+}
+
+#[test]
+fn part_duration_drift_is_flagged() {
+    let file = fs::File::open("tests/resources/drifting-parts.m3u8").expect("Opened test file");
+    let playlist = read_playlist(file).expect("Parsed test file");
+    let issues = playlist.validate_profile(ValidationProfile::Rfc8216);
+    let rules: Vec<Rule> = issues.iter().map(|issue| issue.rule).collect();
+    assert_eq!(
+        rules,
+        vec![Rule::UnevenPartDurations, Rule::PartsDoNotSumToSegment]
+    );
+    assert_eq!(
+        issues[0].message,
+        "part 11.1 lasts 0.45s while earlier parts of the segment last 0.5s"
+    );
+}
+
+#[test]
+fn preload_hint_must_point_past_published_parts() {
+    let file = fs::File::open("tests/resources/drifting-parts.m3u8").expect("Opened test file");
+    let mut playlist = read_playlist(file).expect("Parsed test file");
+    let hint_rules = |playlist: &MediaPlaylist| -> Vec<Rule> {
+        playlist
+            .validate_profile(ValidationProfile::Rfc8216)
+            .into_iter()
+            .map(|issue| issue.rule)
+            .filter(|rule| {
+                matches!(
+                    rule,
+                    Rule::PreloadHintAlreadyPublished | Rule::PreloadHintSkipsParts
+                )
+            })
+            .collect()
+    };
+    assert!(hint_rules(&playlist).is_empty());
+
+    for (uri, expected) in [
+        ("filePart12.0.mp4", vec![Rule::PreloadHintAlreadyPublished]),
+        ("filePart11.3.mp4", vec![Rule::PreloadHintAlreadyPublished]),
+        ("filePart11.9.mp4", vec![Rule::PreloadHintAlreadyPublished]),
+        ("filePart12.3.mp4", vec![Rule::PreloadHintSkipsParts]),
+        ("filePart13.0.mp4", vec![]),
+    ] {
+        playlist.set_preload_hint(Some(PreloadHint::new(PreloadHintType::Part, uri)));
+        assert_eq!(hint_rules(&playlist), expected, "hint {}", uri);
+    }
+}
+
+#[test]
+fn preload_hint_must_continue_the_byte_range_of_the_last_part() {
+    let mut playlist = parse_bytes(
+        b"#EXTM3U
+#EXT-X-VERSION:9
+#EXT-X-TARGETDURATION:4
+#EXT-X-PART-INF:PART-TARGET=1
+#EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=3
+#EXT-X-PART:DURATION=1,URI=\"segment1.mp4\",BYTERANGE=1000@0,INDEPENDENT=YES
+#EXT-X-PART:DURATION=1,URI=\"segment1.mp4\",BYTERANGE=800
+",
+    )
+    .expect("Parsed playlist");
+    let hint_rules = |playlist: &MediaPlaylist| -> Vec<Rule> {
+        playlist
+            .validate_profile(ValidationProfile::Rfc8216)
+            .into_iter()
+            .map(|issue| issue.rule)
+            .filter(|rule| {
+                matches!(
+                    rule,
+                    Rule::PreloadHintAlreadyPublished | Rule::PreloadHintSkipsParts
+                )
+            })
+            .collect()
+    };
+
+    for (start, expected) in [
+        (Some(1800), vec![]),
+        (Some(1000), vec![Rule::PreloadHintAlreadyPublished]),
+        (None, vec![Rule::PreloadHintAlreadyPublished]),
+        (Some(2400), vec![Rule::PreloadHintSkipsParts]),
+    ] {
+        let mut hint = PreloadHint::new(PreloadHintType::Part, "segment1.mp4");
+        hint.byterange_start = start;
+        hint.byterange_length = Some(600);
+        playlist.set_preload_hint(Some(hint));
+        assert_eq!(hint_rules(&playlist), expected, "hint at {:?}", start);
+    }
+
+    // Without a byte range the hint names the whole resource again
+    playlist.set_preload_hint(Some(PreloadHint::new(
+        PreloadHintType::Part,
+        "segment1.mp4",
+    )));
+    assert_eq!(
+        hint_rules(&playlist),
+        vec![Rule::PreloadHintAlreadyPublished]
+    );
+}
+
+#[test]
+fn rendition_reports_must_track_the_live_edge() {
+    let file = fs::File::open("tests/resources/ll-hls.m3u8").expect("Opened test file");
+    let playlist = read_playlist(file).expect("Parsed test file");
+    let report_rules = |reports: Vec<RenditionReport>| -> Vec<Rule> {
+        let mut playlist = playlist.clone();
+        playlist.set_rendition_reports(reports);
+        playlist
+            .validate_profile(ValidationProfile::Rfc8216)
+            .into_iter()
+            .map(|issue| issue.rule)
+            .collect()
+    };
+    // The live edge is part 273.3
+    assert!(report_rules(vec![
+        RenditionReport::new("../1M/waitForMSN.php", 273, Some(2)),
+        RenditionReport::new("../4M/waitForMSN.php", 273, Some(4)),
+    ])
+    .is_empty());
+    assert_eq!(
+        report_rules(vec![
+            RenditionReport::new("../1M/waitForMSN.php", 272, Some(11)),
+            RenditionReport::new("../4M/waitForMSN.php", 274, Some(1)),
+            RenditionReport::new("https://cdn.example.com/4M.m3u8", 273, Some(3)),
+        ]),
+        vec![
+            Rule::RenditionReportStale,
+            Rule::RenditionReportAhead,
+            Rule::RenditionReportUriNotRelative,
+        ]
+    );
+
+    // LAST-MSN and LAST-PART are decimal-integers, which go past i64::MAX
+    assert_eq!(
+        report_rules(vec![
+            RenditionReport::new("../1M/waitForMSN.php", u64::MAX, None),
+            RenditionReport::new("../4M/waitForMSN.php", 273, Some(u64::MAX)),
+        ]),
+        vec![Rule::RenditionReportAhead, Rule::RenditionReportAhead]
+    );
+    let playlist = parse_bytes(
+        b"#EXTM3U\n#EXT-X-TARGETDURATION:4\n#EXT-X-VERSION:9\n\
+          #EXTINF:4,\nsegment0.mp4\n\
+          #EXT-X-RENDITION-REPORT:URI=\"a.m3u8\",LAST-MSN=9223372036854775808\n",
+    )
+    .expect("Parsed playlist");
+    assert!(playlist
+        .validate()
+        .iter()
+        .any(|issue| issue.rule == Rule::RenditionReportAhead));
+}
+
+#[test]
+fn delta_updates_must_keep_the_skip_window() {
+    let file = fs::File::open("tests/resources/ll-hls.m3u8").expect("Opened test file");
+    let playlist = read_playlist(file).expect("Parsed test file");
+    assert!(!playlist
+        .validate_profile(ValidationProfile::Rfc8216)
+        .iter()
+        .any(|issue| issue.rule == Rule::SkipWindowTooLarge));
+
+    // Only a segment and a few parts remain listed while CAN-SKIP-UNTIL is 24s
+    let file = fs::File::open("tests/resources/needs-repair.m3u8").expect("Opened test file");
+    let playlist = read_playlist(file).expect("Parsed test file");
+    let issues = playlist.validate_profile(ValidationProfile::Rfc8216);
+    let issue = issues
+        .iter()
+        .find(|issue| issue.rule == Rule::SkipWindowTooLarge)
+        .expect("Flagged skip window");
+    assert_eq!(issue.severity, Severity::Error);
+    assert_eq!(
+        issue.message,
+        "skipping 1 segments leaves 4.83342s listed, less than CAN-SKIP-UNTIL 24s"
+    );
+
+    // The parts of the segment being published count towards what's listed
+    let skip_rules = |trailing_parts: &str| -> Vec<Rule> {
+        let text = format!(
+            "#EXTM3U
+#EXT-X-VERSION:9
+#EXT-X-TARGETDURATION:4
+#EXT-X-PART-INF:PART-TARGET=1
+#EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,CAN-SKIP-UNTIL=6,PART-HOLD-BACK=3
+#EXT-X-MEDIA-SEQUENCE:10
+#EXT-X-SKIP:SKIPPED-SEGMENTS=1
+#EXTINF:4,
+segment11.mp4
+{}",
+            trailing_parts
+        );
+        parse_bytes(text.as_bytes())
+            .expect("Parsed playlist")
+            .validate_profile(ValidationProfile::Rfc8216)
+            .into_iter()
+            .map(|issue| issue.rule)
+            .filter(|rule| *rule == Rule::SkipWindowTooLarge)
+            .collect()
+    };
+    assert_eq!(
+        skip_rules("#EXT-X-PART:DURATION=1,URI=\"part12.0.mp4\",INDEPENDENT=YES\n"),
+        vec![Rule::SkipWindowTooLarge]
+    );
+    assert_eq!(
+        skip_rules(
+            "#EXT-X-PART:DURATION=1,URI=\"part12.0.mp4\",INDEPENDENT=YES
+#EXT-X-PART:DURATION=1,URI=\"part12.1.mp4\"
+"
+        ),
+        vec![]
+    );
+}
+
+#[test]
+fn client_session_applies_deltas_and_full_responses() {
+    let read = |path: &str| {
+        let file = fs::File::open(path).expect("Opened test file");
+        read_playlist(file).expect("Parsed test file")
+    };
+    let delta = read("tests/resources/ll-hls.m3u8");
+
+    let mut session = ClientSession::new();
+    assert!(!session.wants_delta());
+    assert_eq!(
+        session.receive(delta.clone()).err(),
+        Some(SessionError::NoBasePlaylist)
+    );
+
+    let full = read("tests/resources/ll-hls-full.m3u8");
+    assert_eq!(session.receive(full.clone()), Ok(UpdateKind::Full));
+    assert!(session.wants_delta());
+    assert_eq!(session.receive(delta.clone()), Ok(UpdateKind::Delta));
+    let playlist = session.playlist().expect("Holds a playlist");
+    assert_eq!(playlist.media_segments().len(), 7);
+    assert_eq!(
+        playlist.validate_profile(ValidationProfile::Rfc8216),
+        vec![]
+    );
+
+    // The server stopped offering skips, so deltas are no longer requested
+    let mut builder = MediaPlaylistBuilder::new();
+    builder
+        .target_duration(4)
+        .version(9)
+        .media_sequence_number(270)
+        .server_control(ServerControl::new(
+            true,
+            Duration::from_secs(1),
+            Duration::from_secs(0),
+        ));
+    let no_skips = builder.build().expect("Built playlist");
+    assert_eq!(session.receive(no_skips), Ok(UpdateKind::Full));
+    assert!(!session.wants_delta());
+    assert_eq!(
+        session.receive(delta).err(),
+        Some(SessionError::SkippedSegmentsUnknown {
+            first_msn: 266,
+            last_msn: 268
+        })
+    );
+}
+
+#[test]
+fn reload_coordinator_blocks_renditions_on_the_same_part() {
+    let file = fs::File::open("tests/resources/ll-hls-full.m3u8").expect("Opened test file");
+    let video = read_playlist(file).expect("Parsed test file");
+    let request = |uri: &str, msn, part, skip| ReloadRequest {
+        uri: uri.to_string(),
+        msn,
+        part,
+        skip,
+    };
+
+    let mut coordinator = ReloadCoordinator::new();
+    coordinator.add_rendition("../2M/waitForMSN.php");
+    coordinator.add_rendition("../1M/waitForMSN.php");
+    assert_eq!(
+        coordinator.requests(UNIX_EPOCH),
+        vec![
+            request("../2M/waitForMSN.php", None, None, false),
+            request("../1M/waitForMSN.php", None, None, false),
+        ]
+    );
+    // Nothing new to ask for while both requests are outstanding
+    assert_eq!(coordinator.requests(UNIX_EPOCH), vec![]);
+
+    assert_eq!(
+        coordinator.receive("../2M/waitForMSN.php", video.clone()),
+        Ok(UpdateKind::Full)
+    );
+    assert_eq!(
+        coordinator.requests(UNIX_EPOCH),
+        vec![request("../2M/waitForMSN.php", Some(273), Some(4), true)]
+    );
+    // The 2M playlist reports on the 1M one under the URI it was added by
+    assert_eq!(
+        coordinator.reported("../1M/waitForMSN.php"),
+        Some((273, Some(3)))
+    );
+    assert_eq!(coordinator.reported("../2M/waitForMSN.php"), None);
+
+    // The 1M playlist lags behind what the 2M playlist reported for it
+    let mut builder = MediaPlaylistBuilder::new();
+    builder
+        .target_duration(4)
+        .version(9)
+        .media_sequence_number(271)
+        .server_control(ServerControl::new(
+            true,
+            Duration::from_secs(1),
+            Duration::from_secs(12),
+        ))
+        .trailing_parts(vec![PartialSegment::new(
+            Duration::from_micros(333340),
+            "filePart271.0.mp4",
+        )]);
+    let audio = builder.build().expect("Built playlist");
+    assert_eq!(
+        coordinator.receive("../1M/waitForMSN.php", audio),
+        Ok(UpdateKind::Full)
+    );
+    coordinator.failed("../2M/waitForMSN.php");
+    assert_eq!(
+        coordinator.requests(UNIX_EPOCH),
+        vec![
+            request("../2M/waitForMSN.php", Some(273), Some(4), true),
+            request("../1M/waitForMSN.php", Some(273), Some(4), true),
+        ]
+    );
+    assert_eq!(
+        coordinator.receive("../4M/waitForMSN.php", video).err(),
+        Some(SessionError::UnknownRendition {
+            uri: "../4M/waitForMSN.php".to_string()
+        })
+    );
+}
+
+#[test]
+fn delivery_directives_follow_existing_query_parameters() {
+    let url = "https://example.com/2M/waitForMSN.php";
+    assert_eq!(apply_directives(url, None, None, None), url);
+    assert_eq!(
+        apply_directives(url, Some(273), Some(2), Some(SkipDirective::Yes)),
+        "https://example.com/2M/waitForMSN.php?_HLS_msn=273&_HLS_part=2&_HLS_skip=YES"
+    );
+    assert_eq!(
+        apply_directives(url, Some(273), None, Some(SkipDirective::V2)),
+        "https://example.com/2M/waitForMSN.php?_HLS_msn=273&_HLS_skip=v2"
+    );
+    // A part index means nothing without a media sequence number
+    assert_eq!(apply_directives(url, None, Some(2), None), url);
+
+    // Existing parameters come first and stale directives are replaced
+    assert_eq!(
+        apply_directives(
+            "https://example.com/2M/waitForMSN.php?token=a b&_HLS_msn=1&lang=en#t=10",
+            Some(273),
+            Some(0),
+            None
+        ),
+        "https://example.com/2M/waitForMSN.php?token=a%20b&lang=en&_HLS_msn=273&_HLS_part=0#t=10"
+    );
+    assert_eq!(
+        apply_directives("playlist.m3u8?", Some(5), None, None),
+        "playlist.m3u8?_HLS_msn=5"
+    );
+    assert_eq!(
+        apply_directives("playlist.m3u8?name=caf\u{e9}%2F", Some(5), None, None),
+        "playlist.m3u8?name=caf%C3%A9%2F&_HLS_msn=5"
+    );
+
+    let request = ReloadRequest {
+        uri: "../1M/waitForMSN.php".to_string(),
+        msn: Some(273),
+        part: Some(4),
+        skip: true,
+    };
+    assert_eq!(
+        request.url(),
+        "../1M/waitForMSN.php?_HLS_msn=273&_HLS_part=4&_HLS_skip=YES"
+    );
+}
+
+#[test]
+fn reload_coordinator_abandons_slow_requests_and_falls_back_to_full_reloads() {
+    let file = fs::File::open("tests/resources/ll-hls-full.m3u8").expect("Opened test file");
+    let playlist = read_playlist(file).expect("Parsed test file");
+    let uri = "../2M/waitForMSN.php";
+    let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+    let blocking = ReloadRequest {
+        uri: uri.to_string(),
+        msn: Some(273),
+        part: Some(4),
+        skip: true,
+    };
+    let full = ReloadRequest {
+        uri: uri.to_string(),
+        msn: None,
+        part: None,
+        skip: false,
+    };
+
+    let mut coordinator = ReloadCoordinator::with_policy(ReloadPolicy {
+        max_failures: 2,
+        ..ReloadPolicy::default()
+    });
+    coordinator.add_rendition(uri);
+    assert_eq!(coordinator.requests(at(0)), vec![full.clone()]);
+    // Still waiting within the initial timeout
+    assert_eq!(coordinator.requests(at(9)), vec![]);
+    assert_eq!(coordinator.requests(at(10)), vec![full.clone()]);
+    assert_eq!(
+        coordinator.receive(uri, playlist.clone()),
+        Ok(UpdateKind::Full)
+    );
+
+    // Three target durations of 4s each
+    assert_eq!(coordinator.requests(at(20)), vec![blocking.clone()]);
+    assert_eq!(coordinator.requests(at(31)), vec![]);
+    assert_eq!(coordinator.requests(at(32)), vec![blocking.clone()]);
+    coordinator.failed(uri);
+    assert_eq!(coordinator.requests(at(33)), vec![full.clone()]);
+
+    // A successful response resets the failure count
+    assert_eq!(
+        coordinator.receive(uri, playlist.clone()),
+        Ok(UpdateKind::Full)
+    );
+    assert_eq!(coordinator.requests(at(34)), vec![blocking.clone()]);
+
+    // Multipliers without a representable timeout don't panic
+    for timeout_target_durations in [f32::INFINITY, f32::MAX, f32::NAN, -1.0] {
+        let mut coordinator = ReloadCoordinator::with_policy(ReloadPolicy {
+            timeout_target_durations,
+            ..ReloadPolicy::default()
+        });
+        coordinator.add_rendition(uri);
+        coordinator.requests(at(0));
+        assert_eq!(
+            coordinator.receive(uri, playlist.clone()),
+            Ok(UpdateKind::Full)
+        );
+        assert_eq!(coordinator.requests(at(1)), vec![blocking.clone()]);
+        let retried = coordinator.requests(at(1_000_000));
+        assert_eq!(retried.is_empty(), timeout_target_durations > 0.0);
+    }
+}
+
+#[test]
+fn startup_position_honors_part_hold_back() {
+    let file = fs::File::open("tests/resources/ll-hls-full.m3u8").expect("Opened test file");
+    let playlist = read_playlist(file).expect("Parsed test file");
+    // 273.1 is already a PART-HOLD-BACK from the end, but 273.0 is the
+    // newest INDEPENDENT part
+    assert_eq!(playlist.startup_position(), Some((273, Some(0))));
+
+    let segments = (266..271)
+        .map(|msn| {
+            MediaSegment::new(
+                Duration::from_secs(4),
+                segment_uri(&format!("fileSequence{}.mp4", msn)),
+                vec![],
+                None,
+            )
+        })
+        .collect();
+    let mut builder = MediaPlaylistBuilder::new();
+    builder
+        .target_duration(4)
+        .version(9)
+        .media_sequence_number(266)
+        .server_control(ServerControl::new(
+            false,
+            Duration::from_secs(0),
+            Duration::from_secs(0),
+        ))
+        .media_segments(segments);
+    let playlist = builder.build().expect("Built playlist");
+    assert_eq!(playlist.startup_position(), Some((268, None)));
+
+    builder
+        .media_segments(vec![])
+        .trailing_parts(vec![PartialSegment::new(
+            Duration::from_micros(333340),
+            "filePart266.0.mp4",
+        )]);
+    let playlist = builder.build().expect("Built playlist");
+    assert_eq!(playlist.startup_position(), Some((266, Some(0))));
+
+    builder.trailing_parts(vec![]);
+    let playlist = builder.build().expect("Built playlist");
+    assert_eq!(playlist.startup_position(), None);
+}
+
+#[test]
+fn buffer_simulation_predicts_stalls() {
+    let ms = Duration::from_millis;
+    let simulate = |hold_back, slow_part| {
+        let mut simulation = BufferSimulation::new(hold_back);
+        for index in 0..5 {
+            simulation.push(SimulatedPart {
+                duration: ms(1000),
+                available_at: ms(1000 * (index + 1)),
+                download_time: if index == slow_part {
+                    ms(1500)
+                } else {
+                    ms(200)
+                },
+            });
+        }
+        simulation.run()
+    };
+
+    // Three parts of hold-back ride out the slow download
+    let report = simulate(ms(3000), 3);
+    assert_eq!(report.stalls, vec![]);
+    assert_eq!(report.min_buffer, Some(ms(1500)));
+
+    let report = simulate(ms(1000), 3);
+    assert_eq!(
+        report.stalls,
+        vec![
+            Stall {
+                part: 0,
+                at: ms(1000),
+                duration: ms(200)
+            },
+            Stall {
+                part: 3,
+                at: ms(4200),
+                duration: ms(1300)
+            },
+        ]
+    );
+    assert_eq!(report.total_stall, ms(1500));
+    assert_eq!(report.min_buffer, Some(ms(1000)));
+    assert_eq!(report.occupancy.last(), Some(&(ms(5700), ms(1800))));
+}
+
+#[test]
+fn client_session_tracks_rendition_report_freshness() {
+    let file = fs::File::open("tests/resources/ll-hls-full.m3u8").expect("Opened test file");
+    let playlist = read_playlist(file).expect("Parsed test file");
+    let uri = "../1M/waitForMSN.php";
+
+    let mut session = ClientSession::new();
+    assert!(!session.is_report_usable(uri));
+    session.receive(playlist.clone()).expect("Applied playlist");
+    assert_eq!(session.report_age(uri), Some(0));
+    assert!(session.is_report_usable(uri));
+
+    // A reload that brings nothing new doesn't age the reports
+    session.receive(playlist.clone()).expect("Applied playlist");
+    assert!(session.is_report_usable(uri));
+
+    let mut advanced = playlist.clone();
+    advanced.push_part(PartialSegment::new(
+        Duration::from_micros(333340),
+        "filePart273.4.mp4",
+    ));
+    session.receive(advanced.clone()).expect("Applied playlist");
+    assert_eq!(session.report_age(uri), Some(1));
+    assert!(!session.is_report_usable(uri));
+
+    advanced.set_rendition_reports(vec![RenditionReport::new(uri, 273, Some(4))]);
+    session.receive(advanced).expect("Applied playlist");
+    assert!(session.is_report_usable(uri));
+    assert_eq!(session.report_age("../4M/waitForMSN.php"), None);
+
+    // Reports are looked up by URI alone, however the tag quoted it
+    let source = fs::read_to_string("tests/resources/ll-hls-full.m3u8").expect("Read test file");
+    let bare = source.replace("URI=\"../1M/waitForMSN.php\"", "URI=../1M/waitForMSN.php");
+    let mut session = ClientSession::new();
+    session
+        .receive(parse_bytes(bare.as_bytes()).expect("Parsed playlist"))
+        .expect("Applied playlist");
+    assert_eq!(session.report_age(uri), Some(0));
+    assert_eq!(session.report_age("\"../1M/waitForMSN.php\""), None);
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn blocking_waiters_wake_on_publication_or_deadline() {
+    use llhls_rs::{BlockingWaiters, WaitTimeout};
+    use tokio::time::Instant;
+
+    let waiters = BlockingWaiters::new();
+    let soon = || Instant::now() + Duration::from_millis(20);
+    let later = || Instant::now() + Duration::from_secs(5);
+    waiters.notify_published(273, Some(3));
+    assert_eq!(waiters.published(), Some((273, Some(3))));
+    assert_eq!(waiters.wait_for(273, Some(2), soon()).await, Ok(()));
+    assert_eq!(
+        waiters.wait_for(273, Some(4), soon()).await,
+        Err(WaitTimeout)
+    );
+    assert_eq!(waiters.wait_for(273, None, soon()).await, Err(WaitTimeout));
+
+    let waiting = {
+        let waiters = waiters.clone();
+        tokio::spawn(async move { waiters.wait_for(273, Some(4), later()).await })
+    };
+    waiters.notify_published(273, Some(4));
+    assert_eq!(waiting.await.expect("Waiter finished"), Ok(()));
+
+    // Completing the segment doesn't satisfy a part index past its end
+    waiters.notify_published(273, None);
+    assert_eq!(waiters.wait_for(273, None, soon()).await, Ok(()));
+    assert_eq!(
+        waiters.wait_for(273, Some(5), soon()).await,
+        Err(WaitTimeout)
+    );
+    waiters.notify_published(274, Some(0));
+    assert_eq!(waiters.wait_for(273, Some(5), soon()).await, Ok(()));
+
+    // Going backwards is ignored
+    waiters.notify_published(270, Some(0));
+    assert_eq!(waiters.published(), Some((274, Some(0))));
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn live_writer_broadcasts_publications() {
+    use llhls_rs::{LiveWriter, PublicationEvent};
+
+    let file = fs::File::open("tests/resources/ll-hls-full.m3u8").expect("Opened test file");
+    let mut writer = LiveWriter::new(read_playlist(file).expect("Parsed test file"));
+    let mut events = writer.subscribe();
+    let mut snapshots = writer.snapshots();
+
+    writer.push_part(PartialSegment::new(
+        Duration::from_micros(333340),
+        "filePart273.4.mp4",
+    ));
+    match events.recv().await.expect("Received event") {
+        PublicationEvent::Part { msn, index, part } => {
+            assert_eq!((msn, index), (273, 4));
+            assert_eq!(part.uri, "filePart273.4.mp4");
+        }
+        _ => panic!("Expected a part"),
+    }
+    match events.recv().await.expect("Received event") {
+        PublicationEvent::Snapshot(snapshot) => assert_eq!(snapshot.trailing_parts().len(), 5),
+        _ => panic!("Expected a snapshot"),
+    }
+
+    writer.complete_segment(
+        Duration::from_micros(1666700),
+        segment_uri("fileSequence273.mp4"),
+        None,
+    );
+    assert!(matches!(
+        events.recv().await.expect("Received event"),
+        PublicationEvent::Segment { msn: 273 }
+    ));
+    assert!(snapshots.has_changed().expect("Writer still alive"));
+    assert_eq!(snapshots.borrow_and_update().media_segments().len(), 8);
+    assert!(writer.playlist().trailing_parts().is_empty());
+}
+
+#[test]
+fn live_writer_publishes_rendered_playlists_to_sinks() {
+    let source =
+        fs::read_to_string("tests/resources/ll-hls-canonical.m3u8").expect("Read test file");
+    let file = fs::File::open("tests/resources/ll-hls-canonical.m3u8").expect("Opened test file");
+    let writer = LiveWriter::new(read_playlist(file).expect("Parsed test file"));
+
+    let mut memory = MemorySink::new();
+    writer.publish_to(&mut memory, "2M").expect("Published");
+    assert_eq!(memory.get("2M.m3u8"), Some(source.as_str()));
+    memory.write_delta("2M", "#EXTM3U\n").expect("Published");
+    assert_eq!(memory.get("2M_delta.m3u8"), Some("#EXTM3U\n"));
+
+    let directory = std::env::temp_dir().join(format!("llhls-sink-{}", std::process::id()));
+    fs::create_dir_all(&directory).expect("Created directory");
+    let mut files = FileSink::new(&directory);
+    writer.publish_to(&mut files, "2M").expect("Published");
+    assert_eq!(
+        fs::read_to_string(directory.join("2M.m3u8")).expect("Read playlist"),
+        source
+    );
+    fs::remove_dir_all(&directory).expect("Removed directory");
+}
+
+#[test]
+fn atomic_writes_replace_files_without_leftovers() {
+    let directory = std::env::temp_dir().join(format!("llhls-atomic-{}", std::process::id()));
+    fs::create_dir_all(&directory).expect("Created directory");
+    let path = directory.join("2M.m3u8");
+
+    write_atomically(&path, b"#EXTM3U\n", false).expect("Wrote file");
+    write_atomically(&path, b"#EXTM3U\n#EXT-X-VERSION:9\n", true).expect("Wrote file");
+    assert_eq!(
+        fs::read_to_string(&path).expect("Read file"),
+        "#EXTM3U\n#EXT-X-VERSION:9\n"
+    );
+    let mut sink = FileSink::new(&directory).fsync(true);
+    sink.write_delta("2M", "#EXTM3U\n").expect("Published");
+    let mut names: Vec<_> = fs::read_dir(&directory)
+        .expect("Listed directory")
+        .map(|entry| entry.expect("Read entry").file_name())
+        .collect();
+    names.sort();
+    assert_eq!(names, vec!["2M.m3u8", "2M_delta.m3u8"]);
+
+    assert!(write_atomically(&directory.join("missing/2M.m3u8"), b"", false).is_err());
+    fs::remove_dir_all(&directory).expect("Removed directory");
+}
+
+#[test]
+fn live_writer_trims_to_size_budget() {
+    let mut builder = MediaPlaylistBuilder::new();
+    builder
+        .target_duration(4)
+        .version(9)
+        .part_inf(PartInf::new(Duration::from_secs(1)))
+        .media_sequence_number(0)
+        .server_control(ServerControl::new(
+            true,
+            Duration::from_secs(3),
+            Duration::from_secs(0),
+        ));
+    let mut writer = LiveWriter::new(builder.build().expect("Built playlist"));
+    writer.set_window(Some(Duration::from_secs(40)));
+    for msn in 0..12 {
+        for index in 0..4 {
+            writer.push_part(PartialSegment::new(
+                Duration::from_secs(1),
+                format!("p{}.{}.mp4", msn, index),
+            ));
+        }
+        writer.complete_segment(
+            Duration::from_secs(4),
+            segment_uri(&format!("s{}.mp4", msn)),
+            None,
+        );
+    }
+    // The window keeps ten segments
+    assert_eq!(writer.playlist().media_segments().len(), 10);
+    let full_len = writer.playlist().estimated_serialized_len();
+
+    writer.set_size_budget(Some(full_len));
+    assert_eq!(writer.budget_report(), None);
+
+    // Dropping parts older than three target durations is enough
+    writer.set_size_budget(Some(full_len - 500));
+    assert_eq!(
+        writer.budget_report(),
+        Some(&BudgetReport {
+            serialized_len: 809,
+            parts_trimmed: 28,
+            segments_trimmed: 0,
+            window: Duration::from_secs(40),
+            within_budget: true,
+        })
+    );
+    assert_eq!(writer.playlist().estimated_serialized_len(), 809);
+
+    // Segments go down to three target durations, which still doesn't fit
+    writer.set_size_budget(Some(400));
+    assert_eq!(
+        writer.budget_report(),
+        Some(&BudgetReport {
+            serialized_len: 683,
+            parts_trimmed: 0,
+            segments_trimmed: 7,
+            window: Duration::from_secs(12),
+            within_budget: false,
+        })
+    );
+    assert_eq!(writer.playlist().media_segments().len(), 3);
+}
+
+#[test]
+fn bandwidth_meter_rewrites_multivariant_bandwidths() {
+    let mut playlist = MultivariantPlaylist::new();
+    playlist.version = Some(9);
+    let mut audio = Rendition::new(MediaType::Audio, "aac", "English");
+    audio.uri = Some("audio/en.m3u8".to_string());
+    audio.default = true;
+    playlist.renditions.push(audio);
+    let mut video = VariantStream::new("2M/video.m3u8", 2_000_000);
+    video.codecs = Some("avc1.64001f,mp4a.40.2".to_string());
+    video.resolution = Some((1280, 720));
+    video.audio = Some("aac".to_string());
+    playlist.variants.push(video);
+    playlist
+        .variants
+        .push(VariantStream::new("1M/video.m3u8", 1_000_000));
+
+    let mut meter = BandwidthMeter::new();
+    // 2s segments against a 4s target duration, so the peak may be a single one
+    for bytes in [250_000, 250_000, 500_000, 250_000] {
+        meter.record_segment("2M/video.m3u8", 2.0, bytes);
+    }
+    meter.record_segment("audio/en.m3u8", 4.0, 64_000);
+    assert_eq!(meter.peak_bitrate("2M/video.m3u8", 4), Some(2_000_000));
+    assert_eq!(meter.average_bitrate("2M/video.m3u8"), Some(1_250_000));
+    assert_eq!(meter.peak_bitrate("1M/video.m3u8", 4), None);
+
+    meter.apply(&mut playlist, 4);
+    let video = playlist.variant("2M/video.m3u8").expect("Has variant");
+    assert_eq!(video.bandwidth, 2_128_000);
+    assert_eq!(video.average_bandwidth, Some(1_378_000));
+    // Unmeasured variants keep what they had
+    assert_eq!(
+        playlist
+            .variant("1M/video.m3u8")
+            .expect("Has variant")
+            .bandwidth,
+        1_000_000
+    );
+    assert_eq!(
+        playlist.to_string(),
+        "#EXTM3U\n\
+         #EXT-X-VERSION:9\n\
+         #EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"English\",DEFAULT=YES,URI=\"audio/en.m3u8\"\n\
+         #EXT-X-STREAM-INF:BANDWIDTH=2128000,AVERAGE-BANDWIDTH=1378000,CODECS=\"avc1.64001f,mp4a.40.2\",RESOLUTION=1280x720,AUDIO=\"aac\"\n\
+         2M/video.m3u8\n\
+         #EXT-X-STREAM-INF:BANDWIDTH=1000000\n\
+         1M/video.m3u8\n"
+    );
+}
+
+#[test]
+fn session_data_is_added_to_multivariant_playlists() {
+    let mut playlist = MultivariantPlaylist::new();
+    playlist
+        .add_session_value("com.example.title", "Morning News", Some("en"))
+        .expect("Added session data");
+    playlist
+        .add_session_value("com.example.title", "Nachrichten", Some("de"))
+        .expect("Added session data");
+    playlist
+        .add_session_value("com.example.channel", 7, None)
+        .expect("Added session data");
+    playlist
+        .add_session_uri("com.example.lineup", "lineup.json", None)
+        .expect("Added session data");
+
+    assert_eq!(
+        playlist.add_session_value("com.example.title", "News", Some("en")),
+        Err(SessionDataError::Duplicate {
+            data_id: "com.example.title".to_string(),
+            language: Some("en".to_string())
+        })
+    );
+    assert_eq!(
+        playlist.add_session_value("com.example.quote", "say \"hi\"", None),
+        Err(SessionDataError::Unquotable {
+            data_id: "com.example.quote".to_string()
+        })
+    );
+    assert_eq!(
+        playlist.to_string(),
+        "#EXTM3U\n\
+         #EXT-X-SESSION-DATA:DATA-ID=\"com.example.title\",VALUE=\"Morning News\",LANGUAGE=\"en\"\n\
+         #EXT-X-SESSION-DATA:DATA-ID=\"com.example.title\",VALUE=\"Nachrichten\",LANGUAGE=\"de\"\n\
+         #EXT-X-SESSION-DATA:DATA-ID=\"com.example.channel\",VALUE=\"7\"\n\
+         #EXT-X-SESSION-DATA:DATA-ID=\"com.example.lineup\",URI=\"lineup.json\"\n"
+    );
+}
+
+#[test]
+fn mpeg_ts_playlists_are_told_apart_from_fmp4() {
+    let file = fs::File::open("tests/resources/ll-hls-ts.m3u8").expect("Opened test file");
+    let mut playlist = read_playlist(file).expect("Parsed test file");
+    assert_eq!(playlist.container_format(), Some(ContainerFormat::MpegTs));
+    assert!(playlist
+        .validate_profile(ValidationProfile::Rfc8216)
+        .is_empty());
+
+    // Transport streams carry no init segment to hint at
+    playlist.set_preload_hint(Some(PreloadHint::new(PreloadHintType::Map, "init.mp4")));
+    let rules: Vec<_> = playlist
+        .validate_profile(ValidationProfile::Rfc8216)
+        .into_iter()
+        .map(|issue| issue.rule)
+        .collect();
+    assert_eq!(rules, vec![Rule::MapHintInMpegTs]);
+
+    let file = fs::File::open("tests/resources/ll-hls-full.m3u8").expect("Opened test file");
+    let playlist = read_playlist(file).expect("Parsed test file");
+    assert_eq!(playlist.container_format(), Some(ContainerFormat::Fmp4));
+    assert_eq!(
+        ContainerFormat::from_uri("filePart271.0.m4s?token=abc"),
+        Some(ContainerFormat::Fmp4)
+    );
+    assert_eq!(ContainerFormat::from_uri("segment.aac"), None);
+}
+
+#[test]
+fn live_writer_flags_keyframe_chunks_independent() {
+    let file = fs::File::open("tests/resources/ll-hls-ts.m3u8").expect("Opened test file");
+    let mut writer = LiveWriter::new(read_playlist(file).expect("Parsed test file"));
+    for index in 1..8 {
+        writer.push_chunk(
+            Duration::from_millis(500),
+            format!("segment42.{}.ts", index),
+            index == 4,
+        );
+    }
+    let independent: Vec<_> = writer
+        .playlist()
+        .trailing_parts()
+        .iter()
+        .map(|part| part.independent)
+        .collect();
+    assert_eq!(independent[4], Some(true));
+    assert_eq!(independent.iter().flatten().count(), 2);
+    writer.complete_segment(Duration::from_secs(4), segment_uri("segment42.ts"), None);
+    let missing_independent = |playlist: &MediaPlaylist| {
+        playlist
+            .validate_profile(ValidationProfile::Rfc8216)
+            .into_iter()
+            .filter(|issue| issue.rule == Rule::IndependentPartMissing)
+            .map(|issue| issue.message)
+            .collect::<Vec<_>>()
+    };
+    assert!(missing_independent(writer.playlist()).is_empty());
+
+    // A segment cut without a keyframe in any of its chunks
+    for index in 0..8 {
+        writer.push_chunk(
+            Duration::from_millis(500),
+            format!("segment43.{}.ts", index),
+            false,
+        );
+    }
+    writer.complete_segment(Duration::from_secs(4), segment_uri("segment43.ts"), None);
+    assert_eq!(
+        missing_independent(writer.playlist()),
+        vec!["segment 43 has no part with INDEPENDENT=YES"]
+    );
+}
+
+#[test]
+fn playlist_timing_is_derived_from_encoder_config() {
+    let timing = EncoderConfig::new(120, 30.0, 0.5)
+        .derive()
+        .expect("Derived timing");
+    assert_eq!(timing.target_duration, 4);
+    assert_eq!(timing.part_target, 0.5);
+    assert_eq!(timing.part_hold_back, 1.5);
+    assert_eq!(timing.can_skip_until, 24.0);
+    assert!(timing.warnings.is_empty());
+    let mut builder = MediaPlaylistBuilder::new();
+    let playlist = timing
+        .apply(&mut builder)
+        .version(9)
+        .media_sequence_number(0)
+        .build()
+        .expect("Built playlist");
+    assert_eq!(
+        playlist.to_string(),
+        "#EXTM3U\n\
+         #EXT-X-TARGETDURATION:4\n\
+         #EXT-X-VERSION:9\n\
+         #EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=1.5,CAN-SKIP-UNTIL=24\n\
+         #EXT-X-PART-INF:PART-TARGET=0.5\n\
+         #EXT-X-MEDIA-SEQUENCE:0\n"
+    );
+
+    // 0.3s is 8.991 frames at 29.97fps, and 9-frame chunks don't divide a
+    // 60-frame GOP
+    let mut config = EncoderConfig::new(60, 29.97, 0.3);
+    config.gops_per_segment = 3;
+    let timing = config.derive().expect("Derived timing");
+    assert_eq!(timing.target_duration, 6);
+    assert_eq!(
+        timing.warnings,
+        vec![
+            TimingWarning::ChunkNotFrameAligned {
+                requested: 0.3,
+                actual: 9.0 / 29.97
+            },
+            TimingWarning::KeyframesNotOnPartBoundaries {
+                gop_length: 60,
+                chunk_frames: 9
+            }
+        ]
+    );
+    assert_eq!(
+        EncoderConfig::new(60, 0.0, 0.5).derive(),
+        Err(EncoderConfigError::InvalidFrameRate)
+    );
+
+    // 2^34 frames per segment don't fit a u32
+    let mut config = EncoderConfig::new(1 << 22, 1024.0, 1.0);
+    config.gops_per_segment = 1 << 12;
+    let timing = config.derive().expect("Derived timing");
+    assert_eq!(timing.target_duration, 1 << 24);
+}
+
+#[test]
+fn audio_renditions_are_cut_on_audio_frames_and_wired_into_groups() {
+    let audio = AudioRendition::new("aac", "English", "audio/en.m3u8", 48_000);
+    let timing = EncoderConfig::new(120, 30.0, 0.5)
+        .derive()
+        .expect("Derived timing");
+    // 23 AAC frames of 21.33ms fit in a 0.5s part
+    assert_eq!(
+        audio.part_target(timing.part_target),
+        23.0 * 1024.0 / 48_000.0
+    );
+    let mut builder = MediaPlaylistBuilder::new();
+    let mut playlist = audio
+        .apply(&timing, &mut builder)
+        .version(9)
+        .media_sequence_number(0)
+        .build()
+        .expect("Built playlist");
+    playlist.push_part(audio.part(23, "audio0.0.mp4"));
+    assert!(playlist.to_string().contains(
+        "#EXT-X-PART-INF:PART-TARGET=0.490666666\n\
+         #EXT-X-MEDIA-SEQUENCE:0\n\
+         #EXT-X-PART:DURATION=0.490666666,URI=\"audio0.0.mp4\",INDEPENDENT=YES\n"
+    ));
+
+    let mut multivariant = MultivariantPlaylist::new();
+    multivariant
+        .variants
+        .push(VariantStream::new("2M/video.m3u8", 2_000_000));
+    audio.add_to(&mut multivariant);
+    let mut german = AudioRendition::new("aac", "Deutsch", "audio/de.m3u8", 48_000);
+    german.rendition.language = Some("de".to_string());
+    german.add_to(&mut multivariant);
+    assert_eq!(
+        multivariant.to_string(),
+        "#EXTM3U\n\
+         #EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"English\",DEFAULT=YES,AUTOSELECT=YES,URI=\"audio/en.m3u8\"\n\
+         #EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"Deutsch\",LANGUAGE=\"de\",AUTOSELECT=YES,URI=\"audio/de.m3u8\"\n\
+         #EXT-X-STREAM-INF:BANDWIDTH=2000000,AUDIO=\"aac\"\n\
+         2M/video.m3u8\n"
+    );
+}
+
+#[test]
+fn webvtt_subtitle_playlists_are_generated_with_optional_parts() {
+    let timing = EncoderConfig::new(120, 30.0, 0.5)
+        .derive()
+        .expect("Derived timing");
+    let mut subtitles = SubtitleRendition::new("subs", "English", "subs/en.m3u8");
+    subtitles.rendition.language = Some("en".to_string());
+    let mut builder = MediaPlaylistBuilder::new();
+    let mut playlist = subtitles
+        .apply(&timing, &mut builder)
+        .version(9)
+        .media_sequence_number(0)
+        .build()
+        .expect("Built playlist");
+    for index in 0..8 {
+        playlist
+            .push_part(subtitles.part(Duration::from_millis(500), format!("subs0.{}.vtt", index)));
+    }
+    playlist.complete_segment(Duration::from_secs(4), segment_uri("subs0.vtt"), None);
+    assert_eq!(playlist.container_format(), Some(ContainerFormat::WebVtt));
+    assert!(playlist
+        .validate_profile(ValidationProfile::Rfc8216)
+        .is_empty());
+
+    subtitles.parts = false;
+    let mut builder = MediaPlaylistBuilder::new();
+    let playlist = subtitles
+        .apply(&timing, &mut builder)
+        .version(9)
+        .media_sequence_number(0)
+        .build()
+        .expect("Built playlist");
+    assert!(!playlist.to_string().contains("#EXT-X-PART-INF"));
+
+    let mut multivariant = MultivariantPlaylist::new();
+    multivariant
+        .variants
+        .push(VariantStream::new("2M/video.m3u8", 2_000_000));
+    subtitles.add_to(&mut multivariant);
+    assert_eq!(
+        multivariant.to_string(),
+        "#EXTM3U\n\
+         #EXT-X-MEDIA:TYPE=SUBTITLES,GROUP-ID=\"subs\",NAME=\"English\",LANGUAGE=\"en\",AUTOSELECT=YES,URI=\"subs/en.m3u8\"\n\
+         #EXT-X-STREAM-INF:BANDWIDTH=2000000,SUBTITLES=\"subs\"\n\
+         2M/video.m3u8\n"
+    );
+}
+
+#[test]
+fn live_writer_rotates_init_segments_after_a_discontinuity() {
+    let file = fs::File::open("tests/resources/ll-hls-full.m3u8").expect("Opened test file");
+    let mut writer = LiveWriter::new(read_playlist(file).expect("Parsed test file"));
+    // Parts of segment 273 are out already, so the switch waits for 274
+    writer.rotate_map(Map::new("init-1080p.mp4"));
+    writer.push_part(PartialSegment::new(
+        Duration::from_micros(333340),
+        "filePart273.4.mp4",
+    ));
+    writer.complete_segment(
+        Duration::from_micros(1666700),
+        segment_uri("fileSequence273.mp4"),
+        None,
+    );
+    writer.push_chunk(Duration::from_micros(333340), "filePart274.0.mp4", true);
+    let rendered = writer.playlist().to_string();
+    assert!(rendered.contains(
+        "fileSequence273.mp4\n\
+         #EXT-X-DISCONTINUITY\n\
+         #EXT-X-MAP:URI=\"init-1080p.mp4\"\n\
+         #EXT-X-PART:DURATION=0.33334,URI=\"filePart274.0.mp4\",INDEPENDENT=YES\n"
+    ));
+    assert_eq!(writer.playlist().estimated_serialized_len(), rendered.len());
+    let rules = |playlist: &MediaPlaylist| {
+        playlist
+            .validate_profile(ValidationProfile::Rfc8216)
+            .into_iter()
+            .map(|issue| issue.rule)
+            .filter(|rule| {
+                matches!(
+                    rule,
+                    Rule::MapChangeWithoutDiscontinuity | Rule::MediaDoesNotMatchMap
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    assert!(rules(writer.playlist()).is_empty());
+
+    // Parts after the switch must be of the new init segment's format
+    writer.push_chunk(Duration::from_micros(333340), "filePart274.1.ts", false);
+    assert_eq!(rules(writer.playlist()), vec![Rule::MediaDoesNotMatchMap]);
+
+    // The map stays in effect once its segment leaves the window
+    let mut playlist = writer.playlist().clone();
+    playlist.complete_segment(
+        Duration::from_micros(666680),
+        segment_uri("fileSequence274.mp4"),
+        None,
+    );
+    playlist.push_part(PartialSegment::new(
+        Duration::from_micros(333340),
+        "filePart275.0.mp4",
+    ));
+    playlist.complete_segment(
+        Duration::from_micros(333340),
+        segment_uri("fileSequence275.mp4"),
+        None,
+    );
+    while playlist.media_segments().len() > 1 {
+        playlist.pop_segment();
+    }
+    assert!(playlist.to_string().contains(
+        "#EXT-X-MAP:URI=\"init-1080p.mp4\"\n#EXT-X-PART:DURATION=0.33334,URI=\"filePart275.0.mp4\""
+    ));
+}
+
+#[test]
+fn line_endings_are_configurable_and_preserved() {
+    let source =
+        fs::read_to_string("tests/resources/ll-hls-canonical.m3u8").expect("Read test file");
+    let crlf_source = source.replace('\n', "\r\n");
+    let mut playlist = parse_bytes(crlf_source.as_bytes()).expect("Parsed playlist");
+
+    assert_eq!(playlist.line_ending(), LineEnding::CrLf);
+    assert_eq!(playlist.to_string(), crlf_source);
+    assert_eq!(playlist.estimated_serialized_len(), crlf_source.len());
+
+    playlist.set_line_ending(LineEnding::Lf);
+    assert_eq!(playlist.to_string(), source);
+    assert_eq!(playlist.estimated_serialized_len(), source.len());
+}
+
+#[test]
+fn playlists_serialize_in_chunks() {
+    let file = fs::File::open("tests/resources/ll-hls-full.m3u8").expect("Opened test file");
+    let mut playlist = read_playlist(file).expect("Parsed test file");
+    let chunks: Vec<_> = playlist.serialize_chunks().collect();
+    // Header, one chunk per segment and the tail
+    assert_eq!(chunks.len(), playlist.media_segments().len() + 2);
+    assert!(chunks[0].starts_with("#EXTM3U\n"));
+    assert!(chunks[0].ends_with("#EXT-X-MEDIA-SEQUENCE:266\n"));
+    assert_eq!(chunks.concat(), playlist.to_string());
+
+    playlist.set_line_ending(LineEnding::CrLf);
+    assert_eq!(
+        playlist.serialize_chunks().collect::<String>(),
+        playlist.to_string()
+    );
+}
+
+#[test]
+fn duplicate_unique_tags_follow_the_configured_policy() {
+    let source = fs::read_to_string("tests/resources/ll-hls-canonical.m3u8")
+        .expect("Read test file")
+        .replace(
+            "#EXT-X-TARGETDURATION:4\n",
+            "#EXT-X-TARGETDURATION:4\n#EXT-X-TARGETDURATION:6\n",
+        );
+    let parse = |duplicate_tags| {
+        let options = ParseOptions {
+            duplicate_tags,
+            ..ParseOptions::default()
+        };
+        parse_with_options(&source, options, &mut NoHooks)
+    };
+
+    let target_duration =
+        |playlist: MediaPlaylist| playlist.to_string().lines().nth(1).map(str::to_string);
+    let last = parse(DuplicateTagPolicy::LastWins).expect("Parsed file");
+    assert_eq!(
+        target_duration(last).as_deref(),
+        Some("#EXT-X-TARGETDURATION:6")
+    );
+    let first = parse(DuplicateTagPolicy::FirstWins).expect("Parsed file");
+    assert_eq!(
+        target_duration(first).as_deref(),
+        Some("#EXT-X-TARGETDURATION:4")
+    );
+    assert!(matches!(
+        parse(DuplicateTagPolicy::Error),
+        Err(ParsePlaylistError::DuplicateTag { line: 3, tag }) if tag == "EXT-X-TARGETDURATION"
+    ));
+    assert_eq!(
+        ParseOptions::strict().duplicate_tags,
+        DuplicateTagPolicy::Error
+    );
+}
+
+#[test]
+fn pathological_numbers_are_rejected_or_clamped() {
+    let source = fs::read_to_string("tests/resources/ll-hls-canonical.m3u8")
+        .expect("Read test file")
+        .replace(
+            "#EXTINF:4.00008,\nfileSequence269",
+            "#EXTINF:NaN,\nfileSequence269",
+        )
+        .replace("PART-HOLD-BACK=1", "PART-HOLD-BACK=-1")
+        .replace("LAST-MSN=270", "LAST-MSN=99999999999999999999");
+    let parse = |numbers| {
+        let options = ParseOptions {
+            numbers,
+            ..ParseOptions::default()
+        };
+        let mut hooks = CountingHooks::default();
+        parse_with_options(&source, options, &mut hooks).map(|playlist| (playlist, hooks))
+    };
+
+    assert!(matches!(
+        parse(NumericPolicy::Reject),
+        Err(ParsePlaylistError::InvalidNumber {
+            line: 4,
+            tag,
+            error: NumericError::Negative
+        }) if tag == "EXT-X-SERVER-CONTROL"
+    ));
+    let (playlist, hooks) = parse(NumericPolicy::Clamp).expect("Parsed file");
+    assert_eq!(hooks.warnings.len(), 3);
+    let rendered = playlist.to_string();
+    // Clamped to zero, which is the same as absent
+    assert_eq!(playlist.server_control().part_hold_back(), Duration::ZERO);
+    assert!(!rendered.contains("PART-HOLD-BACK"));
+    assert!(rendered.contains("#EXTINF:0,\nfileSequence269.mp4"));
+    assert!(rendered.contains("LAST-MSN=18446744073709551615"));
+}
+
+#[test]
+fn hexadecimal_sequences_round_trip() {
+    let iv: HexBytes = "0X0000000000000000000000000000002A"
+        .parse()
+        .expect("Parsed hex");
+    assert_eq!(iv.to_string(), "0X0000000000000000000000000000002A");
+    assert_eq!(iv.to_bytes().len(), 16);
+    assert_eq!(iv.to_bytes()[15], 0x2a);
+    let odd: HexBytes = "0xfC1".parse().expect("Parsed hex");
+    assert_eq!(odd.to_bytes(), vec![0x0f, 0xc1]);
+    assert_eq!(odd.digits(), "fC1");
+    assert_eq!(HexBytes::from_bytes(&[0xfc, 0x30]).as_str(), "0xfc30");
+    assert_eq!(
+        "fc30".parse::<HexBytes>(),
+        Err(ParseHexError::MissingPrefix)
+    );
+    assert_eq!("0x".parse::<HexBytes>(), Err(ParseHexError::Empty));
+    assert_eq!("0xfg".parse::<HexBytes>(), Err(ParseHexError::InvalidDigit));
+
+    let mut range = DateRange::new("ad-1", date("2019-02-14T02:14:00.106Z"));
+    range.scte35_out = Some(HexBytes::from_bytes(&[0xfc, 0x30, 0x21]));
+    assert!(range.to_string().ends_with(",SCTE35-OUT=0xfc3021"));
+}
+
+#[test]
+fn enumerated_string_lists_keep_their_commas() {
+    let restrict: EnumeratedStringList = "\"SKIP,JUMP\"".parse().expect("Parsed list");
+    assert_eq!(restrict.iter().collect::<Vec<_>>(), vec!["SKIP", "JUMP"]);
+    assert!(restrict.contains("JUMP"));
+    assert_eq!(restrict.to_string(), "\"SKIP,JUMP\"");
+    assert!("\"\""
+        .parse::<EnumeratedStringList>()
+        .expect("Parsed list")
+        .is_empty());
+    assert_eq!(
+        "SKIP,JUMP".parse::<EnumeratedStringList>(),
+        Err(ParseListError::Unquoted)
+    );
+
+    let mut rendition = Rendition::new(MediaType::Audio, "aac", "English AD");
+    let mut characteristics = EnumeratedStringList::new();
+    characteristics
+        .push("public.accessibility.describes-video")
+        .expect("Added entry");
+    characteristics
+        .push("public.easy-to-read")
+        .expect("Added entry");
+    assert!(characteristics.push("a,b").is_err());
+    rendition.characteristics = Some(characteristics);
+    assert_eq!(
+        rendition.to_string(),
+        "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"English AD\",\
+         CHARACTERISTICS=\"public.accessibility.describes-video,public.easy-to-read\""
+    );
+}
+
+#[test]
+fn unknown_tags_reach_hooks_with_typed_attributes() {
+    #[derive(Default)]
+    struct UnknownTags(Vec<(String, Vec<(String, AttributeValue)>)>);
+
+    impl Hooks for UnknownTags {
+        fn on_unknown_tag(
+            &mut self,
+            tag: &str,
+            _value: &str,
+            attributes: &[(String, AttributeValue)],
+        ) {
+            self.0.push((tag.to_string(), attributes.to_vec()));
+        }
+    }
+
+    let file = fs::File::open("tests/resources/unknown-tag.m3u8").expect("Opened test file");
+    let mut hooks = UnknownTags::default();
+    read_playlist_with_hooks(file, &mut hooks).expect("Parsed test file");
+    assert_eq!(
+        hooks.0,
+        vec![(
+            "EXT-X-CUE-OUT".to_string(),
+            vec![("DURATION".to_string(), AttributeValue::Integer(30))]
+        )]
+    );
+
+    assert_eq!(AttributeValue::parse("1.5"), AttributeValue::Float(1.5));
+    assert_eq!(AttributeValue::parse("-0.5"), AttributeValue::Float(-0.5));
+    assert_eq!(
+        AttributeValue::parse("1920x1080"),
+        AttributeValue::Resolution(1920, 1080)
+    );
+    assert_eq!(
+        AttributeValue::parse("0xFC30"),
+        AttributeValue::Hex("0xFC30".parse().expect("Parsed hex"))
+    );
+    assert_eq!(
+        AttributeValue::parse("YES"),
+        AttributeValue::EnumeratedString("YES".to_string())
+    );
+    let restrict = AttributeValue::parse("\"SKIP,JUMP\"");
+    assert_eq!(restrict.as_str(), Some("SKIP,JUMP"));
+    assert_eq!(restrict.as_list().map(|list| list.len()), Some(2));
+    assert_eq!(restrict.to_string(), "\"SKIP,JUMP\"");
+}
+
+#[test]
+fn attribute_lists_parse_with_quoted_commas() {
+    let attributes = parse_attribute_list(
+        "BANDWIDTH=2128000,CODECS=\"avc1.64001f,mp4a.40.2\",RESOLUTION=1280x720,AUDIO=\"aac\"",
+    )
+    .expect("Parsed attribute list");
+    let names: Vec<_> = attributes.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, vec!["BANDWIDTH", "CODECS", "RESOLUTION", "AUDIO"]);
+    assert_eq!(
+        attributes[1].1,
+        AttributeValue::QuotedString("avc1.64001f,mp4a.40.2".to_string())
+    );
+
+    assert_eq!(
+        parse_attribute_list("URI=\"open"),
+        Err(AttributeListError::UnterminatedQuote)
+    );
+    assert_eq!(
+        parse_attribute_list("DURATION"),
+        Err(AttributeListError::MissingValue {
+            attribute: "DURATION".to_string()
+        })
+    );
+    assert_eq!(
+        parse_attribute_list("duration=1"),
+        Err(AttributeListError::InvalidName {
+            name: "duration".to_string()
+        })
+    );
+    assert_eq!(
+        parse_attribute_list("ID=1,ID=2"),
+        Err(AttributeListError::DuplicateName {
+            name: "ID".to_string()
+        })
+    );
+}
+
+#[test]
+fn custom_tags_parse_and_render_with_their_segment() {
+    #[derive(Debug, PartialEq)]
+    struct CueOut {
+        duration: u32,
+    }
+
+    impl CustomTag for CueOut {
+        fn name(&self) -> &str {
+            "EXT-X-CUE-OUT"
+        }
+
+        fn value(&self) -> Option<String> {
+            Some(format!("DURATION={}", self.duration))
+        }
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+    }
+
+    let mut options = ParseOptions::default();
+    options.custom_tags.register("EXT-X-CUE-OUT", |value| {
+        let duration = value
+            .strip_prefix("DURATION=")
+            .and_then(|duration| duration.parse().ok())
+            .ok_or_else(|| ParseTagError::invalid(value))?;
+        Ok(Arc::new(CueOut { duration }) as Arc<dyn CustomTag>)
+    });
+    let source = fs::read_to_string("tests/resources/unknown-tag.m3u8").expect("Read test file");
+    let file = fs::File::open("tests/resources/unknown-tag.m3u8").expect("Opened test file");
+    let mut hooks = CountingHooks::default();
+    let mut playlist =
+        read_playlist_with_options(file, &options, &mut hooks).expect("Parsed test file");
+    assert!(hooks.warnings.is_empty());
+
+    let segment = playlist.media_segments().get(0).expect("First segment");
+    let cue_out = segment.custom_tags()[0].downcast_ref::<CueOut>();
+    assert_eq!(cue_out, Some(&CueOut { duration: 30 }));
+    assert_eq!(playlist.to_string(), source);
+    assert_eq!(playlist.estimated_serialized_len(), source.len());
+
+    playlist.push_custom_tag(Arc::new(CueOut { duration: 15 }));
+    assert!(playlist
+        .to_string()
+        .ends_with("fileSequence267.mp4\n#EXT-X-CUE-OUT:DURATION=15\n"));
+    playlist.complete_segment(
+        Duration::from_secs(4),
+        segment_uri("fileSequence268.mp4"),
+        None,
+    );
+    let segment = playlist.media_segments().get(2).expect("Completed segment");
+    assert_eq!(segment.custom_tags().len(), 1);
+}
+
+#[test]
+fn errors_carry_stable_codes() {
+    let file =
+        fs::File::open("tests/resources/missing-target-duration.m3u8").expect("Opened test file");
+    let error = read_playlist(file).err().expect("Missing target duration");
+    assert_eq!(error.code(), ErrorCode::MISSING_TARGET_DURATION);
+    assert_eq!(error.code().code, 107);
+
+    let file = fs::File::open("tests/resources/needs-repair.m3u8").expect("Opened test file");
+    let playlist = read_playlist(file).expect("Parsed test file");
+    let issues = playlist.validate_profile(ValidationProfile::Rfc8216);
+    assert!(issues
+        .iter()
+        .any(|issue| issue.code() == ErrorCode::PART_INF_MISSING));
+    assert_eq!(
+        SessionError::NoBasePlaylist.code().name,
+        "client.no_base_playlist"
+    );
+
+    for (index, entry) in ERROR_CODES.iter().enumerate() {
+        assert_eq!(ErrorCode::from_code(entry.code), Some(*entry));
+        assert_eq!(ErrorCode::from_name(entry.name), Some(*entry));
+        assert!(ERROR_CODES[..index]
+            .iter()
+            .all(|earlier| earlier.code < entry.code && earlier.name != entry.name));
+    }
+    assert_eq!(ErrorCode::from_code(999), None);
+    assert_eq!(
+        ErrorCode::BLOCKING_RELOAD_TIMEOUT.to_string(),
+        "303 client.blocking_reload_timeout"
+    );
+}
+
+#[test]
+fn read_any_playlist_detects_multivariant_playlists() {
+    let source = fs::read_to_string("tests/resources/multivariant.m3u8").expect("Read test file");
+    let file = fs::File::open("tests/resources/multivariant.m3u8").expect("Opened test file");
+    let Playlist::Multivariant(multivariant) = read_any_playlist(file).expect("Parsed test file")
+    else {
+        panic!("Expected a multivariant playlist");
+    };
+    assert_eq!(multivariant.to_string(), source);
+    let file = fs::File::open("tests/resources/multivariant.m3u8").expect("Opened test file");
+    assert_eq!(
+        read_multivariant_playlist(file).expect("Parsed test file"),
+        multivariant
+    );
+    let variant = multivariant
+        .variant("2M/lowLatency.m3u8")
+        .expect("2M variant");
+    assert_eq!(variant.codecs.as_deref(), Some("avc1.64001f,mp4a.40.2"));
+    assert_eq!(variant.resolution, Some((1280, 720)));
+    assert_eq!(multivariant.group(MediaType::Audio, "aac").count(), 1);
+    assert_eq!(
+        multivariant.i_frame_streams,
+        vec![IFrameStream {
+            codecs: Some("avc1.64001f".to_string()),
+            resolution: Some((1280, 720)),
+            ..IFrameStream::new("2M/iframes.m3u8", 186000)
+        }]
+    );
+
+    let file = fs::File::open("tests/resources/ll-hls.m3u8").expect("Opened test file");
+    assert!(matches!(read_any_playlist(file), Ok(Playlist::Media(_))));
+    let missing_bandwidth = "#EXTM3U\n#EXT-X-STREAM-INF:CODECS=\"avc1.64001f\"\nlow.m3u8\n";
+    assert!(matches!(
+        missing_bandwidth.parse::<MultivariantPlaylist>(),
+        Err(ParsePlaylistError::MissingField {
+            line: Some(2),
+            field: "bandwidth",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn multivariant_tags_keep_unknown_attributes() {
+    let source = "#EXTM3U\n\
+        #EXT-X-SESSION-DATA:DATA-ID=\"com.example.title\",VALUE=\"Live\",X-ORIGIN=\"studio\"\n\
+        #EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"English\",URI=\"audio.m3u8\",X-BIT-DEPTH=24\n\
+        #EXT-X-STREAM-INF:BANDWIDTH=1280000,X-LAYER=\"base\",X-PRIORITY=2\n\
+        low.m3u8\n\
+        #EXT-X-I-FRAME-STREAM-INF:BANDWIDTH=86000,URI=\"iframes.m3u8\",X-LAYER=\"base\"\n";
+    let multivariant: MultivariantPlaylist = source.parse().expect("Parsed multivariant playlist");
+    assert_eq!(
+        multivariant.session_data[0].extra_attributes["X-ORIGIN"],
+        "\"studio\""
+    );
+    assert_eq!(
+        multivariant.renditions[0].extra_attributes["X-BIT-DEPTH"],
+        "24"
+    );
+    assert_eq!(multivariant.variants[0].extra_attributes.len(), 2);
+    assert_eq!(
+        multivariant.i_frame_streams[0].extra_attributes["X-LAYER"],
+        "\"base\""
+    );
+    assert_eq!(multivariant.to_string(), source);
+}
+
+#[test]
+fn every_playlist_element_renders_as_its_tag() {
+    assert_eq!(
+        ServerControl::new(true, Duration::from_secs(1), Duration::from_secs(24)).to_string(),
+        "#EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=1,CAN-SKIP-UNTIL=24"
+    );
+    assert_eq!(
+        PartInf::new(Duration::from_micros(333340)).to_string(),
+        "#EXT-X-PART-INF:PART-TARGET=0.33334"
+    );
+    let mut hint = PreloadHint::new(PreloadHintType::Part, "filePart271.1.mp4");
+    hint.byterange_start = Some(1024);
+    assert_eq!(
+        hint.to_string(),
+        "#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"filePart271.1.mp4\",BYTERANGE-START=1024"
+    );
+    assert_eq!(
+        RenditionReport::new("../1M/waitForMSN.php", 273, Some(2)).to_string(),
+        "#EXT-X-RENDITION-REPORT:URI=\"../1M/waitForMSN.php\",LAST-MSN=273,LAST-PART=2"
+    );
+    let segment = MediaSegment::new(
+        Duration::from_micros(4000080),
+        segment_uri("fileSequence270.mp4"),
+        Vec::new(),
+        None,
+    );
+    assert_eq!(
+        segment.to_string(),
+        "#EXTINF:4.00008,\nfileSequence270.mp4\n"
+    );
+
+    let file = fs::File::open("tests/resources/ll-hls-full.m3u8").expect("Opened test file");
+    let playlist = read_playlist(file).expect("Parsed test file");
+    let mut written = Vec::new();
+    playlist.write_to(&mut written).expect("Wrote playlist");
+    assert_eq!(
+        String::from_utf8(written).expect("UTF-8"),
+        playlist.to_string()
+    );
+}
+
+#[test]
+fn maps_parse_with_byterange_and_apply_to_later_segments() {
+    let source = fs::read_to_string("tests/resources/ll-hls-map.m3u8").expect("Read test file");
+    let file = fs::File::open("tests/resources/ll-hls-map.m3u8").expect("Opened test file");
+    let playlist = read_playlist(file).expect("Parsed test file");
+    assert_eq!(playlist.to_string(), source);
+    assert_eq!(playlist.estimated_serialized_len(), source.len());
+
+    let first = playlist.media_segments().get(0).expect("First segment");
+    assert_eq!(
+        first.map(),
+        Some(&Map {
+            byterange: Some(ByteRange::new(720, Some(0))),
+            ..Map::new("main.mp4")
+        })
+    );
+    assert!(playlist
+        .media_segments()
+        .get(1)
+        .expect("Second segment")
+        .map()
+        .is_none());
+    assert_eq!(playlist.map_for(1), first.map());
+    assert_eq!(
+        playlist.map_for(2).map(|map| map.uri.as_str()),
+        Some("init-1080p.mp4")
+    );
+    assert_eq!("1024".parse(), Ok(ByteRange::new(1024, None)));
+}
+
+#[test]
+fn date_ranges_parse_and_follow_delta_updates() {
+    let read = |path: &str| {
+        let file = fs::File::open(path).expect("Opened test file");
+        read_playlist(file).expect("Parsed test file")
+    };
+    let mut full = read("tests/resources/ll-hls-full.m3u8");
+    let mut ad = DateRange::new("ad-1", date("2019-02-14T02:14:00.106Z"));
+    ad.planned_duration = Some(30.0);
+    let mut next = DateRange::new("ad-2", date("2019-02-14T02:14:04.106Z"));
+    next.end_on_next = true;
+    full.set_date_ranges(vec![ad, next.clone()]);
+
+    let delta = read("tests/resources/ll-hls-daterange-delta.m3u8");
+    let added = &delta.date_ranges()[0];
+    assert_eq!(added.id, "ad-3");
+    assert_eq!(added.class.as_deref(), Some("com.example.ad"));
+    assert_eq!(added.planned_duration, Some(30.0));
+    assert_eq!(
+        added.client_attributes,
+        vec![("X-AD-ID".to_string(), "\"3\"".to_string())]
+    );
+    assert_eq!(
+        added.scte35_out.as_ref().map(HexBytes::as_str),
+        Some("0xFC002F0000")
+    );
+    assert!(delta
+        .to_string()
+        .contains("#EXT-X-DATERANGE:ID=\"ad-3\",CLASS=\"com.example.ad\""));
+
+    let mut session = ClientSession::new();
+    assert_eq!(session.receive(full.clone()), Ok(UpdateKind::Full));
+    assert_eq!(session.receive(delta.clone()), Ok(UpdateKind::Delta));
+    let playlist = session.playlist().expect("Holds a playlist");
+    assert_eq!(playlist.date_ranges(), &[next, added.clone()]);
+
+    // A response to _HLS_skip=YES lists every daterange still current, so
+    // the ones it leaves out are gone
+    let source = fs::read_to_string("tests/resources/ll-hls-daterange-delta.m3u8")
+        .expect("Read test file")
+        .replace(",CAN-SKIP-DATERANGES=YES", "")
+        .replace(",RECENTLY-REMOVED-DATERANGES=\"ad-1\"", "");
+    let delta = parse_bytes(source.as_bytes()).expect("Parsed playlist");
+    assert_eq!(full.apply_delta(delta), Ok(UpdateKind::Delta));
+    assert_eq!(full.date_ranges(), core::slice::from_ref(added));
+}
+
+#[test]
+fn parse_errors_carry_line_tag_and_attribute() {
+    let read_playlist_from_str = |source: &str| parse_bytes(source.as_bytes());
+    let playlist = "#EXTM3U\n#EXT-X-VERSION:6\n#EXT-X-TARGETDURATION:4\n\
+        #EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=MAYBE\n";
+    let error = read_playlist_from_str(playlist)
+        .err()
+        .expect("Invalid attribute value");
+    match &error {
+        ParsePlaylistError::InvalidTag {
+            line: 4,
+            tag,
+            error: ParseTagError::Attribute(ParseAttributeError::InvalidValue { attribute, value }),
+        } => {
+            assert_eq!(tag, "EXT-X-SERVER-CONTROL");
+            assert_eq!(attribute, "CAN-BLOCK-RELOAD");
+            assert_eq!(value, "MAYBE");
+        }
+        other => panic!("Unexpected error {:?}", other),
+    }
+    assert_eq!(
+        error.to_string(),
+        "line 4: #EXT-X-SERVER-CONTROL: invalid value MAYBE for attribute CAN-BLOCK-RELOAD"
+    );
+    let source = std::error::Error::source(&error).expect("Tag error");
+    assert!(std::error::Error::source(source).is_some());
+
+    let error = read_playlist_from_str("#EXTM3U\n#EXT-X-VERSION:six\n")
+        .err()
+        .expect("Invalid value");
+    assert!(matches!(
+        error,
+        ParsePlaylistError::InvalidTag { line: 2, error: ParseTagError::Invalid { ref value }, .. }
+            if value == "six"
+    ));
+    assert!(matches!(
+        read_playlist_from_str("#EXT-X-VERSION:6\n"),
+        Err(ParsePlaylistError::MissingHeader)
+    ));
+}
+
+#[test]
+fn attribute_lists_keep_commas_inside_quoted_strings() {
+    let range: DateRange = "ID=\"ad,1\",START-DATE=\"2024-01-01T00:00:00Z\",\
+        X-TAGS=\"sports,live\",DURATION=30"
+        .parse()
+        .expect("Parsed daterange");
+    assert_eq!(range.id, "ad,1");
+    assert_eq!(
+        range.client_attributes,
+        vec![("X-TAGS".to_string(), "\"sports,live\"".to_string())]
+    );
+    assert_eq!(range.duration, Some(30.0));
+
+    let invalid = |list: &str| match list.parse::<DateRange>() {
+        Err(ParseTagError::AttributeList(error)) => error,
+        other => panic!("Unexpected result {:?}", other),
+    };
+    assert_eq!(
+        invalid("ID=\"ad-1,START-DATE=\"2024-01-01T00:00:00Z"),
+        AttributeListError::InvalidValue {
+            attribute: "ID".to_string()
+        }
+    );
+    assert_eq!(
+        invalid("ID=\"ad-1\",ID=\"ad-2\""),
+        AttributeListError::DuplicateName {
+            name: "ID".to_string()
+        }
+    );
+    assert_eq!(
+        invalid("ID=\"ad-1\",CLASS"),
+        AttributeListError::MissingValue {
+            attribute: "CLASS".to_string()
+        }
+    );
+    assert_eq!(
+        parse_attribute_list("URI=\"a.mp4"),
+        Err(AttributeListError::UnterminatedQuote)
+    );
+    assert_eq!(parse_attribute_list(""), Ok(Vec::new()));
+}
+
+#[test]
+fn parsed_playlist_is_readable_through_getters() {
+    let file = fs::File::open("tests/resources/ll-hls-canonical.m3u8").expect("Opened test file");
+    let playlist = read_playlist(file).expect("Parsed file");
+    assert_eq!(playlist.target_duration(), 4);
+    assert_eq!(playlist.version(), 9);
+    assert_eq!(playlist.media_sequence_number(), 266);
+    assert_eq!(
+        playlist.part_inf().map(PartInf::part_target),
+        Some(Duration::from_micros(333340))
+    );
+    let server_control = playlist.server_control();
+    assert!(server_control.can_block_reload());
+    assert_eq!(server_control.part_hold_back(), Duration::from_secs(1));
+    assert_eq!(server_control.can_skip_until(), Duration::from_secs(12));
+    assert_eq!(playlist.skip().map(|skip| skip.skipped_segments), Some(3));
+    assert_eq!(
+        playlist.preload_hint().map(|hint| hint.uri.as_str()),
+        Some("filePart271.0.mp4")
+    );
+    let report = &playlist.rendition_reports()[0];
+    assert_eq!(
+        (report.uri(), report.last_msn(), report.last_part()),
+        ("../1M/waitForMSN.php", 270, Some(1))
+    );
+
+    let segments: Vec<&MediaSegment> = playlist.segments().collect();
+    assert_eq!(segments.len(), 2);
+    assert_eq!(segments[0].duration(), Duration::from_micros(4000080));
+    assert_eq!(segments[0].uri().as_str(), "fileSequence269.mp4");
+    assert!(segments[0].parts().is_empty());
+    assert!(segments[0].program_date_time().is_none());
+    assert!(!segments[1].discontinuity());
+    assert_eq!(
+        segments[1].program_date_time(),
+        Some(&date("2019-02-14T02:14:00.106Z"))
+    );
+    assert_eq!(segments[1].parts()[0].independent, Some(true));
+    assert_eq!(playlist.parts().count(), 2);
+}
+
+#[cfg(feature = "reqwest")]
+#[tokio::test]
+async fn ll_hls_client_blocks_on_the_next_part() {
+    use futures_util::StreamExt;
+    use llhls_rs::{ClientError, Fetch, LlHlsClient};
+    use std::{collections::VecDeque, io, sync::Mutex};
+
+    // Serves canned responses, recording the URLs asked for
+    #[derive(Clone, Default)]
+    struct Canned {
+        responses: Arc<Mutex<VecDeque<io::Result<String>>>>,
+        urls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Fetch for Canned {
+        type Error = io::Error;
+
+        async fn fetch(&self, url: &str) -> io::Result<String> {
+            self.urls.lock().unwrap().push(url.to_string());
+            self.responses
+                .lock()
+                .unwrap()
+                .pop_front()
+                .unwrap_or_else(|| Err(io::Error::other("no more responses")))
+        }
+    }
+
+    let full = fs::read_to_string("tests/resources/ll-hls-full.m3u8").expect("Read test file");
+    let canned = Canned::default();
+    canned
+        .responses
+        .lock()
+        .unwrap()
+        .extend([Ok(full.clone()), Ok(full)]);
+    let mut client = LlHlsClient::new(canned.clone(), "https://example.com/2M/live.m3u8?token=a");
+    let first = client.reload().await.expect("Loaded playlist");
+    assert_eq!(first.media_sequence_number(), 266);
+    let request = client.next_request();
+    assert_eq!(
+        (request.msn, request.part, request.skip),
+        (Some(273), Some(4), true)
+    );
+
+    let mut updates = Box::pin(client.updates());
+    assert!(updates.next().await.expect("Update").is_ok());
+    let error = updates.next().await.expect("Update").err().expect("Failed");
+    assert!(matches!(error, ClientError::Fetch(_)));
+    assert_eq!(error.code(), ErrorCode::FETCH_FAILED);
+    assert_eq!(
+        *canned.urls.lock().unwrap(),
+        vec![
+            "https://example.com/2M/live.m3u8?token=a".to_string(),
+            "https://example.com/2M/live.m3u8?token=a&_HLS_msn=273&_HLS_part=4&_HLS_skip=YES"
+                .to_string(),
+            "https://example.com/2M/live.m3u8?token=a&_HLS_msn=273&_HLS_part=4&_HLS_skip=YES"
+                .to_string(),
+        ]
+    );
+}
+
+#[test]
+fn apply_delta_splices_skipped_segments_onto_the_full_playlist() {
+    let read = |path: &str| {
+        let file = fs::File::open(path).expect("Opened test file");
+        read_playlist(file).expect("Parsed test file")
+    };
+    let mut playlist = read("tests/resources/ll-hls-full.m3u8");
+    let delta = read("tests/resources/ll-hls-canonical.m3u8");
+    assert_eq!(playlist.apply_delta(delta), Ok(UpdateKind::Delta));
+    assert!(playlist.skip().is_none());
+    assert_eq!(playlist.media_sequence_number(), 266);
+    let uris: Vec<&str> = playlist
+        .segments()
+        .map(|segment| segment.uri().as_str())
+        .collect();
+    assert_eq!(
+        uris,
+        [
+            "fileSequence266.mp4",
+            "fileSequence267.mp4",
+            "fileSequence268.mp4",
+            "fileSequence269.mp4",
+            "fileSequence270.mp4"
+        ]
+    );
+
+    // The init segment declared on a segment that left the window still
+    // applies to the first one kept
+    let source = "#EXTM3U\n#EXT-X-TARGETDURATION:4\n#EXT-X-VERSION:9\n\
+        #EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=1,CAN-SKIP-UNTIL=12\n\
+        #EXT-X-PART-INF:PART-TARGET=0.33334\n#EXT-X-MEDIA-SEQUENCE:270\n\
+        #EXT-X-SKIP:SKIPPED-SEGMENTS=1\n#EXT-X-MAP:URI=\"init-1080p.mp4\"\n\
+        #EXT-X-PART:DURATION=0.33334,URI=\"filePart271.0.mp4\",INDEPENDENT=YES\n";
+    let mut playlist = read("tests/resources/ll-hls-map.m3u8");
+    let delta = parse_bytes(source.as_bytes()).expect("Parsed delta");
+    assert_eq!(playlist.apply_delta(delta), Ok(UpdateKind::Delta));
+    assert_eq!(playlist.media_segments().len(), 1);
+    assert_eq!(
+        playlist.map_for(0).map(|map| map.uri.as_str()),
+        Some("main.mp4")
+    );
+    assert_eq!(
+        playlist.map_for(1).map(|map| map.uri.as_str()),
+        Some("init-1080p.mp4")
+    );
+
+    // Keys too, unless the first segment listed declares its own
+    let header = "#EXTM3U\n#EXT-X-TARGETDURATION:4\n#EXT-X-VERSION:9\n\
+        #EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=NO,CAN-SKIP-UNTIL=12\n";
+    let full = format!(
+        "{}#EXT-X-MEDIA-SEQUENCE:0\n#EXT-X-KEY:METHOD=AES-128,URI=\"a.key\"\n\
+         #EXTINF:4,\nsegment0.mp4\n#EXTINF:4,\nsegment1.mp4\n",
+        header
+    );
+    let key_uris = |delta: &str| -> Vec<String> {
+        let mut playlist = parse_bytes(full.as_bytes()).expect("Parsed playlist");
+        let delta = parse_bytes(format!("{}{}", header, delta).as_bytes()).expect("Parsed delta");
+        assert_eq!(playlist.apply_delta(delta), Ok(UpdateKind::Delta));
+        playlist
+            .keys_for(0)
+            .into_iter()
+            .filter_map(|key| key.uri.clone())
+            .collect()
+    };
+    assert_eq!(
+        key_uris(
+            "#EXT-X-MEDIA-SEQUENCE:1\n#EXT-X-SKIP:SKIPPED-SEGMENTS=0\n\
+             #EXTINF:4,\nsegment1.mp4\n"
+        ),
+        ["a.key"]
+    );
+    assert_eq!(
+        key_uris(
+            "#EXT-X-MEDIA-SEQUENCE:1\n#EXT-X-SKIP:SKIPPED-SEGMENTS=0\n\
+             #EXT-X-KEY:METHOD=AES-128,URI=\"b.key\"\n#EXTINF:4,\nsegment1.mp4\n"
+        ),
+        ["b.key"]
+    );
+
+    let mut unrelated = read("tests/resources/ll-hls-map.m3u8");
+    let delta = read("tests/resources/ll-hls-canonical.m3u8");
+    assert!(matches!(
+        unrelated.apply_delta(delta),
+        Err(SessionError::SkippedSegmentsUnknown { .. })
+    ));
+    assert_eq!(unrelated.media_sequence_number(), 269);
+}
+
+#[test]
+fn to_delta_skips_the_oldest_segments() {
+    let file = fs::File::open("tests/resources/ll-hls-full.m3u8").expect("Opened test file");
+    let mut full = read_playlist(file).expect("Parsed test file");
+    let delta = full.to_delta(Duration::from_secs(12));
+    assert_eq!(delta.skip().map(|skip| skip.skipped_segments), Some(4));
+    assert_eq!(delta.media_sequence_number(), 266);
+    assert_eq!(
+        delta
+            .segments()
+            .next()
+            .map(|segment| segment.uri().as_str()),
+        Some("fileSequence270.mp4")
+    );
+    assert!(delta
+        .to_string()
+        .contains("#EXT-X-SKIP:SKIPPED-SEGMENTS=4\n#EXTINF:4.00008,\nfileSequence270.mp4"));
+    let mut merged = full.clone();
+    assert_eq!(merged.apply_delta(delta), Ok(UpdateKind::Delta));
+    assert_eq!(merged.to_string(), full.to_string());
+    assert!(full.to_delta(Duration::from_secs(60)).skip().is_none());
+
+    // The parts of the segment being published count towards what stays
+    // listed, as validation counts them
+    let live = parse_bytes(
+        b"#EXTM3U\n#EXT-X-TARGETDURATION:4\n#EXT-X-VERSION:9\n\
+          #EXT-X-PART-INF:PART-TARGET=1\n\
+          #EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=3,CAN-SKIP-UNTIL=6\n\
+          #EXT-X-MEDIA-SEQUENCE:0\n\
+          #EXTINF:4,\nsegment0.mp4\n#EXTINF:4,\nsegment1.mp4\n#EXTINF:4,\nsegment2.mp4\n\
+          #EXT-X-PART:DURATION=1,URI=\"part3.0.mp4\",INDEPENDENT=YES\n\
+          #EXT-X-PART:DURATION=1,URI=\"part3.1.mp4\"\n",
+    )
+    .expect("Parsed playlist");
+    let delta = live.to_delta(Duration::from_secs(6));
+    assert_eq!(delta.skip().map(|skip| skip.skipped_segments), Some(2));
+    assert!(!delta
+        .validate_profile(ValidationProfile::Rfc8216)
+        .iter()
+        .any(|issue| issue.rule == Rule::SkipWindowTooLarge));
+
+    // EXT-X-SKIP needs version 9, and RECENTLY-REMOVED-DATERANGES 10
+    let source = fs::read_to_string("tests/resources/ll-hls-full.m3u8")
+        .expect("Read test file")
+        .replace("#EXT-X-VERSION:9\n", "#EXT-X-VERSION:6\n");
+    let older = parse_bytes(source.as_bytes()).expect("Parsed playlist");
+    assert_eq!(older.to_delta(Duration::from_secs(12)).version(), 9);
+    assert_eq!(older.to_delta(Duration::from_secs(60)).version(), 6);
+    let mut removed = RemovedDateRanges::new();
+    removed.record("ad-0", 271);
+    let delta = older.to_delta_v2(Duration::from_secs(12), &removed);
+    assert_eq!(delta.version(), 10);
+
+    let before = DateRange::new("ad-1", date("2019-02-14T02:13:50.106Z"));
+    let after = DateRange::new("ad-2", date("2019-02-14T02:14:04.106Z"));
+    full.set_date_ranges(vec![before, after.clone()]);
+    let mut removed = RemovedDateRanges::new();
+    removed.record("ad-0", 271);
+    removed.record("ad-old", 265);
+    let delta = full.to_delta_v2(Duration::from_secs(4), &removed);
+    let skip = delta.skip().expect("Delta update");
+    assert_eq!(skip.skipped_segments, 6);
+    assert_eq!(skip.recently_removed_dateranges, ["ad-0"]);
+    assert_eq!(delta.date_ranges(), &[after]);
+    assert_eq!(full.to_delta(Duration::from_secs(4)).date_ranges().len(), 2);
+}
+
+#[test]
+fn endlist_and_playlist_type_are_kept() {
+    let source = fs::read_to_string("tests/resources/ll-hls-canonical.m3u8")
+        .expect("Read test file")
+        .replace(
+            "#EXT-X-MEDIA-SEQUENCE:266\n",
+            "#EXT-X-MEDIA-SEQUENCE:266\n#EXT-X-PLAYLIST-TYPE:EVENT\n",
+        )
+        + "#EXT-X-ENDLIST\n";
+    let mut hooks = CountingHooks::default();
+    let mut playlist =
+        parse_with_options(&source, ParseOptions::default(), &mut hooks).expect("Parsed playlist");
+    assert!(hooks.warnings.is_empty());
+    assert_eq!(playlist.playlist_type(), Some(PlaylistType::Event));
+    assert!(playlist.end_list());
+    assert!(!playlist.is_live());
+    assert_eq!(playlist.media_segments().len(), 2);
+    assert_eq!(playlist.to_string(), source);
+    assert_eq!(playlist.estimated_serialized_len(), source.len());
+
+    playlist.set_end_list(false);
+    playlist.set_playlist_type(None);
+    assert!(playlist.is_live());
+    assert_eq!(
+        playlist.to_string(),
+        fs::read_to_string("tests/resources/ll-hls-canonical.m3u8").expect("Read test file")
+    );
+}
+
+#[test]
+fn discontinuity_sequence_numbers_are_tracked() {
+    let source = fs::read_to_string("tests/resources/ll-hls-canonical.m3u8")
+        .expect("Read test file")
+        .replace(
+            "#EXT-X-MEDIA-SEQUENCE:266\n",
+            "#EXT-X-MEDIA-SEQUENCE:266\n#EXT-X-DISCONTINUITY-SEQUENCE:5\n",
+        )
+        .replace(
+            "#EXT-X-PROGRAM-DATE-TIME",
+            "#EXT-X-DISCONTINUITY\n#EXT-X-PROGRAM-DATE-TIME",
+        )
+        .replace(
+            "#EXT-X-PRELOAD-HINT",
+            "#EXT-X-DISCONTINUITY\n#EXT-X-PRELOAD-HINT",
+        );
+    let playlist = parse_bytes(source.as_bytes()).expect("Parsed playlist");
+    assert_eq!(playlist.discontinuity_sequence(), 5);
+    let flags: Vec<_> = playlist
+        .segments()
+        .map(|segment| segment.discontinuity())
+        .collect();
+    assert_eq!(flags, [false, true]);
+    assert_eq!(playlist.discontinuity_sequence_for(0), 5);
+    assert_eq!(playlist.discontinuity_sequence_for(1), 6);
+    assert_eq!(playlist.discontinuity_sequence_for(2), 7);
+    assert_eq!(playlist.discontinuity_sequence_of(268), None);
+    assert_eq!(playlist.discontinuity_sequence_of(269), Some(5));
+    assert_eq!(playlist.discontinuity_sequence_of(270), Some(6));
+    // Nothing of segment 271 is published yet
+    assert_eq!(playlist.discontinuity_sequence_of(271), None);
+    assert_eq!(playlist.to_string(), source);
+    assert_eq!(playlist.estimated_serialized_len(), source.len());
+
+    // Dropping the segments takes their discontinuities along
+    let mut playlist = playlist;
+    playlist.pop_segment();
+    assert_eq!(playlist.discontinuity_sequence(), 5);
+    playlist.pop_segment();
+    assert_eq!(playlist.discontinuity_sequence(), 6);
+    assert_eq!(playlist.discontinuity_sequence_for(0), 7);
+}
+
+#[test]
+fn keys_apply_until_changed() {
+    let source = fs::read_to_string("tests/resources/ll-hls-canonical.m3u8")
+        .expect("Read test file")
+        .replace(
+            "#EXTINF:4.00008,\nfileSequence269.mp4\n",
+            "#EXT-X-KEY:METHOD=AES-128,URI=\"https://keys.example.com/1\",IV=0x00000000000000000000000000000001\n\
+             #EXTINF:4.00008,\nfileSequence269.mp4\n",
+        )
+        .replace(
+            "#EXT-X-PROGRAM-DATE-TIME",
+            "#EXT-X-KEY:METHOD=SAMPLE-AES,URI=\"skd://2\",KEYFORMAT=\"com.apple.streamingkeydelivery\",KEYFORMATVERSIONS=\"1\"\n\
+             #EXT-X-KEY:METHOD=SAMPLE-AES,URI=\"data:text/plain;base64,AAAA\",KEYFORMAT=\"urn:uuid:edef8ba9-79d6-4ace-a3c8-27dcd51d21ed\"\n\
+             #EXT-X-PROGRAM-DATE-TIME",
+        )
+        .replace(
+            "#EXT-X-PRELOAD-HINT",
+            "#EXT-X-KEY:METHOD=NONE\n#EXT-X-PRELOAD-HINT",
+        );
+    let mut playlist = parse_bytes(source.as_bytes()).expect("Parsed playlist");
+
+    let first = playlist.keys_for(0)[0];
+    assert_eq!(first.method, KeyMethod::Aes128);
+    assert_eq!(first.uri.as_deref(), Some("https://keys.example.com/1"));
+    assert_eq!(
+        first
+            .iv
+            .as_ref()
+            .map(HexBytes::to_bytes)
+            .map(|bytes| bytes[15]),
+        Some(1)
+    );
+    // The first key keeps applying to the identity format the second
+    // segment doesn't redeclare
+    let second = playlist.keys_for(1);
+    assert_eq!(second.len(), 3);
+    assert_eq!(
+        second[0].keyformat.as_deref(),
+        Some("com.apple.streamingkeydelivery")
+    );
+    assert_eq!(second[0].keyformat_versions.as_deref(), Some("1"));
+    assert!(second[..2]
+        .iter()
+        .all(|key| key.method == KeyMethod::SampleAes));
+    assert_eq!(second[2], first);
+    assert_eq!(playlist.keys_for(2), [&Key::none()]);
+    assert_eq!(playlist.to_string(), source);
+    assert_eq!(playlist.estimated_serialized_len(), source.len());
+
+    // The first key moves along to the format the second segment doesn't
+    // declare when its segment leaves the window
+    let first = first.clone();
+    playlist.pop_segment();
+    assert_eq!(playlist.keys_for(0).len(), 3);
+    assert_eq!(playlist.keys_for(0)[2], &first);
+    assert_eq!(
+        playlist
+            .segments()
+            .next()
+            .map(|segment| segment.keys().len()),
+        Some(3)
+    );
+
+    let missing_uri = source.replace(
+        "#EXT-X-KEY:METHOD=AES-128,URI=\"https://keys.example.com/1\",",
+        "#EXT-X-KEY:METHOD=AES-128,",
+    );
+    let error = parse_bytes(missing_uri.as_bytes())
+        .err()
+        .expect("A key without URI is rejected");
+    assert!(matches!(
+        error,
+        ParsePlaylistError::MissingField {
+            line: Some(8),
+            field: "uri",
+            ..
+        }
+    ));
+
+    let multivariant: MultivariantPlaylist = "#EXTM3U\n\
+        #EXT-X-SESSION-KEY:METHOD=SAMPLE-AES,URI=\"skd://2\",KEYFORMAT=\"com.apple.streamingkeydelivery\"\n\
+        #EXT-X-STREAM-INF:BANDWIDTH=1000000\n\
+        low.m3u8\n"
+        .parse()
+        .expect("Parsed multivariant playlist");
+    assert_eq!(multivariant.session_keys.len(), 1);
+    assert_eq!(multivariant.session_keys[0].uri.as_deref(), Some("skd://2"));
+    assert!(multivariant
+        .to_string()
+        .contains("#EXT-X-SESSION-KEY:METHOD=SAMPLE-AES,URI=\"skd://2\",KEYFORMAT=\"com.apple.streamingkeydelivery\"\n"));
+    assert!("#EXTM3U\n#EXT-X-SESSION-KEY:METHOD=NONE\n"
+        .parse::<MultivariantPlaylist>()
+        .is_err());
+}
+
+#[test]
+fn keys_of_other_formats_apply_when_one_rotates() {
+    let source = "#EXTM3U\n#EXT-X-TARGETDURATION:4\n#EXT-X-VERSION:5\n\
+        #EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=NO\n#EXT-X-MEDIA-SEQUENCE:0\n\
+        #EXT-X-KEY:METHOD=SAMPLE-AES,URI=\"skd://1\",KEYFORMAT=\"com.apple.streamingkeydelivery\"\n\
+        #EXT-X-KEY:METHOD=SAMPLE-AES,URI=\"https://keys.example.com/1\"\n\
+        #EXTINF:4,\nsegment0.mp4\n\
+        #EXT-X-KEY:METHOD=SAMPLE-AES,URI=\"skd://2\",KEYFORMAT=\"com.apple.streamingkeydelivery\"\n\
+        #EXTINF:4,\nsegment1.mp4\n\
+        #EXTINF:4,\nsegment2.mp4\n";
+    let mut playlist = parse_bytes(source.as_bytes()).expect("Parsed playlist");
+    let uris = |keys: Vec<&Key>| -> Vec<String> {
+        keys.iter()
+            .map(|key| key.uri.clone().unwrap_or_default())
+            .collect()
+    };
+    assert_eq!(
+        uris(playlist.keys_for(0)),
+        ["skd://1", "https://keys.example.com/1"]
+    );
+    assert_eq!(
+        uris(playlist.keys_for(2)),
+        ["skd://2", "https://keys.example.com/1"]
+    );
+    assert_eq!(playlist.to_string(), source);
+
+    // The identity key moves along with the segment leaving the window
+    playlist.pop_segment();
+    assert_eq!(
+        uris(playlist.keys_for(0)),
+        ["skd://2", "https://keys.example.com/1"]
+    );
+    assert_eq!(playlist.keys_for(1), playlist.keys_for(0));
+}
+
+#[test]
+fn gap_segments_and_parts_are_flagged() {
+    let source = fs::read_to_string("tests/resources/ll-hls-canonical.m3u8")
+        .expect("Read test file")
+        .replace(
+            "#EXTINF:4.00008,\nfileSequence269.mp4\n",
+            "#EXT-X-GAP\n#EXTINF:4.00008,\nfileSequence269.mp4\n",
+        )
+        .replace(
+            "URI=\"filePart270.1.mp4\"",
+            "URI=\"filePart270.1.mp4\",GAP=YES",
+        );
+    let playlist = parse_bytes(source.as_bytes()).expect("Parsed playlist");
+    let gaps: Vec<_> = playlist
+        .segments()
+        .map(|segment| segment.is_gap())
+        .collect();
+    assert_eq!(gaps, [true, false]);
+    let gaps: Vec<_> = playlist.parts().map(|part| part.is_gap()).collect();
+    assert_eq!(gaps, [false, true]);
+    assert_eq!(playlist.to_string(), source);
+    assert_eq!(playlist.estimated_serialized_len(), source.len());
+
+    assert!(parse_bytes(source.replace("GAP=YES", "GAP=MAYBE").as_bytes()).is_err());
+}
+
+#[test]
+fn bitrate_applies_to_later_segments() {
+    let canonical =
+        fs::read_to_string("tests/resources/ll-hls-canonical.m3u8").expect("Read test file");
+    let source = canonical.replace(
+        "#EXTINF:4.00008,\nfileSequence269.mp4\n",
+        "#EXT-X-BITRATE:1000\n#EXTINF:4.00008,\nfileSequence269.mp4\n",
+    );
+    let mut playlist = parse_bytes(source.as_bytes()).expect("Parsed playlist");
+    let bitrates: Vec<_> = playlist
+        .segments()
+        .map(|segment| segment.bitrate())
+        .collect();
+    assert_eq!(bitrates, [Some(1_000_000), Some(1_000_000)]);
+    assert_eq!(playlist.average_bitrate(), Some(1_000_000));
+    assert_eq!(playlist.to_string(), source);
+    assert_eq!(playlist.estimated_serialized_len(), source.len());
+
+    // The next segment declares the bit rate once the first one is gone
+    playlist.pop_segment();
+    assert!(playlist
+        .to_string()
+        .contains("#EXT-X-BITRATE:1000\n#EXT-X-PROGRAM-DATE-TIME"));
+    assert_eq!(
+        playlist.estimated_serialized_len(),
+        playlist.to_string().len()
+    );
+
+    let source = source.replace(
+        "#EXT-X-PROGRAM-DATE-TIME",
+        "#EXT-X-BITRATE:3000\n#EXT-X-PROGRAM-DATE-TIME",
+    );
+    let playlist = parse_bytes(source.as_bytes()).expect("Parsed playlist");
+    assert_eq!(playlist.average_bitrate(), Some(2_000_000));
+    assert_eq!(playlist.to_string(), source);
+
+    let playlist = parse_bytes(canonical.as_bytes()).expect("Parsed playlist");
+    assert_eq!(playlist.average_bitrate(), None);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn playlists_round_trip_through_json() {
+    let file = fs::File::open("tests/resources/ll-hls-canonical.m3u8").expect("Opened test file");
+    let playlist = read_playlist(file).expect("Parsed test file");
+    let json = serde_json::to_string(&playlist).expect("Serialized playlist");
+    let value: serde_json::Value = serde_json::from_str(&json).expect("Valid JSON");
+    assert_eq!(value["media_sequence_number"], 266);
+    assert_eq!(value["media_segments"][0]["uri"], "fileSequence269.mp4");
+    assert_eq!(
+        value["media_segments"][1]["program_date_time"],
+        "2019-02-14T02:14:00.106Z"
+    );
+    let restored: MediaPlaylist = serde_json::from_str(&json).expect("Deserialized playlist");
+    assert_eq!(restored.to_string(), playlist.to_string());
+
+    let multivariant: MultivariantPlaylist =
+        fs::read_to_string("tests/resources/multivariant.m3u8")
+            .expect("Read test file")
+            .parse()
+            .expect("Parsed multivariant playlist");
+    let json = serde_json::to_string(&multivariant).expect("Serialized playlist");
+    assert_eq!(
+        serde_json::from_str::<MultivariantPlaylist>(&json).expect("Deserialized playlist"),
+        multivariant
+    );
+
+    let bad_date = serde_json::to_string(&playlist)
+        .expect("Serialized playlist")
+        .replace("2019-02-14T02:14:00.106Z", "yesterday");
+    #[cfg(any(feature = "chrono", feature = "time"))]
+    assert!(serde_json::from_str::<MediaPlaylist>(&bad_date).is_err());
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
+    let _ = bad_date;
+}
+
+#[test]
+fn builder_fills_defaults_and_validates() {
+    let mut builder = MediaPlaylistBuilder::new();
+    builder
+        .server_control(ServerControl::new(
+            true,
+            Duration::from_secs(1),
+            Duration::from_secs(0),
+        ))
+        .add_part(PartialSegment::new(
+            Duration::from_secs(2),
+            "filePart0.0.mp4",
+        ))
+        .add_part(PartialSegment::new(
+            Duration::from_secs(2),
+            "filePart0.1.mp4",
+        ))
+        .add_segment(MediaSegment::new(
+            Duration::from_secs(4),
+            segment_uri("fileSequence0.mp4"),
+            Vec::new(),
+            None,
+        ))
+        .add_part(PartialSegment::new(
+            Duration::from_millis(1500),
+            "filePart1.0.mp4",
+        ));
+    let playlist = builder.build().expect("Built playlist");
+    assert_eq!(playlist.target_duration(), 4);
+    assert_eq!(playlist.media_sequence_number(), 0);
+    assert_eq!(
+        playlist.part_inf().map(PartInf::part_target),
+        Some(Duration::from_secs(2))
+    );
+    assert_eq!(playlist.version(), 6);
+    assert_eq!(
+        playlist
+            .segments()
+            .next()
+            .map(|segment| segment.parts().len()),
+        Some(2)
+    );
+    assert_eq!(playlist.trailing_parts().len(), 1);
+
+    builder.target_duration(3);
+    let Some(BuilderError::Invalid { issues }) = builder.build().err() else {
+        panic!("A segment longer than the target duration is rejected");
+    };
+    assert!(issues
+        .iter()
+        .all(|issue| issue.rule == Rule::SegmentExceedsTargetDuration));
+
+    builder.target_duration(4).version(3);
+    let Some(BuilderError::Invalid { issues }) = builder.build().err() else {
+        panic!("A version lower than the features need is rejected");
+    };
+    assert_eq!(issues[0].rule, Rule::VersionTooLow);
+}
+
+#[test]
+fn playlist_parser_reads_chunks_as_they_arrive() {
+    let canonical =
+        fs::read_to_string("tests/resources/ll-hls-canonical.m3u8").expect("Read test file");
+    let source = canonical.replace("#EXT-X-SKIP", "# Café\n#EXT-X-SKIP");
+    let mut parser = PlaylistParser::new();
+    let mut hooks = CountingHooks::default();
+    let first_segment_end = source.find("fileSequence269.mp4\n").expect("First segment")
+        + "fileSequence269.mp4\n".len();
+    // Byte by byte, splitting the UTF-8 sequence of the comment
+    for (index, byte) in source.bytes().enumerate() {
+        parser.feed(&[byte], &mut hooks).expect("Parsed chunk");
+        if index < first_segment_end {
+            let completed = usize::from(index + 1 == first_segment_end);
+            assert_eq!(parser.segments().len(), completed);
+        }
+    }
+    assert_eq!(parser.segments().len(), 2);
+    assert_eq!(hooks.segments, 2);
+    let playlist = parser.finish(&mut hooks).expect("Parsed playlist");
+    assert_eq!(playlist.to_string(), canonical);
+
+    // The last line doesn't need a terminator
+    let mut parser = PlaylistParser::new();
+    for chunk in canonical.trim_end().as_bytes().chunks(7) {
+        parser.feed(chunk, &mut NoHooks).expect("Parsed chunk");
+    }
+    assert_eq!(parser.segments().len(), 2);
+    let playlist = parser.finish(&mut NoHooks).expect("Parsed playlist");
+    assert_eq!(playlist.rendition_reports().len(), 1);
+
+    let mut parser = PlaylistParser::new();
+    assert!(matches!(
+        parser.feed(b"#EXT-X-VERSION:9\n", &mut NoHooks),
+        Err(ParsePlaylistError::MissingHeader)
+    ));
+    assert!(matches!(
+        PlaylistParser::new().finish(&mut NoHooks),
+        Err(ParsePlaylistError::MissingHeader)
+    ));
+    let mut parser = PlaylistParser::new();
+    assert!(matches!(
+        parser.feed(b"#EXTM3U\n\xff\n", &mut NoHooks),
+        Err(ParsePlaylistError::InvalidUtf8 { line: 2 })
+    ));
+}
+
+#[test]
+fn diff_lists_what_changed_between_reloads() {
+    let part = |uri: &str| PartialSegment::new(Duration::from_secs(1), uri.to_string());
+    let mut builder = MediaPlaylistBuilder::new();
+    builder
+        .media_sequence_number(10)
+        .server_control(ServerControl::new(
+            true,
+            Duration::from_secs(3),
+            Duration::from_secs(0),
+        ))
+        .add_part(part("part10.0.mp4"))
+        .add_part(part("part10.1.mp4"))
+        .add_segment(MediaSegment::new(
+            Duration::from_secs(2),
+            segment_uri("segment10.mp4"),
+            Vec::new(),
+            None,
+        ))
+        .add_part(part("part11.0.mp4"))
+        .preload_hint(Some(PreloadHint::new(
+            PreloadHintType::Part,
+            "part11.1.mp4",
+        )))
+        .rendition_reports(vec![
+            RenditionReport::new("../audio/en.m3u8", 11, Some(0)),
+            RenditionReport::new("../audio/fr.m3u8", 11, Some(0)),
+        ]);
+    let older = builder.build().expect("Built playlist");
+    assert!(older.diff(&older).is_empty());
+
+    let mut newer = older.clone();
+    newer.push_part(part("part11.1.mp4"));
+    newer.complete_segment(Duration::from_secs(2), segment_uri("segment11.mp4"), None);
+    newer.push_part(part("part12.0.mp4"));
+    newer.pop_segment();
+    newer.set_preload_hint(Some(PreloadHint::new(
+        PreloadHintType::Part,
+        "part12.1.mp4",
+    )));
+    newer.set_rendition_reports(vec![RenditionReport::new("../audio/en.m3u8", 12, Some(0))]);
+
+    let delta = older.diff(&newer);
+    let removed: Vec<_> = delta.removed_segments.iter().map(|(msn, _)| *msn).collect();
+    assert_eq!(removed, [10]);
+    let added: Vec<_> = delta
+        .added_segments
+        .iter()
+        .map(|(msn, segment)| (*msn, segment.parts().len()))
+        .collect();
+    assert_eq!(added, [(11, 2)]);
+    let parts: Vec<_> = delta
+        .added_parts
+        .iter()
+        .map(|(msn, index, part)| (*msn, *index, part.uri.as_str()))
+        .collect();
+    assert_eq!(parts, [(11, 1, "part11.1.mp4"), (12, 0, "part12.0.mp4")]);
+    assert_eq!(
+        delta
+            .preload_hints
+            .map(|hints| hints.iter().map(|hint| hint.uri.as_str()).collect()),
+        Some(vec!["part12.1.mp4"])
+    );
+    assert_eq!(delta.updated_rendition_reports.len(), 1);
+    assert_eq!(delta.updated_rendition_reports[0].last_msn(), 12);
+    assert_eq!(delta.removed_rendition_reports.len(), 1);
+    assert_eq!(delta.removed_rendition_reports[0].uri(), "../audio/fr.m3u8");
+    assert!(!delta.is_empty());
+}
+
+#[test]
+fn durations_keep_every_decimal_digit() {
+    let server_control: ServerControl =
+        "CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=1.000000001,CAN-SKIP-UNTIL=24"
+            .parse()
+            .expect("Parsed EXT-X-SERVER-CONTROL");
+    assert_eq!(server_control.part_hold_back(), Duration::new(1, 1));
+    assert_eq!(server_control.can_skip_until(), Duration::from_secs(24));
+    assert_eq!(
+        server_control.to_string(),
+        "#EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=1.000000001,CAN-SKIP-UNTIL=24"
+    );
+
+    let part: PartialSegment = "DURATION=0.1,URI=\"filePart0.0.mp4\""
+        .parse()
+        .expect("Parsed EXT-X-PART");
+    assert_eq!(part.part_duration, Duration::from_millis(100));
+    assert_eq!(
+        part.to_string(),
+        "#EXT-X-PART:DURATION=0.1,URI=\"filePart0.0.mp4\""
+    );
+}
+
+#[test]
+fn validate_checks_low_latency_timing_and_reload_order() {
+    let file = fs::File::open("tests/resources/ll-hls.m3u8").expect("Opened test file");
+    let playlist = read_playlist(file).expect("Parsed playlist");
+    let rules = |issues: Vec<ValidationIssue>| {
+        issues
+            .into_iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .map(|issue| issue.rule)
+            .collect::<Vec<_>>()
+    };
+    // The spec's own example offers skips of 12s with a target duration of 4
+    assert_eq!(
+        rules(playlist.validate()),
+        vec![Rule::SkipWindowBelowMinimum]
+    );
+
+    let mut builder = MediaPlaylistBuilder::new();
+    builder
+        .target_duration(4)
+        .part_inf(PartInf::new(Duration::from_secs(1)))
+        .server_control(ServerControl::new(
+            true,
+            Duration::from_millis(1500),
+            Duration::from_secs(12),
+        ))
+        .add_part(PartialSegment::new(
+            Duration::from_millis(1200),
+            "filePart0.0.mp4",
+        ));
+    let playlist = builder.build().expect("Built playlist");
+    assert_eq!(
+        rules(playlist.validate()),
+        vec![
+            Rule::PartExceedsPartTarget,
+            Rule::PartHoldBackBelowMinimum,
+            Rule::SkipWindowBelowMinimum
+        ]
+    );
+    // The first edition of the spec doesn't have these requirements
+    assert!(rules(playlist.validate_profile(ValidationProfile::Rfc8216)).is_empty());
+
+    let mut newer = playlist.clone();
+    newer.push_part(PartialSegment::new(
+        Duration::from_secs(1),
+        "filePart0.1.mp4",
+    ));
+    assert!(newer.validate_update(&playlist).is_empty());
+    let issues = playlist.validate_update(&newer);
+    assert_eq!(rules(issues.clone()), vec![Rule::LiveEdgeRegressed]);
+    assert_eq!(issues[0].message, "live edge went from 0.1 back to 0.0");
+    assert_eq!(
+        Rule::MediaSequenceDecreased.code(),
+        ErrorCode::MEDIA_SEQUENCE_DECREASED
+    );
+}
+
+#[cfg(feature = "reqwest")]
+#[tokio::test]
+async fn media_fetcher_downloads_new_media_and_prefetches_the_hint() {
+    use futures_util::StreamExt;
+    use llhls_rs::{Fetch, FetchBytes, LlHlsClient, MediaFetcher, MediaKind, RangeRequest};
+    use std::{collections::VecDeque, io, sync::Mutex};
+
+    // Serves canned playlists, and media whose content is its URL
+    #[derive(Clone, Default)]
+    struct Origin {
+        playlists: Arc<Mutex<VecDeque<String>>>,
+        requests: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Fetch for Origin {
+        type Error = io::Error;
+
+        async fn fetch(&self, _url: &str) -> io::Result<String> {
+            self.playlists
+                .lock()
+                .unwrap()
+                .pop_front()
+                .ok_or_else(|| io::Error::other("no more playlists"))
+        }
+    }
+
+    impl FetchBytes for Origin {
+        type Error = io::Error;
+
+        async fn fetch_bytes(&self, url: &str, range: Option<RangeRequest>) -> io::Result<Vec<u8>> {
+            let request = match range {
+                Some(range) => format!("{} {}", url, range.header_value()),
+                None => url.to_string(),
+            };
+            self.requests.lock().unwrap().push(request);
+            Ok(url.as_bytes().to_vec())
+        }
+    }
+
+    let header = "#EXTM3U\n\
+        #EXT-X-TARGETDURATION:4\n\
+        #EXT-X-VERSION:9\n\
+        #EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=3,CAN-SKIP-UNTIL=24\n\
+        #EXT-X-PART-INF:PART-TARGET=1\n\
+        #EXT-X-MEDIA-SEQUENCE:10\n\
+        #EXT-X-MAP:URI=\"init.mp4\",BYTERANGE=\"720@0\"\n\
+        #EXT-X-PART:DURATION=1,URI=\"part10.0.mp4\",INDEPENDENT=YES\n\
+        #EXT-X-PART:DURATION=1,URI=\"part10.1.mp4\"\n\
+        #EXTINF:2,\n\
+        segment10.mp4\n\
+        #EXT-X-PART:DURATION=1,URI=\"part11.0.mp4\",INDEPENDENT=YES\n";
+    let live = format!(
+        "{}#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"part11.1.mp4\"\n",
+        header
+    );
+    let ended = format!(
+        "{}#EXT-X-PART:DURATION=1,URI=\"part11.1.mp4\"\n\
+         #EXTINF:2,\n\
+         segment11.mp4\n\
+         #EXT-X-ENDLIST\n",
+        header
+    );
+    let origin = Origin::default();
+    origin.playlists.lock().unwrap().extend([live, ended]);
+
+    let base = "https://cdn.example.com/live/video.m3u8?token=a";
+    let client = LlHlsClient::new(origin.clone(), base);
+    let media: Vec<_> = MediaFetcher::new(origin.clone(), base)
+        .media(client)
+        .map(|media| media.expect("Fetched media"))
+        .collect()
+        .await;
+    let fetched: Vec<_> = media
+        .iter()
+        .map(|media| (media.kind, media.msn, media.part, media.url.as_str()))
+        .collect();
+    assert_eq!(
+        fetched,
+        [
+            (
+                MediaKind::Init,
+                10,
+                None,
+                "https://cdn.example.com/live/init.mp4"
+            ),
+            (
+                MediaKind::Part,
+                10,
+                Some(0),
+                "https://cdn.example.com/live/part10.0.mp4"
+            ),
+            (
+                MediaKind::Part,
+                10,
+                Some(1),
+                "https://cdn.example.com/live/part10.1.mp4"
+            ),
+            (
+                MediaKind::Part,
+                11,
+                Some(0),
+                "https://cdn.example.com/live/part11.0.mp4"
+            ),
+            (
+                MediaKind::Part,
+                11,
+                Some(1),
+                "https://cdn.example.com/live/part11.1.mp4"
+            ),
+        ]
+    );
+    assert_eq!(media[4].data, b"https://cdn.example.com/live/part11.1.mp4");
+    // The hinted part was requested once, alongside the reload that listed it
+    assert_eq!(
+        *origin.requests.lock().unwrap(),
+        [
+            "https://cdn.example.com/live/init.mp4 bytes=0-719",
+            "https://cdn.example.com/live/part10.0.mp4",
+            "https://cdn.example.com/live/part10.1.mp4",
+            "https://cdn.example.com/live/part11.0.mp4",
+            "https://cdn.example.com/live/part11.1.mp4",
+        ]
+    );
+}
+
+#[cfg(feature = "reqwest")]
+#[tokio::test]
+async fn media_fetcher_requests_the_byte_ranges_of_parts() {
+    use futures_util::StreamExt;
+    use llhls_rs::{Fetch, FetchBytes, LlHlsClient, MediaFetcher, RangeRequest};
+    use std::{collections::VecDeque, io, sync::Mutex};
+
+    // Serves canned playlists, and segment files of 2000 counting bytes
+    // that are written as they are requested
+    #[derive(Clone, Default)]
+    struct Origin {
+        playlists: Arc<Mutex<VecDeque<String>>>,
+        requests: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Fetch for Origin {
+        type Error = io::Error;
+
+        async fn fetch(&self, _url: &str) -> io::Result<String> {
+            self.playlists
+                .lock()
+                .unwrap()
+                .pop_front()
+                .ok_or_else(|| io::Error::other("no more playlists"))
+        }
+    }
+
+    impl FetchBytes for Origin {
+        type Error = io::Error;
+
+        async fn fetch_bytes(&self, url: &str, range: Option<RangeRequest>) -> io::Result<Vec<u8>> {
+            let file: Vec<u8> = (0..2000).map(|byte| byte as u8).collect();
+            let range = range.expect("Requested a range");
+            self.requests
+                .lock()
+                .unwrap()
+                .push(format!("{} {}", url, range.header_value()));
+            let start = range.start as usize;
+            let end = range
+                .length
+                .map_or(file.len(), |length| start + length as usize);
+            Ok(file[start..end].to_vec())
+        }
+    }
+
+    let header = "#EXTM3U\n\
+        #EXT-X-TARGETDURATION:4\n\
+        #EXT-X-VERSION:9\n\
+        #EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=3\n\
+        #EXT-X-PART-INF:PART-TARGET=1\n\
+        #EXT-X-MEDIA-SEQUENCE:10\n\
+        #EXT-X-PART:DURATION=1,URI=\"segment10.mp4\",BYTERANGE=\"1000@0\",INDEPENDENT=YES\n\
+        #EXT-X-PART:DURATION=1,URI=\"segment10.mp4\",BYTERANGE=\"800\"\n\
+        #EXTINF:2,\n\
+        segment10.mp4\n\
+        #EXT-X-PART:DURATION=1,URI=\"segment11.mp4\",BYTERANGE=\"900@0\",INDEPENDENT=YES\n";
+    // The rest of the segment file being written
+    let live = format!(
+        "{}#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"segment11.mp4\",BYTERANGE-START=900\n",
+        header
+    );
+    let ended = format!(
+        "{}#EXT-X-PART:DURATION=1,URI=\"segment11.mp4\",BYTERANGE=\"700\"\n\
+         #EXTINF:2,\n\
+         segment11.mp4\n\
+         #EXT-X-ENDLIST\n",
+        header
+    );
+    let origin = Origin::default();
+    origin.playlists.lock().unwrap().extend([live, ended]);
+
+    let base = "https://cdn.example.com/live/video.m3u8";
+    let client = LlHlsClient::new(origin.clone(), base);
+    let media: Vec<_> = MediaFetcher::new(origin.clone(), base)
+        .media(client)
+        .map(|media| media.expect("Fetched media"))
+        .collect()
+        .await;
+    let parts: Vec<_> = media
+        .iter()
+        .map(|media| (media.msn, media.part, media.data.len()))
+        .collect();
+    assert_eq!(
+        parts,
+        [
+            (10, Some(0), 1000),
+            (10, Some(1), 800),
+            (11, Some(0), 900),
+            (11, Some(1), 700)
+        ]
+    );
+    assert_eq!(media[1].data[0], (1000 % 256) as u8);
+    assert_eq!(media[3].data[0], (900 % 256) as u8);
+    // The hinted part is taken from the open-ended request for the rest
+    assert_eq!(
+        *origin.requests.lock().unwrap(),
+        [
+            "https://cdn.example.com/live/segment10.mp4 bytes=0-999",
+            "https://cdn.example.com/live/segment10.mp4 bytes=1000-1799",
+            "https://cdn.example.com/live/segment11.mp4 bytes=0-899",
+            "https://cdn.example.com/live/segment11.mp4 bytes=900-",
+        ]
+    );
+}
+
+#[test]
+fn rendition_reports_are_generated_from_sibling_playlists() {
+    let file = fs::File::open("tests/resources/ll-hls.m3u8").expect("Opened test file");
+    let mut current = read_playlist(file).expect("Parsed playlist");
+    let part = |uri: &str| PartialSegment::new(Duration::from_micros(333_340), uri);
+
+    let even = current.clone();
+    let mut next_part = current.clone();
+    next_part.push_part(part("filePart273.4.mp4"));
+    // Already publishing the next segment
+    let mut ahead = next_part.clone();
+    ahead.complete_segment(
+        Duration::from_micros(1_666_700),
+        segment_uri("fileSequence273.mp4"),
+        None,
+    );
+    ahead.push_part(part("filePart274.0.mp4"));
+    let mut builder = MediaPlaylistBuilder::new();
+    builder.server_control(ServerControl::new(
+        true,
+        Duration::from_secs(1),
+        Duration::ZERO,
+    ));
+    let empty = builder.build().expect("Built playlist");
+    // A sibling publishing whole segments only
+    let partless = parse_bytes(
+        b"#EXTM3U\n#EXT-X-TARGETDURATION:4\n#EXT-X-VERSION:9\n#EXT-X-MEDIA-SEQUENCE:272\n\
+          #EXTINF:4,\nfileSequence272.mp4\n",
+    )
+    .expect("Parsed playlist");
+
+    current.refresh_rendition_reports(&[
+        (segment_uri("../1M/waitForMSN.php"), &even),
+        (segment_uri("../4M/waitForMSN.php"), &next_part),
+        (segment_uri("../8M/waitForMSN.php"), &ahead),
+        (segment_uri("../audio/waitForMSN.php"), &empty),
+        (segment_uri("../captions/waitForMSN.php"), &partless),
+    ]);
+    let reports: Vec<_> = current
+        .rendition_reports()
+        .iter()
+        .map(|report| (report.uri(), report.last_msn(), report.last_part()))
+        .collect();
+    assert_eq!(
+        reports,
+        [
+            ("../1M/waitForMSN.php", 273, Some(3)),
+            ("../4M/waitForMSN.php", 273, Some(4)),
+            ("../8M/waitForMSN.php", 273, Some(4)),
+            ("../captions/waitForMSN.php", 272, None),
+        ]
+    );
+    let rendered = current.to_string();
+    assert!(rendered.contains(
+        "#EXT-X-RENDITION-REPORT:URI=\"../8M/waitForMSN.php\",LAST-MSN=273,LAST-PART=4\n"
+    ));
+    assert!(rendered
+        .contains("#EXT-X-RENDITION-REPORT:URI=\"../captions/waitForMSN.php\",LAST-MSN=272\n"));
+    assert_eq!(current.estimated_serialized_len(), rendered.len());
+    let reparsed = parse_bytes(rendered.as_bytes()).expect("Parsed playlist");
+    assert_eq!(reparsed.rendition_reports()[3].last_part(), None);
+}
+
+#[test]
+fn uris_resolve_against_the_playlist_url() {
+    // The examples of RFC 3986 section 5.4
+    let base = segment_uri("http://a/b/c/d;p?q");
+    for (reference, expected) in [
+        ("g:h", "g:h"),
+        ("g", "http://a/b/c/g"),
+        ("./g", "http://a/b/c/g"),
+        ("g/", "http://a/b/c/g/"),
+        ("/g", "http://a/g"),
+        ("//g", "http://g"),
+        ("?y", "http://a/b/c/d;p?y"),
+        ("g?y", "http://a/b/c/g?y"),
+        ("#s", "http://a/b/c/d;p?q#s"),
+        ("g?y#s", "http://a/b/c/g?y#s"),
+        (";x", "http://a/b/c/;x"),
+        ("", "http://a/b/c/d;p?q"),
+        (".", "http://a/b/c/"),
+        ("..", "http://a/b/"),
+        ("../g", "http://a/b/g"),
+        ("../../", "http://a/"),
+        ("../../../g", "http://a/g"),
+        ("/./g", "http://a/g"),
+        ("g/../h", "http://a/b/c/h"),
+        ("./../g", "http://a/b/g"),
+    ] {
+        let part = PartialSegment::new(Duration::from_secs(1), reference.to_string());
+        assert_eq!(part.absolute_uri(&base), expected, "{}", reference);
+    }
+
+    let file = fs::File::open("tests/resources/ll-hls.m3u8").expect("Opened test file");
+    let mut playlist = read_playlist(file).expect("Parsed playlist");
+    let base = segment_uri("https://example.com/2M/waitForMSN.php?_HLS_msn=273&_HLS_part=3");
+    let segment = playlist.segments().next().expect("Segment");
+    assert_eq!(
+        segment.absolute_uri(&base),
+        "https://example.com/2M/fileSequence269.mp4"
+    );
+    playlist.resolve_uris(&base);
+    let rendered = playlist.to_string();
+    assert!(rendered.contains("\nhttps://example.com/2M/fileSequence272.mp4\n"));
+    assert!(rendered.contains("URI=\"https://example.com/2M/filePart273.3.mp4\""));
+    assert!(rendered.contains(
+        "#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"https://example.com/2M/filePart273.4.mp4\"\n"
+    ));
+    assert_eq!(
+        playlist.rendition_reports()[0].uri(),
+        "https://example.com/1M/waitForMSN.php"
+    );
+}
+
+#[test]
+fn media_sequence_numbers_span_the_full_decimal_integer_range() {
+    let source = fs::read_to_string("tests/resources/ll-hls-canonical.m3u8")
+        .expect("Read test file")
+        .replace(
+            "#EXT-X-MEDIA-SEQUENCE:266",
+            "#EXT-X-MEDIA-SEQUENCE:18446744073709551000\n#EXT-X-DISCONTINUITY-SEQUENCE:5000000000",
+        )
+        .replace("LAST-MSN=270", "LAST-MSN=18446744073709551004");
+    let playlist = parse_bytes(source.as_bytes()).expect("Parsed playlist");
+
+    assert_eq!(playlist.media_sequence_number(), 18_446_744_073_709_551_000);
+    assert_eq!(playlist.discontinuity_sequence(), 5_000_000_000);
+    assert_eq!(
+        playlist.discontinuity_sequence_of(18_446_744_073_709_551_004),
+        Some(5_000_000_000)
+    );
+    assert_eq!(
+        playlist.rendition_reports()[0].last_msn(),
+        18_446_744_073_709_551_004
+    );
+    assert_eq!(
+        playlist.startup_position(),
+        Some((18_446_744_073_709_551_003, None))
+    );
+    assert_eq!(playlist.to_string(), source);
+}
+
+#[test]
+fn i_frame_playlists_round_trip() {
+    let file = fs::File::open("tests/resources/i-frames.m3u8").expect("Opened test file");
+    let mut playlist = read_playlist(file).expect("Parsed playlist");
+    assert!(playlist.i_frames_only());
+    assert!(playlist.independent_segments());
+    let byteranges: Vec<_> = playlist
+        .segments()
+        .map(|segment| segment.byterange())
+        .collect();
+    assert_eq!(
+        byteranges,
+        [
+            Some(ByteRange::new(9400, Some(376))),
+            Some(ByteRange::new(7144, Some(297184))),
+            Some(ByteRange::new(10340, None)),
+        ]
+    );
+    let source = fs::read_to_string("tests/resources/i-frames.m3u8").expect("Read test file");
+    assert_eq!(playlist.to_string(), source);
+    assert_eq!(playlist.estimated_serialized_len(), source.len());
+
+    // EXT-X-I-FRAMES-ONLY and EXT-X-BYTERANGE need version 4
+    assert!(playlist
+        .validate_profile(ValidationProfile::Rfc8216)
+        .is_empty());
+    playlist.set_independent_segments(false);
+    assert!(!playlist.to_string().contains("INDEPENDENT-SEGMENTS"));
+    let built = MediaPlaylistBuilder::new()
+        .server_control(ServerControl::new(false, Duration::ZERO, Duration::ZERO))
+        .i_frames_only(true)
+        .add_segment(MediaSegment::new(
+            Duration::from_secs(2),
+            segment_uri("segment0.ts"),
+            Vec::new(),
+            None,
+        ))
+        .build()
+        .expect("Built playlist");
+    assert_eq!(built.version(), 4);
+    assert!(built.to_string().contains("#EXT-X-I-FRAMES-ONLY\n"));
+}
+
+#[test]
+fn define_variables_are_substituted_while_parsing() {
+    let multivariant: MultivariantPlaylist = "#EXTM3U\n\
+        #EXT-X-DEFINE:NAME=\"cdn\",VALUE=\"https://cdn.example.com\"\n\
+        #EXT-X-STREAM-INF:BANDWIDTH=2000000\n\
+        {$cdn}/2M/playlist.m3u8\n"
+        .parse()
+        .expect("Parsed multivariant playlist");
+    assert_eq!(
+        multivariant.variants[0].uri,
+        "https://cdn.example.com/2M/playlist.m3u8"
+    );
+    assert!(multivariant
+        .to_string()
+        .contains("#EXT-X-DEFINE:NAME=\"cdn\""));
+    // Attributes of later revisions of the spec don't fail the tag
+    assert_eq!(
+        "NAME=\"cdn\",VALUE=\"x\",X-SCOPE=SESSION".parse::<Define>(),
+        Ok(Define::Value {
+            name: "cdn".to_string(),
+            value: "x".to_string()
+        })
+    );
+
+    let source = fs::read_to_string("tests/resources/ll-hls-canonical.m3u8")
+        .expect("Read test file")
+        .replace(
+            "#EXT-X-VERSION:9\n",
+            "#EXT-X-VERSION:9\n\
+             #EXT-X-DEFINE:IMPORT=\"cdn\"\n\
+             #EXT-X-DEFINE:QUERYPARAM=\"token\"\n\
+             #EXT-X-DEFINE:NAME=\"part\",VALUE=\"filePart\"\n",
+        )
+        .replace("URI=\"filePart271.0.mp4\"", "URI=\"{$part}271.0.mp4\"");
+    let parse = |source: &str, options: &ParseOptions| {
+        parse_with_options(source, options.clone(), &mut NoHooks)
+    };
+
+    // Quoted-string values can be kept as written
+    let keep = ParseOptions {
+        variables: VariablePolicy::Keep,
+        ..ParseOptions::default()
+    };
+    let raw = parse(&source, &keep).expect("Parsed playlist");
+    assert_eq!(raw.to_string(), source);
+
+    let source = source.replace(
+        "\nfileSequence270.mp4",
+        "\n{$cdn}/fileSequence270.mp4?token={$token}",
+    );
+    let options = ParseOptions {
+        imported_variables: multivariant.variables(),
+        playlist_url: Some("https://example.com/2M/playlist.m3u8?token=abc".to_string()),
+        ..ParseOptions::default()
+    };
+    let playlist = parse(&source, &options).expect("Parsed playlist");
+    assert_eq!(playlist.definitions().len(), 3);
+    let last = playlist.segments().last().expect("Segment");
+    assert_eq!(
+        last.uri().as_str(),
+        "https://cdn.example.com/fileSequence270.mp4?token=abc"
+    );
+    let hint = playlist.preload_hint().expect("Preload hint");
+    assert_eq!(hint.uri, "filePart271.0.mp4");
+
+    assert!(matches!(
+        parse(&source, &ParseOptions::default()),
+        Err(ParsePlaylistError::UndefinedVariable { line: 4, name }) if name == "cdn"
+    ));
+}
+
+#[test]
+fn part_and_map_preload_hints_with_byte_ranges_round_trip() {
+    let hints = "#EXT-X-PRELOAD-HINT:TYPE=MAP,URI=\"init271.mp4\",BYTERANGE-LENGTH=720\n\
+                 #EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"fileSequence271.mp4\",BYTERANGE-START=1200\n";
+    let source = fs::read_to_string("tests/resources/ll-hls-canonical.m3u8")
+        .expect("Read test file")
+        .replace(
+            "#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"filePart271.0.mp4\"\n",
+            hints,
+        );
+    let mut playlist = parse_bytes(source.as_bytes()).expect("Parsed playlist");
+
+    assert_eq!(playlist.preload_hints().len(), 2);
+    let map = playlist.map_preload_hint().expect("MAP hint");
+    assert_eq!(
+        (map.byterange_start, map.byterange_length),
+        (None, Some(720))
+    );
+    // The MAP hint is listed first, yet preload_hint is still the part's
+    assert_eq!(
+        playlist.preload_hint().map(|hint| hint.uri.as_str()),
+        Some("fileSequence271.mp4")
+    );
+    let part = playlist
+        .preload_hint_of(PreloadHintType::Part)
+        .expect("PART hint");
+    assert_eq!(
+        (part.byterange_start, part.byterange_length),
+        (Some(1200), None)
+    );
+    assert_eq!(playlist.to_string(), source);
+    assert_eq!(playlist.estimated_serialized_len(), source.len());
+
+    // A hint replaces the one of its TYPE only
+    playlist.add_preload_hint(PreloadHint::new(PreloadHintType::Part, "filePart271.1.mp4"));
+    let uris: Vec<_> = playlist
+        .preload_hints()
+        .iter()
+        .map(|hint| hint.uri.as_str())
+        .collect();
+    assert_eq!(uris, ["init271.mp4", "filePart271.1.mp4"]);
+}
+
+#[test]
+fn rendition_reports_and_preload_hints_serialize_in_order() {
+    let mut playlist = read_playlist(
+        fs::File::open("tests/resources/ll-hls-canonical.m3u8").expect("Opened file"),
+    )
+    .expect("Parsed playlist");
+    playlist.add_preload_hint(PreloadHint::new(PreloadHintType::Map, "init271.mp4"));
+    playlist.add_rendition_report(RenditionReport::new("../2M/waitForMSN.php", 270, Some(2)));
+    playlist.add_rendition_report(RenditionReport::new("../1M/waitForMSN.php", 270, Some(2)));
+
+    let expected_tail = "#EXT-X-PRELOAD-HINT:TYPE=MAP,URI=\"init271.mp4\"\n\
+                         #EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"filePart271.0.mp4\"\n\
+                         #EXT-X-RENDITION-REPORT:URI=\"../1M/waitForMSN.php\",LAST-MSN=270,LAST-PART=2\n\
+                         #EXT-X-RENDITION-REPORT:URI=\"../2M/waitForMSN.php\",LAST-MSN=270,LAST-PART=2\n";
+    let rendered = playlist.to_string();
+    assert!(rendered.ends_with(expected_tail), "{}", rendered);
+    assert_eq!(playlist.estimated_serialized_len(), rendered.len());
+
+    // Parsing keeps them all, in the same order
+    let reparsed = parse_bytes(rendered.as_bytes()).expect("Parsed playlist");
+    assert_eq!(reparsed.preload_hints().len(), 2);
+    assert_eq!(reparsed.rendition_reports().len(), 2);
+    assert_eq!(reparsed.to_string(), rendered);
+}
+
+#[test]
+fn live_playlist_window_rolls_segments_and_parts() {
+    let mut builder = MediaPlaylistBuilder::new();
+    builder
+        .target_duration(4)
+        .version(9)
+        .part_inf(PartInf::new(Duration::from_secs(1)))
+        .media_sequence_number(100)
+        .server_control(ServerControl::new(
+            true,
+            Duration::from_secs(3),
+            Duration::from_secs(0),
+        ));
+    let mut window = LivePlaylistWindow::new(builder.build().expect("Built playlist"));
+    assert_eq!(window.window(), Duration::from_secs(12));
+    for msn in 100..106 {
+        for index in 0..4 {
+            let position = window.push_part(PartialSegment::new(
+                Duration::from_secs(1),
+                format!("p{}.{}.mp4", msn, index),
+            ));
+            assert_eq!(position, (msn, index));
+        }
+        let completed = window.complete_segment(
+            Duration::from_secs(4),
+            segment_uri(&format!("s{}.mp4", msn)),
+            None,
+        );
+        assert_eq!(completed, msn);
+    }
+
+    // Three target durations of segments stay listed
+    let playlist = window.playlist();
+    assert_eq!(playlist.media_segments().len(), 3);
+    assert_eq!(window.media_sequence_number(), 103);
+    assert_eq!(window.next_msn(), 106);
+    assert!(playlist.to_string().contains("#EXT-X-MEDIA-SEQUENCE:103\n"));
+
+    // Parts more than three target durations from the end are dropped
+    for index in 0..4 {
+        window.push_part(PartialSegment::new(
+            Duration::from_secs(1),
+            format!("p106.{}.mp4", index),
+        ));
+    }
+    let parts: Vec<_> = window
+        .playlist()
+        .segments()
+        .map(|segment| segment.parts().len())
+        .collect();
+    assert_eq!(parts, [0, 4, 4]);
+
+    // A longer window keeps more segments from then on
+    window.set_window(Duration::from_secs(20));
+    window.complete_segment(Duration::from_secs(4), segment_uri("s106.mp4"), None);
+    window.push_part(PartialSegment::new(Duration::from_secs(1), "p107.0.mp4"));
+    window.complete_segment(Duration::from_secs(4), segment_uri("s107.mp4"), None);
+    assert_eq!(window.playlist().media_segments().len(), 5);
+    assert_eq!(window.media_sequence_number(), 103);
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn blocking_playlist_service_holds_requests_until_published() {
+    use llhls_rs::{BlockingPlaylistService, BlockingRequestError};
+
+    let query = |query: &str| DeliveryDirectives::from_query_str(query).expect("Valid directives");
+
+    let file = fs::File::open("tests/resources/ll-hls-full.m3u8").expect("Opened file");
+    let playlist = read_playlist(file).expect("Parsed test file");
+    let mut service = BlockingPlaylistService::new(playlist.clone());
+    service.set_hold_timeout(Some(Duration::from_secs(5)));
+
+    let full = service.respond(&query("")).await.expect("Served");
+    assert_eq!(full, playlist.to_string().into_bytes());
+    // Published media is served at once
+    let served = service.respond(&query("_HLS_msn=273&_HLS_part=3")).await;
+    assert_eq!(served.expect("Served"), full);
+
+    let error = service
+        .respond(&DeliveryDirectives {
+            part: Some(1),
+            ..DeliveryDirectives::default()
+        })
+        .await
+        .err();
+    assert_eq!(error, Some(BlockingRequestError::PartWithoutMsn));
+    let error = service.respond(&query("_HLS_msn=276")).await.err();
+    assert_eq!(
+        error,
+        Some(BlockingRequestError::TooFarAhead {
+            msn: 276,
+            last_msn: 273
+        })
+    );
+    assert_eq!(error.map(|error| error.status_code()), Some(400));
+
+    // A held request is released by the publication of its part
+    let waiting = {
+        let service = service.clone();
+        tokio::spawn(async move {
+            let directives = query("_HLS_msn=273&_HLS_part=4&_HLS_skip=YES");
+            service.respond(&directives).await
+        })
+    };
+    let mut next = playlist.clone();
+    next.push_part(PartialSegment::new(
+        Duration::from_millis(333),
+        "filePart273.4.mp4",
+    ));
+    service.publish(next.clone());
+    let delta = waiting.await.expect("Request finished").expect("Served");
+    let delta = String::from_utf8(delta).expect("UTF-8 playlist");
+    assert!(
+        delta.contains("#EXT-X-SKIP:SKIPPED-SEGMENTS=4\n"),
+        "{}",
+        delta
+    );
+    assert!(delta.contains("filePart273.4.mp4"));
+
+    let mut impatient = service.clone();
+    impatient.set_hold_timeout(Some(Duration::from_millis(20)));
+    let error = impatient.respond(&query("_HLS_msn=274")).await.err();
+    assert_eq!(error, Some(BlockingRequestError::Timeout));
+    assert_eq!(error.map(|error| error.status_code()), Some(503));
+
+    // Requests still held once the playlist ends get the final playlist
+    let waiting = {
+        let service = service.clone();
+        tokio::spawn(async move { service.respond(&query("_HLS_msn=274")).await })
+    };
+    next.set_end_list(true);
+    service.publish(next.clone());
+    let last = waiting.await.expect("Request finished").expect("Served");
+    assert_eq!(last, next.to_string().into_bytes());
+}
+
+#[test]
+fn delivery_directives_round_trip_through_queries() {
+    let directives =
+        DeliveryDirectives::from_query_str("token=abc&_HLS_msn=273&_HLS_part=2&_HLS_skip=v2")
+            .expect("Valid directives");
+    assert_eq!(
+        directives,
+        DeliveryDirectives {
+            msn: Some(273),
+            part: Some(2),
+            skip: Some(SkipDirective::V2),
+            report: Vec::new(),
+        }
+    );
+    assert_eq!(
+        directives.to_query_string(),
+        "_HLS_msn=273&_HLS_part=2&_HLS_skip=v2"
+    );
+    assert_eq!(
+        DeliveryDirectives::from_query_str(""),
+        Ok(DeliveryDirectives::default())
+    );
+
+    let report = DeliveryDirectives {
+        report: vec!["../1M/waitForMSN.php?a=1&b=2".to_string()],
+        ..DeliveryDirectives::default()
+    };
+    let query = report.to_query_string();
+    assert_eq!(query, "_HLS_report=../1M/waitForMSN.php?a%3D1%26b%3D2");
+    assert_eq!(DeliveryDirectives::from_query_str(&query), Ok(report));
+
+    assert_eq!(
+        DeliveryDirectives::from_query_str("_HLS_part=2"),
+        Err(DirectiveError::PartWithoutMsn)
+    );
+    assert_eq!(
+        DeliveryDirectives::from_query_str("_HLS_msn=1&_HLS_msn=2"),
+        Err(DirectiveError::Duplicate("_HLS_msn"))
+    );
+    assert_eq!(
+        DeliveryDirectives::from_query_str("_HLS_msn=-1"),
+        Err(DirectiveError::InvalidValue {
+            name: "_HLS_msn",
+            value: "-1".to_string()
+        })
+    );
+    assert_eq!(
+        DeliveryDirectives::from_query_str("_HLS_msn=1&_HLS_skip=yes"),
+        Err(DirectiveError::InvalidValue {
+            name: "_HLS_skip",
+            value: "yes".to_string()
+        })
+    );
+}
+
+#[cfg(feature = "reqwest")]
+#[tokio::test(start_paused = true)]
+async fn playlist_watcher_paces_reloads_and_backs_off_on_errors() {
+    use futures_util::StreamExt;
+    use llhls_rs::{Fetch, PlaylistWatcher};
+    use std::{collections::VecDeque, io, sync::Mutex};
+    use tokio::time::Instant;
+
+    // Serves canned responses, recording when they were asked for
+    #[derive(Clone)]
+    struct Canned {
+        start: Instant,
+        responses: Arc<Mutex<VecDeque<io::Result<String>>>>,
+        times: Arc<Mutex<Vec<Duration>>>,
+    }
+
+    impl Fetch for Canned {
+        type Error = io::Error;
+
+        async fn fetch(&self, _url: &str) -> io::Result<String> {
+            self.times.lock().unwrap().push(self.start.elapsed());
+            self.responses
+                .lock()
+                .unwrap()
+                .pop_front()
+                .unwrap_or_else(|| Err(io::Error::other("no more responses")))
+        }
+    }
+
+    let polled = fs::read_to_string("tests/resources/ll-hls-full.m3u8")
+        .expect("Read test file")
+        .replace("CAN-BLOCK-RELOAD=YES", "CAN-BLOCK-RELOAD=NO");
+    let ended = polled.replace(
+        "#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"filePart273.4.mp4\"\n",
+        "#EXTINF:4.00008,\nfileSequence273.mp4\n#EXT-X-ENDLIST\n",
+    );
+    let canned = Canned {
+        start: Instant::now(),
+        responses: Arc::new(Mutex::new(VecDeque::from([
+            Ok(polled.clone()),
+            Ok(polled),
+            Err(io::Error::other("unavailable")),
+            Err(io::Error::other("unavailable")),
+            Ok(ended),
+        ]))),
+        times: Arc::default(),
+    };
+    let watcher = PlaylistWatcher::new(canned.clone());
+    let results: Vec<_> = watcher
+        .subscribe("https://example.com/2M/live.m3u8")
+        .collect()
+        .await;
+    let succeeded: Vec<_> = results.iter().map(Result::is_ok).collect();
+    assert_eq!(succeeded, [true, true, false, false, true]);
+    assert_eq!(
+        results
+            .last()
+            .and_then(|result| result.as_ref().ok())
+            .map(MediaPlaylist::end_list),
+        Some(true)
+    );
+
+    // A target duration after a new playlist, half of one after an
+    // unchanged one, plus 500ms then 1s of backoff after failures
+    let seconds: Vec<_> = canned
+        .times
+        .lock()
+        .unwrap()
+        .iter()
+        .map(Duration::as_secs_f32)
+        .collect();
+    assert_eq!(seconds, [0.0, 4.0, 6.0, 8.5, 11.5]);
+}
+
+#[test]
+fn live_edge_targets_an_independent_part_and_measures_latency() {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let file = fs::File::open("tests/resources/ll-hls-full.m3u8").expect("Opened file");
+    let playlist = read_playlist(file).expect("Parsed test file");
+    // Segment 272 starts at 2019-02-14T02:14:00.106Z, so part 273.0 was
+    // captured at 02:14:04.10608
+    let captured: SystemTime = UNIX_EPOCH + Duration::new(1550110444, 106_080_000);
+    let edge = playlist
+        .live_edge(captured + Duration::from_millis(2500))
+        .expect("Live edge");
+    assert_eq!((edge.msn, edge.part_index), (273, Some(0)));
+    let expected = cfg!(any(feature = "chrono", feature = "time"));
+    assert_eq!(edge.latency, expected.then(|| Duration::from_millis(2500)));
+
+    // A clock behind the playlist's reads as no latency
+    let edge = playlist.live_edge(UNIX_EPOCH).expect("Live edge");
+    assert_eq!(edge.latency, expected.then_some(Duration::ZERO));
+}
+
+#[test]
+fn server_control_attributes_are_optional() {
+    let source = fs::read_to_string("tests/resources/ll-hls-full.m3u8")
+        .expect("Read test file")
+        .replace(
+            "#EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=1.0,CAN-SKIP-UNTIL=12.0",
+            "#EXT-X-SERVER-CONTROL:CAN-SKIP-UNTIL=24,CAN-SKIP-DATERANGES=YES,HOLD-BACK=12.5",
+        );
+    let mut playlist = parse_bytes(source.as_bytes()).expect("Parsed playlist");
+
+    let control = playlist.server_control();
+    assert!(!control.can_block_reload());
+    assert_eq!(control.hold_back(), Duration::from_millis(12_500));
+    assert_eq!(control.part_hold_back(), Duration::ZERO);
+    assert_eq!(control.can_skip_until(), Duration::from_secs(24));
+    assert!(control.can_skip_dateranges());
+    // Absent hold backs are derived from the target durations
+    assert_eq!(playlist.hold_back(), Duration::from_millis(12_500));
+    assert_eq!(playlist.part_hold_back(), Duration::from_micros(1_000_020));
+
+    let rendered = playlist.to_string();
+    assert!(rendered.contains(
+        "#EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=NO,HOLD-BACK=12.5,CAN-SKIP-UNTIL=24,\
+         CAN-SKIP-DATERANGES=YES\n"
+    ));
+    assert_eq!(playlist.estimated_serialized_len(), rendered.len());
+
+    let mut control = ServerControl::new(true, Duration::from_secs(1), Duration::ZERO);
+    control.set_can_skip_dateranges(true);
+    playlist.set_server_control(control);
+    assert_eq!(playlist.hold_back(), Duration::from_secs(12));
+    assert!(playlist
+        .to_string()
+        .contains("#EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=1\n"));
+
+    // Playlists may leave out the tag, and EXT-X-MEDIA-SEQUENCE too
+    let source = "#EXTM3U\n#EXT-X-TARGETDURATION:4\n#EXT-X-VERSION:3\n\
+                  #EXTINF:4,\nsegment0.ts\n#EXT-X-ENDLIST\n";
+    let vod = parse_bytes(source.as_bytes()).expect("Parsed playlist");
+    // Rendered without them too
+    assert_eq!(vod.to_string(), source);
+    assert_eq!(vod.estimated_serialized_len(), source.len());
+    assert!(*vod.server_control() == ServerControl::default());
+    assert_eq!(vod.media_sequence_number(), 0);
+    assert_eq!(vod.hold_back(), Duration::from_secs(12));
+    let mut builder = MediaPlaylistBuilder::new();
+    builder.add_segment(MediaSegment::new(
+        Duration::from_secs(4),
+        segment_uri("segment0.ts"),
+        vec![],
+        None,
+    ));
+    let built = builder.build().expect("Built playlist");
+    assert!(*built.server_control() == ServerControl::default());
+}
+
+#[test]
+fn unknown_attributes_are_kept_and_written_back() {
+    let source = fs::read_to_string("tests/resources/ll-hls-full.m3u8")
+        .expect("Read test file")
+        .replace(
+            "CAN-SKIP-UNTIL=12.0",
+            "CAN-SKIP-UNTIL=12.0,FUTURE-HOLD=2.5,ANOTHER=\"x,y\"",
+        )
+        .replace("PART-TARGET=0.33334", "PART-TARGET=0.33334,TIER=LOW")
+        .replace(
+            "#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"filePart273.4.mp4\"",
+            "#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"filePart273.4.mp4\",PRIORITY=1",
+        )
+        .replace(
+            "LAST-MSN=273,LAST-PART=3",
+            "LAST-MSN=273,LAST-PART=3,LAST-I-PART=2",
+        );
+    let mut parser = PlaylistParser::new();
+    parser
+        .feed(source.as_bytes(), &mut NoHooks)
+        .expect("Parsed playlist");
+    let playlist = parser.finish(&mut NoHooks).expect("Parsed playlist");
+
+    let control = playlist.server_control().extra_attributes();
+    assert_eq!(control.len(), 2);
+    assert_eq!(control["FUTURE-HOLD"], "2.5");
+    assert_eq!(control["ANOTHER"], "\"x,y\"");
+    let part_inf = playlist.part_inf().expect("PART-INF");
+    assert_eq!(part_inf.extra_attributes()["TIER"], "LOW");
+    assert_eq!(
+        playlist.preload_hints()[0].extra_attributes["PRIORITY"],
+        "1"
+    );
+    for report in playlist.rendition_reports() {
+        assert_eq!(report.extra_attributes()["LAST-I-PART"], "2");
+    }
+
+    // Written back after the known attributes, in order of name
+    let rendered = playlist.to_string();
+    assert!(rendered.contains(
+        "#EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=1,CAN-SKIP-UNTIL=12,\
+         ANOTHER=\"x,y\",FUTURE-HOLD=2.5\n"
+    ));
+    assert!(rendered.contains("#EXT-X-PART-INF:PART-TARGET=0.33334,TIER=LOW\n"));
+    assert!(rendered.contains("URI=\"filePart273.4.mp4\",PRIORITY=1\n"));
+    assert!(rendered.contains("LAST-MSN=273,LAST-PART=3,LAST-I-PART=2\n"));
+    assert_eq!(playlist.estimated_serialized_len(), rendered.len());
+}
+
+#[test]
+fn extinf_keeps_title_and_duration_digits() {
+    let source = "#EXTM3U\n\
+                  #EXT-X-TARGETDURATION:4\n\
+                  #EXT-X-VERSION:6\n\
+                  #EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=NO\n\
+                  #EXT-X-MEDIA-SEQUENCE:0\n\
+                  #EXTINF:4.000000001,Opening titles, part 1\n\
+                  segment0.ts\n\
+                  #EXTINF:3.96,\n\
+                  segment1.ts\n\
+                  #EXT-X-ENDLIST\n";
+    let mut parser = PlaylistParser::new();
+    parser
+        .feed(source.as_bytes(), &mut NoHooks)
+        .expect("Parsed playlist");
+    let mut playlist = parser.finish(&mut NoHooks).expect("Parsed playlist");
+
+    let segments: Vec<&MediaSegment> = playlist.segments().collect();
+    assert_eq!(segments[0].duration(), Duration::new(4, 1));
+    assert_eq!(segments[0].title(), Some("Opening titles, part 1"));
+    assert_eq!(segments[1].duration(), Duration::from_millis(3960));
+    assert_eq!(segments[1].title(), None);
+    assert_eq!(playlist.to_string(), source);
+    assert_eq!(playlist.estimated_serialized_len(), source.len());
+
+    let mut segment = MediaSegment::new(
+        Duration::from_micros(2_002_002),
+        segment_uri("segment2.ts"),
+        Vec::new(),
+        None,
+    );
+    segment.set_title(Some("Credits".to_string()));
+    playlist.push_segment(segment);
+    assert!(playlist
+        .to_string()
+        .contains("#EXTINF:2.002002,Credits\nsegment2.ts\n"));
+}
+
+#[test]
+fn parse_bytes_survives_mutated_playlists() {
+    // Fragments that reach the parser's edge cases: delimiters, extreme or
+    // malformed numbers, stray tags and broken UTF-8
+    let fragments: &[&[u8]] = &[
+        b",",
+        b"=",
+        b"\"",
+        b"\n",
+        b"\r",
+        b":",
+        b"@",
+        b"-1",
+        b"1e400",
+        b"18446744073709551615",
+        b"99999999999999999999",
+        b"#EXTINF:",
+        b"#EXT-X-PART:DURATION=",
+        b"#EXT-X-MEDIA-SEQUENCE:18446744073709551615\n",
+        b"#EXT-X-SKIP:SKIPPED-SEGMENTS=",
+        b"#EXT-X-DATERANGE:ID=\"a\",START-DATE=",
+        b"#EXT-X-BYTERANGE:",
+        b"#EXT-X-DEFINE:NAME=\"a\",VALUE=\"{$a}\"\n",
+        b"{$a}",
+        b"\xff",
+        b"\xc3",
+    ];
+    // xorshift64, so failures reproduce
+    let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+    let mut random = move |bound: usize| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % bound as u64) as usize
+    };
+    let mut paths: Vec<_> = fs::read_dir("tests/resources")
+        .expect("Listed test files")
+        .map(|entry| entry.expect("Listed test file").path())
+        .collect();
+    paths.sort();
+    for path in paths {
+        let original = fs::read(&path).expect("Read test file");
+        for _ in 0..200 {
+            let mut input = original.clone();
+            for _ in 0..=random(4) {
+                let at = random(input.len() + 1);
+                match random(3) {
+                    0 => {
+                        let fragment = fragments[random(fragments.len())];
+                        input.splice(at..at, fragment.iter().copied());
+                    }
+                    1 => {
+                        let end = (at + random(16)).min(input.len());
+                        input.drain(at..end);
+                    }
+                    _ => {
+                        let end = (at + random(64)).min(input.len());
+                        let copy = input[at..end].to_vec();
+                        let to = random(input.len() + 1);
+                        input.splice(to..to, copy);
+                    }
+                }
+            }
+            let Ok(playlist) = parse_bytes(&input) else {
+                continue;
+            };
+            let rendered = playlist.to_string();
+            assert_eq!(playlist.estimated_serialized_len(), rendered.len());
+            let reparsed = parse_bytes(rendered.as_bytes()).unwrap_or_else(|error| {
+                panic!(
+                    "{} rendered\n{}\nwhich fails with {}",
+                    path.display(),
+                    rendered,
+                    error
+                )
+            });
+            assert_eq!(reparsed.to_string(), rendered);
+        }
+    }
+
+    assert!(matches!(
+        parse_bytes(b"#EXTM3U\n#EXT-X-TARGETDURATION:4\n\xff\n"),
+        Err(ParsePlaylistError::InvalidUtf8 { line: 3 })
+    ));
+    assert!(matches!(
+        parse_bytes(
+            b"#EXTM3U\n#EXT-X-TARGETDURATION:4\n#EXT-X-VERSION:6\n\
+              #EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=NO\n\
+              #EXT-X-MEDIA-SEQUENCE:18446744073709551615\n#EXTINF:4,\na.ts\n"
+        ),
+        Err(ParsePlaylistError::InvalidNumber {
+            line: 5,
+            error: NumericError::Overflow,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn semantically_equal_ignores_formatting() {
+    let source = "#EXTM3U\n\
+                  #EXT-X-TARGETDURATION:4\n\
+                  #EXT-X-VERSION:6\n\
+                  #EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=1.0\n\
+                  #EXT-X-PART-INF:PART-TARGET=0.5\n\
+                  #EXT-X-MEDIA-SEQUENCE:10\n\
+                  #EXT-X-DATERANGE:ID=\"ad\",START-DATE=\"2024-05-01T10:00:00Z\",X-A=\"1\",X-B=\"2\"\n\
+                  #EXT-X-PART:DURATION=0.5,URI=\"a.0.mp4\",INDEPENDENT=YES\n\
+                  #EXTINF:4.000,\n\
+                  a.mp4\n\
+                  #EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"b.0.mp4\"\n";
+    let reordered = "#EXTM3U\r\n\
+                     #EXT-X-TARGETDURATION:4\r\n\
+                     #EXT-X-VERSION:6\r\n\
+                     #EXT-X-SERVER-CONTROL:PART-HOLD-BACK=1,CAN-BLOCK-RELOAD=YES\r\n\
+                     #EXT-X-PART-INF:PART-TARGET=0.50000\r\n\
+                     #EXT-X-MEDIA-SEQUENCE:10\r\n\
+                     \r\n\
+                     #EXT-X-DATERANGE:X-B=\"2\",START-DATE=\"2024-05-01T10:00:00Z\",ID=\"ad\",X-A=\"1\"\r\n\
+                     #EXT-X-PART:INDEPENDENT=YES,URI=\"a.0.mp4\",DURATION=.5\r\n\
+                     #EXTINF:4,\r\n\
+                     a.mp4\r\n\
+                     #EXT-X-PRELOAD-HINT:URI=\"b.0.mp4\",TYPE=PART\r\n";
+    let parse = |text: &str| parse_bytes(text.as_bytes()).expect("Parsed playlist");
+    let playlist = parse(source);
+    assert!(playlist.semantically_equal(&parse(reordered)));
+    assert!(parse(reordered).semantically_equal(&playlist));
+
+    let changed = source.replace("PART-HOLD-BACK=1.0", "PART-HOLD-BACK=1.5");
+    assert!(!playlist.semantically_equal(&parse(&changed)));
+    let changed = source.replace("X-B=\"2\"", "X-B=\"3\"");
+    assert!(!playlist.semantically_equal(&parse(&changed)));
+    let changed = source.replace("#EXTINF:4.000,\na.mp4\n", "#EXTINF:4.000,\nb.mp4\n");
+    assert!(!playlist.semantically_equal(&parse(&changed)));
+}
+
+#[test]
+fn program_date_times_map_media_to_wall_clock_times() {
+    use std::time::SystemTime;
+
+    let source = "#EXTM3U\n\
+                  #EXT-X-TARGETDURATION:4\n\
+                  #EXT-X-VERSION:6\n\
+                  #EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES\n\
+                  #EXT-X-PART-INF:PART-TARGET=2\n\
+                  #EXT-X-MEDIA-SEQUENCE:10\n\
+                  #EXTINF:4,\n\
+                  a.mp4\n\
+                  #EXT-X-PROGRAM-DATE-TIME:2024-05-01T10:00:04.000Z\n\
+                  #EXTINF:4,\n\
+                  b.mp4\n\
+                  #EXT-X-DISCONTINUITY\n\
+                  #EXT-X-PROGRAM-DATE-TIME:2024-05-01T10:01:00.000Z\n\
+                  #EXT-X-PART:DURATION=2,URI=\"c.0.mp4\",INDEPENDENT=YES\n\
+                  #EXT-X-PART:DURATION=2,URI=\"c.1.mp4\"\n\
+                  #EXTINF:4,\n\
+                  c.mp4\n\
+                  #EXT-X-PART:DURATION=2,URI=\"d.0.mp4\",INDEPENDENT=YES\n\
+                  #EXT-X-PART:DURATION=2,URI=\"d.1.mp4\"\n";
+    let playlist = parse_bytes(source.as_bytes()).expect("Parsed playlist");
+    // 2024-05-01T10:00:00Z
+    let start = UNIX_EPOCH + Duration::from_secs(1_714_557_600);
+    let at = |seconds: f64| start + Duration::from_secs_f64(seconds);
+
+    if !cfg!(any(feature = "chrono", feature = "time")) {
+        assert_eq!(playlist.datetime_of(11, None), None);
+        assert_eq!(playlist.segment_at(at(5.0)), None);
+        return;
+    }
+    // Before the first program date time, counting back from it
+    assert_eq!(playlist.datetime_of(10, None), Some(at(0.0)));
+    assert_eq!(playlist.datetime_of(11, None), Some(at(4.0)));
+    // The discontinuity starts over from its own program date time
+    assert_eq!(playlist.datetime_of(12, None), Some(at(60.0)));
+    assert_eq!(playlist.datetime_of(12, Some(1)), Some(at(62.0)));
+    assert_eq!(playlist.datetime_of(13, Some(1)), Some(at(66.0)));
+    assert_eq!(playlist.datetime_of(13, None), None);
+    assert_eq!(playlist.datetime_of(9, None), None);
+    assert_eq!(playlist.datetime_of(11, Some(0)), None);
+
+    assert_eq!(playlist.segment_at(at(0.0)), Some((10, None)));
+    assert_eq!(playlist.segment_at(at(5.5)), Some((11, None)));
+    assert_eq!(playlist.segment_at(at(61.0)), Some((12, Some(0))));
+    assert_eq!(playlist.segment_at(at(67.5)), Some((13, Some(1))));
+    // Between the discontinuity's dates and past the live edge
+    assert_eq!(playlist.segment_at(at(30.0)), None);
+    assert_eq!(playlist.segment_at(at(68.0)), None);
+    let before: SystemTime = at(0.0) - Duration::from_millis(1);
+    assert_eq!(playlist.segment_at(before), None);
+}
+
+#[test]
+fn serialize_into_reuses_the_buffer() {
+    let file = fs::File::open("tests/resources/ll-hls-full.m3u8").expect("Opened test file");
+    let mut playlist = read_playlist(file).expect("Parsed test file");
+    let mut buffer = b"left over".to_vec();
+    playlist.serialize_into(&mut buffer);
+    assert_eq!(buffer, playlist.to_string().into_bytes());
+
+    // The grown buffer fits the next rendering
+    let (capacity, address) = (buffer.capacity(), buffer.as_ptr());
+    playlist.serialize_into(&mut buffer);
+    assert_eq!((buffer.capacity(), buffer.as_ptr()), (capacity, address));
+
+    playlist.set_line_ending(LineEnding::CrLf);
+    playlist.serialize_into(&mut buffer);
+    assert_eq!(buffer, playlist.to_string().into_bytes());
+    assert!(buffer.ends_with(b"LAST-PART=3\r\n"));
+}
+
+#[test]
+fn cmaf_segmenter_publishes_chunks_as_byterange_parts() {
+    let mut builder = MediaPlaylistBuilder::new();
+    builder
+        .target_duration(2)
+        .version(9)
+        .part_inf(PartInf::new(Duration::from_millis(500)))
+        .media_sequence_number(7)
+        .server_control(ServerControl::new(
+            true,
+            Duration::from_millis(1500),
+            Duration::from_secs(0),
+        ));
+    let window = LivePlaylistWindow::new(builder.build().expect("Built playlist"));
+    let mut segmenter =
+        CmafSegmenter::new(window, |msn| segment_uri(&format!("segment{}.mp4", msn)));
+
+    assert_eq!(segmenter.complete_segment(None), None);
+    let first = CmafChunk::new(Duration::from_millis(500), ByteRange::new(1000, None), true);
+    let second = CmafChunk::new(Duration::from_millis(500), ByteRange::new(800, None), false);
+    assert_eq!(segmenter.push_chunk(first), (7, 0));
+    assert_eq!(segmenter.push_chunk(second), (7, 1));
+    let rendered = segmenter.playlist().to_string();
+    assert!(rendered.contains(
+        "#EXT-X-PART:DURATION=0.5,URI=\"segment7.mp4\",INDEPENDENT=YES,BYTERANGE=\"1000@0\"\n"
+    ));
+    assert!(
+        rendered.contains("#EXT-X-PART:DURATION=0.5,URI=\"segment7.mp4\",BYTERANGE=\"800@1000\"\n")
+    );
+    assert!(rendered
+        .contains("#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"segment7.mp4\",BYTERANGE-START=1800\n"));
+
+    assert_eq!(segmenter.complete_segment(None), Some(7));
+    let third = CmafChunk::new(
+        Duration::from_millis(500),
+        ByteRange::new(900, Some(64)),
+        true,
+    );
+    assert_eq!(segmenter.push_chunk(third), (8, 0));
+    let playlist = segmenter.into_publisher().into_playlist();
+    let rendered = playlist.to_string();
+    assert!(rendered.contains("#EXTINF:1,\nsegment7.mp4\n"));
+    assert!(rendered.contains("URI=\"segment8.mp4\",INDEPENDENT=YES,BYTERANGE=\"900@64\"\n"));
+    assert!(rendered
+        .contains("#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"segment8.mp4\",BYTERANGE-START=964\n"));
+
+    // Part byte ranges survive a round trip
+    let reparsed = parse_bytes(rendered.as_bytes()).expect("Parsed playlist");
+    assert_eq!(
+        reparsed.trailing_parts()[0].byterange,
+        Some(ByteRange::new(900, Some(64)))
+    );
+    assert_eq!(reparsed.to_string(), rendered);
+}
+
+#[test]
+fn multivariant_llhls_validation_checks_renditions_together() {
+    let mut multivariant = MultivariantPlaylist::from_str(
+        "#EXTM3U\n\
+         #EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"English\",URI=\"audio/en.m3u8\"\n\
+         #EXT-X-STREAM-INF:BANDWIDTH=2000000,CODECS=\"avc1.64001f,mp4a.40.2\",RESOLUTION=1280x720,AUDIO=\"aac\"\n\
+         video/720p.m3u8\n\
+         #EXT-X-STREAM-INF:BANDWIDTH=800000,CODECS=\"avc1.64001e,mp4a.40.2\",AUDIO=\"aac\"\n\
+         video/360p.m3u8\n",
+    )
+    .expect("Parsed multivariant playlist");
+    let mut broken = VariantStream::new("video/broken.m3u8", 0);
+    broken.codecs = Some("avc1 64001e".to_string());
+    broken.resolution = Some((0, 360));
+    multivariant.variants.push(broken);
+    let media = |part_target: &str, reports: &[&str]| {
+        let mut source = format!(
+            "#EXTM3U\n#EXT-X-TARGETDURATION:4\n#EXT-X-VERSION:9\n\
+             #EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=3\n\
+             #EXT-X-PART-INF:PART-TARGET={}\n#EXT-X-MEDIA-SEQUENCE:1\n\
+             #EXTINF:4,\nsegment1.mp4\n",
+            part_target
+        );
+        for report in reports {
+            source.push_str(&format!(
+                "#EXT-X-RENDITION-REPORT:URI=\"{}\",LAST-MSN=1,LAST-PART=0\n",
+                report
+            ));
+        }
+        parse_bytes(source.as_bytes()).expect("Parsed media playlist")
+    };
+    let mut media_playlists = BTreeMap::new();
+    media_playlists.insert(
+        "video/720p.m3u8".to_string(),
+        media("1", &["360p.m3u8", "../audio/en.m3u8"]),
+    );
+    media_playlists.insert(
+        "video/360p.m3u8".to_string(),
+        media("0.5", &["720p.m3u8", "480p.m3u8"]),
+    );
+    media_playlists.insert("audio/en.m3u8".to_string(), media("1", &[]));
+
+    let issues = multivariant.validate_llhls(&media_playlists);
+    let rules: Vec<_> = issues.iter().map(|issue| issue.rule).collect();
+    assert_eq!(
+        rules,
+        [
+            Rule::StreamInfAttributeMissing,
+            Rule::StreamInfAttributeInvalid,
+            Rule::StreamInfAttributeInvalid,
+            Rule::StreamInfAttributeInvalid,
+            Rule::PartTargetMismatch,
+            Rule::RenditionReportUnknown,
+        ]
+    );
+    assert_eq!(
+        issues[4].message,
+        "video/360p.m3u8 has PART-TARGET 0.5s but video/720p.m3u8 has PART-TARGET 1s"
+    );
+    assert!(issues[5].message.contains("480p.m3u8"));
+    assert_eq!(issues[5].code().name, "validation.rendition_report_unknown");
+
+    // Consistent renditions pass
+    media_playlists.insert("video/360p.m3u8".to_string(), media("1", &["720p.m3u8"]));
+    let consistent = MultivariantPlaylist::from_str(
+        "#EXTM3U\n\
+         #EXT-X-STREAM-INF:BANDWIDTH=2000000,CODECS=\"avc1.64001f\",RESOLUTION=1280x720\n\
+         video/720p.m3u8\n\
+         #EXT-X-STREAM-INF:BANDWIDTH=800000,CODECS=\"avc1.64001e\",RESOLUTION=640x360\n\
+         video/360p.m3u8\n",
+    )
+    .expect("Parsed multivariant playlist");
+    media_playlists.insert("video/720p.m3u8".to_string(), media("1", &["360p.m3u8"]));
+    assert!(consistent.validate_llhls(&media_playlists).is_empty());
+}
+
+#[test]
+fn session_data_and_keys_round_trip() {
+    let source = "#EXTM3U\n\
+                  #EXT-X-SESSION-DATA:DATA-ID=\"com.example.title\",VALUE=\"Morning News\"\n\
+                  #EXT-X-SESSION-DATA:DATA-ID=\"com.example.title\",VALUE=\"Journal du matin\",LANGUAGE=\"fr\"\n\
+                  #EXT-X-SESSION-DATA:DATA-ID=\"com.example.lineup\",URI=\"lineup.json\"\n\
+                  #EXT-X-SESSION-DATA:DATA-ID=\"com.example.logo\",URI=\"logo.png\",FORMAT=RAW\n\
+                  #EXT-X-SESSION-KEY:METHOD=SAMPLE-AES,URI=\"skd://key\",KEYFORMAT=\"com.apple.streamingkeydelivery\"\n\
+                  #EXT-X-STREAM-INF:BANDWIDTH=2000000\n\
+                  video.m3u8\n";
+    let playlist = MultivariantPlaylist::from_str(source).expect("Parsed multivariant playlist");
+    assert_eq!(playlist.session_data.len(), 4);
+    assert_eq!(playlist.to_string(), source);
+
+    let title = |language| {
+        playlist
+            .session_data_for("com.example.title", language)
+            .and_then(SessionData::value)
+    };
+    assert_eq!(title(Some("FR")), Some("Journal du matin"));
+    assert_eq!(title(Some("de")), Some("Morning News"));
+    assert_eq!(title(None), Some("Morning News"));
+    assert!(playlist
+        .session_data_for("com.example.none", None)
+        .is_none());
+
+    let lineup = playlist
+        .session_data_for("com.example.lineup", None)
+        .expect("Lineup listed");
+    assert_eq!(lineup.format, SessionDataFormat::Json);
+    assert_eq!(
+        lineup.absolute_uri(&segment_uri("https://example.com/live/master.m3u8")),
+        Some("https://example.com/live/lineup.json".to_string())
+    );
+    let logo = playlist
+        .session_data_for("com.example.logo", None)
+        .expect("Logo listed");
+    assert_eq!(logo.format, SessionDataFormat::Raw);
+    assert_eq!(logo.value(), None);
+    assert_eq!(playlist.session_keys[0].method, KeyMethod::SampleAes);
+
+    // FORMAT only applies to data at a URI, and keys must encrypt
+    for invalid in [
+        "#EXT-X-SESSION-DATA:DATA-ID=\"a\",VALUE=\"b\",FORMAT=RAW",
+        "#EXT-X-SESSION-DATA:DATA-ID=\"a\",URI=\"b\",FORMAT=XML",
+        "#EXT-X-SESSION-KEY:METHOD=NONE",
+    ] {
+        let source = format!("#EXTM3U\n{}\n", invalid);
+        assert!(
+            MultivariantPlaylist::from_str(&source).is_err(),
+            "{}",
+            invalid
+        );
+    }
+}
+
+#[test]
+fn multivariant_select_picks_the_variant_to_play() {
+    let source = "#EXTM3U\n\
+                  #EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"English\",URI=\"audio/en.m3u8\"\n\
+                  #EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"French\",URI=\"audio/fr.m3u8\"\n\
+                  #EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"ac3\",NAME=\"English\",URI=\"audio/ac3.m3u8\"\n\
+                  #EXT-X-MEDIA:TYPE=SUBTITLES,GROUP-ID=\"subs\",NAME=\"English\",URI=\"subs/en.m3u8\"\n\
+                  #EXT-X-STREAM-INF:BANDWIDTH=6000000,AVERAGE-BANDWIDTH=4500000,CODECS=\"hvc1.2.4.L123.B0,mp4a.40.2\",RESOLUTION=1920x1080,AUDIO=\"aac\"\n\
+                  hevc/1080p.m3u8\n\
+                  #EXT-X-STREAM-INF:BANDWIDTH=5000000,CODECS=\"avc1.640028,mp4a.40.2\",RESOLUTION=1920x1080,SCORE=1,AUDIO=\"aac\",SUBTITLES=\"subs\"\n\
+                  avc/1080p.m3u8\n\
+                  #EXT-X-STREAM-INF:BANDWIDTH=3000000,CODECS=\"avc1.64001f,mp4a.40.2\",RESOLUTION=1280x720,SCORE=2,AUDIO=\"aac\"\n\
+                  avc/720p.m3u8\n\
+                  #EXT-X-STREAM-INF:BANDWIDTH=1000000,CODECS=\"avc1.64001e,ac-3\",RESOLUTION=640x360,AUDIO=\"ac3\"\n\
+                  avc/360p.m3u8\n";
+    let playlist = MultivariantPlaylist::from_str(source).expect("Parsed multivariant playlist");
+    assert_eq!(playlist.to_string(), source);
+    let everything = |_: &str| true;
+    let avc_aac = |codec: &str| codec.starts_with("avc1") || codec.starts_with("mp4a");
+    let select = |bandwidth, resolution, codecs: &dyn Fn(&str) -> bool| {
+        playlist
+            .select(bandwidth, resolution, codecs)
+            .map(|variant| variant.uri.as_str())
+    };
+
+    // SCORE beats BANDWIDTH, and AVERAGE-BANDWIDTH decides whether HEVC fits
+    assert_eq!(select(5_000_000, None, &everything), Some("avc/720p.m3u8"));
+    let mut unscored = playlist.clone();
+    for variant in &mut unscored.variants {
+        variant.score = None;
+    }
+    assert_eq!(
+        unscored
+            .select(5_000_000, None, everything)
+            .map(|variant| variant.uri.as_str()),
+        Some("hevc/1080p.m3u8")
+    );
+    // Codecs and display size rule variants out
+    assert_eq!(select(800_000, None, &avc_aac), Some("avc/720p.m3u8"));
+    assert_eq!(
+        select(10_000_000, Some((1280, 720)), &everything),
+        Some("avc/720p.m3u8")
+    );
+    assert_eq!(select(100_000, None, &everything), Some("avc/360p.m3u8"));
+    assert_eq!(select(10_000_000, None, &|_| false), None);
+
+    assert_eq!(
+        playlist.group_ids(MediaType::Audio).collect::<Vec<_>>(),
+        ["aac", "ac3"]
+    );
+    let variant = playlist.variant("avc/1080p.m3u8").expect("Variant listed");
+    let names: Vec<_> = playlist
+        .audio_renditions(variant)
+        .map(|rendition| rendition.name.as_str())
+        .collect();
+    assert_eq!(names, ["English", "French"]);
+    assert_eq!(playlist.subtitle_renditions(variant).count(), 1);
+    let variant = playlist.variant("avc/720p.m3u8").expect("Variant listed");
+    assert_eq!(playlist.subtitle_renditions(variant).count(), 0);
+}
+
+#[test]
+fn tags_before_trailing_parts_round_trip() {
+    let source = "#EXTM3U\n#EXT-X-TARGETDURATION:4\n#EXT-X-VERSION:9\n\
+        #EXT-X-PART-INF:PART-TARGET=1\n\
+        #EXTINF:4,\nsegment0.mp4\n\
+        #EXT-X-BITRATE:800\n\
+        #EXT-X-PROGRAM-DATE-TIME:2019-02-14T02:13:40.106Z\n\
+        #EXT-X-GAP\n\
+        #EXT-X-PART:DURATION=1,URI=\"part1.0.mp4\"\n";
+    let mut playlist = parse_bytes(source.as_bytes()).expect("Parsed playlist");
+    assert_eq!(playlist.to_string(), source);
+    assert_eq!(playlist.estimated_serialized_len(), source.len());
+
+    // They belong to the segment once it completes
+    playlist.complete_segment(Duration::from_secs(1), segment_uri("segment1.mp4"), None);
+    let segment = playlist.media_segments().last().expect("Completed segment");
+    assert_eq!(
+        segment.program_date_time(),
+        Some(&date("2019-02-14T02:13:40.106Z"))
+    );
+    assert!(segment.is_gap());
+    assert_eq!(segment.bitrate(), Some(800_000));
+    assert!(playlist.to_string().ends_with(
+        "#EXT-X-BITRATE:800\n\
+         #EXT-X-PROGRAM-DATE-TIME:2019-02-14T02:13:40.106Z\n\
+         #EXT-X-PART:DURATION=1,URI=\"part1.0.mp4\"\n\
+         #EXT-X-GAP\n#EXTINF:1,\nsegment1.mp4\n"
+    ));
+}
+
+#[test]
+fn unknown_tags_round_trip_in_place() {
+    let source = fs::read_to_string("tests/resources/unknown-tag.m3u8").expect("Read test file");
+    let playlist = parse_bytes(source.as_bytes()).expect("Parsed test file");
+    assert_eq!(
+        playlist.unknown_tags(),
+        [UnknownTag::new(
+            "EXT-X-CUE-OUT",
+            Some("DURATION=30".to_string())
+        )]
+    );
+    assert_eq!(playlist.to_string(), source);
+
+    let source = "#EXTM3U\n\
+        #EXT-X-TARGETDURATION:4\n\
+        #EXT-X-VERSION:9\n\
+        #EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=1\n\
+        #EXT-X-PART-INF:PART-TARGET=1\n\
+        #EXT-X-MEDIA-SEQUENCE:10\n\
+        #EXT-X-PROGRAM-DATE-TIME:2024-01-01T00:00:00.000Z\n\
+        #EXT-X-CUE-OUT:30\n\
+        #EXTINF:4,\n\
+        10.mp4\n\
+        #EXT-X-CUE-IN\n\
+        #EXTINF:4,\n\
+        11.mp4\n\
+        #EXT-X-ASSET:CAID=0x1\n\
+        #EXT-X-PART:DURATION=1,URI=\"12.0.mp4\"\n";
+    let mut playlist = parse_bytes(source.as_bytes()).expect("Parsed media playlist");
+    assert!(playlist.unknown_tags().is_empty());
+    let unknown_tags = |index| playlist.media_segments().get(index).unwrap().unknown_tags();
+    assert_eq!(
+        unknown_tags(0),
+        [UnknownTag::new("EXT-X-CUE-OUT", Some("30".to_string()))]
+    );
+    assert_eq!(unknown_tags(1), [UnknownTag::new("EXT-X-CUE-IN", None)]);
+    assert_eq!(playlist.to_string(), source);
+    assert_eq!(playlist.estimated_serialized_len(), source.len());
+
+    // The tags of the segment being published go with it once complete
+    playlist.complete_segment(Duration::from_secs(1), segment_uri("12.mp4"), None);
+    assert_eq!(
+        playlist.media_segments().last().unwrap().unknown_tags(),
+        [UnknownTag::new("EXT-X-ASSET", Some("CAID=0x1".to_string()))]
+    );
+
+    // Tags between parts stay there, and those after EXT-X-ENDLIST last
+    let source = "#EXTM3U\n\
+        #EXT-X-TARGETDURATION:4\n\
+        #EXT-X-VERSION:9\n\
+        #EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=3\n\
+        #EXT-X-PART-INF:PART-TARGET=1\n\
+        #EXT-X-MEDIA-SEQUENCE:10\n\
+        #EXT-X-PART:DURATION=1,URI=\"10.0.mp4\"\n\
+        #EXT-X-CUE-OUT:30\n\
+        #EXT-X-PART:DURATION=1,URI=\"10.1.mp4\"\n\
+        #EXT-X-CUE-IN\n\
+        #EXTINF:2,\n\
+        10.mp4\n\
+        #EXT-X-PART:DURATION=1,URI=\"11.0.mp4\"\n\
+        #EXT-X-ASSET:CAID=0x1\n\
+        #EXT-X-PART:DURATION=1,URI=\"11.1.mp4\"\n\
+        #EXT-X-ENDLIST\n\
+        #EXT-X-VENDOR-CHECKSUM:abc\n";
+    let playlist = parse_bytes(source.as_bytes()).expect("Parsed media playlist");
+    let positions: Vec<_> = playlist
+        .media_segments()
+        .first()
+        .expect("Segment listed")
+        .unknown_tags()
+        .iter()
+        .map(|tag| tag.position)
+        .collect();
+    assert_eq!(
+        positions,
+        [TagPosition::BeforePart(1), TagPosition::BeforePart(2)]
+    );
+    assert_eq!(playlist.to_string(), source);
+    assert_eq!(playlist.estimated_serialized_len(), source.len());
+
+    let source = "#EXTM3U\n\
+        #EXT-X-VERSION:9\n\
+        #EXT-X-CONTENT-STEERING:SERVER-URI=\"/steering\"\n\
+        #EXT-X-STREAM-INF:BANDWIDTH=1000000\n\
+        video.m3u8\n";
+    let playlist = MultivariantPlaylist::from_str(source).expect("Parsed multivariant playlist");
+    assert_eq!(
+        playlist.unknown_tags,
+        [UnknownTag::new(
+            "EXT-X-CONTENT-STEERING",
+            Some("SERVER-URI=\"/steering\"".to_string())
+        )]
+    );
+    assert_eq!(playlist.to_string(), source);
+}
+
+#[test]
+fn map_uris_rewrites_every_uri_in_one_pass() {
+    let source = fs::read_to_string("tests/resources/ll-hls-map.m3u8").expect("Read test file");
+    let mut playlist = parse_bytes(source.as_bytes()).expect("Parsed test file");
+    playlist.push_key(Key::new(KeyMethod::Aes128, "https://keys.example.com/1"));
+
+    let mut kinds = Vec::new();
+    playlist.map_uris(|kind, uri| {
+        kinds.push(kind);
+        if kind == UriKind::Key {
+            return uri.to_string();
+        }
+        format!("{}?token=abc", uri)
+    });
+    for kind in [
+        UriKind::Segment,
+        UriKind::Part,
+        UriKind::Map,
+        UriKind::Key,
+        UriKind::PreloadHint,
+        UriKind::RenditionReport,
+    ] {
+        assert!(kinds.contains(&kind), "{:?} not mapped", kind);
+    }
+    let expected = source
+        .replace(".mp4", ".mp4?token=abc")
+        .replace(".php", ".php?token=abc")
+        .replace(
+            "#EXT-X-MAP:URI=\"init",
+            "#EXT-X-KEY:METHOD=AES-128,URI=\"https://keys.example.com/1\"\n\
+             #EXT-X-MAP:URI=\"init",
+        );
+    assert_eq!(playlist.to_string(), expected);
+
+    // Hostnames swapped on absolute URIs only
+    playlist.resolve_uris(&segment_uri(
+        "https://origin.example.com/live/2M/index.m3u8",
+    ));
+    playlist.map_uris(|_, uri| uri.replace("://origin.example.com/", "://cdn.example.com/"));
+    let segment = playlist.media_segments().first().expect("Segment listed");
+    assert_eq!(
+        segment.uri().as_str(),
+        "https://cdn.example.com/live/2M/fileSequence269.mp4?token=abc"
+    );
+    assert_eq!(
+        playlist.rendition_reports()[0].uri(),
+        "https://cdn.example.com/live/1M/waitForMSN.php?token=abc"
+    );
+    assert_eq!(
+        playlist.keys_for(usize::MAX)[0].uri.as_deref(),
+        Some("https://keys.example.com/1")
+    );
+}
+
+#[test]
+fn segments_and_parts_are_found_by_msn() {
+    let file = fs::File::open("tests/resources/ll-hls-map.m3u8").expect("Opened test file");
+    let mut playlist = read_playlist(file).expect("Parsed test file");
+    let uri = |segment: Option<&MediaSegment>| segment.map(|segment| segment.uri().to_string());
+    assert_eq!(uri(playlist.get(268)), None);
+    assert_eq!(
+        uri(playlist.get(269)).as_deref(),
+        Some("fileSequence269.mp4")
+    );
+    assert_eq!(
+        uri(playlist.get(270)).as_deref(),
+        Some("fileSequence270.mp4")
+    );
+    assert_eq!(uri(playlist.get(271)), None);
+
+    let part_uri = |msn, index| {
+        playlist
+            .get_part(msn, index)
+            .map(|part: &PartialSegment| part.uri.clone())
+    };
+    assert_eq!(part_uri(269, 0), None);
+    assert_eq!(part_uri(270, 1).as_deref(), Some("filePart270.1.mp4"));
+    assert_eq!(part_uri(270, 2), None);
+    // The segment still being published
+    assert_eq!(part_uri(271, 0).as_deref(), Some("filePart271.0.mp4"));
+    assert_eq!(part_uri(272, 0), None);
+    assert_eq!((playlist.last_msn(), playlist.last_part()), (271, Some(0)));
+
+    playlist.complete_segment(
+        Duration::from_secs(1),
+        segment_uri("fileSequence271.mp4"),
+        None,
+    );
+    assert_eq!(
+        uri(playlist.get(271)).as_deref(),
+        Some("fileSequence271.mp4")
+    );
+    assert_eq!((playlist.last_msn(), playlist.last_part()), (271, Some(0)));
+    playlist.complete_segment(
+        Duration::from_secs(4),
+        segment_uri("fileSequence272.mp4"),
+        None,
+    );
+    assert_eq!((playlist.last_msn(), playlist.last_part()), (272, None));
+
+    // Removing the oldest segment advances the media sequence
+    let mut trimmed = playlist.clone();
+    trimmed.pop_segment();
+    assert_eq!(uri(trimmed.get(269)), None);
+    assert_eq!(
+        uri(trimmed.get(270)).as_deref(),
+        Some("fileSequence270.mp4")
+    );
+}
+
+#[test]
+fn uri_policy_repairs_or_keeps_invalid_segment_uris() {
+    let source = "#EXTM3U\n\
+        #EXT-X-TARGETDURATION:4\n\
+        #EXT-X-VERSION:9\n\
+        #EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=1\n\
+        #EXT-X-MEDIA-SEQUENCE:1\n\
+        #EXTINF:4,\n\
+        segment 1.mp4\n\
+        #EXTINF:4,\n\
+        caf\u{e9}/50%off.mp4\n\
+        #EXTINF:4,\n\
+        https://cdn.example.com/3.mp4?token=a%20b\n";
+    let parse = |uris| {
+        let mut parser = PlaylistParser::with_options(ParseOptions {
+            uris,
+            ..ParseOptions::default()
+        });
+        let mut hooks = CountingHooks::default();
+        let playlist = parser
+            .feed(source.as_bytes(), &mut hooks)
+            .and_then(|()| parser.finish(&mut hooks));
+        (playlist, hooks.warnings)
+    };
+    let uris = |playlist: &MediaPlaylist| -> Vec<String> {
+        playlist
+            .media_segments()
+            .iter()
+            .map(|segment| segment.uri().to_string())
+            .collect()
+    };
+
+    if !cfg!(feature = "fluent-uri") {
+        // URIs aren't validated, so every policy keeps them as written
+        let (playlist, warnings) = parse(UriPolicy::Strict);
+        assert_eq!(playlist.expect("Parsed playlist").to_string(), source);
+        assert!(warnings.is_empty());
+        return;
+    }
+    assert!(matches!(
+        parse(UriPolicy::Strict).0,
+        Err(ParsePlaylistError::InvalidUri { line: 7, .. })
+    ));
+
+    let (playlist, warnings) = parse(UriPolicy::Repair);
+    let playlist = playlist.expect("Parsed playlist");
+    assert_eq!(
+        uris(&playlist),
+        [
+            "segment%201.mp4",
+            "caf%C3%A9/50%25off.mp4",
+            "https://cdn.example.com/3.mp4?token=a%20b"
+        ]
+    );
+    assert_eq!(
+        warnings,
+        [
+            ParseWarning::RepairedUri {
+                line: 7,
+                uri: "segment 1.mp4".to_string()
+            },
+            ParseWarning::RepairedUri {
+                line: 9,
+                uri: "caf\u{e9}/50%off.mp4".to_string()
+            }
+        ]
+    );
+    assert!(playlist.to_string().contains("\nsegment%201.mp4\n"));
+
+    let (playlist, _) = parse(UriPolicy::Raw);
+    let mut playlist = playlist.expect("Parsed playlist");
+    assert_eq!(playlist.to_string(), source);
+    assert_eq!(playlist.estimated_serialized_len(), source.len());
+    let segment = playlist.media_segments().first().expect("Segment listed");
+    assert_eq!(segment.raw_uri(), Some("segment 1.mp4"));
+    assert_eq!(segment.written_uri(), "segment 1.mp4");
+    assert_eq!(segment.uri().as_str(), "segment%201.mp4");
+
+    // A line still invalid once rewritten is kept as written too
+    playlist.map_uris(|_, uri| uri.replace("segment", "clip"));
+    let segment = playlist.media_segments().first().expect("Segment listed");
+    assert_eq!(segment.raw_uri(), Some("clip 1.mp4"));
+    assert_eq!(segment.uri().as_str(), "clip%201.mp4");
+    assert!(playlist.to_string().contains("\nclip 1.mp4\n"));
+
+    // Rewriting starts from the URI as written
+    playlist.map_uris(|_, uri| uri.replace(' ', "-"));
+    let segment = playlist.media_segments().first().expect("Segment listed");
+    assert_eq!(segment.raw_uri(), None);
+    assert_eq!(segment.uri().as_str(), "clip-1.mp4");
+}
+
+#[test]
+fn independent_no_parts_round_trip() {
+    let source = "#EXTM3U\n\
+        #EXT-X-TARGETDURATION:4\n\
+        #EXT-X-VERSION:9\n\
+        #EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=1\n\
+        #EXT-X-PART-INF:PART-TARGET=1\n\
+        #EXT-X-MEDIA-SEQUENCE:1\n\
+        #EXT-X-PART:DURATION=1,URI=\"1.0.mp4\",INDEPENDENT=YES\n\
+        #EXT-X-PART:DURATION=1,URI=\"1.1.mp4\",INDEPENDENT=NO\n";
+    let playlist = parse_bytes(source.as_bytes()).expect("Parsed media playlist");
+    assert_eq!(playlist.trailing_parts()[1].independent, Some(false));
+    let rendered = playlist.to_string();
+    assert_eq!(rendered, source);
+    let reparsed = parse_bytes(rendered.as_bytes()).expect("Parsed rendered playlist");
+    assert_eq!(reparsed.to_string(), source);
+}
@@ -0,0 +1,48 @@
+// Only uses what the crate offers without its std feature
+use core::str::FromStr;
+
+use llhls_rs::{
+    parse_any_playlist, parse_bytes, MultivariantPlaylist, NoHooks, ParsePlaylistError, Playlist,
+    PlaylistParser,
+};
+
+const CANONICAL: &str = include_str!("resources/ll-hls-canonical.m3u8");
+const MULTIVARIANT: &str = include_str!("resources/multivariant.m3u8");
+
+#[test]
+fn parses_and_renders_playlists_from_bytes() {
+    let playlist = parse_bytes(CANONICAL.as_bytes()).expect("Parsed test playlist");
+    assert_eq!(playlist.to_string(), CANONICAL);
+
+    // Fed in chunks that split lines, as they arrive off the network
+    let mut parser = PlaylistParser::new();
+    for chunk in CANONICAL.as_bytes().chunks(7) {
+        parser.feed(chunk, &mut NoHooks).expect("Fed chunk");
+    }
+    let fed = parser.finish(&mut NoHooks).expect("Parsed test playlist");
+    assert_eq!(fed.to_string(), CANONICAL);
+
+    assert!(matches!(
+        parse_bytes(b"#EXTM3U\n#EXT-X-VERSION:9\n\xff\n"),
+        Err(ParsePlaylistError::InvalidUtf8 { line: 3 })
+    ));
+
+    let multivariant = MultivariantPlaylist::from_str(MULTIVARIANT).expect("Parsed test playlist");
+    assert!(!multivariant.variants.is_empty());
+}
+
+#[test]
+fn parses_either_kind_of_playlist_from_text() {
+    match parse_any_playlist(CANONICAL).expect("Parsed test playlist") {
+        Playlist::Media(playlist) => assert_eq!(playlist.to_string(), CANONICAL),
+        Playlist::Multivariant(_) => panic!("Parsed a media playlist as multivariant"),
+    }
+    assert!(matches!(
+        parse_any_playlist(MULTIVARIANT),
+        Ok(Playlist::Multivariant(_))
+    ));
+    assert!(matches!(
+        parse_any_playlist("#EXT-X-VERSION:9\n"),
+        Err(ParsePlaylistError::MissingHeader)
+    ));
+}